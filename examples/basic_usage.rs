@@ -3,6 +3,7 @@
 //! This example demonstrates how to use the library API to find duplicate files.
 
 use rclean::{PatternType, WalkOptions};
+use std::path::PathBuf;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Basic rclean usage example\n");
@@ -29,7 +30,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let globset = builder.build()?;
     let pattern = PatternType::Glob(globset);
 
-    let rust_files = rclean::find_advanced(&files, &pattern);
+    let rust_files =
+        rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     println!("Found {} Rust files", rust_files.len());
 
     // Example 3: Using walk with options (respecting .gitignore)
@@ -39,6 +41,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         respect_gitignore: false,
         respect_ignore: false,
         max_depth: Some(3),
+        ..WalkOptions::default()
     };
 
     let all_files = rclean::walk_with_options(".", &walk_options)?;
@@ -49,7 +52,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Example 4: Finding duplicates with checksum
     println!("\nExample 4: Finding duplicate files");
-    let test_files = vec!["Cargo.toml".to_string(), "README.md".to_string()];
+    let test_files = vec![PathBuf::from("Cargo.toml"), PathBuf::from("README.md")];
 
     let checksums = rclean::checksum(&test_files)?;
     let duplicates = rclean::find_duplicates(checksums);
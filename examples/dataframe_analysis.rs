@@ -16,20 +16,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         respect_gitignore: true,
         respect_ignore: true,
         max_depth: None,
+        ..WalkOptions::default()
     };
 
     // Run deduplication with DataFrame support
     println!("Analyzing current directory for duplicates...\n");
-    let df = rclean::run_with_advanced_options(
+    let report = rclean::run_with_advanced_options(
         ".",
         &pattern,
         &walk_options,
         None, // No CSV output in this example
     )?;
 
-    // Display results
+    // Display results; the library itself prints nothing
+    let df = report.df;
     println!("Analysis complete!");
     println!("Total files analyzed: {}", df.height());
+    println!(
+        "Duplicates: {} files in {} groups",
+        report.duplicate_files, report.duplicate_groups
+    );
+    if !report.diagnostics.is_empty() {
+        println!("{}", report.diagnostics);
+    }
 
     // Show statistics
     if let Ok(stats_df) = rclean::generate_statistics(&df) {
@@ -38,15 +47,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Validate duplicates
-    if let Err(e) = rclean::validate_duplicates(&df) {
-        eprintln!("Validation error: {}", e);
+    match rclean::validate_duplicates(&df) {
+        Ok(groups) => {
+            for (hash, count) in groups {
+                println!("  {} -> {} files", hash, count);
+            }
+        },
+        Err(e) => eprintln!("Validation error: {}", e),
     }
 
     // Example of using similarity detection
     println!("\n=== Similarity Analysis ===");
     println!("Running similarity analysis with 70% threshold...");
 
-    let _similar_df = rclean::run_with_similarity(
+    let similar = rclean::run_with_similarity(
         ".",
         &pattern,
         &walk_options,
@@ -54,7 +68,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         None,
     )?;
 
-    println!("Similarity analysis complete!");
+    println!(
+        "Similarity analysis complete! {} groups of similar files",
+        similar.similar_groups.len()
+    );
 
     Ok(())
 }
@@ -3,27 +3,35 @@
 //! This example demonstrates the different pattern matching capabilities.
 
 use rclean::{Glob, GlobSetBuilder, PatternType, Regex};
+use std::path::PathBuf;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("RDedupe Pattern Matching Examples\n");
 
     // Sample file list
-    let files = vec![
-        "src/main.rs".to_string(),
-        "src/lib.rs".to_string(),
-        "tests/test_utils.rs".to_string(),
-        "examples/basic.rs".to_string(),
-        "README.md".to_string(),
-        "Cargo.toml".to_string(),
-        "build.rs".to_string(),
-        ".gitignore".to_string(),
-        "docs/guide.md".to_string(),
-    ];
+    let files: Vec<PathBuf> = [
+        "src/main.rs",
+        "src/lib.rs",
+        "tests/test_utils.rs",
+        "examples/basic.rs",
+        "README.md",
+        "Cargo.toml",
+        "build.rs",
+        ".gitignore",
+        "docs/guide.md",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
 
     // Example 1: Literal pattern matching
     println!("Example 1: Literal pattern matching");
     let literal_pattern = PatternType::Literal("src".to_string());
-    let matches = rclean::find_advanced(&files, &literal_pattern);
+    let matches = rclean::find_advanced(
+        &files,
+        &literal_pattern,
+        &rclean::filters::FileFilter::default(),
+    );
     println!("Files containing 'src': {:?}", matches);
 
     // Example 2: Glob pattern matching
@@ -33,14 +41,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     builder.add(Glob::new("**/*.md")?);
     let globset = builder.build()?;
     let glob_pattern = PatternType::Glob(globset);
-    let matches = rclean::find_advanced(&files, &glob_pattern);
+    let matches = rclean::find_advanced(
+        &files,
+        &glob_pattern,
+        &rclean::filters::FileFilter::default(),
+    );
     println!("Files matching '*.rs' or '**/*.md': {:?}", matches);
 
     // Example 3: Regex pattern matching
     println!("\nExample 3: Regex pattern matching");
     let regex = Regex::new(r"^(src|tests)/.*\.rs$")?;
     let regex_pattern = PatternType::Regex(regex);
-    let matches = rclean::find_advanced(&files, &regex_pattern);
+    let matches = rclean::find_advanced(
+        &files,
+        &regex_pattern,
+        &rclean::filters::FileFilter::default(),
+    );
     println!(
         "Files matching regex '^(src|tests)/.*\\.rs$': {:?}",
         matches
@@ -53,15 +69,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     builder.add(Glob::new("!.gitignore")?); // Note: negation in globs might not work as expected
     let globset = builder.build()?;
     let pattern = PatternType::Glob(globset);
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     println!("Files matching '**/test*.rs': {:?}", matches);
 
     // Example 5: Case sensitivity
     println!("\nExample 5: Case sensitivity in patterns");
     let pattern1 = PatternType::Literal("README".to_string());
     let pattern2 = PatternType::Literal("readme".to_string());
-    let matches1 = rclean::find_advanced(&files, &pattern1);
-    let matches2 = rclean::find_advanced(&files, &pattern2);
+    let matches1 =
+        rclean::find_advanced(&files, &pattern1, &rclean::filters::FileFilter::default());
+    let matches2 =
+        rclean::find_advanced(&files, &pattern2, &rclean::filters::FileFilter::default());
     println!("Files containing 'README': {:?}", matches1);
     println!("Files containing 'readme': {:?}", matches2);
 
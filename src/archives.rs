@@ -0,0 +1,360 @@
+//! Archive members as duplicate candidates.
+//!
+//! A loose file often has a copy packed in a `.zip`, `.tar`, `.tar.gz` or
+//! `.tgz` next to it. With [`crate::DedupeOptions::archives`], the members
+//! of every scanned archive are read and hashed from their decompressed
+//! content, and each becomes a file of its own with a virtual path such as
+//! `backup.tar.gz!/src/main.rs`, so duplicate groups can mix real files and
+//! archive members.
+//!
+//! A member cannot be removed without rewriting its archive: it never counts
+//! as reclaimable, it is kept before any loose copy of it, and the
+//! [`ARCHIVE_COLUMN`] of the report names the archive it lives in (see
+//! [`archive_duplicates`]). Archives nested in archives are not opened, and
+//! empty members are left out, as they would match every empty file.
+
+use crate::cancel::CancelToken;
+use crate::hashing::{self, HashAlgorithm, MemoryBudget};
+use crate::paths::{self, display};
+use crate::progress::ProgressSink;
+use flate2::read::GzDecoder;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Separates the archive path from the member name in a virtual path.
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+/// Name of the column holding the archive a member was read from, null for
+/// real files.
+pub const ARCHIVE_COLUMN: &str = "archive";
+
+/// Archive formats whose members can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Format of the archive at `path`, judged by its file name.
+    pub fn of_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file stored in an archive, hashed from its decompressed content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    /// Virtual path: the archive path, [`MEMBER_SEPARATOR`] and `name`
+    pub path: PathBuf,
+    /// Path of the member inside the archive
+    pub name: PathBuf,
+    pub size_bytes: u64,
+    pub content_hash: String,
+}
+
+/// Virtual path of the member `name` of `archive`, e.g.
+/// `backup.zip!/src/main.rs`.
+pub fn member_path(archive: &Path, name: &Path) -> PathBuf {
+    let mut path = OsString::from(archive.as_os_str());
+    path.push(MEMBER_SEPARATOR);
+    for (index, component) in name.components().enumerate() {
+        if index > 0 {
+            path.push("/");
+        }
+        path.push(component.as_os_str());
+    }
+    PathBuf::from(path)
+}
+
+/// `name` without leading `./`, or `None` if it is absolute or climbs out
+/// of the archive with `..`.
+fn member_name(name: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {},
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!clean.as_os_str().is_empty()).then_some(clean)
+}
+
+/// Read and hash every non-empty file member of `archive` with `algorithm`,
+/// streaming each through a buffer reserved from `budget`.
+///
+/// Bytes hashed are reported to `progress`. Once `cancel` fires, the members
+/// read so far are returned.
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be opened, is not a supported
+/// archive, or a member cannot be decompressed.
+pub fn read_members(
+    archive: &Path,
+    algorithm: HashAlgorithm,
+    budget: &MemoryBudget,
+    progress: &dyn ProgressSink,
+    cancel: &CancelToken,
+) -> io::Result<Vec<ArchiveMember>> {
+    let format = ArchiveFormat::of_path(archive)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a supported archive"))?;
+    let file = io::BufReader::new(fs::File::open(archive)?);
+    let reader = MemberReader {
+        archive,
+        algorithm,
+        budget,
+        progress,
+        cancel,
+    };
+    match format {
+        ArchiveFormat::Zip => reader.read_zip(file),
+        ArchiveFormat::Tar => reader.read_tar(file),
+        ArchiveFormat::TarGz => reader.read_tar(GzDecoder::new(file)),
+    }
+}
+
+/// Hashes the members of one archive.
+struct MemberReader<'a> {
+    archive: &'a Path,
+    algorithm: HashAlgorithm,
+    budget: &'a MemoryBudget,
+    progress: &'a dyn ProgressSink,
+    cancel: &'a CancelToken,
+}
+
+impl MemberReader<'_> {
+    fn read_zip(&self, reader: impl io::Read + io::Seek) -> io::Result<Vec<ArchiveMember>> {
+        let mut zip = ::zip::ZipArchive::new(reader)?;
+        let mut members = Vec::new();
+        for index in 0..zip.len() {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let mut entry = zip.by_index(index)?;
+            if !entry.is_file() {
+                continue;
+            }
+            let Some(name) = entry.enclosed_name() else {
+                continue;
+            };
+            let size = entry.size();
+            members.extend(self.hash(&name, size, &mut entry)?);
+        }
+        Ok(members)
+    }
+
+    fn read_tar(&self, reader: impl io::Read) -> io::Result<Vec<ArchiveMember>> {
+        let mut tar = tar::Archive::new(reader);
+        let mut members = Vec::new();
+        for entry in tar.entries()? {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry.path()?.into_owned();
+            let size = entry.size();
+            members.extend(self.hash(&name, size, &mut entry)?);
+        }
+        Ok(members)
+    }
+
+    /// Hash one member, unless it is empty or its name is unsafe.
+    fn hash(
+        &self,
+        name: &Path,
+        size: u64,
+        reader: impl io::Read,
+    ) -> io::Result<Option<ArchiveMember>> {
+        let Some(name) = member_name(name).filter(|_| size > 0) else {
+            return Ok(None);
+        };
+        let mut hasher = self.algorithm.hasher();
+        let bytes = hashing::hash_reader_with_budget(reader, hasher.as_mut(), None, self.budget)?;
+        self.progress.bytes_hashed(bytes);
+        Ok(Some(ArchiveMember {
+            path: member_path(self.archive, &name),
+            name,
+            size_bytes: bytes,
+            content_hash: hasher.finish_hex(),
+        }))
+    }
+}
+
+/// A duplicate that lives inside an archive and cannot be removed on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveDuplicate {
+    #[serde(with = "crate::paths::serde_path")]
+    pub member: PathBuf,
+    #[serde(with = "crate::paths::serde_path")]
+    pub archive: PathBuf,
+}
+
+impl fmt::Display for ArchiveDuplicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is inside {} and cannot be removed on its own",
+            display(&self.member),
+            display(&self.archive)
+        )
+    }
+}
+
+/// Every duplicate in `df` that is an archive member, by path. Frames
+/// without an `archive` column have none.
+///
+/// # Errors
+///
+/// Returns an error if the path or duplicate columns are missing.
+pub fn archive_duplicates(df: &DataFrame) -> PolarsResult<Vec<ArchiveDuplicate>> {
+    let Ok(archives) = df.column(ARCHIVE_COLUMN) else {
+        return Ok(Vec::new());
+    };
+    let archives = archives.utf8()?;
+    let paths = df.column("file_path")?.utf8()?;
+    let hex = df
+        .column(paths::HEX_COLUMN)
+        .ok()
+        .map(Series::utf8)
+        .transpose()?;
+    let is_duplicate = df.column("is_duplicate")?.bool()?;
+
+    // Archives are listed by their display text; their own rows give the
+    // exact path
+    let path_of = |row: usize| {
+        let hex = hex.and_then(|hex| hex.get(row));
+        Some(paths::from_column(paths.get(row)?, hex))
+    };
+    let by_display: HashMap<&str, PathBuf> = (0..df.height())
+        .filter_map(|row| Some((paths.get(row)?, path_of(row)?)))
+        .collect();
+
+    let mut duplicates: Vec<ArchiveDuplicate> = (0..df.height())
+        .filter(|&row| is_duplicate.get(row) == Some(true))
+        .filter_map(|row| {
+            let archive = archives.get(row)?;
+            Some(ArchiveDuplicate {
+                member: path_of(row)?,
+                archive: by_display
+                    .get(archive)
+                    .cloned()
+                    .unwrap_or_else(|| PathBuf::from(archive)),
+            })
+        })
+        .collect();
+    duplicates.sort_by(|a, b| a.member.cmp(&b.member));
+    Ok(duplicates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use std::error::Error;
+    use std::io::Write as _;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_archive_format_and_member_path() {
+        assert_eq!(
+            ArchiveFormat::of_path(Path::new("a/backup.TAR.GZ")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::of_path(Path::new("x.tgz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(ArchiveFormat::of_path(Path::new("x.gz")), None);
+        assert_eq!(
+            member_path(Path::new("dir/backup.zip"), Path::new("src/main.rs")),
+            PathBuf::from("dir/backup.zip!/src/main.rs")
+        );
+        assert_eq!(
+            member_name(Path::new("./src/main.rs")),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(member_name(Path::new("../etc/passwd")), None);
+    }
+
+    #[test]
+    fn test_zip_and_tar_gz_members_hash_like_files() -> Result<(), Box<dyn Error>> {
+        let dir = TempDir::new()?;
+        let algorithm = HashAlgorithm::Blake3;
+
+        let zip_path = dir.path().join("backup.zip");
+        let mut zip = ::zip::ZipWriter::new(fs::File::create(&zip_path)?);
+        let options = ::zip::write::SimpleFileOptions::default();
+        zip.add_directory("src/", options)?;
+        zip.start_file("src/main.rs", options)?;
+        zip.write_all(b"fn main() {}")?;
+        zip.start_file("empty", options)?;
+        zip.finish()?;
+
+        let tgz_path = dir.path().join("backup.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&tgz_path)?,
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(12);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "./src/main.rs", &b"fn main() {}"[..])?;
+        tar.into_inner()?.finish()?;
+
+        let mut hasher = algorithm.hasher();
+        hasher.update(b"fn main() {}");
+        let expected = hasher.finish_hex();
+        let cancel = CancelToken::new();
+        for archive in [&zip_path, &tgz_path] {
+            let members = read_members(
+                archive,
+                algorithm,
+                MemoryBudget::global(),
+                &NoProgress,
+                &cancel,
+            )?;
+            assert_eq!(members.len(), 1);
+            assert_eq!(members[0].name, PathBuf::from("src/main.rs"));
+            assert_eq!(
+                members[0].path,
+                member_path(archive, Path::new("src/main.rs"))
+            );
+            assert_eq!(members[0].size_bytes, 12);
+            assert_eq!(members[0].content_hash, expected);
+        }
+
+        fs::write(dir.path().join("broken.zip"), "not a zip")?;
+        assert!(read_members(
+            &dir.path().join("broken.zip"),
+            algorithm,
+            MemoryBudget::global(),
+            &NoProgress,
+            &cancel
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! Atomic replacement of small state files (hash cache, size snapshots).
+//!
+//! The new content is written to a temporary file next to the target and
+//! renamed over it, so readers see either the old or the new file, never a
+//! partial one. Every write uses its own temporary name, so two processes
+//! saving the same file at once cannot tear each other's copy; the last
+//! rename wins.
+
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Temporary sibling of `path`, unique to this process and write.
+fn temp_path(path: &Path) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// Replace the file at `path` with `contents`, creating its parent
+/// directories if needed.
+///
+/// # Errors
+///
+/// Returns an error if the temporary file cannot be written or renamed; the
+/// file at `path` is left as it was.
+pub fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = temp_path(path);
+    let written = fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(contents))
+        .and_then(|()| fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_concurrent_writers_never_tear_the_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("state.json");
+        let contents: Vec<Vec<u8>> = (0..4u8).map(|n| vec![b'a' + n; 64 * 1024]).collect();
+
+        std::thread::scope(|scope| {
+            for content in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        write(path, content).unwrap();
+                    }
+                });
+            }
+        });
+
+        let written = fs::read(&path).unwrap();
+        assert!(contents.contains(&written));
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+}
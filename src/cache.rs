@@ -0,0 +1,547 @@
+//! Persistent hash cache for incremental rescans.
+//!
+//! Hashing dominates the cost of a dedupe run, yet between two nightly scans
+//! of the same tree almost nothing changes. The cache remembers the partial,
+//! full and fuzzy hashes of every file it has seen, keyed by
+//! `(device, inode, size, mtime)`. A file whose key is unchanged is not read
+//! again; any write, truncation or replacement changes the key and forces a
+//! fresh hash.
+//!
+//! Entries are stored as JSON, by default under `$XDG_CACHE_HOME/rclean/`.
+//! [`HashCache::prune`] and [`HashCache::invalidate`] back the `rclean cache`
+//! subcommands.
+
+use crate::hashing::HashAlgorithm;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// On-disk format version; caches with another version are discarded.
+const CACHE_VERSION: u32 = 1;
+
+/// File name of the cache inside the cache directory.
+const CACHE_FILE_NAME: &str = "hash-cache.json";
+
+/// Error types for hash cache operations
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("Failed to access hash cache {0}: {1}")]
+    Io(PathBuf, io::Error),
+
+    #[error("Corrupt hash cache {0}: {1}")]
+    Corrupt(PathBuf, serde_json::Error),
+}
+
+/// Result type for hash cache operations
+pub type CacheResult<T> = Result<T, CacheError>;
+
+/// Identity of a file's content as far as the cache is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub device: u64,
+    pub inode: u64,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub mtime_ns: i64,
+}
+
+impl CacheKey {
+    /// Build the key from a file's current metadata.
+    ///
+    /// Returns `None` when the file cannot be stat'ed, or on platforms without
+    /// stable device and inode numbers.
+    pub fn for_path(path: impl AsRef<Path>) -> Option<Self> {
+        fs::metadata(path)
+            .ok()
+            .and_then(|m| Self::from_metadata(&m))
+    }
+
+    /// Build the key from metadata that has already been read.
+    #[cfg(unix)]
+    pub fn from_metadata(metadata: &fs::Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt as _;
+
+        Some(Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.len(),
+            mtime_ns: metadata
+                .mtime()
+                .saturating_mul(1_000_000_000)
+                .saturating_add(metadata.mtime_nsec()),
+        })
+    }
+
+    /// Build the key from metadata that has already been read.
+    #[cfg(not(unix))]
+    pub fn from_metadata(_metadata: &fs::Metadata) -> Option<Self> {
+        None
+    }
+}
+
+/// Hashes remembered for one file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// Absolute path the file was last seen at (hardlinks share an entry).
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    /// Edge hashes from the partial hashing stage, per algorithm.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub partial: BTreeMap<HashAlgorithm, String>,
+    /// Full content hashes, per algorithm.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub full: BTreeMap<HashAlgorithm, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuzzy: Option<String>,
+    /// Unix timestamp (seconds) of the last scan that used this entry.
+    pub last_seen: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredEntry {
+    #[serde(flatten)]
+    key: CacheKey,
+    #[serde(flatten)]
+    entry: CacheEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredCache {
+    version: u32,
+    entries: Vec<StoredEntry>,
+}
+
+/// Summary of a cache's contents, as shown by `rclean cache inspect`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub full_hashes: BTreeMap<HashAlgorithm, usize>,
+    pub partial_hashes: usize,
+    pub fuzzy_hashes: usize,
+    /// Total size of the files covered by the cache.
+    pub covered_bytes: u64,
+    pub oldest_seen: Option<u64>,
+    pub newest_seen: Option<u64>,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Entries:        {}", self.entries)?;
+        writeln!(f, "Covered bytes:  {}", self.covered_bytes)?;
+        writeln!(f, "Partial hashes: {}", self.partial_hashes)?;
+        writeln!(f, "Fuzzy hashes:   {}", self.fuzzy_hashes)?;
+        for (algorithm, count) in &self.full_hashes {
+            writeln!(f, "Full {algorithm:<9} {count}")?;
+        }
+        match (self.oldest_seen, self.newest_seen) {
+            (Some(oldest), Some(newest)) => {
+                write!(f, "Last seen:      {oldest} .. {newest} (unix time)")
+            },
+            _ => write!(f, "Last seen:      -"),
+        }
+    }
+}
+
+/// Persistent map from [`CacheKey`] to the hashes of that file.
+///
+/// Lookups and inserts take `&self` so the cache can be shared by the rayon
+/// hashing loops; call [`HashCache::save`] once the scan is done.
+#[derive(Debug)]
+pub struct HashCache {
+    path: Option<PathBuf>,
+    entries: RwLock<HashMap<CacheKey, CacheEntry>>,
+    now: u64,
+}
+
+/// Absolute form of `path`, so entries from relative scans can be invalidated
+/// by prefix later.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Per-user cache directory of rclean: `$XDG_CACHE_HOME/rclean`, falling
+/// back to `~/.cache/rclean`.
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|dir| dir.join("rclean"))
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl Default for HashCache {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+impl HashCache {
+    /// Default cache location: `$XDG_CACHE_HOME/rclean/hash-cache.json`,
+    /// falling back to `~/.cache/rclean/` and then the current directory.
+    pub fn default_path() -> PathBuf {
+        cache_dir().map_or_else(
+            || PathBuf::from(".rclean-hash-cache.json"),
+            |dir| dir.join(CACHE_FILE_NAME),
+        )
+    }
+
+    /// An empty cache that is never written to disk.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            entries: RwLock::new(HashMap::new()),
+            now: unix_now(),
+        }
+    }
+
+    /// Load the cache at `path`, starting empty if it does not exist yet or
+    /// was written by an incompatible version.
+    pub fn open(path: impl Into<PathBuf>) -> CacheResult<Self> {
+        let path = path.into();
+        let entries = match fs::read(&path) {
+            Ok(bytes) => {
+                let stored: StoredCache = serde_json::from_slice(&bytes)
+                    .map_err(|e| CacheError::Corrupt(path.clone(), e))?;
+                if stored.version == CACHE_VERSION {
+                    stored
+                        .entries
+                        .into_iter()
+                        .map(|stored| (stored.key, stored.entry))
+                        .collect()
+                } else {
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(CacheError::Io(path, e)),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            entries: RwLock::new(entries),
+            now: unix_now(),
+        })
+    }
+
+    /// Location the cache is loaded from and saved to, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Write the cache back to its file atomically (see [`crate::atomic`]).
+    /// In-memory caches are not written.
+    pub fn save(&self) -> CacheResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut entries: Vec<StoredEntry> = self
+            .read()
+            .iter()
+            .map(|(key, entry)| StoredEntry {
+                key: *key,
+                entry: entry.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.entry.path.cmp(&b.entry.path));
+
+        let stored = StoredCache {
+            version: CACHE_VERSION,
+            entries,
+        };
+        let json = serde_json::to_vec(&stored).map_err(|e| CacheError::Corrupt(path.clone(), e))?;
+        crate::atomic::write(path, &json).map_err(|e| CacheError::Io(path.clone(), e))
+    }
+
+    /// Number of cached files.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Whether the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Copy of the entry for `key`, if cached.
+    pub fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
+        self.read().get(key).cloned()
+    }
+
+    /// Cached full content hash.
+    pub fn full_hash(&self, key: &CacheKey, algorithm: HashAlgorithm) -> Option<String> {
+        self.read()
+            .get(key)
+            .and_then(|entry| entry.full.get(&algorithm).cloned())
+    }
+
+    /// Cached partial (edge) hash.
+    pub fn partial_hash(&self, key: &CacheKey, algorithm: HashAlgorithm) -> Option<String> {
+        self.read()
+            .get(key)
+            .and_then(|entry| entry.partial.get(&algorithm).cloned())
+    }
+
+    /// Cached fuzzy hash.
+    pub fn fuzzy_hash(&self, key: &CacheKey) -> Option<String> {
+        self.read().get(key).and_then(|entry| entry.fuzzy.clone())
+    }
+
+    /// Record a full content hash for the file at `path`.
+    pub fn insert_full(&self, key: CacheKey, path: &Path, algorithm: HashAlgorithm, digest: &str) {
+        self.update(key, path, |entry| {
+            entry.full.insert(algorithm, digest.to_string());
+        });
+    }
+
+    /// Record a partial (edge) hash for the file at `path`.
+    pub fn insert_partial(
+        &self,
+        key: CacheKey,
+        path: &Path,
+        algorithm: HashAlgorithm,
+        digest: &str,
+    ) {
+        self.update(key, path, |entry| {
+            entry.partial.insert(algorithm, digest.to_string());
+        });
+    }
+
+    /// Record a fuzzy hash for the file at `path`.
+    pub fn insert_fuzzy(&self, key: CacheKey, path: &Path, digest: &str) {
+        self.update(key, path, |entry| entry.fuzzy = Some(digest.to_string()));
+    }
+
+    /// Mark an entry as used by the current scan so age-based pruning keeps it.
+    pub fn touch(&self, key: &CacheKey, path: &Path) {
+        let path = absolute(path);
+        if let Some(entry) = self.write().get_mut(key) {
+            entry.last_seen = self.now;
+            entry.path = path;
+        }
+    }
+
+    fn update(&self, key: CacheKey, path: &Path, apply: impl FnOnce(&mut CacheEntry)) {
+        let path = absolute(path);
+        let mut entries = self.write();
+        let entry = entries.entry(key).or_default();
+        entry.path = path;
+        entry.last_seen = self.now;
+        apply(entry);
+    }
+
+    /// Summarize the cache contents.
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.read();
+        let mut stats = CacheStats {
+            entries: entries.len(),
+            ..CacheStats::default()
+        };
+        for (key, entry) in entries.iter() {
+            stats.covered_bytes += key.size;
+            stats.partial_hashes += entry.partial.len();
+            stats.fuzzy_hashes += usize::from(entry.fuzzy.is_some());
+            for algorithm in entry.full.keys() {
+                *stats.full_hashes.entry(*algorithm).or_default() += 1;
+            }
+            stats.oldest_seen = Some(
+                stats
+                    .oldest_seen
+                    .map_or(entry.last_seen, |t| t.min(entry.last_seen)),
+            );
+            stats.newest_seen = Some(
+                stats
+                    .newest_seen
+                    .map_or(entry.last_seen, |t| t.max(entry.last_seen)),
+            );
+        }
+        stats
+    }
+
+    /// Cached entries whose path, as shown by [`crate::paths::display`],
+    /// contains `filter`, sorted by path.
+    pub fn entries(&self, filter: Option<&str>) -> Vec<(CacheKey, CacheEntry)> {
+        let mut entries: Vec<(CacheKey, CacheEntry)> = self
+            .read()
+            .iter()
+            .filter(|(_, entry)| {
+                filter.is_none_or(|f| crate::paths::display(&entry.path).contains(f))
+            })
+            .map(|(key, entry)| (*key, entry.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.1.path.cmp(&b.1.path));
+        entries
+    }
+
+    /// Drop stale entries: files that are gone or whose key no longer matches,
+    /// plus entries not seen for more than `max_age_secs` when given.
+    ///
+    /// Returns the number of entries removed.
+    pub fn prune(&self, max_age_secs: Option<u64>) -> usize {
+        let mut entries = self.write();
+        let before = entries.len();
+        let now = self.now;
+        entries.retain(|key, entry| {
+            let fresh = max_age_secs.is_none_or(|age| now.saturating_sub(entry.last_seen) <= age);
+            fresh && CacheKey::for_path(&entry.path).as_ref() == Some(key)
+        });
+        before - entries.len()
+    }
+
+    /// Remove entries whose path starts with `prefix`, or every entry when
+    /// `prefix` is `None`. Returns the number of entries removed.
+    pub fn invalidate(&self, prefix: Option<&Path>) -> usize {
+        let mut entries = self.write();
+        let before = entries.len();
+        match prefix {
+            Some(prefix) => entries.retain(|_, entry| !entry.path.starts_with(prefix)),
+            None => entries.clear(),
+        }
+        before - entries.len()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<CacheKey, CacheEntry>> {
+        self.entries
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<CacheKey, CacheEntry>> {
+        self.entries
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_roundtrip_through_disk() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "content").unwrap();
+        let key = CacheKey::for_path(&file).unwrap();
+
+        let cache_path = dir.path().join("nested").join("cache.json");
+        let cache = HashCache::open(&cache_path).unwrap();
+        assert!(cache.is_empty());
+        cache.insert_full(key, &file, HashAlgorithm::Blake3, "abc");
+        cache.insert_fuzzy(key, &file, "3:abc:abc");
+        cache.save().unwrap();
+
+        let reloaded = HashCache::open(&cache_path).unwrap();
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(
+            reloaded.full_hash(&key, HashAlgorithm::Blake3).as_deref(),
+            Some("abc")
+        );
+        assert!(reloaded.full_hash(&key, HashAlgorithm::Md5).is_none());
+        assert_eq!(reloaded.fuzzy_hash(&key).as_deref(), Some("3:abc:abc"));
+    }
+
+    #[test]
+    fn test_modified_file_changes_key() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "one").unwrap();
+        let before = CacheKey::for_path(&file).unwrap();
+        fs::write(&file, "three").unwrap();
+        let after = CacheKey::for_path(&file).unwrap();
+        assert_ne!(before, after);
+        assert_eq!(before.inode, after.inode);
+    }
+
+    #[test]
+    fn test_prune_drops_missing_and_changed_files() {
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("kept");
+        let removed = dir.path().join("removed");
+        let changed = dir.path().join("changed");
+        for file in [&kept, &removed, &changed] {
+            fs::write(file, "x").unwrap();
+        }
+
+        let cache = HashCache::in_memory();
+        for file in [&kept, &removed, &changed] {
+            let key = CacheKey::for_path(file).unwrap();
+            cache.insert_full(key, file, HashAlgorithm::Md5, "h");
+        }
+        fs::remove_file(&removed).unwrap();
+        fs::write(&changed, "longer").unwrap();
+
+        assert_eq!(cache.prune(None), 2);
+        assert_eq!(cache.entries(None).len(), 1);
+        assert_eq!(cache.entries(None)[0].1.path, kept);
+    }
+
+    #[test]
+    fn test_invalidate_by_prefix() {
+        let cache = HashCache::in_memory();
+        for (inode, path) in [(1, "/data/a/x"), (2, "/data/a/y"), (3, "/data/ab/z")] {
+            let key = CacheKey {
+                device: 1,
+                inode,
+                size: 1,
+                mtime_ns: 0,
+            };
+            cache.insert_full(key, Path::new(path), HashAlgorithm::Md5, "h");
+        }
+
+        assert_eq!(cache.invalidate(Some(Path::new("/data/a"))), 2);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats().full_hashes[&HashAlgorithm::Md5], 1);
+        assert_eq!(cache.invalidate(None), 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_incompatible_version_starts_empty() {
+        let dir = TempDir::new().unwrap();
+        let cache_path = dir.path().join("cache.json");
+        fs::write(&cache_path, r#"{"version":0,"entries":[]}"#).unwrap();
+        assert!(HashCache::open(&cache_path).unwrap().is_empty());
+
+        fs::write(&cache_path, "not json").unwrap();
+        assert!(matches!(
+            HashCache::open(&cache_path),
+            Err(CacheError::Corrupt(..))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_survives_save() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new().unwrap();
+        let latin1 = dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9"));
+        fs::create_dir(&latin1).unwrap();
+        let file = latin1.join("a.txt");
+        fs::write(&file, "content").unwrap();
+        let key = CacheKey::for_path(&file).unwrap();
+
+        let cache_path = dir.path().join("cache.json");
+        let cache = HashCache::open(&cache_path).unwrap();
+        cache.insert_full(key, &file, HashAlgorithm::Blake3, "abc");
+        cache.save().unwrap();
+
+        let reloaded = HashCache::open(&cache_path).unwrap();
+        assert_eq!(reloaded.entries(None)[0].1.path, file);
+        assert_eq!(reloaded.prune(None), 0);
+        assert_eq!(reloaded.invalidate(Some(&latin1)), 1);
+    }
+}
@@ -0,0 +1,115 @@
+//! Cooperative cancellation of long scans.
+//!
+//! A [`CancelToken`] is checked before each directory entry while walking and
+//! before each file while stat'ing and hashing. Once it has been cancelled,
+//! or its deadline has passed, the scan stops early and returns what it
+//! gathered so far, with [`ScanDiagnostics::interrupted`] set so that the
+//! result is recognisably incomplete. A file that is already being read is
+//! finished first.
+//!
+//! [`ScanDiagnostics::interrupted`]: crate::ScanDiagnostics::interrupted
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Why a scan stopped before it was done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interruption {
+    /// [`CancelToken::cancel`] was called
+    Cancelled,
+    /// The token's deadline passed
+    DeadlineExceeded,
+}
+
+impl fmt::Display for Interruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::DeadlineExceeded => write!(f, "stopped at its deadline"),
+        }
+    }
+}
+
+/// Handle used to stop a running scan from another thread.
+///
+/// Clones share the cancelled flag, so a clone kept by the caller cancels
+/// the scan that received the original. The default token never expires.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The same token, also expiring at `deadline`.
+    #[must_use]
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// The same token, also expiring `timeout` from now.
+    #[must_use]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self,
+        }
+    }
+
+    /// Ask every scan holding this token, or a clone of it, to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Why work should stop, or `None` while it may go on.
+    pub fn interruption(&self) -> Option<Interruption> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Some(Interruption::Cancelled)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Some(Interruption::DeadlineExceeded)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.interruption().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_cancellation() {
+        let token = CancelToken::new();
+        let scan = token.clone();
+        assert_eq!(scan.interruption(), None);
+        token.cancel();
+        assert_eq!(scan.interruption(), Some(Interruption::Cancelled));
+    }
+
+    #[test]
+    fn test_deadline() {
+        let expired = CancelToken::new().with_deadline(Instant::now());
+        assert_eq!(expired.interruption(), Some(Interruption::DeadlineExceeded));
+
+        let later = CancelToken::new().with_timeout(Duration::from_secs(3600));
+        assert!(!later.is_cancelled());
+        later.cancel();
+        assert_eq!(later.interruption(), Some(Interruption::Cancelled));
+    }
+}
@@ -418,6 +418,7 @@ mod tests {
         SimpleFileInfo {
             path: PathBuf::from(path),
             size_bytes: size,
+            allocated_bytes: size,
             ssdeep_hash: hash,
         }
     }
@@ -664,6 +665,7 @@ mod property_tests {
                 SimpleFileInfo {
                     path: std::path::PathBuf::from(format!("file_{}.dat", i)),
                     size_bytes: 1024 * (i as u64 + 1),
+                    allocated_bytes: 1024 * (i as u64 + 1),
                     ssdeep_hash: Some(format!("3:{}:{}", base_hash, i % 5)),
                 }
             }).collect()
@@ -0,0 +1,249 @@
+//! Paths skipped during a scan, and why.
+//!
+//! Walking and hashing never stop at a single unreadable entry. Instead, every
+//! path that had to be left out is recorded in a [`ScanDiagnostics`] together
+//! with a [`SkipReason`], and the scanning functions return it alongside their
+//! result so that the CLI and the MCP server can show what was not analyzed.
+//! A scan stopped by a [`CancelToken`] records why in the same place.
+
+use crate::cancel::{CancelToken, Interruption};
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Skipped paths listed by [`ScanDiagnostics`]'s `Display` before the rest
+/// are summarised.
+const DISPLAY_LIMIT: usize = 20;
+
+/// Why a path was left out of a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The path could not be read for lack of permissions
+    PermissionDenied,
+    /// The path disappeared between being listed and being read
+    Vanished,
+    /// Any other I/O failure
+    IoError,
+    /// A directory on another filesystem, skipped because of
+    /// [`crate::WalkOptions::same_file_system`]
+    OtherFilesystem,
+    /// A symlinked directory leading back to one of its ancestors
+    SymlinkLoop,
+}
+
+impl SkipReason {
+    /// Reason matching an I/O error.
+    pub fn of_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::NotFound => Self::Vanished,
+            _ => Self::IoError,
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::PermissionDenied => "permission denied",
+            Self::Vanished => "vanished",
+            Self::IoError => "I/O error",
+            Self::OtherFilesystem => "on another filesystem",
+            Self::SymlinkLoop => "symlink loop",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// One path left out of a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedPath {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    pub reason: SkipReason,
+    /// The underlying error message, if there was one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl fmt::Display for SkippedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", paths::display(&self.path), self.reason)?;
+        if let Some(message) = &self.message {
+            write!(f, " ({message})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every path skipped while producing a result.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanDiagnostics {
+    pub skipped: Vec<SkippedPath>,
+    /// Set when the scan was stopped early; the result then only covers the
+    /// files reached before that
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interrupted: Option<Interruption>,
+}
+
+impl ScanDiagnostics {
+    /// Record `path` as skipped for `reason`.
+    pub fn push(&mut self, path: impl Into<PathBuf>, reason: SkipReason) {
+        self.skipped.push(SkippedPath {
+            path: path.into(),
+            reason,
+            message: None,
+        });
+    }
+
+    /// Record `path` as skipped because of `error`.
+    pub fn push_io(&mut self, path: impl Into<PathBuf>, error: &io::Error) {
+        self.skipped.push(SkippedPath {
+            path: path.into(),
+            reason: SkipReason::of_io(error),
+            message: Some(error.to_string()),
+        });
+    }
+
+    /// Record `path` as skipped because of `error`.
+    pub fn push_error(&mut self, path: impl Into<PathBuf>, error: &crate::RcleanError) {
+        self.skipped.push(SkippedPath {
+            path: path.into(),
+            reason: error.skip_reason(),
+            message: Some(error.to_string()),
+        });
+    }
+
+    /// Record why `cancel` stopped a stage before it reached every item.
+    /// The first interruption recorded is kept.
+    pub fn interrupt(&mut self, cancel: &CancelToken) {
+        self.interrupted = self.interrupted.or(cancel.interruption());
+    }
+
+    /// Append the paths skipped by another stage.
+    pub fn extend(&mut self, other: Self) {
+        self.skipped.extend(other.skipped);
+        self.interrupted = self.interrupted.or(other.interrupted);
+    }
+
+    /// Whether the scan ran to the end (paths may still have been skipped).
+    pub fn is_complete(&self) -> bool {
+        self.interrupted.is_none()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.skipped.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.skipped.len()
+    }
+
+    /// Number of paths skipped for `reason`.
+    pub fn count(&self, reason: SkipReason) -> usize {
+        self.skipped.iter().filter(|s| s.reason == reason).count()
+    }
+
+    /// Paths skipped for `reason`.
+    pub fn paths(&self, reason: SkipReason) -> impl Iterator<Item = &Path> {
+        self.skipped
+            .iter()
+            .filter(move |s| s.reason == reason)
+            .map(|s| s.path.as_path())
+    }
+}
+
+impl fmt::Display for ScanDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(interruption) = self.interrupted {
+            write!(f, "Scan {interruption}, results are incomplete")?;
+            if self.is_empty() {
+                return Ok(());
+            }
+            writeln!(f)?;
+        }
+        let mut reasons: Vec<SkipReason> = self.skipped.iter().map(|s| s.reason).collect();
+        reasons.sort();
+        reasons.dedup();
+        let counts: Vec<String> = reasons
+            .into_iter()
+            .map(|reason| format!("{} {reason}", self.count(reason)))
+            .collect();
+        write!(f, "Skipped {} paths: {}", self.len(), counts.join(", "))?;
+        for skipped in self.skipped.iter().take(DISPLAY_LIMIT) {
+            write!(f, "\n  {skipped}")?;
+        }
+        if self.len() > DISPLAY_LIMIT {
+            write!(f, "\n  ... and {} more", self.len() - DISPLAY_LIMIT)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reasons_are_classified_and_summarised() {
+        let mut diagnostics = ScanDiagnostics::default();
+        diagnostics.push_io(
+            "/data/private",
+            &io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        diagnostics.push_io("/data/tmp.part", &io::Error::from(io::ErrorKind::NotFound));
+        let mut later = ScanDiagnostics::default();
+        later.push("/data/loop", SkipReason::SymlinkLoop);
+        diagnostics.extend(later);
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics.count(SkipReason::Vanished), 1);
+        assert_eq!(
+            diagnostics
+                .paths(SkipReason::SymlinkLoop)
+                .collect::<Vec<_>>(),
+            vec![Path::new("/data/loop")]
+        );
+        let text = diagnostics.to_string();
+        assert!(
+            text.starts_with("Skipped 3 paths: 1 permission denied, 1 vanished, 1 symlink loop")
+        );
+        assert!(text.contains("\n  /data/loop: symlink loop"));
+
+        let json = serde_json::to_value(&diagnostics).unwrap();
+        assert_eq!(json["skipped"][0]["reason"], "permission_denied");
+        assert!(json["skipped"][2].get("message").is_none());
+        assert!(json.get("interrupted").is_none());
+    }
+
+    #[test]
+    fn test_interruption_is_kept_and_shown() {
+        let mut diagnostics = ScanDiagnostics::default();
+        diagnostics.interrupt(&CancelToken::new());
+        assert!(diagnostics.is_complete());
+
+        let token = CancelToken::new();
+        token.cancel();
+        let mut stage = ScanDiagnostics::default();
+        stage.interrupt(&token);
+        diagnostics.extend(stage);
+        assert_eq!(diagnostics.interrupted, Some(Interruption::Cancelled));
+        assert_eq!(
+            diagnostics.to_string(),
+            "Scan cancelled, results are incomplete"
+        );
+
+        diagnostics.push("/data/loop", SkipReason::SymlinkLoop);
+        assert!(diagnostics
+            .to_string()
+            .starts_with("Scan cancelled, results are incomplete\nSkipped 1 paths"));
+        let json = serde_json::to_value(&diagnostics).unwrap();
+        assert_eq!(json["interrupted"], "cancelled");
+    }
+}
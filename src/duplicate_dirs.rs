@@ -0,0 +1,457 @@
+//! Duplicate directory trees.
+//!
+//! File-level dedupe reports a copied project of 40,000 files as 40,000
+//! duplicate groups. This pass hashes every directory Merkle-style, from the
+//! names and content hashes of its files and the names and hashes of its
+//! subdirectories, so identical trees share one hash and can be reported as
+//! a single [`DuplicateDir`]. A directory whose every file also exists, at
+//! the same relative path and with the same content, under another directory
+//! holding more is reported as a subset of it.
+//!
+//! Only the topmost match is reported: once a copy is reported, nothing
+//! beneath it is, and its files are left out of the file-level duplicate
+//! groups (see [`mark_covered`]). Directories are compared on the scanned
+//! files only, so with a pattern or filter, files it excludes and empty
+//! subdirectories do not count, and archive members are compared as part of
+//! their archive only. A directory holding a file that was not hashed or
+//! could not be read is never reported as a copy.
+
+use crate::paths::display;
+use crate::roots::ScanRoot;
+use crate::{FileInfo, ScanDiagnostics};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How the copies of a [`DuplicateDir`] relate to the directory kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirMatchKind {
+    /// The copies hold exactly the same tree
+    Identical,
+    /// Everything in the copy also exists in the kept directory, which holds
+    /// more
+    Subset,
+}
+
+impl fmt::Display for DirMatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Identical => write!(f, "identical"),
+            Self::Subset => write!(f, "subset"),
+        }
+    }
+}
+
+/// Directories whose whole content also exists under `keep`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateDir {
+    pub kind: DirMatchKind,
+    /// The copy kept: a reference copy if there is one, else the first by
+    /// path; for a subset, the directory that contains it
+    #[serde(with = "crate::paths::serde_path")]
+    pub keep: PathBuf,
+    /// Copies that could be removed; never under a reference root
+    #[serde(with = "crate::paths::serde_paths")]
+    pub copies: Vec<PathBuf>,
+    /// Files in one copy
+    pub files: usize,
+    /// Total size of one copy
+    pub size_bytes: u64,
+    /// Bytes freed by removing every copy, hardlinks excluded
+    pub reclaimable_bytes: u64,
+}
+
+impl fmt::Display for DuplicateDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let copies: Vec<_> = self.copies.iter().map(|copy| display(copy)).collect();
+        write!(
+            f,
+            "{} ({}, {} files, {} bytes): {} ({} bytes reclaimable)",
+            display(&self.keep),
+            self.kind,
+            self.files,
+            self.size_bytes,
+            copies.join(", "),
+            self.reclaimable_bytes
+        )
+    }
+}
+
+/// One directory below a scan root, with totals over its whole subtree.
+#[derive(Debug, Default)]
+struct DirNode {
+    /// Indices of the files directly inside
+    files: Vec<usize>,
+    subdirs: BTreeSet<PathBuf>,
+    /// Indices of every file in the subtree
+    all_files: Vec<usize>,
+    size_bytes: u64,
+    physical_bytes: u64,
+    /// Longest chain of subdirectories below
+    height: usize,
+    /// Merkle hash; `None` when some file below has no content hash
+    hash: Option<String>,
+}
+
+/// Directories under the scan roots, built from the scanned files.
+struct Tree<'a> {
+    file_infos: &'a [FileInfo],
+    dirs: BTreeMap<PathBuf, DirNode>,
+}
+
+impl<'a> Tree<'a> {
+    fn new(file_infos: &'a [FileInfo], roots: &[ScanRoot], diagnostics: &ScanDiagnostics) -> Self {
+        let mut dirs: BTreeMap<PathBuf, DirNode> = BTreeMap::new();
+        for (index, file_info) in file_infos.iter().enumerate() {
+            // Archive members are not in any directory of their own
+            let Some(root) = roots
+                .iter()
+                .find(|root| file_info.path.starts_with(&root.path))
+                .filter(|_| file_info.archive.is_none())
+            else {
+                continue;
+            };
+            let mut child: Option<&Path> = None;
+            for dir in file_info.path.ancestors().skip(1) {
+                let node = dirs.entry(dir.to_path_buf()).or_default();
+                match child {
+                    Some(child) => {
+                        node.subdirs.insert(child.to_path_buf());
+                    },
+                    None => node.files.push(index),
+                }
+                node.all_files.push(index);
+                node.size_bytes += file_info.size_bytes;
+                node.physical_bytes += file_info.physical_size_bytes;
+                if dir == root.path {
+                    break;
+                }
+                child = Some(dir);
+            }
+        }
+
+        // Directories with unreadable entries are incomplete
+        let tainted: HashSet<&Path> = diagnostics
+            .skipped
+            .iter()
+            .flat_map(|skipped| skipped.path.ancestors())
+            .collect();
+
+        let mut tree = Self { file_infos, dirs };
+        let mut order: Vec<PathBuf> = tree.dirs.keys().cloned().collect();
+        order.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        for dir in order {
+            let (height, hash) = if tainted.contains(dir.as_path()) {
+                (tree.height(&dir), None)
+            } else {
+                (tree.height(&dir), tree.merkle_hash(&dir))
+            };
+            if let Some(node) = tree.dirs.get_mut(&dir) {
+                node.height = height;
+                node.hash = hash;
+            }
+        }
+        tree
+    }
+
+    fn height(&self, dir: &Path) -> usize {
+        self.dirs[dir]
+            .subdirs
+            .iter()
+            .map(|subdir| self.dirs[subdir].height + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Hash of the sorted names and hashes of the entries of `dir`, whose
+    /// subdirectories have already been hashed.
+    fn merkle_hash(&self, dir: &Path) -> Option<String> {
+        let node = &self.dirs[dir];
+        let mut entries: Vec<(&[u8], u8, &str)> = Vec::new();
+        for &index in &node.files {
+            let file_info = &self.file_infos[index];
+            let name = file_info.path.file_name()?;
+            entries.push((
+                name.as_encoded_bytes(),
+                b'f',
+                file_info.content_hash.as_deref()?,
+            ));
+        }
+        for subdir in &node.subdirs {
+            let name = subdir.file_name()?;
+            entries.push((
+                name.as_encoded_bytes(),
+                b'd',
+                self.dirs[subdir].hash.as_deref()?,
+            ));
+        }
+        entries.sort_unstable();
+
+        let mut hasher = blake3::Hasher::new();
+        for (name, kind, hash) in entries {
+            hasher.update(&[kind]);
+            hasher.update(name);
+            hasher.update(&[0]);
+            hasher.update(hash.as_bytes());
+            hasher.update(&[0]);
+        }
+        Some(hasher.finalize().to_hex().to_string())
+    }
+
+    fn entry(&self, kind: DirMatchKind, keep: PathBuf, copies: Vec<PathBuf>) -> DuplicateDir {
+        let node = &self.dirs[&keep];
+        let (files, size_bytes) = match kind {
+            DirMatchKind::Identical => (node.all_files.len(), node.size_bytes),
+            DirMatchKind::Subset => {
+                let copy = &self.dirs[&copies[0]];
+                (copy.all_files.len(), copy.size_bytes)
+            },
+        };
+        DuplicateDir {
+            kind,
+            reclaimable_bytes: copies
+                .iter()
+                .map(|copy| self.dirs[copy].physical_bytes)
+                .sum(),
+            keep,
+            copies,
+            files,
+            size_bytes,
+        }
+    }
+}
+
+fn is_covered(path: &Path, covered: &HashSet<PathBuf>) -> bool {
+    path.ancestors().any(|dir| covered.contains(dir))
+}
+
+/// Find identical directory trees, and directories that are subsets of
+/// others, among the directories holding `file_infos` under `roots`.
+///
+/// `file_infos` must carry content hashes and hardlink and reference flags.
+/// Trees made up only of empty files are left to [`crate::empty_dirs`].
+pub fn find_duplicate_dirs(
+    file_infos: &[FileInfo],
+    roots: &[ScanRoot],
+    diagnostics: &ScanDiagnostics,
+) -> Vec<DuplicateDir> {
+    let tree = Tree::new(file_infos, roots, diagnostics);
+    let references = crate::roots::reference_paths(roots);
+    let is_reference = |dir: &Path| crate::roots::is_under_reference(dir, &references);
+
+    let mut by_hash: HashMap<&str, Vec<&Path>> = HashMap::new();
+    for (dir, node) in &tree.dirs {
+        if let Some(hash) = node.hash.as_deref() {
+            by_hash.entry(hash).or_default().push(dir);
+        }
+    }
+    let mut files_by_hash: HashMap<&str, Vec<&Path>> = HashMap::new();
+    let mut hash_of: HashMap<&Path, &str> = HashMap::new();
+    for file_info in file_infos {
+        if let Some(hash) = file_info.content_hash.as_deref() {
+            files_by_hash.entry(hash).or_default().push(&file_info.path);
+            hash_of.insert(&file_info.path, hash);
+        }
+    }
+
+    // A directory is looked at before everything it contains, so only the
+    // topmost copy is reported
+    let mut order: Vec<(&PathBuf, &DirNode)> = tree
+        .dirs
+        .iter()
+        .filter(|(_, node)| node.size_bytes > 0)
+        .collect();
+    order.sort_by(|(a, a_node), (b, b_node)| {
+        b_node.height.cmp(&a_node.height).then_with(|| a.cmp(b))
+    });
+
+    let mut found = Vec::new();
+    let mut covered: HashSet<PathBuf> = HashSet::new();
+    let mut grouped: HashSet<&str> = HashSet::new();
+    for (dir, node) in order {
+        let Some(hash) = node.hash.as_deref() else {
+            continue;
+        };
+        if is_covered(dir, &covered) {
+            continue;
+        }
+
+        if grouped.insert(hash) {
+            let members: Vec<&Path> = by_hash[hash]
+                .iter()
+                .copied()
+                .filter(|member| !is_covered(member, &covered))
+                .collect();
+            let keep = members
+                .iter()
+                .copied()
+                .find(|member| is_reference(member))
+                .unwrap_or(dir);
+            let copies: Vec<PathBuf> = members
+                .iter()
+                .filter(|&&member| member != keep && !is_reference(member))
+                .map(|member| member.to_path_buf())
+                .collect();
+            if !copies.is_empty() {
+                covered.extend(copies.iter().cloned());
+                found.push(tree.entry(DirMatchKind::Identical, keep.to_path_buf(), copies));
+                continue;
+            }
+        }
+        if is_covered(dir, &covered) || is_reference(dir) {
+            continue;
+        }
+
+        // Subset: every file at the same relative path and with the same
+        // content under a directory holding more
+        let relative = |index: usize| file_infos[index].path.strip_prefix(dir).ok();
+        // Start from the file with the fewest copies
+        let Some(&first) = node.all_files.iter().min_by_key(|&&index| {
+            file_infos[index]
+                .content_hash
+                .as_deref()
+                .map_or(0, |hash| files_by_hash[hash].len())
+        }) else {
+            continue;
+        };
+        let (Some(first_relative), Some(first_hash)) =
+            (relative(first), file_infos[first].content_hash.as_deref())
+        else {
+            continue;
+        };
+        let depth = first_relative.components().count();
+
+        let mut supersets: Vec<&Path> = files_by_hash[first_hash]
+            .iter()
+            .filter(|path| path.ends_with(first_relative))
+            .filter_map(|path| path.ancestors().nth(depth))
+            .filter(|superset| {
+                !superset.starts_with(dir)
+                    && !dir.starts_with(superset)
+                    && !is_covered(superset, &covered)
+                    && tree
+                        .dirs
+                        .get(*superset)
+                        .is_some_and(|other| other.all_files.len() > node.all_files.len())
+            })
+            .collect();
+        supersets.sort();
+        supersets.dedup();
+        let superset = supersets.into_iter().find(|superset| {
+            node.all_files.iter().all(|&index| {
+                let (Some(relative), Some(hash)) =
+                    (relative(index), file_infos[index].content_hash.as_deref())
+                else {
+                    return false;
+                };
+                hash_of.get(superset.join(relative).as_path()) == Some(&hash)
+            })
+        });
+        if let Some(superset) = superset {
+            covered.insert(dir.clone());
+            found.push(tree.entry(
+                DirMatchKind::Subset,
+                superset.to_path_buf(),
+                vec![dir.clone()],
+            ));
+        }
+    }
+
+    found
+}
+
+/// Flag the files inside a reported copy with
+/// [`FileInfo::in_duplicate_dir`], so they are not grouped again file by
+/// file.
+pub fn mark_covered(file_infos: &mut [FileInfo], duplicate_dirs: &[DuplicateDir]) {
+    let copies: HashSet<PathBuf> = duplicate_dirs
+        .iter()
+        .flat_map(|dir| dir.copies.iter().cloned())
+        .collect();
+    for file_info in file_infos {
+        file_info.in_duplicate_dir = is_covered(&file_info.path, &copies);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_tree(root: &Path, files: &[(&str, &str)]) {
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    fn scan(root: &Path) -> Vec<DuplicateDir> {
+        let files = crate::walk(root).unwrap();
+        let (file_infos, _, diagnostics) = crate::collect_file_info_with_report(
+            &files,
+            crate::HashAlgorithm::Md5,
+            None,
+            crate::hashing::MemoryBudget::global(),
+            &crate::progress::NoProgress,
+            &crate::CancelToken::default(),
+        )
+        .unwrap();
+        find_duplicate_dirs(&file_infos, &[ScanRoot::candidate(root)], &diagnostics)
+    }
+
+    #[test]
+    fn test_identical_trees_are_reported_once() {
+        let dir = TempDir::new().unwrap();
+        let project = [
+            ("src/main.rs", "fn main() {}"),
+            ("src/lib/mod.rs", "pub mod a;"),
+            ("README.md", "readme"),
+        ];
+        for copy in ["project", "backup/project-copy"] {
+            let files: Vec<(String, &str)> = project
+                .iter()
+                .map(|(path, content)| (format!("{copy}/{path}"), *content))
+                .collect();
+            let files: Vec<(&str, &str)> = files.iter().map(|(p, c)| (p.as_str(), *c)).collect();
+            write_tree(dir.path(), &files);
+        }
+        // Same content under another name is not part of the tree
+        write_tree(dir.path(), &[("other/start.rs", "fn main() {}")]);
+
+        let found = scan(dir.path());
+        assert_eq!(found.len(), 1, "{found:?}");
+        assert_eq!(found[0].kind, DirMatchKind::Identical);
+        assert_eq!(found[0].keep, dir.path().join("backup/project-copy"));
+        assert_eq!(found[0].copies, vec![dir.path().join("project")]);
+        assert_eq!(found[0].files, 3);
+        assert_eq!(found[0].reclaimable_bytes, 12 + 10 + 6);
+    }
+
+    #[test]
+    fn test_renamed_file_breaks_identity_but_subset_is_found() {
+        let dir = TempDir::new().unwrap();
+        write_tree(
+            dir.path(),
+            &[
+                ("photos/2020/a.jpg", "aaaa"),
+                ("photos/2020/b.jpg", "bbbb"),
+                ("photos/2021/c.jpg", "cccc"),
+                ("usb/2020/a.jpg", "aaaa"),
+                ("usb/2020/b.jpg", "bbbb"),
+                ("renamed/2020/a.jpg", "aaaa"),
+                ("renamed/2020/b2.jpg", "bbbb"),
+            ],
+        );
+
+        let found = scan(dir.path());
+        assert_eq!(found.len(), 1, "{found:?}");
+        assert_eq!(found[0].kind, DirMatchKind::Subset);
+        assert_eq!(found[0].keep, dir.path().join("photos"));
+        assert_eq!(found[0].copies, vec![dir.path().join("usb")]);
+        assert_eq!((found[0].files, found[0].size_bytes), (2, 8));
+    }
+}
@@ -0,0 +1,373 @@
+//! Empty directory detection and pruning.
+//!
+//! A directory is reported when nothing worth keeping is left under it: no
+//! entries at all, only zero-byte files, or only files that operating systems
+//! and file managers leave behind ([`JUNK_FILES`], e.g. `.DS_Store`), possibly
+//! spread over nested directories of the same kind. Only the topmost such
+//! directory below the scan root is reported, since pruning it removes
+//! everything beneath, deepest entries first. Symlinks, special files and
+//! unreadable directories count as content, so they are never pruned.
+//!
+//! The scan looks at every entry, hidden or ignored, because a directory
+//! holding only a hidden file is not empty.
+
+use crate::cancel::CancelToken;
+use crate::paths::display;
+use crate::ScanDiagnostics;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File names (compared case-insensitively) that do not keep a directory
+/// from being empty.
+pub const JUNK_FILES: &[&str] = &[".DS_Store", "Thumbs.db", "ehthumbs.db", "desktop.ini"];
+
+/// What is left in a directory that holds nothing worth keeping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyDirKind {
+    /// No files at all, at most nested empty directories
+    #[default]
+    Empty,
+    /// Zero-byte files only
+    EmptyFiles,
+    /// Junk files, possibly alongside zero-byte files
+    Junk,
+}
+
+impl fmt::Display for EmptyDirKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty"),
+            Self::EmptyFiles => write!(f, "empty files only"),
+            Self::Junk => write!(f, "junk files only"),
+        }
+    }
+}
+
+/// A directory that can be pruned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmptyDir {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    pub kind: EmptyDirKind,
+    /// Empty and junk files that go with the directory
+    pub files: usize,
+    /// Their total size
+    pub size_bytes: u64,
+}
+
+/// Totals for a directory tree with nothing worth keeping.
+#[derive(Debug, Clone, Copy, Default)]
+struct Contents {
+    kind: EmptyDirKind,
+    files: usize,
+    size_bytes: u64,
+}
+
+impl Contents {
+    fn add(&mut self, other: Self) {
+        self.kind = self.kind.max(other.kind);
+        self.files += other.files;
+        self.size_bytes += other.size_bytes;
+    }
+}
+
+fn is_junk(name: &std::ffi::OsStr) -> bool {
+    let name = name.to_string_lossy();
+    JUNK_FILES
+        .iter()
+        .any(|junk| junk.eq_ignore_ascii_case(&name))
+}
+
+/// Contents of a regular file that does not keep its directory, or `None`.
+fn removable_file(path: &Path, metadata: &fs::Metadata) -> Option<Contents> {
+    let kind = if path.file_name().is_some_and(is_junk) {
+        EmptyDirKind::Junk
+    } else if metadata.len() == 0 {
+        EmptyDirKind::EmptyFiles
+    } else {
+        return None;
+    };
+    Some(Contents {
+        kind,
+        files: 1,
+        size_bytes: metadata.len(),
+    })
+}
+
+/// Everything found while scanning one tree.
+struct Scan<'a> {
+    found: Vec<EmptyDir>,
+    diagnostics: ScanDiagnostics,
+    cancel: &'a CancelToken,
+}
+
+impl Scan<'_> {
+    /// Contents of `dir` if nothing under it is worth keeping. Otherwise its
+    /// prunable subdirectories are recorded, as are those of the scan root.
+    fn dir(&mut self, dir: &Path, is_root: bool) -> Option<Contents> {
+        if self.cancel.is_cancelled() {
+            self.diagnostics.interrupt(self.cancel);
+            return None;
+        }
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.diagnostics.push_io(dir, &e);
+                return None;
+            },
+        };
+
+        let mut contents = Some(Contents::default());
+        let mut prunable = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.diagnostics.push_io(dir, &e);
+                    contents = None;
+                    continue;
+                },
+            };
+            let path = entry.path();
+            let item = match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => {
+                    let child = self.dir(&path, false);
+                    if let Some(child) = child {
+                        prunable.push((path, child));
+                    }
+                    child
+                },
+                Ok(file_type) if file_type.is_file() => match entry.metadata() {
+                    Ok(metadata) => removable_file(&path, &metadata),
+                    Err(e) => {
+                        self.diagnostics.push_io(&path, &e);
+                        None
+                    },
+                },
+                Ok(_) => None,
+                Err(e) => {
+                    self.diagnostics.push_io(&path, &e);
+                    None
+                },
+            };
+            match (contents.as_mut(), item) {
+                (Some(contents), Some(item)) => contents.add(item),
+                _ => contents = None,
+            }
+        }
+
+        if contents.is_none() || is_root {
+            self.found
+                .extend(prunable.into_iter().map(|(path, contents)| EmptyDir {
+                    path,
+                    kind: contents.kind,
+                    files: contents.files,
+                    size_bytes: contents.size_bytes,
+                }));
+        }
+        contents
+    }
+}
+
+/// Find the topmost prunable directories under `root` (never `root` itself),
+/// sorted by path, and the paths that could not be read.
+///
+/// # Examples
+///
+/// ```
+/// # use std::fs;
+/// # let dir = tempfile::tempdir().unwrap();
+/// fs::create_dir_all(dir.path().join("old/cache")).unwrap();
+/// fs::write(dir.path().join("old/.DS_Store"), "junk").unwrap();
+/// fs::create_dir(dir.path().join("src")).unwrap();
+/// fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+///
+/// let cancel = rclean::CancelToken::new();
+/// let (dirs, _) = rclean::empty_dirs::find_empty_dirs(dir.path(), &cancel);
+/// assert_eq!(dirs.len(), 1);
+/// assert_eq!(dirs[0].path, dir.path().join("old"));
+/// assert_eq!(dirs[0].kind, rclean::empty_dirs::EmptyDirKind::Junk);
+/// ```
+pub fn find_empty_dirs(root: &Path, cancel: &CancelToken) -> (Vec<EmptyDir>, ScanDiagnostics) {
+    let mut scan = Scan {
+        found: Vec::new(),
+        diagnostics: ScanDiagnostics::default(),
+        cancel,
+    };
+    scan.dir(root, true);
+    scan.found.sort_by(|a, b| a.path.cmp(&b.path));
+    (scan.found, scan.diagnostics)
+}
+
+/// Outcome for one directory passed to [`prune_empty_dirs`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneOutcome {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    /// Files removed (or that would be) with the directory
+    pub files: usize,
+    pub size_bytes: u64,
+    /// `None` on success (or in a dry run), otherwise why the directory was kept
+    pub error: Option<String>,
+}
+
+/// Result of pruning empty directories.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub outcomes: Vec<PruneOutcome>,
+}
+
+impl PruneReport {
+    /// Directories that were kept because pruning them failed.
+    pub fn failures(&self) -> impl Iterator<Item = &PruneOutcome> {
+        self.outcomes.iter().filter(|o| o.error.is_some())
+    }
+}
+
+impl fmt::Display for PruneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+        for outcome in &self.outcomes {
+            match &outcome.error {
+                None => writeln!(f, "{verb}: {}", display(&outcome.path))?,
+                Some(error) => writeln!(f, "Skipped: {} ({error})", display(&outcome.path))?,
+            }
+        }
+        let pruned: Vec<&PruneOutcome> =
+            self.outcomes.iter().filter(|o| o.error.is_none()).collect();
+        write!(
+            f,
+            "{verb} {} directories, {} files, {} bytes",
+            pruned.len(),
+            pruned.iter().map(|o| o.files).sum::<usize>(),
+            pruned.iter().map(|o| o.size_bytes).sum::<u64>()
+        )
+    }
+}
+
+/// Delete the empty and junk files under `dir`, deepest first, then `dir`
+/// itself. Anything else found on the way stops the removal.
+fn remove_tree(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            remove_tree(&path)?;
+        } else if file_type.is_file() && removable_file(&path, &entry.metadata()?).is_some() {
+            fs::remove_file(&path)?;
+        } else {
+            return Err(io::Error::other(format!("{} is not empty", display(&path))));
+        }
+    }
+    fs::remove_dir(dir)
+}
+
+/// Remove each of `dirs` with its empty and junk files, unless `dry_run`.
+///
+/// Every directory is scanned again first and kept if anything worth keeping
+/// has appeared in it since it was found.
+pub fn prune_empty_dirs(dirs: &[EmptyDir], dry_run: bool) -> PruneReport {
+    let outcomes = dirs
+        .iter()
+        .map(|dir| {
+            let mut scan = Scan {
+                found: Vec::new(),
+                diagnostics: ScanDiagnostics::default(),
+                cancel: &CancelToken::default(),
+            };
+            let error = match scan.dir(&dir.path, false) {
+                None => Some("no longer empty".to_string()),
+                Some(_) if dry_run => None,
+                Some(_) => remove_tree(&dir.path).err().map(|e| e.to_string()),
+            };
+            PruneOutcome {
+                path: dir.path.clone(),
+                files: dir.files,
+                size_bytes: dir.size_bytes,
+                error,
+            }
+        })
+        .collect();
+    PruneReport { dry_run, outcomes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, name: &str, content: &str) {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_only_topmost_prunable_dirs_are_reported() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("empty/nested/deeper")).unwrap();
+        write(root, "placeholders/a/.gitkeep", "");
+        write(root, "photos/Thumbs.db", "cache");
+        write(root, "photos/2020/.DS_Store", "junk");
+        write(root, "src/lib.rs", "code");
+        fs::create_dir(root.join("src/unused")).unwrap();
+
+        let (dirs, diagnostics) = find_empty_dirs(root, &CancelToken::new());
+        assert!(diagnostics.is_empty());
+        let found: Vec<(PathBuf, EmptyDirKind, usize, u64)> = dirs
+            .into_iter()
+            .map(|d| (d.path, d.kind, d.files, d.size_bytes))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (root.join("empty"), EmptyDirKind::Empty, 0, 0),
+                (root.join("photos"), EmptyDirKind::Junk, 2, 9),
+                (root.join("placeholders"), EmptyDirKind::EmptyFiles, 1, 0),
+                (root.join("src/unused"), EmptyDirKind::Empty, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prune_removes_bottom_up_and_rechecks() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(root, "old/a/b/.DS_Store", "junk");
+        write(root, "old/empty.log", "");
+        fs::create_dir(root.join("later")).unwrap();
+
+        let (dirs, _) = find_empty_dirs(root, &CancelToken::new());
+        assert_eq!(dirs.len(), 2);
+        write(root, "later/notes.txt", "now has content");
+
+        let report = prune_empty_dirs(&dirs, true);
+        assert!(root.join("old/a/b/.DS_Store").exists());
+        assert_eq!(report.failures().count(), 1);
+
+        let report = prune_empty_dirs(&dirs, false);
+        assert!(!root.join("old").exists());
+        assert!(root.join("later/notes.txt").exists());
+        assert_eq!(
+            report
+                .failures()
+                .map(|o| o.path.clone())
+                .collect::<Vec<_>>(),
+            vec![root.join("later")]
+        );
+        assert!(report
+            .to_string()
+            .ends_with("Removed 1 directories, 2 files, 4 bytes"));
+    }
+}
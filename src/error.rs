@@ -0,0 +1,59 @@
+//! Error type of the scanning and reporting functions at the crate root.
+
+use crate::cache::CacheError;
+use crate::diagnostics::SkipReason;
+use crate::growth::SnapshotError;
+use polars::prelude::PolarsError;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Error types for scanning, hashing and reporting
+#[derive(Debug, Error)]
+pub enum RcleanError {
+    #[error("Cannot access {}: {source}", crate::paths::display(.path))]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Directory walk failed: {0}")]
+    Walk(#[from] ignore::Error),
+
+    #[error("DataFrame operation failed: {0}")]
+    Polars(#[from] PolarsError),
+
+    #[error(transparent)]
+    Cache(#[from] CacheError),
+
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+
+    #[error("Fuzzy hash comparison failed: {0}")]
+    Similarity(#[from] ssdeep::Error),
+
+    #[error("DataFrame has no content hash column")]
+    MissingHashColumn,
+}
+
+impl RcleanError {
+    /// An I/O error on `path`.
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    /// How a path that failed with this error is reported in
+    /// [`crate::ScanDiagnostics`].
+    pub fn skip_reason(&self) -> SkipReason {
+        match self {
+            Self::Io { source, .. } => SkipReason::of_io(source),
+            Self::Walk(error) => error
+                .io_error()
+                .map_or(SkipReason::IoError, SkipReason::of_io),
+            _ => SkipReason::IoError,
+        }
+    }
+}
+
+/// Result type for scanning, hashing and reporting
+pub type RcleanResult<T> = Result<T, RcleanError>;
@@ -0,0 +1,338 @@
+//! File filters shared by every subcommand and MCP tool.
+//!
+//! A [`FileFilter`] narrows the walked files by size, modification and access
+//! time, file kind and ripgrep-style type names such as `rust` or `images`.
+//! [`crate::find_advanced`] evaluates it together with the path pattern. Files
+//! are only stat'ed when a size, time or kind criterion is set.
+
+use ignore::types::{Types, TypesBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Error types for filter construction
+#[derive(Debug, Error)]
+pub enum FilterError {
+    #[error("Invalid age: {0} (use s, m, h, d or w suffix)")]
+    InvalidAge(String),
+
+    #[error("Invalid time: {0} (use YYYY-MM-DD, YYYY-MM-DDTHH:MM:SS or an age such as 90d)")]
+    InvalidTime(String),
+
+    #[error("Invalid file type selection: {0}")]
+    Types(#[from] ignore::Error),
+}
+
+/// Result type for filter construction
+pub type FilterResult<T> = Result<T, FilterError>;
+
+/// Type names added to ripgrep's built-in definitions.
+const EXTRA_TYPES: &[(&str, &[&str])] = &[
+    (
+        "images",
+        &[
+            "*.jpg", "*.jpeg", "*.png", "*.gif", "*.bmp", "*.tif", "*.tiff", "*.webp", "*.heic",
+            "*.heif", "*.raw", "*.cr2", "*.nef", "*.arw", "*.dng", "*.svg", "*.ico",
+        ],
+    ),
+    (
+        "video",
+        &[
+            "*.mp4", "*.m4v", "*.mkv", "*.mov", "*.avi", "*.wmv", "*.flv", "*.webm", "*.mpg",
+            "*.mpeg", "*.3gp",
+        ],
+    ),
+    (
+        "audio",
+        &[
+            "*.mp3", "*.flac", "*.wav", "*.aac", "*.m4a", "*.ogg", "*.opus", "*.wma", "*.aiff",
+        ],
+    ),
+    (
+        "archives",
+        &[
+            "*.zip", "*.tar", "*.gz", "*.tgz", "*.bz2", "*.xz", "*.zst", "*.7z", "*.rar", "*.iso",
+            "*.dmg",
+        ],
+    ),
+    (
+        "documents",
+        &[
+            "*.pdf", "*.doc", "*.docx", "*.odt", "*.rtf", "*.xls", "*.xlsx", "*.ods", "*.ppt",
+            "*.pptx", "*.odp", "*.epub",
+        ],
+    ),
+];
+
+/// Whether a path must itself be a symlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    /// Regular files, not reached through a symlink of their own name
+    File,
+    /// Symbolic links to files (only walked with `follow_links`)
+    Symlink,
+}
+
+/// Criteria a file must meet besides the path pattern. The default filter
+/// accepts every file.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<SystemTime>,
+    pub modified_before: Option<SystemTime>,
+    pub accessed_after: Option<SystemTime>,
+    pub accessed_before: Option<SystemTime>,
+    pub kind: Option<FileKind>,
+    types: Option<Types>,
+}
+
+impl FileFilter {
+    /// Only accept files of the named types: ripgrep's definitions (`rust`,
+    /// `py`, `markdown`, ...) plus `images`, `video`, `audio`, `archives` and
+    /// `documents`. An empty list accepts every type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FilterError::Types`] if a name is unknown.
+    pub fn with_types<S: AsRef<str>>(mut self, names: &[S]) -> FilterResult<Self> {
+        self.types = if names.is_empty() {
+            None
+        } else {
+            let mut builder = TypesBuilder::new();
+            builder.add_defaults();
+            for (name, globs) in EXTRA_TYPES {
+                for glob in *globs {
+                    builder.add(name, glob)?;
+                }
+            }
+            for name in names {
+                builder.select(name.as_ref());
+            }
+            Some(builder.build()?)
+        };
+        Ok(self)
+    }
+
+    /// Whether the filter accepts every file.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_none() && !self.needs_metadata()
+    }
+
+    fn needs_metadata(&self) -> bool {
+        self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.modified_after.is_some()
+            || self.modified_before.is_some()
+            || self.accessed_after.is_some()
+            || self.accessed_before.is_some()
+            || self.kind.is_some()
+    }
+
+    /// Whether the file at `path` meets every criterion. Files that cannot be
+    /// stat'ed, or lack a timestamp a criterion needs, are rejected.
+    pub fn matches(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if let Some(types) = &self.types {
+            if !types.matched(path, false).is_whitelist() {
+                return false;
+            }
+        }
+        if !self.needs_metadata() {
+            return true;
+        }
+
+        let Ok(link_metadata) = fs::symlink_metadata(path) else {
+            return false;
+        };
+        let is_symlink = link_metadata.file_type().is_symlink();
+        if let Some(kind) = self.kind {
+            if (kind == FileKind::Symlink) != is_symlink {
+                return false;
+            }
+        }
+        let metadata = if is_symlink {
+            match fs::metadata(path) {
+                Ok(metadata) => metadata,
+                Err(_) => return false,
+            }
+        } else {
+            link_metadata
+        };
+
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        within(
+            metadata.modified().ok(),
+            self.modified_after,
+            self.modified_before,
+        ) && within(
+            metadata.accessed().ok(),
+            self.accessed_after,
+            self.accessed_before,
+        )
+    }
+}
+
+/// Whether `time` lies strictly between the optional bounds.
+fn within(time: Option<SystemTime>, after: Option<SystemTime>, before: Option<SystemTime>) -> bool {
+    if after.is_none() && before.is_none() {
+        return true;
+    }
+    time.is_some_and(|time| {
+        after.is_none_or(|after| time > after) && before.is_none_or(|before| time < before)
+    })
+}
+
+/// Parse an age such as `90d`, `12h` or `2w` into seconds; a bare number is
+/// read as days.
+///
+/// # Errors
+///
+/// Returns [`FilterError::InvalidAge`] for anything else.
+pub fn parse_age(age: &str) -> FilterResult<u64> {
+    let age = age.trim().to_lowercase();
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (number, unit) = age.split_at(split);
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(FilterError::InvalidAge(age)),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or(FilterError::InvalidAge(age))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Seconds since the epoch of `YYYY-MM-DD` or `YYYY-MM-DD[T ]HH:MM[:SS][Z]` (UTC).
+fn parse_timestamp(text: &str) -> Option<i64> {
+    let (date, time) = match text.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.trim_end_matches('Z'))),
+        None => (text, None),
+    };
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let seconds = match time {
+        None => 0,
+        Some(time) => {
+            let mut parts = time.split(':').map(str::parse::<i64>);
+            let hours = parts.next()?.ok()?;
+            let minutes = parts.next()?.ok()?;
+            let seconds = parts.next().transpose().ok()?.unwrap_or(0);
+            if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 59 {
+                return None;
+            }
+            hours * 3600 + minutes * 60 + seconds
+        },
+    };
+    Some(days_from_civil(year, month, day) * 86_400 + seconds)
+}
+
+/// Parse a point in time: an absolute UTC date (`2024-01-31`,
+/// `2024-01-31T08:00:00`) or an age relative to now (`90d`).
+///
+/// # Errors
+///
+/// Returns [`FilterError::InvalidTime`] if `text` is neither.
+pub fn parse_time(text: &str) -> FilterResult<SystemTime> {
+    let text = text.trim();
+    if let Some(seconds) = parse_timestamp(text) {
+        let offset = Duration::from_secs(seconds.unsigned_abs());
+        return Ok(if seconds >= 0 {
+            UNIX_EPOCH + offset
+        } else {
+            UNIX_EPOCH - offset
+        });
+    }
+    let age = parse_age(text).map_err(|_| FilterError::InvalidTime(text.to_string()))?;
+    Ok(SystemTime::now()
+        .checked_sub(Duration::from_secs(age))
+        .unwrap_or(UNIX_EPOCH))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(
+            parse_time("1970-01-02").unwrap(),
+            UNIX_EPOCH + Duration::from_secs(86_400)
+        );
+        assert_eq!(
+            parse_time("2024-02-29T12:30:00Z").unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1_709_209_800)
+        );
+        let ninety_days_ago = parse_time("90d").unwrap();
+        let elapsed = SystemTime::now().duration_since(ninety_days_ago).unwrap();
+        assert!(elapsed >= Duration::from_secs(90 * 86_400));
+        assert!(parse_time("2024-13-01").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_filter_by_size_time_and_type() {
+        let dir = TempDir::new().unwrap();
+        let small = dir.path().join("small.rs");
+        let large = dir.path().join("large.jpg");
+        fs::write(&small, "fn main() {}").unwrap();
+        fs::write(&large, vec![0u8; 4096]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&large)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))
+            .unwrap();
+        let (small, large) = (small.to_str().unwrap(), large.to_str().unwrap());
+
+        assert!(FileFilter::default().is_empty());
+        let sized = FileFilter {
+            min_size: Some(1024),
+            ..FileFilter::default()
+        };
+        assert!(!sized.matches(small));
+        assert!(sized.matches(large));
+
+        let old = FileFilter {
+            modified_before: Some(parse_time("2000-01-01").unwrap()),
+            ..FileFilter::default()
+        };
+        assert!(old.matches(large));
+        assert!(!old.matches(small));
+
+        let rust = FileFilter::default().with_types(&["rust"]).unwrap();
+        assert!(rust.matches(small));
+        assert!(!rust.matches(large));
+        let images = FileFilter::default().with_types(&["images"]).unwrap();
+        assert!(images.matches(large));
+        assert!(FileFilter::default().with_types(&["nonsense"]).is_err());
+    }
+}
@@ -0,0 +1,472 @@
+//! Growth tracking between outlier scans.
+//!
+//! Each tracked scan records a [`SizeSnapshot`]: the size of every file and
+//! the total size of every directory under the scan root, with paths stored
+//! relative to the root. Snapshots are kept per root in a [`SnapshotStore`],
+//! by default `$XDG_CACHE_HOME/rclean/size-snapshots.json`, and a new scan is
+//! compared with an earlier one to find the files and directories that grew
+//! the most and the large paths that appeared since ([`GrowthReport`]).
+//!
+//! A snapshot covers the files the scan analyzed, so runs compared with each
+//! other should use the same filters. Interrupted scans are compared but not
+//! recorded, as their missing files would show up as growth next time.
+
+use crate::cache::cache_dir;
+use crate::paths::display;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// On-disk format version; stores with another version are discarded.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// File name of the store inside the cache directory.
+const SNAPSHOT_FILE_NAME: &str = "size-snapshots.json";
+
+/// Snapshots kept per scan root; the oldest are dropped beyond that.
+pub const MAX_SNAPSHOTS: usize = 8;
+
+/// Smallest growth, and smallest new path, reported.
+pub const GROWTH_MIN_BYTES: u64 = 1024 * 1024;
+
+const SECS_PER_DAY: f64 = 86_400.0;
+
+/// Error types for snapshot store operations
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Failed to access size snapshots {}: {1}", display(.0))]
+    Io(PathBuf, io::Error),
+
+    #[error("Corrupt size snapshots {}: {1}", display(.0))]
+    Corrupt(PathBuf, serde_json::Error),
+}
+
+/// Result type for snapshot store operations
+pub type SnapshotResult<T> = Result<T, SnapshotError>;
+
+/// Whether a tracked path is a file or a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathKind {
+    File,
+    Directory,
+}
+
+impl fmt::Display for PathKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File => write!(f, "file"),
+            Self::Directory => write!(f, "directory"),
+        }
+    }
+}
+
+/// Size of one path in a [`SizeSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    /// Path relative to the scan root; empty for the root itself
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// Sizes under a scan root at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeSnapshot {
+    /// Unix timestamp (seconds) of the scan
+    pub taken_at: u64,
+    pub files: Vec<SnapshotEntry>,
+    /// Total size of the files under each directory, the root included
+    pub dirs: Vec<SnapshotEntry>,
+}
+
+impl SizeSnapshot {
+    /// Snapshot of `files` (path and size) found under `root`. Files outside
+    /// `root` are left out.
+    pub fn from_files<'a>(
+        root: &Path,
+        files: impl IntoIterator<Item = (&'a Path, u64)>,
+        taken_at: u64,
+    ) -> Self {
+        let mut file_sizes = BTreeMap::new();
+        let mut dir_sizes: BTreeMap<PathBuf, u64> = BTreeMap::new();
+        for (path, bytes) in files {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            for dir in relative.ancestors().skip(1) {
+                *dir_sizes.entry(dir.to_path_buf()).or_default() += bytes;
+            }
+            file_sizes.insert(relative.to_path_buf(), bytes);
+        }
+
+        let entries = |sizes: BTreeMap<PathBuf, u64>| {
+            sizes
+                .into_iter()
+                .map(|(path, bytes)| SnapshotEntry { path, bytes })
+                .collect()
+        };
+        Self {
+            taken_at,
+            files: entries(file_sizes),
+            dirs: entries(dir_sizes),
+        }
+    }
+
+    /// Total size of the files in the snapshot.
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|entry| entry.bytes).sum()
+    }
+
+    fn sizes(&self) -> HashMap<(PathKind, &Path), u64> {
+        let files = self
+            .files
+            .iter()
+            .map(|entry| ((PathKind::File, entry.path.as_path()), entry.bytes));
+        let dirs = self
+            .dirs
+            .iter()
+            .map(|entry| ((PathKind::Directory, entry.path.as_path()), entry.bytes));
+        files.chain(dirs).collect()
+    }
+}
+
+/// A file or directory that grew by at least [`GROWTH_MIN_BYTES`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathGrowth {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    pub kind: PathKind,
+    pub previous_bytes: u64,
+    pub current_bytes: u64,
+    pub growth_bytes: u64,
+    /// Growth relative to the previous size; `None` when that was zero
+    pub growth_percent: Option<f64>,
+    pub bytes_per_day: f64,
+}
+
+/// A file or directory of at least [`GROWTH_MIN_BYTES`] that did not exist
+/// in the baseline. Only the topmost new path is listed, not its contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewPath {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    pub kind: PathKind,
+    pub bytes: u64,
+}
+
+/// Differences between a scan and an earlier snapshot of the same root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthReport {
+    pub baseline_taken_at: u64,
+    pub taken_at: u64,
+    pub elapsed_days: f64,
+    /// Change in the total size under the root; negative if it shrank
+    pub total_growth_bytes: i64,
+    /// Largest growth first
+    pub growing: Vec<PathGrowth>,
+    /// Largest first
+    pub new_paths: Vec<NewPath>,
+}
+
+/// Compare `current` with `baseline`, both snapshots of `root`, listing at
+/// most `top_n` growing and new paths each. Reported paths are joined to
+/// `root`; the root's own growth is the report's total.
+pub fn compare(
+    root: &Path,
+    baseline: &SizeSnapshot,
+    current: &SizeSnapshot,
+    top_n: Option<usize>,
+) -> GrowthReport {
+    let elapsed_secs = current.taken_at.saturating_sub(baseline.taken_at);
+    let elapsed_days = elapsed_secs as f64 / SECS_PER_DAY;
+    let previous = baseline.sizes();
+
+    let mut growing = Vec::new();
+    let mut new_paths = Vec::new();
+    for ((kind, path), bytes) in current.sizes() {
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        match previous.get(&(kind, path)) {
+            Some(&previous_bytes) if bytes >= previous_bytes + GROWTH_MIN_BYTES => {
+                let growth_bytes = bytes - previous_bytes;
+                growing.push(PathGrowth {
+                    path: root.join(path),
+                    kind,
+                    previous_bytes,
+                    current_bytes: bytes,
+                    growth_bytes,
+                    growth_percent: (previous_bytes > 0)
+                        .then(|| growth_bytes as f64 / previous_bytes as f64 * 100.0),
+                    bytes_per_day: growth_bytes as f64 * SECS_PER_DAY / elapsed_secs.max(1) as f64,
+                });
+            },
+            Some(_) => {},
+            None if bytes >= GROWTH_MIN_BYTES => {
+                let parent_existed = path.parent().is_none_or(|parent| {
+                    parent.as_os_str().is_empty()
+                        || previous.contains_key(&(PathKind::Directory, parent))
+                });
+                if parent_existed {
+                    new_paths.push(NewPath {
+                        path: root.join(path),
+                        kind,
+                        bytes,
+                    });
+                }
+            },
+            None => {},
+        }
+    }
+
+    growing.sort_by(|a, b| {
+        b.growth_bytes
+            .cmp(&a.growth_bytes)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    new_paths.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    if let Some(top_n) = top_n {
+        growing.truncate(top_n);
+        new_paths.truncate(top_n);
+    }
+
+    GrowthReport {
+        baseline_taken_at: baseline.taken_at,
+        taken_at: current.taken_at,
+        elapsed_days,
+        total_growth_bytes: current.total_bytes() as i64 - baseline.total_bytes() as i64,
+        growing,
+        new_paths,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSnapshots {
+    version: u32,
+    roots: BTreeMap<String, Vec<SizeSnapshot>>,
+}
+
+/// Size snapshots of every tracked root, oldest first, saved as one JSON
+/// file.
+#[derive(Debug)]
+pub struct SnapshotStore {
+    path: PathBuf,
+    roots: BTreeMap<String, Vec<SizeSnapshot>>,
+}
+
+/// Key of `root` in the store: its absolute path, escaped if it is not
+/// UTF-8 (see [`crate::paths::display`]).
+fn root_key(root: &Path) -> String {
+    let absolute = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+    display(&absolute).into_owned()
+}
+
+impl SnapshotStore {
+    /// Default store location: `$XDG_CACHE_HOME/rclean/size-snapshots.json`,
+    /// falling back to `~/.cache/rclean/` and then the current directory.
+    pub fn default_path() -> PathBuf {
+        cache_dir().map_or_else(
+            || PathBuf::from(".rclean-size-snapshots.json"),
+            |dir| dir.join(SNAPSHOT_FILE_NAME),
+        )
+    }
+
+    /// Load the store at `path`, starting empty if it does not exist yet or
+    /// was written by an incompatible version.
+    pub fn open(path: impl Into<PathBuf>) -> SnapshotResult<Self> {
+        let path = path.into();
+        let roots = match fs::read(&path) {
+            Ok(bytes) => {
+                let stored: StoredSnapshots = serde_json::from_slice(&bytes)
+                    .map_err(|e| SnapshotError::Corrupt(path.clone(), e))?;
+                if stored.version == SNAPSHOT_VERSION {
+                    stored.roots
+                } else {
+                    BTreeMap::new()
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(SnapshotError::Io(path, e)),
+        };
+        Ok(Self { path, roots })
+    }
+
+    /// Location the store is loaded from and saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Snapshots of `root`, oldest first.
+    pub fn history(&self, root: &Path) -> &[SizeSnapshot] {
+        self.roots.get(&root_key(root)).map_or(&[], Vec::as_slice)
+    }
+
+    /// The newest snapshot of `root` taken at least `min_age_secs` before
+    /// `now`.
+    pub fn baseline(&self, root: &Path, min_age_secs: u64, now: u64) -> Option<&SizeSnapshot> {
+        self.history(root)
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.taken_at.saturating_add(min_age_secs) <= now)
+    }
+
+    /// Add `snapshot` to the history of `root`, keeping the newest
+    /// [`MAX_SNAPSHOTS`].
+    pub fn record(&mut self, root: &Path, snapshot: SizeSnapshot) {
+        let history = self.roots.entry(root_key(root)).or_default();
+        history.push(snapshot);
+        history.sort_by_key(|snapshot| snapshot.taken_at);
+        let excess = history.len().saturating_sub(MAX_SNAPSHOTS);
+        history.drain(..excess);
+    }
+
+    /// Write the store back to its file atomically (see [`crate::atomic`]).
+    pub fn save(&self) -> SnapshotResult<()> {
+        let path = &self.path;
+        let stored = StoredSnapshots {
+            version: SNAPSHOT_VERSION,
+            roots: self.roots.clone(),
+        };
+        let json =
+            serde_json::to_vec(&stored).map_err(|e| SnapshotError::Corrupt(path.clone(), e))?;
+        crate::atomic::write(path, &json).map_err(|e| SnapshotError::Io(path.clone(), e))
+    }
+}
+
+/// Compare `current`, a snapshot of `root`, with the newest snapshot in the
+/// store at `store_path` that is at least `min_age_secs` old, then record
+/// `current` in the store if `record` is set.
+///
+/// Returns `None` when there is no such snapshot yet.
+///
+/// # Errors
+///
+/// Returns an error if the store cannot be read, parsed or written.
+pub fn track_growth(
+    store_path: &Path,
+    root: &Path,
+    current: SizeSnapshot,
+    min_age_secs: u64,
+    top_n: Option<usize>,
+    record: bool,
+) -> SnapshotResult<Option<GrowthReport>> {
+    let mut store = SnapshotStore::open(store_path)?;
+    let report = store
+        .baseline(root, min_age_secs, current.taken_at)
+        .map(|baseline| compare(root, baseline, &current, top_n));
+    if record {
+        store.record(root, current);
+        store.save()?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn snapshot(taken_at: u64, files: &[(&str, u64)]) -> SizeSnapshot {
+        let root = Path::new("/data");
+        let paths: Vec<(PathBuf, u64)> = files
+            .iter()
+            .map(|(path, bytes)| (root.join(path), *bytes))
+            .collect();
+        SizeSnapshot::from_files(
+            root,
+            paths.iter().map(|(path, bytes)| (path.as_path(), *bytes)),
+            taken_at,
+        )
+    }
+
+    #[test]
+    fn test_snapshot_sums_directories() {
+        let snapshot = snapshot(0, &[("logs/a.log", 3), ("logs/old/b.log", 4), ("c", 5)]);
+        let dirs: Vec<(&str, u64)> = snapshot
+            .dirs
+            .iter()
+            .map(|entry| (entry.path.to_str().unwrap(), entry.bytes))
+            .collect();
+        assert_eq!(dirs, [("", 12), ("logs", 7), ("logs/old", 4)]);
+        assert_eq!(snapshot.total_bytes(), 12);
+    }
+
+    #[test]
+    fn test_compare_reports_growth_and_new_paths() {
+        let day = 86_400;
+        let baseline = snapshot(
+            day,
+            &[("logs/app.log", 2 * MB), ("db.sqlite", MB), ("empty", 0)],
+        );
+        let current = snapshot(
+            3 * day,
+            &[
+                ("logs/app.log", 6 * MB),
+                ("db.sqlite", MB + 10),
+                ("empty", 2 * MB),
+                ("cache/x/blob", 5 * MB),
+                ("logs/new.log", 3 * MB),
+            ],
+        );
+
+        let report = compare(Path::new("/data"), &baseline, &current, None);
+        assert_eq!(report.elapsed_days, 2.0);
+        assert_eq!(report.total_growth_bytes, (14 * MB + 10) as i64);
+
+        let growing: Vec<(&str, PathKind, u64)> = report
+            .growing
+            .iter()
+            .map(|g| (g.path.to_str().unwrap(), g.kind, g.growth_bytes))
+            .collect();
+        assert_eq!(
+            growing,
+            [
+                ("/data/logs", PathKind::Directory, 7 * MB),
+                ("/data/logs/app.log", PathKind::File, 4 * MB),
+                ("/data/empty", PathKind::File, 2 * MB),
+            ]
+        );
+        assert_eq!(report.growing[1].growth_percent, Some(200.0));
+        assert_eq!(report.growing[1].bytes_per_day, (2 * MB) as f64);
+        assert_eq!(report.growing[2].growth_percent, None);
+
+        let new_paths: Vec<&str> = report
+            .new_paths
+            .iter()
+            .map(|n| n.path.to_str().unwrap())
+            .collect();
+        assert_eq!(new_paths, ["/data/cache", "/data/logs/new.log"]);
+
+        let limited = compare(Path::new("/data"), &baseline, &current, Some(1));
+        assert_eq!((limited.growing.len(), limited.new_paths.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_store_keeps_recent_snapshots_and_picks_baseline() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("snapshots.json");
+        let root = Path::new("/data");
+
+        let mut store = SnapshotStore::open(&path).unwrap();
+        for taken_at in 0..10 {
+            store.record(root, snapshot(taken_at * 100, &[("a", taken_at)]));
+        }
+        store.save().unwrap();
+
+        let store = SnapshotStore::open(&path).unwrap();
+        let history = store.history(root);
+        assert_eq!(history.len(), MAX_SNAPSHOTS);
+        assert_eq!(history[0].taken_at, 200);
+        assert_eq!(store.baseline(root, 0, 1000).unwrap().taken_at, 900);
+        assert_eq!(store.baseline(root, 350, 1000).unwrap().taken_at, 600);
+        assert!(store.baseline(root, 900, 1000).is_none());
+        assert!(store.history(Path::new("/other")).is_empty());
+    }
+}
@@ -0,0 +1,1136 @@
+//! Staged content hashing for duplicate detection.
+//!
+//! Reading and hashing every file is the dominant cost of a scan, so candidates
+//! are narrowed down in three stages before any full hash is computed:
+//!
+//! 1. **Size** - files are bucketed by `size_bytes`; a file with a unique size
+//!    cannot have a duplicate and is never read.
+//! 2. **Partial** - the first and last [`PARTIAL_HASH_BLOCK`] bytes of each
+//!    remaining file are hashed; files whose edges differ are dropped.
+//! 3. **Full** - only files that still collide are hashed completely.
+//!
+//! Every stage records how many bytes it actually read in a [`HashingReport`].
+//!
+//! The content hash itself is pluggable through [`HashAlgorithm`], which hands
+//! out a [`ContentHasher`] for MD5, BLAKE3, xxh3 or SHA-256.
+//!
+//! Files are never loaded into memory whole. Content is streamed through a
+//! buffer of at most [`HASH_BUFFER_SIZE`] bytes, and every buffer is reserved
+//! from a [`MemoryBudget`] first, so the read buffers in flight stay bounded
+//! by the budget rather than by file size times thread count. The budget is
+//! passed in by the caller (see [`crate::DedupeOptions::memory_budget`]);
+//! functions without one share [`MemoryBudget::global`].
+//!
+//! Only read buffers are counted, at their real size: one per file being
+//! hashed or fuzzy hashed, two per byte-for-byte comparison, and a text
+//! file with room for its decoded copy while its normalized text is hashed
+//! (see [`crate::normalize`]). Hasher state, the hash cache and the
+//! collected results are not. With [`HASH_BUFFER_SIZE`] buffers, the
+//! [`DEFAULT_MEMORY_BUDGET`] admits 1024 concurrent reads, so it only
+//! throttles very wide thread pools; a smaller budget trades throughput for
+//! a lower peak.
+
+use crate::cache::{CacheKey, HashCache};
+use crate::cancel::CancelToken;
+use crate::diagnostics::ScanDiagnostics;
+use crate::progress::{Phase, ProgressSink};
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
+use serde::{Deserialize, Serialize};
+use sha2::Digest as _;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Seek as _, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex};
+
+/// Number of bytes hashed from each end of a file during the partial stage.
+pub const PARTIAL_HASH_BLOCK: u64 = 4096;
+
+/// Size of the read buffer used when streaming file content into a hasher.
+pub const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Default budget for hashing buffers (64 MiB), used by
+/// [`MemoryBudget::global`].
+pub const DEFAULT_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Counting semaphore over bytes, shared by all hashing threads.
+///
+/// Readers block in [`MemoryBudget::reserve`] until enough of the budget is
+/// free, so a wide rayon pool cannot allocate more buffer memory than the limit.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    state: Mutex<BudgetState>,
+    released: Condvar,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    limit: usize,
+    in_use: usize,
+}
+
+/// Reservation against a [`MemoryBudget`], returned to the budget on drop.
+#[derive(Debug)]
+pub struct BudgetGuard<'a> {
+    budget: &'a MemoryBudget,
+    bytes: usize,
+}
+
+static GLOBAL_BUDGET: MemoryBudget = MemoryBudget::new(DEFAULT_MEMORY_BUDGET);
+
+impl MemoryBudget {
+    /// Create a budget allowing `limit` bytes (at least one) to be reserved
+    /// at once.
+    pub const fn new(limit: usize) -> Self {
+        let limit = if limit == 0 { 1 } else { limit };
+        Self {
+            state: Mutex::new(BudgetState { limit, in_use: 0 }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// The budget shared by hashing functions that are not given one, limited
+    /// to [`DEFAULT_MEMORY_BUDGET`].
+    pub fn global() -> &'static Self {
+        &GLOBAL_BUDGET
+    }
+
+    /// Current limit in bytes.
+    pub fn limit(&self) -> usize {
+        self.lock().limit
+    }
+
+    /// Bytes currently reserved.
+    pub fn in_use(&self) -> usize {
+        self.lock().in_use
+    }
+
+    /// Change the limit. Reservations already handed out are unaffected.
+    pub fn set_limit(&self, limit: usize) {
+        self.lock().limit = limit.max(1);
+        self.released.notify_all();
+    }
+
+    /// Reserve up to `bytes`, blocking until they are available.
+    ///
+    /// Requests larger than the whole limit are clamped to it, so the returned
+    /// guard may hold fewer bytes than asked for; size buffers by
+    /// [`BudgetGuard::bytes`].
+    pub fn reserve(&self, bytes: usize) -> BudgetGuard<'_> {
+        let mut state = self.lock();
+        let bytes = bytes.clamp(1, state.limit);
+        while state.in_use + bytes > state.limit {
+            state = self
+                .released
+                .wait(state)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+        }
+        state.in_use += bytes;
+        BudgetGuard {
+            budget: self,
+            bytes,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BudgetState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl BudgetGuard<'_> {
+    /// Number of bytes held by this reservation.
+    pub const fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for BudgetGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.budget.lock();
+        state.in_use -= self.bytes;
+        drop(state);
+        self.budget.released.notify_all();
+    }
+}
+
+/// Incremental content hasher producing a lowercase hex digest.
+pub trait ContentHasher: Send {
+    /// Feed more content into the hash.
+    fn update(&mut self, data: &[u8]);
+
+    /// Consume the hasher and return the hex digest.
+    fn finish_hex(self: Box<Self>) -> String;
+}
+
+impl ContentHasher for md5::Context {
+    fn update(&mut self, data: &[u8]) {
+        self.consume(data);
+    }
+
+    fn finish_hex(self: Box<Self>) -> String {
+        format!("{:x}", self.compute())
+    }
+}
+
+impl ContentHasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        Self::update(self, data);
+    }
+
+    fn finish_hex(self: Box<Self>) -> String {
+        self.finalize().to_hex().to_string()
+    }
+}
+
+impl ContentHasher for xxhash_rust::xxh3::Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        Self::update(self, data);
+    }
+
+    fn finish_hex(self: Box<Self>) -> String {
+        format!("{:032x}", self.digest128())
+    }
+}
+
+impl ContentHasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn finish_hex(self: Box<Self>) -> String {
+        format!("{:x}", self.finalize())
+    }
+}
+
+/// Stream `reader` into `hasher` through a buffer from the global budget,
+/// returning bytes read.
+///
+/// At most `limit` bytes are consumed when given.
+pub fn hash_reader(
+    reader: impl io::Read,
+    hasher: &mut dyn ContentHasher,
+    limit: Option<u64>,
+) -> io::Result<u64> {
+    hash_reader_with_budget(reader, hasher, limit, MemoryBudget::global())
+}
+
+/// [`hash_reader`] with the buffer reserved from `budget`.
+pub fn hash_reader_with_budget(
+    mut reader: impl io::Read,
+    hasher: &mut dyn ContentHasher,
+    limit: Option<u64>,
+    budget: &MemoryBudget,
+) -> io::Result<u64> {
+    let reservation = budget.reserve(HASH_BUFFER_SIZE);
+    let mut buffer = vec![0u8; reservation.bytes()];
+    let mut remaining = limit.unwrap_or(u64::MAX);
+    let mut total = 0u64;
+
+    while remaining > 0 {
+        let want = buffer
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let read = match reader.read(&mut buffer[..want]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..read]);
+        total += read as u64;
+        remaining -= read as u64;
+    }
+
+    Ok(total)
+}
+
+/// Stream a whole file through `algorithm`, returning the digest and bytes read.
+pub fn hash_file(path: impl AsRef<Path>, algorithm: HashAlgorithm) -> io::Result<(String, u64)> {
+    hash_file_with_budget(path, algorithm, MemoryBudget::global())
+}
+
+/// [`hash_file`] with the buffer reserved from `budget`.
+pub fn hash_file_with_budget(
+    path: impl AsRef<Path>,
+    algorithm: HashAlgorithm,
+    budget: &MemoryBudget,
+) -> io::Result<(String, u64)> {
+    let file = fs::File::open(path)?;
+    let mut hasher = algorithm.hasher();
+    let bytes = hash_reader_with_budget(file, hasher.as_mut(), None, budget)?;
+    Ok((hasher.finish_hex(), bytes))
+}
+
+/// Read from `reader` until `buffer` is full or the input ends.
+fn fill(reader: &mut impl io::Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Whether two files have identical content, compared byte for byte.
+///
+/// Both files are streamed through buffers taken from the global budget, and
+/// the comparison stops at the first difference.
+pub fn files_identical(a: impl AsRef<Path>, b: impl AsRef<Path>) -> io::Result<bool> {
+    files_identical_with_budget(a, b, MemoryBudget::global())
+}
+
+/// [`files_identical`] with the buffers reserved from `budget`.
+pub fn files_identical_with_budget(
+    a: impl AsRef<Path>,
+    b: impl AsRef<Path>,
+    budget: &MemoryBudget,
+) -> io::Result<bool> {
+    let (mut a, mut b) = (fs::File::open(a)?, fs::File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let reservation = budget.reserve(2 * HASH_BUFFER_SIZE);
+    let half = (reservation.bytes() / 2).max(1);
+    let (mut buffer_a, mut buffer_b) = (vec![0u8; half], vec![0u8; half]);
+    loop {
+        let read_a = fill(&mut a, &mut buffer_a)?;
+        let read_b = fill(&mut b, &mut buffer_b)?;
+        if buffer_a[..read_a] != buffer_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Partition `paths` into sets of byte-identical files, as indices.
+///
+/// The first set holds `paths[0]` and every file identical to it. Files that
+/// differ are partitioned among themselves the same way, so unreadable files
+/// end up alone. Comparison buffers are reserved from `budget`.
+pub fn split_by_content(paths: &[&Path], budget: &MemoryBudget) -> Vec<Vec<usize>> {
+    let mut remaining: Vec<usize> = (0..paths.len()).collect();
+    let mut partitions = Vec::new();
+
+    while let Some((&first, rest)) = remaining.split_first() {
+        let (mut same, different): (Vec<usize>, Vec<usize>) =
+            rest.par_iter().partition(|&&other| {
+                files_identical_with_budget(paths[first], paths[other], budget).unwrap_or(false)
+            });
+        same.insert(0, first);
+        partitions.push(same);
+        remaining = different;
+    }
+
+    partitions
+}
+
+/// Fuzzy (ssdeep) hash of a file, or `None` if it cannot be read.
+pub fn fuzzy_hash_file(path: impl AsRef<Path>) -> Option<String> {
+    fuzzy_hash_file_with_budget(path, MemoryBudget::global())
+}
+
+/// [`fuzzy_hash_file`], throttled by `budget`.
+///
+/// libfuzzy streams the file itself; the call still takes a buffer's worth of
+/// `budget` so it is throttled alongside content hashing. libfuzzy cannot
+/// open paths that are not valid UTF-8, so those files are hashed through a
+/// temporary symlink with a UTF-8 name instead (on Unix; elsewhere they get
+/// no fuzzy hash).
+pub fn fuzzy_hash_file_with_budget(
+    path: impl AsRef<Path>,
+    budget: &MemoryBudget,
+) -> Option<String> {
+    let path = path.as_ref();
+    let _reservation = budget.reserve(HASH_BUFFER_SIZE);
+    // ssdeep panics on paths that are not valid UTF-8 or contain NUL
+    if let Some(path) = path.to_str().filter(|p| !p.contains('\0')) {
+        return ssdeep::hash_from_file(path).ok();
+    }
+    fuzzy_hash_through_alias(path)
+}
+
+#[cfg(unix)]
+fn fuzzy_hash_through_alias(path: &Path) -> Option<String> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT_ALIAS: AtomicU64 = AtomicU64::new(0);
+
+    let target = std::path::absolute(path).ok()?;
+    let alias = std::env::temp_dir().join(format!(
+        ".rclean-fuzzy-{}-{}",
+        std::process::id(),
+        NEXT_ALIAS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let alias_str = alias.to_str().filter(|p| !p.contains('\0'))?;
+    std::os::unix::fs::symlink(&target, &alias).ok()?;
+    let hash = ssdeep::hash_from_file(alias_str).ok();
+    let _ = fs::remove_file(&alias);
+    hash
+}
+
+#[cfg(not(unix))]
+fn fuzzy_hash_through_alias(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Content hash algorithm used for duplicate detection.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// MD5 (default, kept for compatibility with existing reports)
+    #[default]
+    Md5,
+    /// BLAKE3, cryptographically strong and fast
+    Blake3,
+    /// xxh3 128-bit, non-cryptographic and fastest for pure dedupe
+    Xxh3,
+    /// SHA-256, for integrity evidence
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// All supported algorithms.
+    pub const ALL: [Self; 4] = [Self::Md5, Self::Blake3, Self::Xxh3, Self::Sha256];
+
+    /// Lowercase algorithm name, as accepted by `--hash` and the MCP `hash` argument.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "md5",
+            Self::Blake3 => "blake3",
+            Self::Xxh3 => "xxh3",
+            Self::Sha256 => "sha256",
+        }
+    }
+
+    /// Name of the `DataFrame` column (and CSV header) holding this algorithm's digests.
+    pub const fn column_name(self) -> &'static str {
+        match self {
+            Self::Md5 => "md5_hash",
+            Self::Blake3 => "blake3_hash",
+            Self::Xxh3 => "xxh3_hash",
+            Self::Sha256 => "sha256_hash",
+        }
+    }
+
+    /// Create a fresh incremental hasher.
+    pub fn hasher(self) -> Box<dyn ContentHasher> {
+        match self {
+            Self::Md5 => Box::new(md5::Context::new()),
+            Self::Blake3 => Box::new(blake3::Hasher::new()),
+            Self::Xxh3 => Box::new(xxhash_rust::xxh3::Xxh3::new()),
+            Self::Sha256 => Box::new(sha2::Sha256::new()),
+        }
+    }
+
+    /// Hash a complete buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rclean::hashing::HashAlgorithm;
+    ///
+    /// assert_eq!(
+    ///     HashAlgorithm::Md5.digest(b"foo"),
+    ///     "acbd18db4cc2f85cedef654fccc4a4d8"
+    /// );
+    /// assert_eq!(HashAlgorithm::Sha256.digest(b"foo").len(), 64);
+    /// ```
+    pub fn digest(self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finish_hex()
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| {
+                algorithm.name() == s || (s == "sha-256" && *algorithm == Self::Sha256)
+            })
+            .ok_or_else(|| format!("Unknown hash algorithm: {s} (use md5, blake3, xxh3 or sha256)"))
+    }
+}
+
+/// Counters for a single hashing stage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageStats {
+    /// Files that entered the stage.
+    pub files_in: usize,
+    /// Files still considered duplicate candidates after the stage.
+    pub candidates_out: usize,
+    /// Bytes read from disk during the stage.
+    pub bytes_read: u64,
+}
+
+/// Per-stage accounting for a staged hashing run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashingReport {
+    pub size: StageStats,
+    pub partial: StageStats,
+    pub full: StageStats,
+}
+
+impl HashingReport {
+    /// Total bytes read across all stages.
+    pub const fn total_bytes_read(&self) -> u64 {
+        self.size.bytes_read + self.partial.bytes_read + self.full.bytes_read
+    }
+}
+
+impl fmt::Display for HashingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>12} {:>12} {:>16}",
+            "stage", "files", "candidates", "bytes_read"
+        )?;
+        for (name, stage) in [
+            ("size", &self.size),
+            ("partial", &self.partial),
+            ("full", &self.full),
+        ] {
+            writeln!(
+                f,
+                "{:<8} {:>12} {:>12} {:>16}",
+                name, stage.files_in, stage.candidates_out, stage.bytes_read
+            )?;
+        }
+        write!(f, "Total bytes read: {}", self.total_bytes_read())
+    }
+}
+
+/// Result of hashing the edges of a file.
+struct PartialHash {
+    digest: String,
+    bytes_read: u64,
+    /// The whole file fit inside the edge blocks, so `digest` is already the full hash.
+    complete: bool,
+}
+
+/// Hash the first and last [`PARTIAL_HASH_BLOCK`] bytes of a file.
+///
+/// Files no larger than two blocks are read completely, in which case the
+/// returned digest equals the full content hash.
+fn partial_hash(
+    path: &Path,
+    size: u64,
+    algorithm: HashAlgorithm,
+    budget: &MemoryBudget,
+) -> io::Result<PartialHash> {
+    if size <= PARTIAL_HASH_BLOCK * 2 {
+        let (digest, bytes_read) = hash_file_with_budget(path, algorithm, budget)?;
+        return Ok(PartialHash {
+            digest,
+            bytes_read,
+            complete: true,
+        });
+    }
+
+    let mut file = fs::File::open(path)?;
+    let mut hasher = algorithm.hasher();
+    let block = Some(PARTIAL_HASH_BLOCK);
+    let head = hash_reader_with_budget(&mut file, hasher.as_mut(), block, budget)?;
+    file.seek(SeekFrom::End(-(PARTIAL_HASH_BLOCK as i64)))?;
+    let tail = hash_reader_with_budget(&mut file, hasher.as_mut(), block, budget)?;
+
+    Ok(PartialHash {
+        digest: hasher.finish_hex(),
+        bytes_read: head + tail,
+        complete: false,
+    })
+}
+
+/// Indices of all entries that share their key with at least one other entry.
+fn colliding<K: std::hash::Hash + Eq>(keys: impl Iterator<Item = (usize, K)>) -> Vec<usize> {
+    let mut buckets: HashMap<K, Vec<usize>> = HashMap::new();
+    for (index, key) in keys {
+        buckets.entry(key).or_default().push(index);
+    }
+
+    let mut indices: Vec<usize> = buckets
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .flatten()
+        .collect();
+    indices.sort_unstable();
+    indices
+}
+
+/// Run the size, partial and full hashing stages over `(path, size_bytes)` entries.
+///
+/// Returns the full content hash for every entry that survived to the last
+/// stage, aligned with `entries`. Entries ruled out earlier, or that could not
+/// be read, are `None`; the unreadable ones are listed in the returned
+/// [`ScanDiagnostics`].
+///
+/// The partial and full stages are reported to `progress` as they run; the
+/// caller reports [`ProgressSink::finished`] once its own work is done.
+///
+/// # Examples
+///
+/// ```
+/// # use std::fs;
+/// # let dir = tempfile::tempdir().unwrap();
+/// let a = dir.path().join("a.txt");
+/// let b = dir.path().join("b.txt");
+/// let c = dir.path().join("c.txt");
+/// fs::write(&a, "same").unwrap();
+/// fs::write(&b, "same").unwrap();
+/// fs::write(&c, "unique size").unwrap();
+///
+/// let entries: Vec<(std::path::PathBuf, u64)> = [&a, &b, &c]
+///     .iter()
+///     .map(|p| (p.to_path_buf(), fs::metadata(p).unwrap().len()))
+///     .collect();
+///
+/// let progress = rclean::progress::NoProgress;
+/// let algorithm = rclean::hashing::HashAlgorithm::Blake3;
+/// let (hashes, report, diagnostics) =
+///     rclean::hashing::staged_hash(&entries, algorithm, &progress);
+/// assert!(hashes[0].is_some());
+/// assert_eq!(hashes[0], hashes[1]);
+/// assert!(hashes[2].is_none()); // unique size, never read
+/// assert_eq!(report.size.candidates_out, 2);
+/// assert!(diagnostics.is_empty());
+/// ```
+pub fn staged_hash(
+    entries: &[(PathBuf, u64)],
+    algorithm: HashAlgorithm,
+    progress: &dyn ProgressSink,
+) -> (Vec<Option<String>>, HashingReport, ScanDiagnostics) {
+    staged_hash_with_cache(
+        entries,
+        algorithm,
+        None,
+        MemoryBudget::global(),
+        progress,
+        &CancelToken::default(),
+    )
+}
+
+/// [`staged_hash`] backed by a persistent [`HashCache`].
+///
+/// Partial and full hashes of files whose [`CacheKey`] is unchanged are taken
+/// from the cache without reading the file; everything hashed during the run
+/// is recorded in it. Cached hashes count as zero bytes read. Read buffers
+/// are reserved from `budget`.
+///
+/// Once `cancel` fires, no further file is read: entries not reached yet are
+/// `None` and the returned diagnostics are marked as interrupted.
+pub fn staged_hash_with_cache(
+    entries: &[(PathBuf, u64)],
+    algorithm: HashAlgorithm,
+    cache: Option<&HashCache>,
+    budget: &MemoryBudget,
+    progress: &dyn ProgressSink,
+    cancel: &CancelToken,
+) -> (Vec<Option<String>>, HashingReport, ScanDiagnostics) {
+    let mut report = HashingReport::default();
+    let mut hashes: Vec<Option<String>> = vec![None; entries.len()];
+    let mut diagnostics = ScanDiagnostics::default();
+
+    // Stage 1: bucket by size, no I/O
+    let size_candidates = colliding(entries.iter().map(|(_, size)| *size).enumerate());
+    report.size = StageStats {
+        files_in: entries.len(),
+        candidates_out: size_candidates.len(),
+        bytes_read: 0,
+    };
+
+    let keys: HashMap<usize, CacheKey> = match cache {
+        Some(_) => size_candidates
+            .par_iter()
+            .filter_map(|&index| CacheKey::for_path(&entries[index].0).map(|key| (index, key)))
+            .collect(),
+        None => HashMap::new(),
+    };
+    let cached = |index: usize| cache.zip(keys.get(&index));
+
+    // Stage 2: hash the edges of each size candidate
+    progress.phase_started(Phase::PartialHash, size_candidates.len() as u64);
+
+    let partials: Vec<(usize, io::Result<PartialHash>)> = size_candidates
+        .par_iter()
+        .filter(|_| !cancel.is_cancelled())
+        .map(|&index| {
+            let (path, size) = &entries[index];
+            if let Some((cache, key)) = cached(index) {
+                if let Some(hash) = cached_partial_hash(cache, key, path, *size, algorithm) {
+                    return (index, Ok(hash));
+                }
+            }
+
+            let hash = partial_hash(path, *size, algorithm, budget);
+            if let (Ok(hash), Some((cache, key))) = (&hash, cached(index)) {
+                if hash.complete {
+                    cache.insert_full(*key, path, algorithm, &hash.digest);
+                } else {
+                    cache.insert_partial(*key, path, algorithm, &hash.digest);
+                }
+            }
+            (index, hash)
+        })
+        .inspect(|(_, hash)| {
+            progress.items_done(1);
+            if let Ok(hash) = hash {
+                progress.bytes_hashed(hash.bytes_read);
+            }
+        })
+        .collect();
+    if partials.len() < size_candidates.len() {
+        diagnostics.interrupt(cancel);
+    }
+    let partials: Vec<(usize, PartialHash)> = partials
+        .into_iter()
+        .filter_map(|(index, hash)| match hash {
+            Ok(hash) => Some((index, hash)),
+            Err(e) => {
+                diagnostics.push_io(&entries[index].0, &e);
+                None
+            },
+        })
+        .collect();
+
+    let partial_candidates = colliding(
+        partials
+            .iter()
+            .map(|(index, hash)| (*index, (entries[*index].1, hash.digest.as_str()))),
+    );
+    report.partial = StageStats {
+        files_in: size_candidates.len(),
+        candidates_out: partial_candidates.len(),
+        bytes_read: partials.iter().map(|(_, hash)| hash.bytes_read).sum(),
+    };
+
+    // Stage 3: full hash for files that still collide, reusing complete partial reads
+    let partial_by_index: HashMap<usize, &PartialHash> = partials
+        .iter()
+        .map(|(index, hash)| (*index, hash))
+        .collect();
+    let (complete, needs_full): (Vec<usize>, Vec<usize>) = partial_candidates
+        .iter()
+        .copied()
+        .partition(|index| partial_by_index[index].complete);
+
+    for index in complete {
+        hashes[index] = Some(partial_by_index[&index].digest.clone());
+    }
+
+    progress.phase_started(Phase::FullHash(algorithm), needs_full.len() as u64);
+
+    let fulls: Vec<(usize, io::Result<(String, u64)>)> = needs_full
+        .par_iter()
+        .filter(|_| !cancel.is_cancelled())
+        .map(|&index| {
+            let path = &entries[index].0;
+            if let Some((cache, key)) = cached(index) {
+                if let Some(digest) = cache.full_hash(key, algorithm) {
+                    return (index, Ok((digest, 0)));
+                }
+            }
+
+            let hash = hash_file_with_budget(path, algorithm, budget);
+            if let (Ok((digest, _)), Some((cache, key))) = (&hash, cached(index)) {
+                cache.insert_full(*key, path, algorithm, digest);
+            }
+            (index, hash)
+        })
+        .inspect(|(_, hash)| {
+            progress.items_done(1);
+            if let Ok((_, bytes)) = hash {
+                progress.bytes_hashed(*bytes);
+            }
+        })
+        .collect();
+
+    if fulls.len() < needs_full.len() {
+        diagnostics.interrupt(cancel);
+    }
+    let mut full_bytes = 0;
+    for (index, hash) in fulls {
+        match hash {
+            Ok((digest, bytes)) => {
+                hashes[index] = Some(digest);
+                full_bytes += bytes;
+            },
+            Err(e) => diagnostics.push_io(&entries[index].0, &e),
+        }
+    }
+
+    report.full = StageStats {
+        files_in: partial_candidates.len(),
+        candidates_out: colliding(
+            hashes
+                .iter()
+                .enumerate()
+                .filter_map(|(index, hash)| hash.as_deref().map(|h| (index, h))),
+        )
+        .len(),
+        bytes_read: full_bytes,
+    };
+
+    (hashes, report, diagnostics)
+}
+
+/// Partial hash served from the cache, without touching the file.
+///
+/// Small files are hashed whole in the partial stage, so their cached full
+/// hash doubles as a complete partial hash.
+fn cached_partial_hash(
+    cache: &HashCache,
+    key: &CacheKey,
+    path: &Path,
+    size: u64,
+    algorithm: HashAlgorithm,
+) -> Option<PartialHash> {
+    let hash = if size <= PARTIAL_HASH_BLOCK * 2 {
+        cache.full_hash(key, algorithm).map(|digest| PartialHash {
+            digest,
+            bytes_read: 0,
+            complete: true,
+        })
+    } else {
+        cache
+            .partial_hash(key, algorithm)
+            .map(|digest| PartialHash {
+                digest,
+                bytes_read: 0,
+                complete: false,
+            })
+    }?;
+    cache.touch(key, path);
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use tempfile::TempDir;
+
+    fn entries_for(dir: &TempDir, files: &[(&str, Vec<u8>)]) -> Vec<(PathBuf, u64)> {
+        files
+            .iter()
+            .map(|(name, content)| {
+                let path = dir.path().join(name);
+                fs::write(&path, content).unwrap();
+                (path, content.len() as u64)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unique_sizes_are_never_read() {
+        let dir = TempDir::new().unwrap();
+        let entries = entries_for(
+            &dir,
+            &[
+                ("a", b"1".to_vec()),
+                ("b", b"22".to_vec()),
+                ("c", b"333".to_vec()),
+            ],
+        );
+
+        let (hashes, report, _) = staged_hash(&entries, HashAlgorithm::Md5, &NoProgress);
+        assert!(hashes.iter().all(Option::is_none));
+        assert_eq!(report.size.candidates_out, 0);
+        assert_eq!(report.total_bytes_read(), 0);
+    }
+
+    #[test]
+    fn test_partial_stage_drops_different_edges() {
+        let dir = TempDir::new().unwrap();
+        let size = (PARTIAL_HASH_BLOCK * 4) as usize;
+        let mut different_tail = vec![b'x'; size];
+        different_tail[size - 1] = b'y';
+        let entries = entries_for(
+            &dir,
+            &[
+                ("a", vec![b'x'; size]),
+                ("b", vec![b'x'; size]),
+                ("c", different_tail),
+            ],
+        );
+
+        let (hashes, report, _) = staged_hash(&entries, HashAlgorithm::Md5, &NoProgress);
+        assert_eq!(hashes[0], hashes[1]);
+        assert!(hashes[0].is_some());
+        assert!(hashes[2].is_none());
+        assert_eq!(report.partial.files_in, 3);
+        assert_eq!(report.partial.candidates_out, 2);
+        assert_eq!(report.partial.bytes_read, PARTIAL_HASH_BLOCK * 2 * 3);
+        assert_eq!(report.full.bytes_read, (size * 2) as u64);
+    }
+
+    #[test]
+    fn test_full_stage_separates_middle_differences() {
+        let dir = TempDir::new().unwrap();
+        let size = (PARTIAL_HASH_BLOCK * 4) as usize;
+        let mut different_middle = vec![b'x'; size];
+        different_middle[size / 2] = b'y';
+        let entries = entries_for(&dir, &[("a", vec![b'x'; size]), ("b", different_middle)]);
+
+        let (hashes, report, _) = staged_hash(&entries, HashAlgorithm::Md5, &NoProgress);
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(report.partial.candidates_out, 2);
+        assert_eq!(report.full.candidates_out, 0);
+    }
+
+    #[test]
+    fn test_small_files_skip_second_read() {
+        let dir = TempDir::new().unwrap();
+        let entries = entries_for(&dir, &[("a", b"same".to_vec()), ("b", b"same".to_vec())]);
+
+        let (hashes, report, _) = staged_hash(&entries, HashAlgorithm::Md5, &NoProgress);
+        assert_eq!(
+            hashes[0].as_deref(),
+            Some(format!("{:x}", md5::compute(b"same")).as_str())
+        );
+        assert_eq!(hashes[0], hashes[1]);
+        assert_eq!(report.full.bytes_read, 0);
+        assert_eq!(report.full.candidates_out, 2);
+    }
+
+    #[test]
+    fn test_cancelled_hashing_reads_nothing() {
+        let dir = TempDir::new().unwrap();
+        let entries = entries_for(&dir, &[("a", b"same".to_vec()), ("b", b"same".to_vec())]);
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let (hashes, report, diagnostics) = staged_hash_with_cache(
+            &entries,
+            HashAlgorithm::Md5,
+            None,
+            MemoryBudget::global(),
+            &NoProgress,
+            &cancel,
+        );
+        assert!(hashes.iter().all(Option::is_none));
+        assert_eq!(report.total_bytes_read(), 0);
+        assert_eq!(
+            diagnostics.interrupted,
+            Some(crate::cancel::Interruption::Cancelled)
+        );
+    }
+
+    #[test]
+    fn test_algorithms_produce_distinct_digests() {
+        let digests: Vec<String> = HashAlgorithm::ALL
+            .iter()
+            .map(|algorithm| algorithm.digest(b"rclean"))
+            .collect();
+
+        assert_eq!(digests[0].len(), 32);
+        assert_eq!(digests[1].len(), 64);
+        assert_eq!(digests[2].len(), 32);
+        assert_eq!(digests[3].len(), 64);
+        assert_ne!(digests[0], digests[2]);
+        assert_ne!(digests[1], digests[3]);
+    }
+
+    #[test]
+    fn test_incremental_matches_one_shot() {
+        for algorithm in HashAlgorithm::ALL {
+            let mut hasher = algorithm.hasher();
+            hasher.update(b"rcl");
+            hasher.update(b"ean");
+            assert_eq!(hasher.finish_hex(), algorithm.digest(b"rclean"));
+        }
+    }
+
+    #[test]
+    fn test_hash_file_streams_past_buffer_size() {
+        let dir = TempDir::new().unwrap();
+        let content: Vec<u8> = (0..HASH_BUFFER_SIZE * 3 + 17).map(|i| i as u8).collect();
+        let path = dir.path().join("big");
+        fs::write(&path, &content).unwrap();
+
+        for algorithm in HashAlgorithm::ALL {
+            let (digest, bytes) = hash_file(&path, algorithm).unwrap();
+            assert_eq!(digest, algorithm.digest(&content));
+            assert_eq!(bytes, content.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_hash_reader_honours_limit() {
+        let mut hasher = HashAlgorithm::Md5.hasher();
+        let read = hash_reader(&b"rclean"[..], hasher.as_mut(), Some(3)).unwrap();
+        assert_eq!(read, 3);
+        assert_eq!(hasher.finish_hex(), HashAlgorithm::Md5.digest(b"rcl"));
+    }
+
+    #[test]
+    fn test_memory_budget_clamps_and_releases() {
+        let budget = MemoryBudget::new(1000);
+        let first = budget.reserve(600);
+        assert_eq!(first.bytes(), 600);
+        assert_eq!(budget.in_use(), 600);
+
+        let released = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                // Clamped to the whole limit, so it must wait for `first`
+                let second = budget.reserve(5000);
+                assert!(released.load(std::sync::atomic::Ordering::SeqCst));
+                assert_eq!(second.bytes(), 1000);
+            });
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            released.store(true, std::sync::atomic::Ordering::SeqCst);
+            drop(first);
+        });
+        assert_eq!(budget.in_use(), 0);
+    }
+
+    #[test]
+    fn test_split_by_content_separates_differing_members() {
+        let dir = TempDir::new().unwrap();
+        let mut tail_differs = vec![7u8; HASH_BUFFER_SIZE * 2];
+        let same = tail_differs.clone();
+        *tail_differs.last_mut().unwrap() = 8;
+        let entries = entries_for(
+            &dir,
+            &[
+                ("a", same.clone()),
+                ("b", tail_differs.clone()),
+                ("c", same),
+                ("d", tail_differs),
+            ],
+        );
+        let missing = dir.path().join("missing");
+        let mut paths: Vec<&Path> = entries.iter().map(|(p, _)| p.as_path()).collect();
+        paths.push(&missing);
+
+        assert!(files_identical(paths[0], paths[2]).unwrap());
+        assert!(!files_identical(paths[0], paths[1]).unwrap());
+        assert_eq!(
+            split_by_content(&paths, MemoryBudget::global()),
+            vec![vec![0, 2], vec![1, 3], vec![4]]
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_hash_file_unreadable_is_none() {
+        let dir = TempDir::new().unwrap();
+        assert!(fuzzy_hash_file(dir.path().join("missing")).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fuzzy_hash_of_non_utf8_path_matches() {
+        use std::os::unix::ffi::OsStrExt as _;
+
+        let dir = TempDir::new().unwrap();
+        let content: Vec<u8> = (0..8192u32).map(|i| (i * 7 % 251) as u8).collect();
+        let plain = dir.path().join("plain.bin");
+        let latin1 = dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9.bin"));
+        fs::write(&plain, &content).unwrap();
+        fs::write(&latin1, &content).unwrap();
+
+        let budget = MemoryBudget::new(HASH_BUFFER_SIZE);
+        let expected = fuzzy_hash_file(&plain);
+        assert!(expected.is_some());
+        assert_eq!(fuzzy_hash_file_with_budget(&latin1, &budget), expected);
+        assert_eq!(budget.in_use(), 0);
+    }
+
+    #[test]
+    fn test_cached_rescan_reads_nothing() {
+        let dir = TempDir::new().unwrap();
+        let size = (PARTIAL_HASH_BLOCK * 4) as usize;
+        let entries = entries_for(
+            &dir,
+            &[
+                ("a", vec![b'x'; size]),
+                ("b", vec![b'x'; size]),
+                ("c", b"same".to_vec()),
+                ("d", b"same".to_vec()),
+            ],
+        );
+        let cache = HashCache::in_memory();
+        // One buffer at a time is enough, just slower
+        let budget = MemoryBudget::new(HASH_BUFFER_SIZE);
+        let pb = NoProgress;
+
+        let (first, report, _) = staged_hash_with_cache(
+            &entries,
+            HashAlgorithm::Md5,
+            Some(&cache),
+            &budget,
+            &pb,
+            &CancelToken::default(),
+        );
+        assert!(report.total_bytes_read() > 0);
+
+        let (second, report, _) = staged_hash_with_cache(
+            &entries,
+            HashAlgorithm::Md5,
+            Some(&cache),
+            &budget,
+            &pb,
+            &CancelToken::default(),
+        );
+        assert_eq!(first, second);
+        assert_eq!(report.total_bytes_read(), 0);
+
+        // A changed file misses the cache and is read again
+        fs::write(&entries[1].0, vec![b'y'; size]).unwrap();
+        let (third, report, _) = staged_hash_with_cache(
+            &entries,
+            HashAlgorithm::Md5,
+            Some(&cache),
+            &budget,
+            &pb,
+            &CancelToken::default(),
+        );
+        assert!(third[0].is_none());
+        assert_eq!(report.partial.bytes_read, PARTIAL_HASH_BLOCK * 2);
+        assert_eq!(budget.in_use(), 0);
+    }
+
+    #[test]
+    fn test_hash_algorithm_from_str() {
+        assert_eq!("BLAKE3".parse::<HashAlgorithm>(), Ok(HashAlgorithm::Blake3));
+        assert_eq!(
+            "sha-256".parse::<HashAlgorithm>(),
+            Ok(HashAlgorithm::Sha256)
+        );
+        assert!("crc32".parse::<HashAlgorithm>().is_err());
+        assert_eq!(HashAlgorithm::Xxh3.column_name(), "xxh3_hash");
+    }
+}
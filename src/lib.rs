@@ -1,25 +1,52 @@
 //! A high-performance disk cleanup library with parallel processing.
 //!
 //! This library provides functionality to scan directories, find duplicate files
-//! based on MD5 hashes, detect storage outliers, and generate detailed reports
-//! using Polars `DataFrames`.
+//! based on content hashes (MD5, BLAKE3, xxh3 or SHA-256), detect storage
+//! outliers, and generate detailed reports using Polars `DataFrames`.
 
+pub mod archives;
+pub mod atomic;
+pub mod cache;
+pub mod cancel;
 pub mod clustering;
+pub mod diagnostics;
+pub mod duplicate_dirs;
+pub mod empty_dirs;
+pub mod error;
+pub mod filters;
+pub mod growth;
+pub mod hashing;
 pub mod mcp_server;
 pub mod models;
+pub mod normalize;
 pub mod outliers;
-
+pub mod paths;
+pub mod patterns;
+pub mod plan;
+pub mod progress;
+pub mod quarantine;
+pub mod reclaim;
+pub mod replace;
+pub mod resolve;
+pub mod roots;
+
+use cache::CacheKey;
+pub use cache::HashCache;
+pub use cancel::CancelToken;
+pub use diagnostics::ScanDiagnostics;
+pub use error::{RcleanError, RcleanResult};
 pub use globset::{Glob, GlobSet, GlobSetBuilder};
+pub use hashing::HashAlgorithm;
 use ignore::WalkBuilder;
-use indicatif::{ParallelProgressIterator as _, ProgressStyle};
 use polars::prelude::*;
+pub use progress::ProgressSink;
 use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 pub use regex::Regex;
 use std::collections::HashMap;
-use std::error::Error;
 use std::fs;
-use std::path::Path;
-use walkdir::WalkDir;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 // Re-export for CLI usage
 pub use comfy_table;
@@ -35,6 +62,20 @@ pub enum PatternType {
     Glob(GlobSet),
     /// Regular expression matching.
     Regex(Regex),
+    /// Include and exclude patterns of mixed kinds (see [`patterns`]).
+    Set(patterns::PatternSet),
+}
+
+impl PatternType {
+    /// Whether `text` matches the pattern.
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Self::Literal(s) => text.contains(s.as_str()),
+            Self::Glob(g) => g.is_match(text),
+            Self::Regex(r) => r.is_match(text),
+            Self::Set(set) => set.is_match(text),
+        }
+    }
 }
 
 /// Options for directory walking.
@@ -48,6 +89,15 @@ pub struct WalkOptions {
     pub respect_ignore: bool,
     /// Maximum depth to traverse.
     pub max_depth: Option<usize>,
+    /// Follow symbolic links; links leading back to an ancestor directory
+    /// are skipped instead of looping forever.
+    pub follow_links: bool,
+    /// Stay on the filesystem of the walk root. Directories on another
+    /// filesystem (e.g. `/proc`, NFS or bind mounts) are skipped and listed.
+    pub same_file_system: bool,
+    /// Stops the walk, and the hashing of the files it found, once cancelled
+    /// or past its deadline; the partial result is marked as interrupted.
+    pub cancel: CancelToken,
 }
 
 impl Default for WalkOptions {
@@ -57,6 +107,87 @@ impl Default for WalkOptions {
             respect_gitignore: true,
             respect_ignore: true,
             max_depth: None,
+            follow_links: false,
+            same_file_system: false,
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+impl WalkOptions {
+    /// Options that list every file: hidden files included, ignore files not
+    /// consulted. Used by [`walk`].
+    pub fn unfiltered() -> Self {
+        Self {
+            include_hidden: true,
+            respect_gitignore: false,
+            respect_ignore: false,
+            ..Self::default()
+        }
+    }
+}
+
+/// Files found by a directory walk, together with what was skipped.
+#[derive(Debug, Clone, Default)]
+pub struct WalkSummary {
+    /// Every file found, including ones whose names are not valid UTF-8.
+    pub files: Vec<PathBuf>,
+    /// Unreadable entries, mount points on other filesystems (with
+    /// [`WalkOptions::same_file_system`]) and symlink loops that were skipped.
+    pub diagnostics: ScanDiagnostics,
+}
+
+/// Options controlling duplicate detection.
+#[derive(Debug, Clone, Default)]
+pub struct DedupeOptions {
+    /// Content hash algorithm used to identify duplicates.
+    pub hash_algorithm: HashAlgorithm,
+    /// Similarity threshold (0-100) for fuzzy matching; `None` disables it.
+    pub similarity: Option<u32>,
+    /// Budget the hashing read buffers of this run are reserved from; share
+    /// one between runs to cap them together. `None` uses
+    /// [`hashing::MemoryBudget::global`].
+    pub memory_budget: Option<Arc<hashing::MemoryBudget>>,
+    /// Persistent hash cache to reuse and update; `None` hashes from scratch.
+    pub cache_path: Option<PathBuf>,
+    /// Confirm every duplicate group with a byte-for-byte comparison, splitting
+    /// off members whose content differs despite an equal hash.
+    pub paranoid: bool,
+    /// Further roots scanned alongside the main path. Files under a
+    /// reference root are never modified or suggested for deletion (see
+    /// [`roots`]).
+    pub roots: Vec<roots::ScanRoot>,
+    /// Size, age, kind and type criteria applied along with the pattern.
+    pub filter: filters::FileFilter,
+    /// Also find identical directory trees and directories contained in
+    /// others, reporting each as one entry (see [`duplicate_dirs`]).
+    pub directories: bool,
+    /// Also read the members of zip and tar archives among the matched files
+    /// and treat each as a file of its own (see [`archives`]). Only dedupe
+    /// reports include them; resolve and plan never touch archive members.
+    pub archives: bool,
+    /// Also group text files whose content matches once normalized, as
+    /// normalized duplicates apart from exact ones (see [`normalize`]);
+    /// `None` disables it.
+    pub normalize_text: Option<normalize::TextNormalization>,
+    /// Receives hashing progress; `None` reports nothing.
+    pub progress: Option<Arc<dyn ProgressSink>>,
+}
+
+impl DedupeOptions {
+    /// The sink hashing progress is reported to.
+    pub fn progress(&self) -> &dyn ProgressSink {
+        match &self.progress {
+            Some(progress) => progress.as_ref(),
+            None => &progress::NoProgress,
+        }
+    }
+
+    /// The budget hashing buffers are reserved from.
+    pub fn memory_budget(&self) -> &hashing::MemoryBudget {
+        match &self.memory_budget {
+            Some(budget) => budget,
+            None => hashing::MemoryBudget::global(),
         }
     }
 }
@@ -83,12 +214,16 @@ pub fn display_thread_info() -> String {
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct FileInfo {
-    pub path: String,
+    pub path: PathBuf,
     pub name: String,
     pub extension: String,
     pub size_bytes: u64,
     pub size_mb: f64,
-    pub md5_hash: String,
+    /// Full content hash, or `None` when staged hashing ruled the file out as
+    /// a duplicate before it had to be read in full.
+    pub content_hash: Option<String>,
+    /// Algorithm that produced `content_hash`.
+    pub hash_algorithm: HashAlgorithm,
     pub fuzzy_hash: Option<String>,
     pub is_duplicate: bool,
     pub duplicate_group: Option<String>,
@@ -97,20 +232,119 @@ pub struct FileInfo {
     pub similarity_score: Option<f64>,
     pub created: Option<String>,
     pub modified: Option<String>,
+    /// Device and inode numbers identifying the physical file (`None` off Unix).
+    pub device: Option<u64>,
+    pub inode: Option<u64>,
+    /// Number of hard links to the inode, including ones outside the scan.
+    pub nlink: u64,
+    /// Another scanned path already represents this inode, so this path
+    /// occupies no additional disk space.
+    pub is_hardlink: bool,
+    /// Bytes this path adds to disk usage: `size_bytes`, or 0 for a hardlink.
+    pub physical_size_bytes: u64,
+    /// Bytes freed by removing this path when one copy of its duplicate group
+    /// is kept: `size_bytes` for every physical copy but the group's first.
+    pub reclaimable_bytes: u64,
+    /// Paranoid verification found that this file's bytes differ from the
+    /// rest of its hash group (a collision or a change mid-scan), so it was
+    /// split off into its own group.
+    pub verification_failed: bool,
+    /// The file lies under a reference root, so it is kept rather than
+    /// counted as reclaimable.
+    pub is_reference: bool,
+    /// The file lies in a directory reported as a copy of another one (see
+    /// [`duplicate_dirs`]), so it is not grouped file by file.
+    pub in_duplicate_dir: bool,
+    /// For a member read from an archive (see [`archives`]), the archive it
+    /// lives in; `path` is then a virtual path inside it.
+    pub archive: Option<PathBuf>,
+    /// Hash of the normalized text, for a text file when normalized
+    /// comparison ran (see [`normalize`]).
+    pub normalized_hash: Option<String>,
+    /// Normalized group of a text file whose normalized content matches
+    /// another file's while their bytes differ.
+    pub normalized_group: Option<String>,
 }
 
 impl FileInfo {
-    pub fn new(path: &str) -> Result<Self, Box<dyn Error>> {
-        let path_obj = Path::new(path);
-        let metadata = fs::metadata(path)?;
+    /// Build file information with full MD5 and fuzzy hashes.
+    pub fn new(path: impl AsRef<Path>) -> RcleanResult<Self> {
+        Self::with_algorithm(path, HashAlgorithm::default())
+    }
+
+    /// Build file information with a full content hash from `algorithm` and a fuzzy hash.
+    pub fn with_algorithm(path: impl AsRef<Path>, algorithm: HashAlgorithm) -> RcleanResult<Self> {
+        Self::with_cache(path, algorithm, None)
+    }
+
+    /// Like [`FileInfo::with_algorithm`], reusing and recording hashes in `cache`.
+    pub fn with_cache(
+        path: impl AsRef<Path>,
+        algorithm: HashAlgorithm,
+        cache: Option<&HashCache>,
+    ) -> RcleanResult<Self> {
+        Self::with_cache_and_budget(path, algorithm, cache, hashing::MemoryBudget::global())
+    }
+
+    /// Like [`FileInfo::with_cache`], reserving read buffers from `budget`.
+    pub fn with_cache_and_budget(
+        path: impl AsRef<Path>,
+        algorithm: HashAlgorithm,
+        cache: Option<&HashCache>,
+        budget: &hashing::MemoryBudget,
+    ) -> RcleanResult<Self> {
+        let path = path.as_ref();
+        let mut info = Self::from_metadata(path)?;
+        let cached = cache.zip(CacheKey::for_path(path));
+
+        let cached_digest = cached.and_then(|(cache, key)| cache.full_hash(&key, algorithm));
+        let digest = match cached_digest {
+            Some(digest) => digest,
+            None => {
+                let (digest, _) = hashing::hash_file_with_budget(path, algorithm, budget)
+                    .map_err(|e| RcleanError::io(path, e))?;
+                if let Some((cache, key)) = cached {
+                    cache.insert_full(key, path, algorithm, &digest);
+                }
+                digest
+            },
+        };
+        info.content_hash = Some(digest);
+        info.hash_algorithm = algorithm;
+
+        // Calculate fuzzy hash for similarity detection
+        // Skip files that are too small (< 512 bytes) or too large (> 100MB)
+        info.fuzzy_hash = if (512..1024 * 1024 * 100).contains(&info.size_bytes) {
+            let cached_fuzzy = cached.and_then(|(cache, key)| cache.fuzzy_hash(&key));
+            cached_fuzzy.or_else(|| {
+                let fuzzy = hashing::fuzzy_hash_file_with_budget(path, budget)?;
+                if let Some((cache, key)) = cached {
+                    cache.insert_fuzzy(key, path, &fuzzy);
+                }
+                Some(fuzzy)
+            })
+        } else {
+            None
+        };
+        if let Some((cache, key)) = cached {
+            cache.touch(&key, path);
+        }
 
-        let name = path_obj
+        Ok(info)
+    }
+
+    /// Build file information from metadata only, without reading the content.
+    pub fn from_metadata(path: impl AsRef<Path>) -> RcleanResult<Self> {
+        let path = path.as_ref();
+        let metadata = fs::metadata(path).map_err(|e| RcleanError::io(path, e))?;
+
+        let name = path
             .file_name()
             .unwrap_or_default()
             .to_string_lossy()
             .to_string();
 
-        let extension = path_obj
+        let extension = path
             .extension()
             .unwrap_or_default()
             .to_string_lossy()
@@ -120,17 +354,6 @@ impl FileInfo {
         #[allow(clippy::cast_precision_loss)]
         let size_mb = size_bytes as f64 / 1_048_576.0; // Convert bytes to MB
 
-        let file_content = fs::read(path)?;
-        let md5_hash = format!("{:x}", md5::compute(&file_content));
-
-        // Calculate fuzzy hash for similarity detection
-        // Skip files that are too small (< 512 bytes) or too large (> 100MB)
-        let fuzzy_hash = if file_content.len() >= 512 && file_content.len() < 1024 * 1024 * 100 {
-            ssdeep::hash(&file_content).ok()
-        } else {
-            None
-        };
-
         let created = metadata
             .created()
             .ok()
@@ -143,14 +366,17 @@ impl FileInfo {
             .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
             .map(|duration| format!("{}", duration.as_secs()));
 
+        let (device, inode, nlink) = inode_info(&metadata);
+
         Ok(Self {
-            path: path.to_string(),
+            path: path.to_path_buf(),
             name,
             extension,
             size_bytes,
             size_mb,
-            md5_hash,
-            fuzzy_hash,
+            content_hash: None,
+            hash_algorithm: HashAlgorithm::default(),
+            fuzzy_hash: None,
             is_duplicate: false,
             duplicate_group: None,
             is_similar: false,
@@ -158,62 +384,193 @@ impl FileInfo {
             similarity_score: None,
             created,
             modified,
+            device,
+            inode,
+            nlink,
+            is_hardlink: false,
+            physical_size_bytes: size_bytes,
+            reclaimable_bytes: 0,
+            verification_failed: false,
+            is_reference: false,
+            in_duplicate_dir: false,
+            archive: None,
+            normalized_hash: None,
+            normalized_group: None,
         })
     }
-}
 
-pub fn walk(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let mut files = Vec::new();
-    let mut permission_errors = 0;
+    /// File information for `member`, read from the archive described by
+    /// `archive`. Members have no inode and add no disk usage of their own.
+    pub fn archive_member(
+        archive: &Self,
+        member: archives::ArchiveMember,
+        algorithm: HashAlgorithm,
+    ) -> Self {
+        let name = member
+            .name
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let extension = member
+            .name
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        Self {
+            path: member.path,
+            name,
+            extension,
+            size_bytes: member.size_bytes,
+            size_mb: member.size_bytes as f64 / 1_048_576.0,
+            content_hash: Some(member.content_hash),
+            hash_algorithm: algorithm,
+            fuzzy_hash: None,
+            is_duplicate: false,
+            duplicate_group: None,
+            is_similar: false,
+            similarity_group: None,
+            similarity_score: None,
+            created: archive.created.clone(),
+            modified: archive.modified.clone(),
+            device: None,
+            inode: None,
+            nlink: 1,
+            is_hardlink: false,
+            physical_size_bytes: 0,
+            reclaimable_bytes: 0,
+            verification_failed: false,
+            is_reference: archive.is_reference,
+            in_duplicate_dir: false,
+            archive: Some(archive.path.clone()),
+            normalized_hash: None,
+            normalized_group: None,
+        }
+    }
 
-    for entry in WalkDir::new(path) {
-        match entry {
-            Ok(entry) => {
-                if entry.file_type().is_file() {
-                    if let Some(path_str) = entry.path().to_str() {
-                        files.push(path_str.to_string());
-                    }
-                }
-            },
-            Err(err) => {
-                // Check if it's a recoverable error
-                if let Some(io_err) = err.io_error() {
-                    match io_err.kind() {
-                        std::io::ErrorKind::PermissionDenied
-                        | std::io::ErrorKind::InvalidInput
-                        | std::io::ErrorKind::InvalidData
-                        | std::io::ErrorKind::NotFound => {
-                            permission_errors += 1;
-                            // Continue walking, don't fail on these errors
-                            continue;
-                        },
-                        _ => {
-                            // For other IO errors, propagate them
-                            return Err(Box::new(err));
-                        },
-                    }
-                }
-                // For non-IO errors, propagate them
-                return Err(Box::new(err));
-            },
+    /// Identity of the physical file, if the platform provides one.
+    pub const fn physical_id(&self) -> Option<(u64, u64)> {
+        match (self.device, self.inode) {
+            (Some(device), Some(inode)) => Some((device, inode)),
+            _ => None,
         }
     }
+}
+
+/// Device, inode and link count of a file.
+#[cfg(unix)]
+fn inode_info(metadata: &fs::Metadata) -> (Option<u64>, Option<u64>, u64) {
+    use std::os::unix::fs::MetadataExt as _;
+    (Some(metadata.dev()), Some(metadata.ino()), metadata.nlink())
+}
 
-    if permission_errors > 0 {
-        eprintln!("⚠️  Skipped {permission_errors} directories due to permission errors");
+/// Device, inode and link count of a file.
+#[cfg(not(unix))]
+fn inode_info(_metadata: &fs::Metadata) -> (Option<u64>, Option<u64>, u64) {
+    (None, None, 1)
+}
+
+/// Mark every path after the first one of each inode as a hardlink that adds
+/// no physical size; archive members add none either. Returns the number of
+/// hardlinks found.
+fn collapse_hardlinks(file_infos: &mut [FileInfo]) -> usize {
+    let mut seen = std::collections::HashSet::new();
+    let mut hardlinks = 0;
+    for file_info in file_infos.iter_mut() {
+        let is_hardlink = file_info.physical_id().is_some_and(|id| !seen.insert(id));
+        file_info.is_hardlink = is_hardlink;
+        file_info.physical_size_bytes = if is_hardlink || file_info.archive.is_some() {
+            0
+        } else {
+            file_info.size_bytes
+        };
+        hardlinks += usize::from(is_hardlink);
     }
+    hardlinks
+}
 
-    Ok(files)
+/// Walk a directory recursively and return every file path, including hidden
+/// files and files excluded by ignore rules.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked.
+pub fn walk(path: impl AsRef<Path>) -> RcleanResult<Vec<PathBuf>> {
+    walk_with_options(path, &WalkOptions::unfiltered())
+}
+
+/// Device number of `path` (`None` off Unix, where filesystems are not compared).
+#[cfg(unix)]
+fn device_of(path: &Path, follow_links: bool) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt as _;
+    let metadata = if follow_links {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    };
+    metadata.ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_path: &Path, _follow_links: bool) -> Option<u64> {
+    None
+}
+
+/// The symlink that closed a loop, if `err` reports one.
+fn loop_child(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => loop_child(err),
+        _ => None,
+    }
+}
+
+/// The path `err` refers to, if it names one.
+fn error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        },
+        _ => None,
+    }
 }
 
 /// Walk a directory recursively with gitignore support and return all file paths.
 ///
+/// Unreadable entries, skipped mount points and symlink loops are left out;
+/// use [`walk_with_summary`] to learn which ones.
+///
+/// Files whose names are not valid UTF-8 are returned like any other.
+///
 /// # Errors
 ///
-/// Returns an error if:
-/// - The directory cannot be accessed
-/// - A path contains invalid UTF-8
-pub fn walk_with_options(path: &str, options: &WalkOptions) -> Result<Vec<String>, Box<dyn Error>> {
+/// Returns an error if the walk cannot be set up, e.g. because of an invalid
+/// ignore file.
+pub fn walk_with_options(
+    path: impl AsRef<Path>,
+    options: &WalkOptions,
+) -> RcleanResult<Vec<PathBuf>> {
+    walk_with_summary(path, options).map(|summary| summary.files)
+}
+
+/// Walk a directory like [`walk_with_options`], returning the files together
+/// with a [`ScanDiagnostics`] listing every entry that was skipped: entries
+/// that could not be read (permission denied, vanished, other I/O errors),
+/// mount points on other filesystems and symlink loops.
+///
+/// # Errors
+///
+/// Returns an error if the walk cannot be set up, e.g. because of an invalid
+/// ignore file.
+pub fn walk_with_summary(
+    path: impl AsRef<Path>,
+    options: &WalkOptions,
+) -> RcleanResult<WalkSummary> {
+    let path = path.as_ref();
     let mut builder = WalkBuilder::new(path);
 
     // Configure the walker
@@ -222,96 +579,127 @@ pub fn walk_with_options(path: &str, options: &WalkOptions) -> Result<Vec<String
         .ignore(options.respect_ignore)
         .git_ignore(options.respect_gitignore)
         .git_global(options.respect_gitignore)
-        .git_exclude(options.respect_gitignore);
+        .git_exclude(options.respect_gitignore)
+        .follow_links(options.follow_links);
 
     if let Some(max_depth) = options.max_depth {
         builder.max_depth(Some(max_depth));
     }
 
+    let skipped_mounts = Arc::new(Mutex::new(Vec::new()));
+    if options.same_file_system {
+        if let Some(root_device) = device_of(path, true) {
+            let follow_links = options.follow_links;
+            let skipped = Arc::clone(&skipped_mounts);
+            builder.filter_entry(move |entry| {
+                if entry.depth() == 0 || !entry.file_type().is_some_and(|ft| ft.is_dir()) {
+                    return true;
+                }
+                let on_root_device = device_of(entry.path(), follow_links)
+                    .is_none_or(|device| device == root_device);
+                if !on_root_device {
+                    if let Ok(mut skipped) = skipped.lock() {
+                        skipped.push(entry.path().to_path_buf());
+                    }
+                }
+                on_root_device
+            });
+        }
+    }
+
     let walker = builder.build();
-    let mut files = Vec::new();
-    let mut permission_errors = 0;
+    let mut summary = WalkSummary::default();
 
     for entry in walker {
+        if options.cancel.is_cancelled() {
+            summary.diagnostics.interrupt(&options.cancel);
+            break;
+        }
         match entry {
             Ok(entry) => {
                 if entry.file_type().is_some_and(|ft| ft.is_file()) {
-                    if let Some(path_str) = entry.path().to_str() {
-                        files.push(path_str.to_string());
-                    }
+                    summary.files.push(entry.into_path());
                 }
             },
             Err(err) => {
-                // Check if it's a recoverable error
-                if let Some(io_err) = err.io_error() {
-                    match io_err.kind() {
-                        std::io::ErrorKind::PermissionDenied
-                        | std::io::ErrorKind::InvalidInput
-                        | std::io::ErrorKind::InvalidData
-                        | std::io::ErrorKind::NotFound => {
-                            permission_errors += 1;
-                            // Continue walking, don't fail on these errors
-                            continue;
-                        },
-                        _ => {
-                            // For other IO errors, propagate them
-                            return Err(Box::new(err));
-                        },
-                    }
+                if let Some(child) = loop_child(&err) {
+                    summary
+                        .diagnostics
+                        .push(child, diagnostics::SkipReason::SymlinkLoop);
+                    continue;
                 }
-                // For non-IO errors, propagate them
-                return Err(Box::new(err));
+                // Unreadable entries are skipped, anything else is fatal
+                let Some(io_err) = err.io_error() else {
+                    return Err(err.into());
+                };
+                let skipped = error_path(&err).unwrap_or(path);
+                summary.diagnostics.push_io(skipped, io_err);
             },
         }
     }
 
-    if permission_errors > 0 {
-        eprintln!("⚠️  Skipped {permission_errors} directories due to permission errors");
+    let mut skipped_mounts = skipped_mounts
+        .lock()
+        .map(|mut skipped| std::mem::take(&mut *skipped))
+        .unwrap_or_default();
+    skipped_mounts.sort();
+    for mount in skipped_mounts {
+        summary
+            .diagnostics
+            .push(mount, diagnostics::SkipReason::OtherFilesystem);
     }
-
-    Ok(files)
+    Ok(summary)
 }
 
 /// Find files matching a pattern.
 ///
+/// Paths are matched in their [`paths::display`] form, so bytes that are
+/// not valid UTF-8 are matched as `\xNN`.
+///
 /// # Examples
 ///
 /// ```
+/// use std::path::PathBuf;
+///
 /// let files = vec![
-///     "test.txt".to_string(),
-///     "data.csv".to_string(),
-///     "test_data.txt".to_string(),
+///     PathBuf::from("test.txt"),
+///     PathBuf::from("data.csv"),
+///     PathBuf::from("test_data.txt"),
 /// ];
 /// let matches = rclean::find(&files, "test");
 /// assert_eq!(matches.len(), 2);
-/// assert!(matches.contains(&"test.txt".to_string()));
-/// assert!(matches.contains(&"test_data.txt".to_string()));
+/// assert!(matches.contains(&PathBuf::from("test.txt")));
+/// assert!(matches.contains(&PathBuf::from("test_data.txt")));
 /// ```
 #[must_use]
-pub fn find(files: &[String], pattern: &str) -> Vec<String> {
+pub fn find(files: &[PathBuf], pattern: &str) -> Vec<PathBuf> {
     files
         .iter()
-        .filter(|file| file.contains(pattern))
+        .filter(|file| paths::display(file).contains(pattern))
         .cloned()
         .collect()
 }
 
-/// Find files matching an advanced pattern.
+/// Find files matching an advanced pattern that also pass `filter` (size,
+/// age, kind and type criteria; see [`filters`]). Like [`find`], patterns
+/// see the [`paths::display`] form of each path.
 ///
 /// # Examples
 ///
 /// ```
 /// use rclean::{PatternType, find_advanced, GlobSetBuilder, Glob};
+/// use rclean::filters::FileFilter;
+/// use std::path::PathBuf;
 ///
 /// let files = vec![
-///     "test.txt".to_string(),
-///     "data.csv".to_string(),
-///     "test_data.json".to_string(),
+///     PathBuf::from("test.txt"),
+///     PathBuf::from("data.csv"),
+///     PathBuf::from("test_data.json"),
 /// ];
 ///
 /// // Literal pattern
 /// let pattern = PatternType::Literal("test".to_string());
-/// let matches = find_advanced(&files, &pattern);
+/// let matches = find_advanced(&files, &pattern, &FileFilter::default());
 /// assert_eq!(matches.len(), 2);
 ///
 /// // Glob pattern
@@ -319,123 +707,442 @@ pub fn find(files: &[String], pattern: &str) -> Vec<String> {
 /// builder.add(Glob::new("*.txt").unwrap());
 /// let globset = builder.build().unwrap();
 /// let pattern = PatternType::Glob(globset);
-/// let matches = find_advanced(&files, &pattern);
+/// let matches = find_advanced(&files, &pattern, &FileFilter::default());
 /// assert_eq!(matches.len(), 1);
-/// assert_eq!(matches[0], "test.txt");
+/// assert_eq!(matches[0], PathBuf::from("test.txt"));
 /// ```
 #[must_use]
-pub fn find_advanced(files: &[String], pattern: &PatternType) -> Vec<String> {
+pub fn find_advanced(
+    files: &[PathBuf],
+    pattern: &PatternType,
+    filter: &filters::FileFilter,
+) -> Vec<PathBuf> {
     files
         .iter()
-        .filter(|file| match pattern {
-            PatternType::Literal(s) => file.contains(s),
-            PatternType::Glob(g) => g.is_match(file),
-            PatternType::Regex(r) => r.is_match(file),
-        })
+        .filter(|file| pattern.is_match(&paths::display(file)))
+        .filter(|file| filter.matches(file))
         .cloned()
         .collect()
 }
 
-// New function to collect detailed file information - TRUE PARALLEL VERSION
 /// Collect detailed file information in parallel.
 ///
+/// Content hashes are computed with the staged pipeline from [`hashing`], so
+/// files that cannot have a duplicate keep `content_hash: None`.
+///
 /// # Errors
 ///
-/// Returns an error if progress bar creation fails.
-pub fn collect_file_info(files: &[String]) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+/// Currently infallible; unreadable files are left out.
+pub fn collect_file_info(files: &[PathBuf]) -> RcleanResult<Vec<FileInfo>> {
+    collect_file_info_with_report(
+        files,
+        HashAlgorithm::default(),
+        None,
+        hashing::MemoryBudget::global(),
+        &progress::NoProgress,
+        &CancelToken::default(),
+    )
+    .map(|(file_infos, _, _)| file_infos)
+}
+
+/// Collect detailed file information and report the bytes read by each hashing stage.
+///
+/// With a `cache`, unchanged files are served from it instead of being read.
+/// Files that could not be stat'ed or read are left out and listed in the
+/// returned [`ScanDiagnostics`]. Hashing progress is reported to `progress`.
+/// Once `cancel` fires, files not reached yet are left out or left unhashed
+/// and the diagnostics are marked as interrupted.
+///
+/// # Errors
+///
+/// Currently infallible; unreadable files are left out.
+pub fn collect_file_info_with_report(
+    files: &[PathBuf],
+    algorithm: HashAlgorithm,
+    cache: Option<&HashCache>,
+    budget: &hashing::MemoryBudget,
+    progress: &dyn ProgressSink,
+    cancel: &CancelToken,
+) -> RcleanResult<(Vec<FileInfo>, hashing::HashingReport, ScanDiagnostics)> {
     if files.is_empty() {
-        return Ok(Vec::new());
+        return Ok((
+            Vec::new(),
+            hashing::HashingReport::default(),
+            ScanDiagnostics::default(),
+        ));
     }
 
-    println!(
-        "\nAnalyzing {} files with {} threads...",
-        files.len(),
-        rayon::current_num_threads()
-    );
+    // Metadata only: failed files are dropped here, before any hashing
+    let mut diagnostics = ScanDiagnostics::default();
+    let results: Vec<(&PathBuf, RcleanResult<FileInfo>)> = files
+        .par_iter()
+        .filter(|_| !cancel.is_cancelled())
+        .map(|file_path| (file_path, FileInfo::from_metadata(file_path)))
+        .collect();
+    if results.len() < files.len() {
+        diagnostics.interrupt(cancel);
+    }
+    let mut file_infos = Vec::with_capacity(results.len());
+    for (file_path, result) in results {
+        match result {
+            Ok(file_info) => file_infos.push(file_info),
+            Err(e) => diagnostics.push_error(file_path, &e),
+        }
+    }
+
+    // Hash each physical file once; hardlinks inherit the hash of their inode
+    collapse_hardlinks(&mut file_infos);
+    let physical: Vec<usize> = (0..file_infos.len())
+        .filter(|&index| !file_infos[index].is_hardlink)
+        .collect();
+    let entries: Vec<(PathBuf, u64)> = physical
+        .iter()
+        .map(|&index| (file_infos[index].path.clone(), file_infos[index].size_bytes))
+        .collect();
+
+    let (hashes, report, unread) =
+        hashing::staged_hash_with_cache(&entries, algorithm, cache, budget, progress, cancel);
+    diagnostics.extend(unread);
+    progress.finished();
+
+    let mut inode_hashes = HashMap::new();
+    for (index, hash) in physical.into_iter().zip(hashes) {
+        if let Some(id) = file_infos[index].physical_id() {
+            inode_hashes.insert(id, hash.clone());
+        }
+        file_infos[index].content_hash = hash;
+    }
+    for file_info in &mut file_infos {
+        if file_info.is_hardlink {
+            file_info.content_hash = file_info
+                .physical_id()
+                .and_then(|id| inode_hashes.get(&id).cloned().flatten());
+        }
+        file_info.hash_algorithm = algorithm;
+    }
 
-    let pb = indicatif::ProgressBar::new(files.len() as u64);
-    #[allow(clippy::expect_used)]
-    let sty = ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) {msg}")
-        .expect("Failed to create progress bar template")
-        .progress_chars("##-");
+    Ok((file_infos, report, diagnostics))
+}
 
-    pb.set_style(sty);
-    pb.set_message("Computing MD5 hashes...");
+/// Collect file information with full content and fuzzy hashes for every file.
+///
+/// Similarity detection needs the content of every file, so the staged
+/// pipeline cannot skip any reads here.
+fn collect_full_file_info(
+    files: &[PathBuf],
+    algorithm: HashAlgorithm,
+    cache: Option<&HashCache>,
+    budget: &hashing::MemoryBudget,
+    progress: &dyn ProgressSink,
+    cancel: &CancelToken,
+) -> RcleanResult<(Vec<FileInfo>, ScanDiagnostics)> {
+    if files.is_empty() {
+        return Ok((Vec::new(), ScanDiagnostics::default()));
+    }
 
-    // Enable steady tick to ensure spinner is visible
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    progress.phase_started(progress::Phase::FuzzyHash(algorithm), files.len() as u64);
 
     // TRUE PARALLEL: Each thread processes files independently, no shared mutex
-    let file_infos: Vec<Option<FileInfo>> = files
+    let results: Vec<(&PathBuf, RcleanResult<FileInfo>)> = files
         .par_iter()
-        .progress_with(pb.clone())
-        .map(|file_path| FileInfo::new(file_path).ok())
+        .filter(|_| !cancel.is_cancelled())
+        .map(|file_path| {
+            let info = FileInfo::with_cache_and_budget(file_path, algorithm, cache, budget);
+            (file_path, info)
+        })
+        .inspect(|_| progress.items_done(1))
         .collect();
 
-    pb.finish_with_message("✓ File analysis complete!");
-    println!();
-
-    // Filter out failed files
-    let valid_infos: Vec<FileInfo> = file_infos.into_iter().flatten().collect();
+    progress.finished();
 
-    Ok(valid_infos)
+    // Set failed files aside
+    let mut diagnostics = ScanDiagnostics::default();
+    if results.len() < files.len() {
+        diagnostics.interrupt(cancel);
+    }
+    let mut file_infos = Vec::with_capacity(results.len());
+    for (file_path, result) in results {
+        match result {
+            Ok(file_info) => file_infos.push(file_info),
+            Err(e) => diagnostics.push_error(file_path, &e),
+        }
+    }
+    Ok((file_infos, diagnostics))
 }
 
-// Create Polars DataFrame from file information
-pub fn create_dataframe(mut file_infos: Vec<FileInfo>) -> Result<DataFrame, Box<dyn Error>> {
-    // Group files by hash to identify duplicates
+/// Assign duplicate groups by content hash, returning the number of duplicate
+/// files and groups. Files without a hash are never duplicates, and paths that
+/// are all hardlinks to one inode are a single physical file, not a group.
+/// Groups made up only of reference files are not reported, and a reference
+/// copy, or else an archive member, is always the one that would be kept.
+/// Archive members are never reclaimable. Files in a duplicate directory are
+/// left out.
+///
+/// With a `paranoid` budget, every group is confirmed byte for byte, through
+/// buffers reserved from it, and members that differ are split into groups
+/// of their own (see [`split_unverified`]).
+fn mark_duplicates(
+    file_infos: &mut [FileInfo],
+    paranoid: Option<&hashing::MemoryBudget>,
+) -> (usize, usize) {
+    collapse_hardlinks(file_infos);
+
     let mut hash_groups: HashMap<String, Vec<usize>> = HashMap::new();
 
     for (index, file_info) in file_infos.iter().enumerate() {
-        hash_groups
-            .entry(file_info.md5_hash.clone())
-            .or_default()
-            .push(index);
+        if file_info.in_duplicate_dir {
+            continue;
+        }
+        if let Some(hash) = &file_info.content_hash {
+            hash_groups.entry(hash.clone()).or_default().push(index);
+        }
+    }
+
+    if let Some(budget) = paranoid {
+        hash_groups = hash_groups
+            .into_iter()
+            .flat_map(|(hash, indices)| split_unverified(file_infos, &hash, indices, budget))
+            .collect();
     }
 
     // Mark duplicates and assign group IDs - ONLY for files that actually have duplicates
     let mut duplicate_count = 0;
+    let mut group_count = 0;
     for (hash, indices) in &hash_groups {
-        if indices.len() > 1 {
-            let group_id = hash.clone();
+        let physical: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&index| !file_infos[index].is_hardlink)
+            .collect();
+        let has_candidate = indices.iter().any(|&index| !file_infos[index].is_reference);
+        if physical.len() > 1 && has_candidate {
             duplicate_count += indices.len();
+            group_count += 1;
 
             for &index in indices {
                 file_infos[index].is_duplicate = true;
-                file_infos[index].duplicate_group = Some(group_id.clone());
+                file_infos[index].duplicate_group = Some(hash.clone());
+            }
+            // The first reference copy, or else the first archive member, or
+            // else the first physical copy, is the one that would be kept
+            let keep = physical
+                .iter()
+                .copied()
+                .find(|&index| file_infos[index].is_reference)
+                .or_else(|| {
+                    physical
+                        .iter()
+                        .copied()
+                        .find(|&index| file_infos[index].archive.is_some())
+                })
+                .unwrap_or(physical[0]);
+            for &index in &physical {
+                let file_info = &mut file_infos[index];
+                if index != keep && !file_info.is_reference && file_info.archive.is_none() {
+                    file_info.reclaimable_bytes = file_info.size_bytes;
+                }
             }
         }
     }
 
-    println!(
-        "Found {} files in {} duplicate groups",
-        duplicate_count,
-        hash_groups.iter().filter(|(_, v)| v.len() > 1).count()
-    );
+    (duplicate_count, group_count)
+}
+
+/// Byte-compare the physical members of one hash group and split it into
+/// sets of identical files. The first set keeps `hash` as its group id; the
+/// others get `hash~1`, `hash~2`, ... and their members (including hardlinks
+/// to them) are flagged with [`FileInfo::verification_failed`].
+fn split_unverified(
+    file_infos: &mut [FileInfo],
+    hash: &str,
+    indices: Vec<usize>,
+    budget: &hashing::MemoryBudget,
+) -> Vec<(String, Vec<usize>)> {
+    let physical: Vec<usize> = indices
+        .iter()
+        .copied()
+        .filter(|&index| !file_infos[index].is_hardlink)
+        .collect();
+    if physical.len() < 2 {
+        return vec![(hash.to_string(), indices)];
+    }
+
+    let paths: Vec<&Path> = physical
+        .iter()
+        .map(|&index| file_infos[index].path.as_path())
+        .collect();
+    let partitions = hashing::split_by_content(&paths, budget);
+
+    let mut groups = Vec::with_capacity(partitions.len());
+    for (n, partition) in partitions.into_iter().enumerate() {
+        let ids: Vec<Option<(u64, u64)>> = partition
+            .iter()
+            .map(|&p| file_infos[physical[p]].physical_id())
+            .collect();
+        let members: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&index| {
+                let file_info = &file_infos[index];
+                partition.iter().any(|&p| physical[p] == index)
+                    || (file_info.is_hardlink && ids.contains(&file_info.physical_id()))
+            })
+            .collect();
+        if n > 0 {
+            for &index in &members {
+                file_infos[index].verification_failed = true;
+            }
+        }
+        let id = if n == 0 {
+            hash.to_string()
+        } else {
+            format!("{hash}~{n}")
+        };
+        groups.push((id, members));
+    }
+    groups
+}
+
+/// Confirm duplicate groups (as returned by [`find_duplicates`]) byte for
+/// byte, splitting off members whose content differs from the group's first
+/// file. Returns the confirmed groups with at least two members and the
+/// number of files that were split off. Comparison buffers are reserved from
+/// `budget`.
+pub fn verify_duplicate_groups(
+    groups: Vec<Vec<PathBuf>>,
+    budget: &hashing::MemoryBudget,
+) -> (Vec<Vec<PathBuf>>, usize) {
+    let mut verified = Vec::with_capacity(groups.len());
+    let mut split = 0;
+    for group in groups {
+        let paths: Vec<&Path> = group.iter().map(PathBuf::as_path).collect();
+        for (n, partition) in hashing::split_by_content(&paths, budget)
+            .into_iter()
+            .enumerate()
+        {
+            if n > 0 {
+                split += partition.len();
+            }
+            if partition.len() > 1 {
+                verified.push(partition.into_iter().map(|i| group[i].clone()).collect());
+            }
+        }
+    }
+    (verified, split)
+}
+
+/// Name of the content hash column in a dedupe `DataFrame`, if it has one.
+///
+/// The column is named after the algorithm that produced it, e.g. `md5_hash`
+/// or `blake3_hash`.
+pub fn hash_column_name(df: &DataFrame) -> Option<&'static str> {
+    let names = df.get_column_names();
+    HashAlgorithm::ALL
+        .into_iter()
+        .map(HashAlgorithm::column_name)
+        .find(|column| names.contains(column))
+}
+
+/// Empty dedupe `DataFrame` with the same schema as a populated one.
+fn empty_dataframe(algorithm: HashAlgorithm, with_similarity: bool) -> PolarsResult<DataFrame> {
+    let mut df = df! [
+        "file_path" => Vec::<String>::new(),
+        paths::HEX_COLUMN => Vec::<Option<String>>::new(),
+        "file_name" => Vec::<String>::new(),
+        "extension" => Vec::<String>::new(),
+        "size_bytes" => Vec::<u64>::new(),
+        "size_mb" => Vec::<f64>::new(),
+        "physical_size_bytes" => Vec::<u64>::new(),
+        reclaim::RECLAIMABLE_COLUMN => Vec::<u64>::new(),
+        algorithm.column_name() => Vec::<Option<String>>::new(),
+        "is_duplicate" => Vec::<bool>::new(),
+        "duplicate_group" => Vec::<Option<String>>::new(),
+        "device" => Vec::<Option<u64>>::new(),
+        "inode" => Vec::<Option<u64>>::new(),
+        "is_hardlink" => Vec::<bool>::new(),
+        "verification_failed" => Vec::<bool>::new(),
+        roots::REFERENCE_COLUMN => Vec::<bool>::new(),
+        archives::ARCHIVE_COLUMN => Vec::<Option<String>>::new(),
+        normalize::NORMALIZED_DUPLICATE_COLUMN => Vec::<bool>::new(),
+        normalize::NORMALIZED_GROUP_COLUMN => Vec::<Option<String>>::new(),
+    ]?;
+
+    if with_similarity {
+        df.with_column(Series::new("is_similar", Vec::<bool>::new()))?;
+        df.with_column(Series::new("similarity_score", Vec::<Option<f64>>::new()))?;
+    }
+
+    Ok(df)
+}
+
+// Create Polars DataFrame from file information
+pub fn create_dataframe(mut file_infos: Vec<FileInfo>) -> RcleanResult<DataFrame> {
+    mark_duplicates(&mut file_infos, None);
+    build_dataframe(&file_infos)
+}
+
+/// Build the dedupe `DataFrame` from files already through [`mark_duplicates`].
+fn build_dataframe(file_infos: &[FileInfo]) -> RcleanResult<DataFrame> {
+    let algorithm = file_infos
+        .first()
+        .map_or_else(HashAlgorithm::default, |f| f.hash_algorithm);
 
     // Extract data for DataFrame columns
-    let paths: Vec<String> = file_infos.iter().map(|f| f.path.clone()).collect();
+    let file_paths: Vec<String> = file_infos
+        .iter()
+        .map(|f| paths::display(&f.path).into_owned())
+        .collect();
+    let path_hex: Vec<Option<String>> = file_infos.iter().map(|f| paths::to_hex(&f.path)).collect();
     let names: Vec<String> = file_infos.iter().map(|f| f.name.clone()).collect();
     let extensions: Vec<String> = file_infos.iter().map(|f| f.extension.clone()).collect();
     let sizes_bytes: Vec<u64> = file_infos.iter().map(|f| f.size_bytes).collect();
     let sizes_mb: Vec<f64> = file_infos.iter().map(|f| f.size_mb).collect();
-    let hashes: Vec<String> = file_infos.iter().map(|f| f.md5_hash.clone()).collect();
+    let hashes: Vec<Option<String>> = file_infos.iter().map(|f| f.content_hash.clone()).collect();
     let is_duplicate: Vec<bool> = file_infos.iter().map(|f| f.is_duplicate).collect();
     let duplicate_groups: Vec<Option<String>> = file_infos
         .iter()
         .map(|f| f.duplicate_group.clone())
         .collect();
+    let physical_sizes: Vec<u64> = file_infos.iter().map(|f| f.physical_size_bytes).collect();
+    let reclaimable: Vec<u64> = file_infos.iter().map(|f| f.reclaimable_bytes).collect();
+    let devices: Vec<Option<u64>> = file_infos.iter().map(|f| f.device).collect();
+    let inodes: Vec<Option<u64>> = file_infos.iter().map(|f| f.inode).collect();
+    let is_hardlink: Vec<bool> = file_infos.iter().map(|f| f.is_hardlink).collect();
+    let verification_failed: Vec<bool> = file_infos.iter().map(|f| f.verification_failed).collect();
+    let is_reference: Vec<bool> = file_infos.iter().map(|f| f.is_reference).collect();
+    let archive: Vec<Option<String>> = file_infos
+        .iter()
+        .map(|f| f.archive.as_deref().map(|a| paths::display(a).into_owned()))
+        .collect();
+    let is_normalized_duplicate: Vec<bool> = file_infos
+        .iter()
+        .map(|f| f.normalized_group.is_some())
+        .collect();
+    let normalized_groups: Vec<Option<String>> = file_infos
+        .iter()
+        .map(|f| f.normalized_group.clone())
+        .collect();
 
     let df = df! [
-        "file_path" => paths,
+        "file_path" => file_paths,
+        paths::HEX_COLUMN => path_hex,
         "file_name" => names,
         "extension" => extensions,
         "size_bytes" => sizes_bytes,
         "size_mb" => sizes_mb,
-        "md5_hash" => hashes,
+        "physical_size_bytes" => physical_sizes,
+        reclaim::RECLAIMABLE_COLUMN => reclaimable,
+        algorithm.column_name() => hashes,
         "is_duplicate" => is_duplicate,
         "duplicate_group" => duplicate_groups,
+        "device" => devices,
+        "inode" => inodes,
+        "is_hardlink" => is_hardlink,
+        "verification_failed" => verification_failed,
+        roots::REFERENCE_COLUMN => is_reference,
+        archives::ARCHIVE_COLUMN => archive,
+        normalize::NORMALIZED_DUPLICATE_COLUMN => is_normalized_duplicate,
+        normalize::NORMALIZED_GROUP_COLUMN => normalized_groups,
     ]?;
 
     Ok(df)
@@ -449,12 +1156,24 @@ pub fn create_dataframe(mut file_infos: Vec<FileInfo>) -> Result<DataFrame, Box<
 /// - `DataFrame` column access fails
 /// - `DataFrame` creation fails
 #[allow(clippy::cast_precision_loss)]
-pub fn generate_statistics(df: &DataFrame) -> Result<DataFrame, Box<dyn Error>> {
+pub fn generate_statistics(df: &DataFrame) -> RcleanResult<DataFrame> {
     let total_files = df.height();
     let total_size_bytes: u64 = df.column("size_bytes")?.sum().unwrap_or(0);
     #[allow(clippy::cast_precision_loss)]
     let total_size_mb = total_size_bytes as f64 / 1_048_576.0;
 
+    // Hardlinked paths share one physical copy; frames without the column
+    // (built by older callers) are treated as all-physical.
+    let physical_size_bytes: u64 = df
+        .column("physical_size_bytes")
+        .map_or(Some(total_size_bytes), |c| c.sum())
+        .unwrap_or(0);
+    let physical_size_mb = physical_size_bytes as f64 / 1_048_576.0;
+    let hardlinked_files = df.column("is_hardlink").map_or(0, |c| {
+        c.bool()
+            .map_or(0, |b| b.into_iter().filter(|x| x.unwrap_or(false)).count())
+    });
+
     let duplicate_count = df
         .column("is_duplicate")?
         .bool()?
@@ -462,23 +1181,35 @@ pub fn generate_statistics(df: &DataFrame) -> Result<DataFrame, Box<dyn Error>>
         .filter(|x| x.unwrap_or(false))
         .count();
 
+    // Bytes freed by keeping one copy per duplicate group
+    let reclaimable_bytes: u64 = df
+        .column(reclaim::RECLAIMABLE_COLUMN)
+        .map_or(Some(0), |c| c.sum())
+        .unwrap_or(0);
+    let reclaimable_mb = reclaimable_bytes as f64 / 1_048_576.0;
+
     let unique_extensions = df.column("extension")?.unique()?.len();
 
     #[allow(clippy::cast_precision_loss)]
     let avg_file_size_mb = total_size_mb / total_files as f64;
 
     let stats_df = df! [
-        "metric" => vec!["total_files", "duplicate_files", "total_size_mb", "avg_file_size_mb", "unique_extensions"],
-        "value" => vec![total_files as f64, duplicate_count as f64, total_size_mb, avg_file_size_mb, unique_extensions as f64],
+        "metric" => vec!["total_files", "duplicate_files", "total_size_mb", "physical_size_mb", "reclaimable_mb", "hardlinked_files", "avg_file_size_mb", "unique_extensions"],
+        "value" => vec![total_files as f64, duplicate_count as f64, total_size_mb, physical_size_mb, reclaimable_mb, hardlinked_files as f64, avg_file_size_mb, unique_extensions as f64],
     ]?;
 
     Ok(stats_df)
 }
 
-// Validate duplicate detection logic
-pub fn validate_duplicates(df: &DataFrame) -> Result<(), Box<dyn Error>> {
-    println!("\n=== Duplicate Detection Validation ===");
-
+/// Validate duplicate detection: group the duplicates in `df` by content
+/// hash and return each hash with its number of files, sorted by hash. A
+/// frame without duplicates yields an empty list.
+///
+/// # Errors
+///
+/// Returns [`RcleanError::MissingHashColumn`] if `df` has duplicates but no
+/// content hash column, or an error if a `DataFrame` operation fails.
+pub fn validate_duplicates(df: &DataFrame) -> RcleanResult<Vec<(String, usize)>> {
     // Group by hash and check consistency
     let duplicates = df
         .clone()
@@ -487,71 +1218,104 @@ pub fn validate_duplicates(df: &DataFrame) -> Result<(), Box<dyn Error>> {
         .collect()?;
 
     if duplicates.height() == 0 {
-        println!("✓ No duplicates found - validation passed");
-        return Ok(());
+        return Ok(Vec::new());
     }
 
     // Group duplicates by their hash to verify consistency
+    let hash_column = hash_column_name(&duplicates).ok_or(RcleanError::MissingHashColumn)?;
     let grouped = duplicates
         .lazy()
-        .group_by([col("md5_hash")])
-        .agg([
-            col("file_path").count().alias("file_count"),
-            col("duplicate_group").first().alias("group_id"),
-        ])
+        .group_by([col(hash_column)])
+        .agg([col("file_path").count().alias("file_count")])
+        .sort(hash_column, SortOptions::default())
         .collect()?;
 
-    println!("Duplicate groups found:");
-    let max_display = 50; // Limit output to prevent terminal overflow
-    let total_groups = grouped.height();
-    let display_count = std::cmp::min(max_display, total_groups);
-
-    for row in 0..display_count {
-        let hash = grouped.column("md5_hash")?.get(row)?;
-        let count = grouped.column("file_count")?.get(row)?;
-        println!("  Hash: {hash} -> {count} files");
-    }
-
-    if total_groups > max_display {
-        println!(
-            "  ... and {} more duplicate groups (showing first {})",
-            total_groups - max_display,
-            max_display
-        );
-    }
-
-    println!("✓ Duplicate detection validation completed");
-    Ok(())
+    let hashes = grouped.column(hash_column)?.utf8()?;
+    let counts = grouped.column("file_count")?.idx()?;
+    Ok(hashes
+        .into_iter()
+        .zip(counts)
+        .filter_map(|(hash, count)| Some((hash?.to_string(), count? as usize)))
+        .collect())
 }
 
 // Generate CSV report - ONLY for duplicate files
-pub fn generate_csv_report(df: &mut DataFrame, output_path: &str) -> Result<(), Box<dyn Error>> {
-    // Filter to only include actual duplicates
-    let duplicates_only = df
-        .clone()
-        .lazy()
-        .filter(col("is_duplicate").eq(lit(true)))
-        .collect()?;
+//
+// Each row also carries its group's total in `group_reclaimable_bytes`.
+// Returns the number of rows written; without duplicates no file is created.
+pub fn generate_csv_report(df: &mut DataFrame, output_path: &str) -> RcleanResult<usize> {
+    // Filter to only include actual duplicates, exact or normalized
+    let mut is_duplicate = col("is_duplicate").eq(lit(true));
+    if df.column(normalize::NORMALIZED_DUPLICATE_COLUMN).is_ok() {
+        is_duplicate = is_duplicate.or(col(normalize::NORMALIZED_DUPLICATE_COLUMN).eq(lit(true)));
+    }
+    let duplicates_only = df.clone().lazy().filter(is_duplicate).collect()?;
+    let duplicates_only = reclaim::with_group_reclaimable(&duplicates_only)?;
 
     if duplicates_only.height() == 0 {
-        println!("No duplicates found - CSV report not generated");
-        return Ok(());
+        return Ok(0);
     }
 
-    let mut file = fs::File::create(output_path)?;
+    let mut file = fs::File::create(output_path).map_err(|e| RcleanError::io(output_path, e))?;
     let mut duplicates_df = duplicates_only;
 
     CsvWriter::new(&mut file)
         .include_header(true)
         .finish(&mut duplicates_df)?;
 
-    println!(
-        "CSV report generated: {} ({} duplicate files)",
-        output_path,
-        duplicates_df.height()
-    );
+    Ok(duplicates_df.height())
+}
 
-    Ok(())
+/// What a dedupe run found. The library prints nothing; the CLI formats this
+/// report, and the MCP server returns parts of it as JSON.
+#[derive(Debug, Clone, Default)]
+pub struct DedupeReport {
+    /// One row per analyzed file, as built by [`create_dataframe`] (with
+    /// similarity columns when similarity detection ran).
+    pub df: DataFrame,
+    /// Files that matched the pattern and filter.
+    pub matched_files: usize,
+    /// Files in a duplicate group, and the number of groups.
+    pub duplicate_files: usize,
+    pub duplicate_groups: usize,
+    /// Files that paranoid verification split off from their hash group.
+    pub unverified: Vec<PathBuf>,
+    /// Bytes read by each hashing stage; `None` with similarity detection,
+    /// which reads every file in full.
+    pub hashing: Option<hashing::HashingReport>,
+    /// Groups of fuzzy-similar files, with similarity detection.
+    pub similar_groups: Vec<SimilarFileGroup>,
+    /// Duplicate directory trees, with [`DedupeOptions::directories`]. Their
+    /// files are not counted in the file-level duplicate groups.
+    pub duplicate_dirs: Vec<duplicate_dirs::DuplicateDir>,
+    /// Text files that only match once normalized, with
+    /// [`DedupeOptions::normalize_text`]. None of them is reclaimable.
+    pub normalized_groups: Vec<normalize::NormalizedGroup>,
+    /// Entries in the hash cache before the run, if one was used.
+    pub cache_entries: Option<usize>,
+    /// Rows written to the requested CSV report; 0 if there was nothing to
+    /// write and no file was created.
+    pub csv_rows: Option<usize>,
+    /// Paths that could not be walked, stat'ed or read.
+    pub diagnostics: ScanDiagnostics,
+}
+
+impl DedupeReport {
+    /// Fill in the duplicate counts and verification results of `file_infos`,
+    /// which have been through [`mark_duplicates`].
+    fn count_duplicates(&mut self, file_infos: &[FileInfo]) {
+        let groups: std::collections::HashSet<&str> = file_infos
+            .iter()
+            .filter_map(|f| f.duplicate_group.as_deref())
+            .collect();
+        self.duplicate_files = file_infos.iter().filter(|f| f.is_duplicate).count();
+        self.duplicate_groups = groups.len();
+        self.unverified = file_infos
+            .iter()
+            .filter(|f| f.verification_failed)
+            .map(|f| f.path.clone())
+            .collect();
+    }
 }
 
 /// Run deduplication with `DataFrame` support and optional CSV output.
@@ -563,189 +1327,595 @@ pub fn generate_csv_report(df: &mut DataFrame, output_path: &str) -> Result<(),
 /// - File processing fails
 /// - `DataFrame` operations fail
 pub fn run_with_dataframe(
-    path: &str,
+    path: impl AsRef<Path>,
     pattern: &str,
     output_csv: Option<&str>,
-) -> Result<DataFrame, Box<dyn Error>> {
-    println!("Scanning directory: {path}");
+) -> RcleanResult<DedupeReport> {
+    let path = path.as_ref();
+    let summary = walk_with_summary(path, &WalkOptions::default())?;
+    let files = find(&summary.files, pattern);
+
+    let mut report = DedupeReport {
+        matched_files: files.len(),
+        diagnostics: summary.diagnostics,
+        ..DedupeReport::default()
+    };
+    dedupe_files(
+        &files,
+        &[roots::ScanRoot::candidate(path)],
+        &DedupeOptions::default(),
+        None,
+        &CancelToken::default(),
+        output_csv,
+        &mut report,
+    )?;
+    Ok(report)
+}
+
+/// Run deduplication with `DataFrame` support using advanced pattern matching.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Directory walking fails
+/// - File processing fails
+/// - `DataFrame` operations fail
+pub fn run_with_advanced_options(
+    path: impl AsRef<Path>,
+    pattern: &PatternType,
+    walk_options: &WalkOptions,
+    output_csv: Option<&str>,
+) -> RcleanResult<DedupeReport> {
+    run_dedupe(
+        path,
+        pattern,
+        walk_options,
+        &DedupeOptions::default(),
+        output_csv,
+    )
+}
+
+/// Run deduplication with full control over hashing and similarity detection.
+///
+/// The content hash column of the report's `DataFrame` (and of the CSV
+/// report) is named after `options.hash_algorithm`, e.g. `blake3_hash`.
+/// Hashing progress goes to `options.progress`; nothing is printed.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Directory walking fails
+/// - File processing fails
+/// - `DataFrame` operations fail
+pub fn run_dedupe(
+    path: impl AsRef<Path>,
+    pattern: &PatternType,
+    walk_options: &WalkOptions,
+    options: &DedupeOptions,
+    output_csv: Option<&str>,
+) -> RcleanResult<DedupeReport> {
+    let scan_roots = scan_roots(path.as_ref(), options);
+    let summary = roots::walk_roots(&scan_roots, walk_options)?;
+    let files = find_advanced(&summary.files, pattern, &options.filter);
+
+    let cache = options
+        .cache_path
+        .as_ref()
+        .map(HashCache::open)
+        .transpose()?;
+    let cache = cache.as_ref();
+
+    let mut report = DedupeReport {
+        matched_files: files.len(),
+        cache_entries: cache.map(HashCache::len),
+        diagnostics: summary.diagnostics,
+        ..DedupeReport::default()
+    };
+    match options.similarity {
+        Some(threshold) => similarity_files(
+            &files,
+            &scan_roots,
+            options,
+            threshold,
+            cache,
+            &walk_options.cancel,
+            output_csv,
+            &mut report,
+        ),
+        None => dedupe_files(
+            &files,
+            &scan_roots,
+            options,
+            cache,
+            &walk_options.cancel,
+            output_csv,
+            &mut report,
+        ),
+    }?;
+
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
 
-    let files = walk(path)?;
-    let files = find(&files, pattern);
+    Ok(report)
+}
 
-    println!("Found {} files matching pattern '{}'", files.len(), pattern);
+/// `path` as a candidate root, followed by the extra roots in `options`.
+fn scan_roots(path: &Path, options: &DedupeOptions) -> Vec<roots::ScanRoot> {
+    std::iter::once(roots::ScanRoot::candidate(path))
+        .chain(options.roots.iter().cloned())
+        .collect()
+}
 
-    if files.is_empty() {
-        println!("No files found to analyze.");
-        return Ok(df! [
-            "file_path" => Vec::<String>::new(),
-            "file_name" => Vec::<String>::new(),
-            "extension" => Vec::<String>::new(),
-            "size_bytes" => Vec::<u64>::new(),
-            "size_mb" => Vec::<f64>::new(),
-            "md5_hash" => Vec::<String>::new(),
-            "is_duplicate" => Vec::<bool>::new(),
-            "duplicate_group" => Vec::<Option<String>>::new(),
-        ]?);
+/// With [`DedupeOptions::directories`], find duplicate directories among
+/// `file_infos` and take their files out of file-level grouping. Skipped for
+/// an interrupted scan, whose directories may be incomplete.
+fn mark_duplicate_dirs(
+    file_infos: &mut [FileInfo],
+    scan_roots: &[roots::ScanRoot],
+    options: &DedupeOptions,
+    report: &mut DedupeReport,
+) {
+    if !options.directories || !report.diagnostics.is_complete() {
+        return;
     }
+    collapse_hardlinks(file_infos);
+    report.duplicate_dirs =
+        duplicate_dirs::find_duplicate_dirs(file_infos, scan_roots, &report.diagnostics);
+    duplicate_dirs::mark_covered(file_infos, &report.duplicate_dirs);
+}
 
-    let file_infos = collect_file_info(&files)?;
-    let df = create_dataframe(file_infos)?;
+/// With [`DedupeOptions::archives`], append the members of the archives
+/// among `file_infos`. Files the staged pipeline left unhashed are hashed in
+/// full when their size matches a member, so they can be grouped with it.
+/// Archives that cannot be read are recorded in `diagnostics`.
+fn add_archive_members(
+    file_infos: &mut Vec<FileInfo>,
+    options: &DedupeOptions,
+    cache: Option<&HashCache>,
+    cancel: &CancelToken,
+    diagnostics: &mut ScanDiagnostics,
+) {
+    if !options.archives {
+        return;
+    }
+    let algorithm = options.hash_algorithm;
+    let progress = options.progress();
+    let archives: Vec<usize> = (0..file_infos.len())
+        .filter(|&index| {
+            let file_info = &file_infos[index];
+            !file_info.is_hardlink && archives::ArchiveFormat::of_path(&file_info.path).is_some()
+        })
+        .collect();
 
-    // Print summary statistics
-    let stats = generate_statistics(&df)?;
+    progress.phase_started(progress::Phase::ArchiveMembers, archives.len() as u64);
+    let results: Vec<(usize, io::Result<Vec<archives::ArchiveMember>>)> = archives
+        .par_iter()
+        .filter(|_| !cancel.is_cancelled())
+        .map(|&index| {
+            let members = archives::read_members(
+                &file_infos[index].path,
+                algorithm,
+                options.memory_budget(),
+                progress,
+                cancel,
+            );
+            (index, members)
+        })
+        .inspect(|_| progress.items_done(1))
+        .collect();
+    if results.len() < archives.len() || cancel.is_cancelled() {
+        diagnostics.interrupt(cancel);
+    }
+    let mut members = Vec::new();
+    for (index, result) in results {
+        match result {
+            Ok(read) => members.extend(
+                read.into_iter()
+                    .map(|member| FileInfo::archive_member(&file_infos[index], member, algorithm)),
+            ),
+            Err(e) => diagnostics.push_io(&file_infos[index].path, &e),
+        }
+    }
 
-    println!("\n=== File Analysis Summary ===");
-    println!("{stats}");
+    let member_sizes: std::collections::HashSet<u64> =
+        members.iter().map(|member| member.size_bytes).collect();
+    let unhashed: Vec<usize> = (0..file_infos.len())
+        .filter(|&index| {
+            let file_info = &file_infos[index];
+            file_info.content_hash.is_none() && member_sizes.contains(&file_info.size_bytes)
+        })
+        .collect();
+    progress.phase_started(progress::Phase::FullHash(algorithm), unhashed.len() as u64);
+    let hashes: Vec<(usize, io::Result<String>)> = unhashed
+        .par_iter()
+        .filter(|_| !cancel.is_cancelled())
+        .map(|&index| {
+            let path = &file_infos[index].path;
+            let cached = cache.zip(CacheKey::for_path(path));
+            if let Some(digest) = cached.and_then(|(cache, key)| cache.full_hash(&key, algorithm)) {
+                return (index, Ok(digest));
+            }
+            let hash = hashing::hash_file_with_budget(path, algorithm, options.memory_budget());
+            let hash = hash.map(|(digest, bytes)| {
+                progress.bytes_hashed(bytes);
+                if let Some((cache, key)) = cached {
+                    cache.insert_full(key, path, algorithm, &digest);
+                }
+                digest
+            });
+            (index, hash)
+        })
+        .inspect(|_| progress.items_done(1))
+        .collect();
+    progress.finished();
+    if hashes.len() < unhashed.len() {
+        diagnostics.interrupt(cancel);
+    }
+    for (index, hash) in hashes {
+        match hash {
+            Ok(digest) => file_infos[index].content_hash = Some(digest),
+            Err(e) => diagnostics.push_io(&file_infos[index].path, &e),
+        }
+    }
 
-    // Validate duplicate detection
-    validate_duplicates(&df)?;
+    file_infos.extend(members);
+}
 
-    // Show duplicate information
-    let duplicates = df
-        .clone()
-        .lazy()
-        .filter(col("is_duplicate").eq(lit(true)))
-        .collect()?;
+/// With [`DedupeOptions::normalize_text`], hash the normalized text of every
+/// text file and group those that match while their bytes differ. Archive
+/// members and files in a duplicate directory are left out.
+fn mark_normalized_duplicates(
+    file_infos: &mut [FileInfo],
+    options: &DedupeOptions,
+    cancel: &CancelToken,
+    report: &mut DedupeReport,
+) {
+    let Some(normalization) = options.normalize_text else {
+        return;
+    };
+    let algorithm = options.hash_algorithm;
+    let progress = options.progress();
+    let candidates: Vec<usize> = (0..file_infos.len())
+        .filter(|&index| {
+            let file_info = &file_infos[index];
+            file_info.archive.is_none()
+                && !file_info.in_duplicate_dir
+                && !file_info.is_hardlink
+                && file_info.size_bytes <= normalize::MAX_TEXT_BYTES
+        })
+        .collect();
 
-    if duplicates.height() > 0 {
-        println!("\n=== Duplicate Files Found ===");
-        println!("{duplicates}");
-    } else {
-        println!("\nNo duplicate files found.");
+    progress.phase_started(progress::Phase::NormalizedHash, candidates.len() as u64);
+    let hashes: Vec<(usize, io::Result<Option<String>>)> = candidates
+        .par_iter()
+        .filter(|_| !cancel.is_cancelled())
+        .map(|&index| {
+            let path = &file_infos[index].path;
+            (
+                index,
+                normalize::normalized_hash(path, algorithm, normalization, options.memory_budget()),
+            )
+        })
+        .inspect(|_| progress.items_done(1))
+        .collect();
+    progress.finished();
+    if hashes.len() < candidates.len() {
+        report.diagnostics.interrupt(cancel);
+    }
+    for (index, hash) in hashes {
+        match hash {
+            Ok(hash) => file_infos[index].normalized_hash = hash,
+            Err(e) => report.diagnostics.push_io(&file_infos[index].path, &e),
+        }
     }
+    report.normalized_groups = normalize::mark_normalized_groups(file_infos);
+}
 
-    // Generate CSV report if requested
-    if let Some(csv_path) = output_csv {
-        let mut df_copy = df.clone();
+/// Flag the files that lie under one of the reference roots.
+fn mark_references(file_infos: &mut [FileInfo], scan_roots: &[roots::ScanRoot]) {
+    let references = roots::reference_paths(scan_roots);
+    for file_info in file_infos {
+        file_info.is_reference = roots::is_under_reference(&file_info.path, &references);
+    }
+}
 
-        generate_csv_report(&mut df_copy, csv_path)?;
+/// Walk `path` and return the duplicate groups among matching files, as
+/// [`find_duplicates`] does, honouring the hashing options in `options`.
+///
+/// With reference roots in `options.roots`, groups without any file outside
+/// them are dropped. Paths that could not be walked or read are returned
+/// alongside the groups.
+///
+/// # Errors
+///
+/// Returns an error if directory walking fails or the hash cache cannot be
+/// loaded or saved.
+pub fn scan_duplicate_groups(
+    path: impl AsRef<Path>,
+    pattern: &PatternType,
+    walk_options: &WalkOptions,
+    options: &DedupeOptions,
+) -> RcleanResult<(Vec<Vec<PathBuf>>, ScanDiagnostics)> {
+    let cache = options
+        .cache_path
+        .as_ref()
+        .map(HashCache::open)
+        .transpose()?;
+    let scanned = duplicate_groups(
+        path.as_ref(),
+        pattern,
+        walk_options,
+        options,
+        cache.as_ref(),
+    )?;
+    if let Some(cache) = &cache {
+        cache.save()?;
     }
+    Ok(scanned)
+}
 
-    Ok(df)
+fn duplicate_groups(
+    path: &Path,
+    pattern: &PatternType,
+    walk_options: &WalkOptions,
+    options: &DedupeOptions,
+    cache: Option<&HashCache>,
+) -> RcleanResult<(Vec<Vec<PathBuf>>, ScanDiagnostics)> {
+    let summary = roots::walk_roots(&scan_roots(path, options), walk_options)?;
+    let files = find_advanced(&summary.files, pattern, &options.filter);
+    let (checksums, _, unread) = checksum_with_report(
+        &files,
+        options.hash_algorithm,
+        cache,
+        options.memory_budget(),
+        options.progress(),
+        &walk_options.cancel,
+    )?;
+    let mut diagnostics = summary.diagnostics;
+    diagnostics.extend(unread);
+
+    let mut groups = find_duplicates(checksums);
+    if options.paranoid {
+        groups = verify_duplicate_groups(groups, options.memory_budget()).0;
+    }
+    let references = roots::reference_paths(&options.roots);
+    groups.retain(|group| {
+        group
+            .iter()
+            .any(|path| !roots::is_under_reference(path, &references))
+    });
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    Ok((groups, diagnostics))
 }
 
-/// Run deduplication with `DataFrame` support using advanced pattern matching.
+/// Find duplicates under `path` and keep one file per group according to
+/// `resolve_options.policy`, removing the rest unless it is a dry run.
+///
+/// Reference roots in `options.roots` are added to
+/// `resolve_options.references`, so their files are always kept. Every file
+/// is fingerprinted with the hashes from the scan and verified again before
+/// it is changed, so files modified in the meantime are skipped. Paths that
+/// could not be scanned are returned alongside the report.
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - Directory walking fails
-/// - File processing fails
-/// - `DataFrame` operations fail
-pub fn run_with_advanced_options(
-    path: &str,
+/// Returns an error if scanning for duplicates or saving the cache fails.
+pub fn run_resolve(
+    path: impl AsRef<Path>,
     pattern: &PatternType,
     walk_options: &WalkOptions,
-    output_csv: Option<&str>,
-) -> Result<DataFrame, Box<dyn Error>> {
-    println!("Scanning directory: {path}");
-
-    let files = walk_with_options(path, walk_options)?;
-    let files = find_advanced(&files, pattern);
+    options: &DedupeOptions,
+    resolve_options: &resolve::ResolveOptions,
+) -> RcleanResult<(resolve::ResolveReport, ScanDiagnostics)> {
+    let cache = match &options.cache_path {
+        Some(cache_path) => HashCache::open(cache_path)?,
+        None => HashCache::in_memory(),
+    };
+    let (groups, diagnostics) =
+        duplicate_groups(path.as_ref(), pattern, walk_options, options, Some(&cache))?;
+    let mut resolve_options = resolve_options.clone();
+    resolve_options
+        .references
+        .extend(roots::reference_paths(&options.roots));
+
+    let mut resolutions = resolve::plan_resolution_with_references(
+        &groups,
+        &resolve_options.policy,
+        &resolve_options.references,
+    );
+    if !resolve_options.dry_run {
+        resolve::fingerprint_resolutions(&mut resolutions, options.hash_algorithm, &cache);
+    }
+    let report = resolve::apply_resolution(&resolutions, &resolve_options);
+    cache.save()?;
+    Ok((report, diagnostics))
+}
 
-    println!("Found {} files matching pattern", files.len());
+/// Find duplicates under `path` and build a reviewable plan that keeps one
+/// file per group according to `policy` and applies `action` to the rest.
+///
+/// Hashes computed during the scan are reused for the plan's fingerprints, so
+/// duplicates are not read twice even without a persistent cache. Files under
+/// reference roots in `options.roots` are only ever kept. Paths that could
+/// not be scanned are returned alongside the plan.
+///
+/// # Errors
+///
+/// Returns an error if scanning for duplicates or saving the cache fails.
+pub fn run_plan(
+    path: impl AsRef<Path>,
+    pattern: &PatternType,
+    walk_options: &WalkOptions,
+    options: &DedupeOptions,
+    policy: &resolve::KeepPolicy,
+    action: resolve::ResolveAction,
+) -> RcleanResult<(plan::Plan, ScanDiagnostics)> {
+    let cache = match &options.cache_path {
+        Some(cache_path) => HashCache::open(cache_path)?,
+        None => HashCache::in_memory(),
+    };
+    let (groups, diagnostics) =
+        duplicate_groups(path.as_ref(), pattern, walk_options, options, Some(&cache))?;
+    let plan = plan::build_plan(
+        &groups,
+        policy,
+        &roots::reference_paths(&options.roots),
+        action,
+        options.hash_algorithm,
+        Some(&cache),
+    );
+    cache.save()?;
+    Ok((plan, diagnostics))
+}
 
+/// Hash and optionally export duplicates among already-matched files,
+/// filling in `report`.
+fn dedupe_files(
+    files: &[PathBuf],
+    scan_roots: &[roots::ScanRoot],
+    options: &DedupeOptions,
+    cache: Option<&HashCache>,
+    cancel: &CancelToken,
+    output_csv: Option<&str>,
+    report: &mut DedupeReport,
+) -> RcleanResult<()> {
+    let algorithm = options.hash_algorithm;
     if files.is_empty() {
-        println!("No files found to analyze.");
-        return Ok(df! [
-            "file_path" => Vec::<String>::new(),
-            "file_name" => Vec::<String>::new(),
-            "extension" => Vec::<String>::new(),
-            "size_bytes" => Vec::<u64>::new(),
-            "size_mb" => Vec::<f64>::new(),
-            "md5_hash" => Vec::<String>::new(),
-            "is_duplicate" => Vec::<bool>::new(),
-            "duplicate_group" => Vec::<Option<String>>::new(),
-        ]?);
+        report.df = empty_dataframe(algorithm, false)?;
+        return Ok(());
     }
 
-    let file_infos = collect_file_info(&files)?;
-    let df = create_dataframe(file_infos)?;
-
-    // Print summary statistics
-    let stats = generate_statistics(&df)?;
-
-    println!("\n=== File Analysis Summary ===");
-    println!("{stats}");
-
-    // Validate duplicate detection
-    validate_duplicates(&df)?;
-
-    // Show duplicate information
-    let duplicates = df
-        .clone()
-        .lazy()
-        .filter(col("is_duplicate").eq(lit(true)))
-        .collect()?;
-
-    if duplicates.height() > 0 {
-        println!("\n=== Duplicate Files Found ===");
-        println!("{duplicates}");
-    } else {
-        println!("\nNo duplicate files found.");
-    }
+    let (mut file_infos, hashing_report, unread) = collect_file_info_with_report(
+        files,
+        algorithm,
+        cache,
+        options.memory_budget(),
+        options.progress(),
+        cancel,
+    )?;
+    report.diagnostics.extend(unread);
+    report.hashing = Some(hashing_report);
+    mark_references(&mut file_infos, &options.roots);
+    add_archive_members(
+        &mut file_infos,
+        options,
+        cache,
+        cancel,
+        &mut report.diagnostics,
+    );
+    mark_duplicate_dirs(&mut file_infos, scan_roots, options, report);
+    mark_duplicates(
+        &mut file_infos,
+        options.paranoid.then(|| options.memory_budget()),
+    );
+    report.count_duplicates(&file_infos);
+    mark_normalized_duplicates(&mut file_infos, options, cancel, report);
+    report.df = build_dataframe(&file_infos)?;
 
     // Generate CSV report if requested
     if let Some(csv_path) = output_csv {
-        let mut df_copy = df.clone();
-
-        generate_csv_report(&mut df_copy, csv_path)?;
+        let mut df_copy = report.df.clone();
+        report.csv_rows = Some(generate_csv_report(&mut df_copy, csv_path)?);
     }
 
-    Ok(df)
+    Ok(())
 }
 
-/*  TRUE PARALLEL version of checksum using rayon with no mutex contention
-Uses indicatif to show a progress bar
-*/
+/// Content hash mapped to every path that produced it.
+pub type Checksums = HashMap<String, Vec<PathBuf>>;
+
 /// Compute checksums for files in parallel.
 ///
+/// Only files that survive the size and partial stages of [`hashing`] are
+/// hashed in full, so the returned map omits files that cannot be duplicates.
+/// Hardlinks are collapsed to the first path seen for each inode, so
+/// [`find_duplicates`] only groups distinct physical files.
+///
 /// # Errors
 ///
-/// Returns an error if progress bar creation fails.
+/// Currently infallible; unreadable files are left out.
+pub fn checksum(files: &[PathBuf]) -> RcleanResult<Checksums> {
+    checksum_with_report(
+        files,
+        HashAlgorithm::default(),
+        None,
+        hashing::MemoryBudget::global(),
+        &progress::NoProgress,
+        &CancelToken::default(),
+    )
+    .map(|(checksums, _, _)| checksums)
+}
+
+/// Compute checksums and report the bytes read by each hashing stage.
 ///
-/// # Panics
+/// With a `cache`, unchanged files are served from it instead of being read.
+/// Files that could not be stat'ed or read are listed in the returned
+/// [`ScanDiagnostics`]. Hashing progress is reported to `progress`, and
+/// `cancel` stops it early as in [`collect_file_info_with_report`].
 ///
-/// Panics if progress bar template creation fails.
-pub fn checksum(files: &[String]) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
-    println!(
-        "Computing checksums with {} threads...",
-        rayon::current_num_threads()
-    );
-
-    let pb = indicatif::ProgressBar::new(files.len() as u64);
-    #[allow(clippy::expect_used)]
-    let sty = ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
-        .expect("Failed to create progress bar template");
-
-    pb.set_style(sty);
-
-    // TRUE PARALLEL: Each thread computes checksums independently
-    let file_checksums: Vec<(String, String)> = files
+/// # Errors
+///
+/// Currently infallible; unreadable files are left out.
+pub fn checksum_with_report(
+    files: &[PathBuf],
+    algorithm: HashAlgorithm,
+    cache: Option<&HashCache>,
+    budget: &hashing::MemoryBudget,
+    progress: &dyn ProgressSink,
+    cancel: &CancelToken,
+) -> RcleanResult<(Checksums, hashing::HashingReport, ScanDiagnostics)> {
+    let results: Vec<(&PathBuf, io::Result<fs::Metadata>)> = files
         .par_iter()
-        .progress_with(pb)
-        .filter_map(|file| {
-            fs::read(file).ok().map(|content| {
-                let checksum = format!("{:x}", md5::compute(&content));
-                (checksum, file.clone())
-            })
+        .filter(|_| !cancel.is_cancelled())
+        .map(|file| (file, fs::metadata(file)))
+        .collect();
+    let mut diagnostics = ScanDiagnostics::default();
+    if results.len() < files.len() {
+        diagnostics.interrupt(cancel);
+    }
+    let mut metadata = Vec::with_capacity(results.len());
+    for (file, result) in results {
+        match result {
+            Ok(m) => metadata.push((file, m)),
+            Err(e) => diagnostics.push_io(file, &e),
+        }
+    }
+
+    // Hardlinks are one physical file: only the first path of each inode is kept
+    let mut seen = std::collections::HashSet::new();
+    let entries: Vec<(PathBuf, u64)> = metadata
+        .into_iter()
+        .filter(|(_, m)| {
+            let (device, inode, _) = inode_info(m);
+            device.zip(inode).is_none_or(|id| seen.insert(id))
         })
+        .map(|(file, m)| (file.clone(), m.len()))
         .collect();
 
+    let (hashes, report, unread) =
+        hashing::staged_hash_with_cache(&entries, algorithm, cache, budget, progress, cancel);
+    diagnostics.extend(unread);
+    progress.finished();
+
     // Sequential grouping (this part must be sequential anyway)
-    let mut checksums: HashMap<String, Vec<String>> = HashMap::new();
-    for (hash, file_path) in file_checksums {
-        checksums.entry(hash).or_default().push(file_path);
+    let mut checksums: Checksums = HashMap::new();
+    for ((file_path, _), hash) in entries.into_iter().zip(hashes) {
+        if let Some(hash) = hash {
+            checksums.entry(hash).or_default().push(file_path);
+        }
     }
 
-    Ok(checksums)
+    Ok((checksums, report, diagnostics))
 }
 
 /*
 Find all the files with more than one entry in the HashMap
 */
-pub fn find_duplicates(checksums: HashMap<String, Vec<String>>) -> Vec<Vec<String>> {
+pub fn find_duplicates(checksums: Checksums) -> Vec<Vec<PathBuf>> {
     let mut duplicates = Vec::new();
 
     for (_checksum, files) in checksums {
@@ -786,7 +1956,7 @@ pub fn find_duplicates(checksums: HashMap<String, Vec<String>>) -> Vec<Vec<Strin
 ///     Err(_) => panic!("Failed to calculate similarity"),
 /// }
 /// ```
-pub fn calculate_similarity(hash1: &str, hash2: &str) -> Result<u32, Box<dyn Error>> {
+pub fn calculate_similarity(hash1: &str, hash2: &str) -> RcleanResult<u32> {
     Ok(u32::from(ssdeep::compare(hash1, hash2)?))
 }
 
@@ -804,7 +1974,7 @@ pub type SimilarFileGroup = Vec<(FileInfo, f64)>;
 pub fn find_similar_files(
     file_infos: &[FileInfo],
     threshold: u32,
-) -> Result<Vec<SimilarFileGroup>, Box<dyn Error>> {
+) -> RcleanResult<Vec<SimilarFileGroup>> {
     let mut similar_groups: Vec<SimilarFileGroup> = Vec::new();
     let mut processed: Vec<bool> = vec![false; file_infos.len()];
 
@@ -842,29 +2012,33 @@ pub fn find_similar_files(
     Ok(similar_groups)
 }
 
-/// Run the deduplication process.
+/// Run the deduplication process, returning the duplicate groups among the
+/// files under `path` matching `pattern`, and the paths that were skipped.
 ///
 /// # Errors
 ///
 /// Returns an error if:
 /// - Directory walking fails
 /// - Checksum computation fails
-pub fn run(path: &str, pattern: &str) -> Result<(), Box<dyn Error>> {
-    let files = walk(path)?;
-    let files = find(&files, pattern);
-
-    println!("Found {} files matching {pattern}", files.len());
-
-    let checksums = checksum(&files)?;
-    let duplicates = find_duplicates(checksums);
-
-    println!("Found {} duplicate(s)", duplicates.len());
-
-    for duplicate in duplicates {
-        println!("{duplicate:?}");
-    }
-
-    Ok(())
+pub fn run(
+    path: impl AsRef<Path>,
+    pattern: &str,
+) -> RcleanResult<(Vec<Vec<PathBuf>>, ScanDiagnostics)> {
+    let summary = walk_with_summary(path, &WalkOptions::default())?;
+    let files = find(&summary.files, pattern);
+
+    let (checksums, _, unread) = checksum_with_report(
+        &files,
+        HashAlgorithm::default(),
+        None,
+        hashing::MemoryBudget::global(),
+        &progress::NoProgress,
+        &CancelToken::default(),
+    )?;
+    let mut diagnostics = summary.diagnostics;
+    diagnostics.extend(unread);
+
+    Ok((find_duplicates(checksums), diagnostics))
 }
 
 /// Run deduplication with similarity detection using fuzzy hashing.
@@ -883,67 +2057,69 @@ pub fn run(path: &str, pattern: &str) -> Result<(), Box<dyn Error>> {
 /// - File processing fails
 /// - `DataFrame` operations fail
 pub fn run_with_similarity(
-    path: &str,
+    path: impl AsRef<Path>,
     pattern: &PatternType,
     walk_options: &WalkOptions,
     similarity_threshold: u32,
     output_csv: Option<&str>,
-) -> Result<DataFrame, Box<dyn Error>> {
-    println!("Scanning directory: {path}");
-    println!("Similarity threshold: {similarity_threshold}%");
-
-    let files = walk_with_options(path, walk_options)?;
-    let files = find_advanced(&files, pattern);
-
-    println!("Found {} files matching pattern", files.len());
+) -> RcleanResult<DedupeReport> {
+    let options = DedupeOptions {
+        similarity: Some(similarity_threshold),
+        ..DedupeOptions::default()
+    };
+    run_dedupe(path, pattern, walk_options, &options, output_csv)
+}
 
+/// Find exact duplicates and fuzzy-similar files among already-matched files,
+/// filling in `report`.
+fn similarity_files(
+    files: &[PathBuf],
+    scan_roots: &[roots::ScanRoot],
+    options: &DedupeOptions,
+    similarity_threshold: u32,
+    cache: Option<&HashCache>,
+    cancel: &CancelToken,
+    output_csv: Option<&str>,
+    report: &mut DedupeReport,
+) -> RcleanResult<()> {
+    let algorithm = options.hash_algorithm;
     if files.is_empty() {
-        println!("No files found to analyze.");
-        return Ok(df! [
-            "file_path" => Vec::<String>::new(),
-            "file_name" => Vec::<String>::new(),
-            "extension" => Vec::<String>::new(),
-            "size_bytes" => Vec::<u64>::new(),
-            "size_mb" => Vec::<f64>::new(),
-            "md5_hash" => Vec::<String>::new(),
-            "is_duplicate" => Vec::<bool>::new(),
-            "duplicate_group" => Vec::<Option<String>>::new(),
-            "is_similar" => Vec::<bool>::new(),
-            "similarity_score" => Vec::<Option<f64>>::new(),
-        ]?);
-    }
-
-    let mut file_infos = collect_file_info(&files)?;
-
-    // First, find exact duplicates (existing functionality)
-    let mut hash_groups: HashMap<String, Vec<usize>> = HashMap::new();
-    for (index, file_info) in file_infos.iter().enumerate() {
-        hash_groups
-            .entry(file_info.md5_hash.clone())
-            .or_default()
-            .push(index);
+        report.df = empty_dataframe(algorithm, true)?;
+        return Ok(());
     }
 
-    let mut duplicate_count = 0;
-    for (hash, indices) in &hash_groups {
-        if indices.len() > 1 {
-            let group_id = hash.clone();
-            duplicate_count += indices.len();
+    let (mut file_infos, unread) = collect_full_file_info(
+        files,
+        algorithm,
+        cache,
+        options.memory_budget(),
+        options.progress(),
+        cancel,
+    )?;
+    report.diagnostics.extend(unread);
+    mark_references(&mut file_infos, &options.roots);
+    add_archive_members(
+        &mut file_infos,
+        options,
+        cache,
+        cancel,
+        &mut report.diagnostics,
+    );
+    mark_duplicate_dirs(&mut file_infos, scan_roots, options, report);
 
-            for &index in indices {
-                file_infos[index].is_duplicate = true;
-                file_infos[index].duplicate_group = Some(group_id.clone());
-            }
-        }
-    }
+    // First, find exact duplicates (existing functionality)
+    mark_duplicates(
+        &mut file_infos,
+        options.paranoid.then(|| options.memory_budget()),
+    );
+    report.count_duplicates(&file_infos);
+    mark_normalized_duplicates(&mut file_infos, options, cancel, report);
 
     // Then, find similar files using fuzzy hashing
     let similar_groups = find_similar_files(&file_infos, similarity_threshold)?;
 
-    let mut similar_count = 0;
     for (group_idx, group) in similar_groups.iter().enumerate() {
         let group_id = format!("similar_{group_idx}");
-        similar_count += group.len();
 
         for (file_info, score) in group {
             // Find the file in our file_infos vector and update it
@@ -955,82 +2131,34 @@ pub fn run_with_similarity(
         }
     }
 
-    println!(
-        "\nFound {} exact duplicates in {} groups",
-        duplicate_count,
-        hash_groups.iter().filter(|(_, v)| v.len() > 1).count()
-    );
-
-    println!(
-        "Found {} similar files in {} groups (≥{}% similarity)",
-        similar_count,
-        similar_groups.len(),
-        similarity_threshold
-    );
-
     // Create DataFrame with similarity information
-    let df = create_dataframe_with_similarity(&file_infos)?;
-
-    // Print similar files
-    if !similar_groups.is_empty() {
-        println!("\n=== Similar Files Found (≥{similarity_threshold}% similarity) ===");
-        for (idx, group) in similar_groups.iter().enumerate() {
-            println!("\nGroup {} ({} files):", idx + 1, group.len());
-            for (file_info, score) in group {
-                println!(
-                    "  {:.1}% - {} ({:.2} MB)",
-                    score, file_info.path, file_info.size_mb
-                );
-            }
-        }
-    }
+    report.df = create_dataframe_with_similarity(&file_infos)?;
+    report.similar_groups = similar_groups;
 
     // Generate CSV if requested
     if let Some(csv_path) = output_csv {
-        generate_csv_report_with_similarity(&df, csv_path)?;
+        report.csv_rows = Some(generate_csv_report_with_similarity(&report.df, csv_path)?);
     }
 
-    Ok(df)
+    Ok(())
 }
 
 /// Create `DataFrame` including similarity information.
-fn create_dataframe_with_similarity(file_infos: &[FileInfo]) -> Result<DataFrame, Box<dyn Error>> {
-    let paths: Vec<String> = file_infos.iter().map(|f| f.path.clone()).collect();
-    let names: Vec<String> = file_infos.iter().map(|f| f.name.clone()).collect();
-    let extensions: Vec<String> = file_infos.iter().map(|f| f.extension.clone()).collect();
-    let sizes_bytes: Vec<u64> = file_infos.iter().map(|f| f.size_bytes).collect();
-    let sizes_mb: Vec<f64> = file_infos.iter().map(|f| f.size_mb).collect();
-    let hashes: Vec<String> = file_infos.iter().map(|f| f.md5_hash.clone()).collect();
-    let is_duplicate: Vec<bool> = file_infos.iter().map(|f| f.is_duplicate).collect();
-    let duplicate_groups: Vec<Option<String>> = file_infos
-        .iter()
-        .map(|f| f.duplicate_group.clone())
-        .collect();
+fn create_dataframe_with_similarity(file_infos: &[FileInfo]) -> RcleanResult<DataFrame> {
     let is_similar: Vec<bool> = file_infos.iter().map(|f| f.is_similar).collect();
     let similarity_scores: Vec<Option<f64>> =
         file_infos.iter().map(|f| f.similarity_score).collect();
 
-    let df = df! [
-        "file_path" => paths,
-        "file_name" => names,
-        "extension" => extensions,
-        "size_bytes" => sizes_bytes,
-        "size_mb" => sizes_mb,
-        "md5_hash" => hashes,
-        "is_duplicate" => is_duplicate,
-        "duplicate_group" => duplicate_groups,
-        "is_similar" => is_similar,
-        "similarity_score" => similarity_scores,
-    ]?;
+    let mut df = build_dataframe(file_infos)?;
+    df.with_column(Series::new("is_similar", is_similar))?;
+    df.with_column(Series::new("similarity_score", similarity_scores))?;
 
     Ok(df)
 }
 
-/// Generate CSV report including similarity information.
-fn generate_csv_report_with_similarity(
-    df: &DataFrame,
-    output_path: &str,
-) -> Result<(), Box<dyn Error>> {
+/// Generate CSV report including similarity information, returning the
+/// number of rows written.
+fn generate_csv_report_with_similarity(df: &DataFrame, output_path: &str) -> RcleanResult<usize> {
     // Filter to include duplicates OR similar files
     let filtered = df
         .clone()
@@ -1038,39 +2166,35 @@ fn generate_csv_report_with_similarity(
         .filter(
             col("is_duplicate")
                 .eq(lit(true))
+                .or(col(normalize::NORMALIZED_DUPLICATE_COLUMN).eq(lit(true)))
                 .or(col("is_similar").eq(lit(true))),
         )
         .collect()?;
+    let filtered = reclaim::with_group_reclaimable(&filtered)?;
 
     if filtered.height() == 0 {
-        println!("No duplicates or similar files found - CSV report not generated");
-        return Ok(());
+        return Ok(0);
     }
 
-    let mut file = fs::File::create(output_path)?;
+    let mut file = fs::File::create(output_path).map_err(|e| RcleanError::io(output_path, e))?;
     let mut filtered_df = filtered;
 
     CsvWriter::new(&mut file)
         .include_header(true)
         .finish(&mut filtered_df)?;
 
-    println!(
-        "CSV report generated: {} ({} duplicate/similar files)",
-        output_path,
-        filtered_df.height()
-    );
-
-    Ok(())
+    Ok(filtered_df.height())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::diagnostics::SkipReason;
+    use std::error::Error;
     use std::fs;
-    use std::path::Path;
     use tempfile::TempDir;
 
-    fn create_test_files(dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    fn create_test_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
         // Create some test files
         fs::write(dir.join("file1.txt"), "content1")?;
         fs::write(dir.join("file2.txt"), "content1")?; // Duplicate of file1
@@ -1085,13 +2209,13 @@ mod tests {
         fs::write(subdir.join("file6.txt"), "content1")?; // Another duplicate
 
         Ok(vec![
-            dir.join("file1.txt").to_string_lossy().to_string(),
-            dir.join("file2.txt").to_string_lossy().to_string(),
-            dir.join("file3.txt").to_string_lossy().to_string(),
-            dir.join("file4.rs").to_string_lossy().to_string(),
-            dir.join(".hidden").to_string_lossy().to_string(),
-            subdir.join("file5.txt").to_string_lossy().to_string(),
-            subdir.join("file6.txt").to_string_lossy().to_string(),
+            dir.join("file1.txt"),
+            dir.join("file2.txt"),
+            dir.join("file3.txt"),
+            dir.join("file4.rs"),
+            dir.join(".hidden"),
+            subdir.join("file5.txt"),
+            subdir.join("file6.txt"),
         ])
     }
 
@@ -1125,10 +2249,13 @@ mod tests {
             respect_gitignore: false,
             respect_ignore: false,
             max_depth: None,
+            ..WalkOptions::default()
         };
 
         let walked_files = walk_with_options(temp_dir.path().to_str().unwrap(), &options)?;
-        assert!(walked_files.iter().any(|f| f.contains(".hidden")));
+        assert!(walked_files
+            .iter()
+            .any(|f| f.to_string_lossy().contains(".hidden")));
 
         // Test with max depth
         let options = WalkOptions {
@@ -1136,28 +2263,77 @@ mod tests {
             respect_gitignore: true,
             respect_ignore: true,
             max_depth: Some(1),
+            ..WalkOptions::default()
         };
 
         let walked_files = walk_with_options(temp_dir.path().to_str().unwrap(), &options)?;
         // Should not find files in subdirectory
-        assert!(!walked_files.iter().any(|f| f.contains("subdir")));
+        assert!(!walked_files
+            .iter()
+            .any(|f| f.to_string_lossy().contains("subdir")));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_links_skips_symlink_loops() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        let nested = temp_dir.path().join("nested");
+        fs::create_dir_all(&nested)?;
+        fs::write(nested.join("file.txt"), "content")?;
+        std::os::unix::fs::symlink(temp_dir.path(), nested.join("back"))?;
+        let root = temp_dir.path().to_str().unwrap();
+
+        let summary = walk_with_summary(root, &WalkOptions::default())?;
+        assert_eq!(summary.files.len(), 1);
+        assert!(summary.diagnostics.is_empty());
+
+        let options = WalkOptions {
+            follow_links: true,
+            ..WalkOptions::default()
+        };
+        let summary = walk_with_summary(root, &options)?;
+        assert_eq!(summary.files.len(), 1);
+        let loops: Vec<&Path> = summary.diagnostics.paths(SkipReason::SymlinkLoop).collect();
+        assert_eq!(loops.len(), 1);
+        assert!(loops[0].ends_with("back"));
+        Ok(())
+    }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_same_file_system_lists_skipped_mounts() -> Result<(), Box<dyn Error>> {
+        // Only meaningful where /proc is mounted separately from /
+        if device_of(Path::new("/proc"), true) == device_of(Path::new("/"), true) {
+            return Ok(());
+        }
+        let options = WalkOptions {
+            max_depth: Some(1),
+            same_file_system: true,
+            ..WalkOptions::default()
+        };
+        let summary = walk_with_summary("/", &options)?;
+        assert!(summary
+            .diagnostics
+            .paths(SkipReason::OtherFilesystem)
+            .any(|mount| mount == Path::new("/proc")));
         Ok(())
     }
 
     #[test]
     fn test_find() {
         let files = vec![
-            "test.txt".to_string(),
-            "data.csv".to_string(),
-            "test_data.txt".to_string(),
-            "readme.md".to_string(),
+            PathBuf::from("test.txt"),
+            PathBuf::from("data.csv"),
+            PathBuf::from("test_data.txt"),
+            PathBuf::from("readme.md"),
         ];
 
         let matches = find(&files, "test");
         assert_eq!(matches.len(), 2);
-        assert!(matches.contains(&"test.txt".to_string()));
-        assert!(matches.contains(&"test_data.txt".to_string()));
+        assert!(matches.contains(&PathBuf::from("test.txt")));
+        assert!(matches.contains(&PathBuf::from("test_data.txt")));
 
         let matches = find(&files, ".txt");
         assert_eq!(matches.len(), 2);
@@ -1169,22 +2345,22 @@ mod tests {
     #[test]
     fn test_find_advanced_literal() {
         let files = vec![
-            "test.txt".to_string(),
-            "data.csv".to_string(),
-            "test_data.txt".to_string(),
+            PathBuf::from("test.txt"),
+            PathBuf::from("data.csv"),
+            PathBuf::from("test_data.txt"),
         ];
 
         let pattern = PatternType::Literal("test".to_string());
-        let matches = find_advanced(&files, &pattern);
+        let matches = find_advanced(&files, &pattern, &filters::FileFilter::default());
         assert_eq!(matches.len(), 2);
     }
 
     #[test]
     fn test_find_advanced_glob() -> Result<(), Box<dyn Error>> {
         let files = vec![
-            "test.txt".to_string(),
-            "data.csv".to_string(),
-            "test.rs".to_string(),
+            PathBuf::from("test.txt"),
+            PathBuf::from("data.csv"),
+            PathBuf::from("test.rs"),
         ];
 
         let mut builder = GlobSetBuilder::new();
@@ -1192,9 +2368,9 @@ mod tests {
         let globset = builder.build()?;
         let pattern = PatternType::Glob(globset);
 
-        let matches = find_advanced(&files, &pattern);
+        let matches = find_advanced(&files, &pattern, &filters::FileFilter::default());
         assert_eq!(matches.len(), 1);
-        assert_eq!(matches[0], "test.txt");
+        assert_eq!(matches[0], Path::new("test.txt"));
 
         Ok(())
     }
@@ -1202,17 +2378,37 @@ mod tests {
     #[test]
     fn test_find_advanced_regex() -> Result<(), Box<dyn Error>> {
         let files = vec![
-            "test.txt".to_string(),
-            "data.csv".to_string(),
-            "test123.txt".to_string(),
+            PathBuf::from("test.txt"),
+            PathBuf::from("data.csv"),
+            PathBuf::from("test123.txt"),
         ];
 
         let regex = Regex::new(r"test\d+\.txt")?;
         let pattern = PatternType::Regex(regex);
 
-        let matches = find_advanced(&files, &pattern);
+        let matches = find_advanced(&files, &pattern, &filters::FileFilter::default());
         assert_eq!(matches.len(), 1);
-        assert_eq!(matches[0], "test123.txt");
+        assert_eq!(matches[0], Path::new("test123.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_advanced_pattern_set() -> Result<(), Box<dyn Error>> {
+        let files = vec![
+            PathBuf::from("src/test.txt"),
+            PathBuf::from("test/data.csv"),
+            PathBuf::from("src/notes.txt"),
+        ];
+
+        let mut set = patterns::PatternSet::new(patterns::MatchTarget::Basename);
+        set.include("test", patterns::PatternKind::Literal)?
+            .include("glob:*.txt", patterns::PatternKind::Literal)?
+            .exclude("regex:^notes", patterns::PatternKind::Literal)?;
+        let pattern = PatternType::Set(set);
+
+        let matches = find_advanced(&files, &pattern, &filters::FileFilter::default());
+        assert_eq!(matches, vec!["src/test.txt".to_string()]);
 
         Ok(())
     }
@@ -1229,7 +2425,7 @@ mod tests {
         assert_eq!(file_info.name, "test.txt");
         assert_eq!(file_info.extension, "txt");
         assert_eq!(file_info.size_bytes, content.len() as u64);
-        assert!(!file_info.md5_hash.is_empty());
+        assert!(file_info.content_hash.is_some_and(|hash| !hash.is_empty()));
 
         Ok(())
     }
@@ -1245,11 +2441,7 @@ mod tests {
         fs::write(&file2, "duplicate content")?;
         fs::write(&file3, "unique content")?;
 
-        let files = vec![
-            file1.to_string_lossy().to_string(),
-            file2.to_string_lossy().to_string(),
-            file3.to_string_lossy().to_string(),
-        ];
+        let files = vec![file1, file2, file3];
 
         let checksums = checksum(&files)?;
         let duplicates = find_duplicates(checksums);
@@ -1293,7 +2485,7 @@ mod tests {
         let test_file = temp_dir.path().join("test.txt");
         fs::write(&test_file, "test content")?;
 
-        let files = vec![test_file.to_string_lossy().to_string()];
+        let files = vec![test_file];
         let file_infos = collect_file_info(&files)?;
 
         assert_eq!(file_infos.len(), 1);
@@ -1302,6 +2494,129 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_vanished_files_are_reported() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        let files = create_test_files(temp_dir.path())?;
+        let gone = temp_dir.path().join("gone.txt");
+        let mut listed = files.clone();
+        listed.push(gone.clone());
+
+        let (file_infos, _, diagnostics) = collect_file_info_with_report(
+            &listed,
+            HashAlgorithm::Md5,
+            None,
+            hashing::MemoryBudget::global(),
+            &progress::NoProgress,
+            &CancelToken::default(),
+        )?;
+        assert_eq!(file_infos.len(), files.len());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics.skipped[0].path, gone);
+        assert_eq!(diagnostics.skipped[0].reason, SkipReason::Vanished);
+
+        let (_, _, diagnostics) = checksum_with_report(
+            &listed,
+            HashAlgorithm::Md5,
+            None,
+            hashing::MemoryBudget::global(),
+            &progress::NoProgress,
+            &CancelToken::default(),
+        )?;
+        assert_eq!(
+            diagnostics.paths(SkipReason::Vanished).collect::<Vec<_>>(),
+            vec![gone.as_path()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancelled_scan_returns_partial_results() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        create_test_files(temp_dir.path())?;
+        let options = WalkOptions {
+            cancel: CancelToken::new().with_deadline(std::time::Instant::now()),
+            ..WalkOptions::default()
+        };
+
+        let summary = walk_with_summary(temp_dir.path(), &options)?;
+        assert!(summary.files.is_empty());
+        assert_eq!(
+            summary.diagnostics.interrupted,
+            Some(cancel::Interruption::DeadlineExceeded)
+        );
+
+        let report = run_dedupe(
+            temp_dir.path().to_str().unwrap(),
+            &PatternType::Literal(String::new()),
+            &options,
+            &DedupeOptions::default(),
+            None,
+        )?;
+        assert_eq!(report.df.height(), 0);
+        assert!(!report.diagnostics.is_complete());
+
+        let files = walk(temp_dir.path())?;
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let (checksums, _, diagnostics) = checksum_with_report(
+            &files,
+            HashAlgorithm::Md5,
+            None,
+            hashing::MemoryBudget::global(),
+            &progress::NoProgress,
+            &cancel,
+        )?;
+        assert!(checksums.is_empty());
+        assert_eq!(
+            diagnostics.interrupted,
+            Some(cancel::Interruption::Cancelled)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_dirs_replace_file_groups() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        for dir in ["project", "copy"] {
+            fs::create_dir_all(temp_dir.path().join(dir).join("src"))?;
+            fs::write(
+                temp_dir.path().join(dir).join("src/main.rs"),
+                "fn main() {}",
+            )?;
+            fs::write(temp_dir.path().join(dir).join("README"), "readme")?;
+        }
+        fs::write(temp_dir.path().join("notes.txt"), "readme")?;
+        let path = temp_dir.path().to_str().unwrap();
+        let pattern = PatternType::Literal(String::new());
+
+        let report = run_dedupe(
+            path,
+            &pattern,
+            &WalkOptions::default(),
+            &DedupeOptions::default(),
+            None,
+        )?;
+        assert!(report.duplicate_dirs.is_empty());
+        assert_eq!(report.duplicate_groups, 2);
+
+        let options = DedupeOptions {
+            directories: true,
+            ..DedupeOptions::default()
+        };
+        let report = run_dedupe(path, &pattern, &WalkOptions::default(), &options, None)?;
+        assert_eq!(report.duplicate_dirs.len(), 1);
+        let duplicate = &report.duplicate_dirs[0];
+        assert_eq!(duplicate.kind, duplicate_dirs::DirMatchKind::Identical);
+        assert_eq!(duplicate.keep, temp_dir.path().join("copy"));
+        assert_eq!(duplicate.copies, vec![temp_dir.path().join("project")]);
+        assert_eq!(duplicate.files, 2);
+        // Only notes.txt and the kept README remain as a file-level group
+        assert_eq!(report.duplicate_groups, 1);
+        assert_eq!(report.duplicate_files, 2);
+        Ok(())
+    }
+
     #[test]
     fn test_create_dataframe() -> Result<(), Box<dyn Error>> {
         let temp_dir = TempDir::new()?;
@@ -1311,10 +2626,7 @@ mod tests {
         fs::write(&file1, "content")?;
         fs::write(&file2, "content")?;
 
-        let files = vec![
-            file1.to_string_lossy().to_string(),
-            file2.to_string_lossy().to_string(),
-        ];
+        let files = vec![file1, file2];
 
         let file_infos = collect_file_info(&files)?;
         let df = create_dataframe(file_infos)?;
@@ -1325,13 +2637,156 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_create_dataframe_names_hash_column() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.txt");
+        let file2 = temp_dir.path().join("file2.txt");
+
+        fs::write(&file1, "content")?;
+        fs::write(&file2, "content")?;
+
+        let files = vec![file1, file2];
+
+        let (file_infos, _, _) = collect_file_info_with_report(
+            &files,
+            HashAlgorithm::Blake3,
+            None,
+            hashing::MemoryBudget::global(),
+            &progress::NoProgress,
+            &CancelToken::default(),
+        )?;
+        let df = create_dataframe(file_infos)?;
+
+        assert_eq!(hash_column_name(&df), Some("blake3_hash"));
+        assert!(df.column("md5_hash").is_err());
+        let hash = df.column("blake3_hash")?.utf8()?.get(0).map(str::to_string);
+        assert_eq!(hash, Some(HashAlgorithm::Blake3.digest(b"content")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_paranoid_mode_splits_hash_collisions() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        let mut paths = Vec::new();
+        for (name, content) in [("a.txt", "same"), ("b.txt", "same"), ("c.txt", "diff")] {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content)?;
+            paths.push(path);
+        }
+
+        // Pretend all three produced the same digest
+        let mut file_infos = paths
+            .iter()
+            .map(|path| {
+                let mut info = FileInfo::from_metadata(path)?;
+                info.content_hash = Some("collision".to_string());
+                Ok(info)
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+        assert_eq!(mark_duplicates(&mut file_infos.clone(), None), (3, 1));
+        assert_eq!(
+            mark_duplicates(&mut file_infos, Some(hashing::MemoryBudget::global())),
+            (2, 1)
+        );
+        assert!(file_infos[2].verification_failed);
+        assert!(!file_infos[2].is_duplicate);
+        assert!(!file_infos[0].verification_failed);
+
+        let (groups, split) =
+            verify_duplicate_groups(vec![paths.clone()], hashing::MemoryBudget::global());
+        assert_eq!(groups, vec![paths[..2].to_vec()]);
+        assert_eq!(split, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_copies_are_kept() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        let reference = temp_dir.path().join("archive");
+        fs::create_dir_all(&reference)?;
+        let mut files = Vec::new();
+        for path in [
+            temp_dir.path().join("a.txt"),
+            reference.join("a.txt"),
+            reference.join("b.txt"),
+        ] {
+            fs::write(&path, "content")?;
+            files.push(path);
+        }
+
+        let mut file_infos = collect_file_info(&files)?;
+        mark_references(&mut file_infos, &[roots::ScanRoot::reference(&reference)]);
+        assert_eq!(mark_duplicates(&mut file_infos, None), (3, 1));
+        let reclaimable: Vec<u64> = file_infos.iter().map(|f| f.reclaimable_bytes).collect();
+        assert_eq!(reclaimable, vec![7, 0, 0]);
+
+        // Duplicates only among reference files are not reported
+        let mut archived = collect_file_info(&files[1..])?;
+        mark_references(&mut archived, &[roots::ScanRoot::reference(&reference)]);
+        assert_eq!(mark_duplicates(&mut archived, None), (0, 0));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinks_collapse_to_one_physical_file() -> Result<(), Box<dyn Error>> {
+        let temp_dir = TempDir::new()?;
+        let original = temp_dir.path().join("original.txt");
+        let link = temp_dir.path().join("link.txt");
+        let copy = temp_dir.path().join("copy.txt");
+        fs::write(&original, "content")?;
+        fs::hard_link(&original, &link)?;
+
+        let files = vec![original, link];
+
+        // Two paths to one inode reclaim nothing, so they are not duplicates
+        let df = create_dataframe(collect_file_info(&files)?)?;
+        let is_duplicate: Vec<Option<bool>> =
+            df.column("is_duplicate")?.bool()?.into_iter().collect();
+        assert_eq!(is_duplicate, vec![Some(false), Some(false)]);
+        let physical: u64 = df.column("physical_size_bytes")?.sum().unwrap_or(0);
+        assert_eq!(physical, 7);
+        assert!(checksum(&files)?.is_empty());
+
+        // A real copy makes a group; the hardlink joins it without adding size
+        fs::write(&copy, "content")?;
+        let mut files = files;
+        files.push(copy);
+        let file_infos = collect_file_info(&files)?;
+        assert!(file_infos[1].is_hardlink);
+        assert_eq!(file_infos[1].content_hash, file_infos[0].content_hash);
+
+        let df = create_dataframe(file_infos)?;
+        let is_duplicate: Vec<Option<bool>> =
+            df.column("is_duplicate")?.bool()?.into_iter().collect();
+        assert_eq!(is_duplicate, vec![Some(true); 3]);
+
+        let stats = generate_statistics(&df)?;
+        let metrics: Vec<Option<&str>> = stats.column("metric")?.utf8()?.into_iter().collect();
+        let values: Vec<Option<f64>> = stats.column("value")?.f64()?.into_iter().collect();
+        let value = |name: &str| values[metrics.iter().position(|m| *m == Some(name)).unwrap()];
+        assert_eq!(value("hardlinked_files"), Some(1.0));
+        // One of the two physical copies can go; the hardlink frees nothing
+        assert_eq!(value("reclaimable_mb"), Some(7.0 / 1_048_576.0));
+        assert!(value("physical_size_mb") < value("total_size_mb"));
+
+        let groups = find_duplicates(checksum(&files)?);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+
+        Ok(())
+    }
+
     #[test]
     fn test_empty_file_handling() -> Result<(), Box<dyn Error>> {
         let temp_dir = TempDir::new()?;
         let empty_file = temp_dir.path().join("empty.txt");
         fs::write(&empty_file, "")?;
 
-        let files = vec![empty_file.to_string_lossy().to_string()];
+        let files = vec![empty_file];
         let file_infos = collect_file_info(&files)?;
 
         assert_eq!(file_infos.len(), 1);
@@ -1350,10 +2805,7 @@ mod tests {
         fs::write(&file1, "content")?;
         fs::write(&file2, "different")?;
 
-        let files = vec![
-            file1.to_string_lossy().to_string(),
-            file2.to_string_lossy().to_string(),
-        ];
+        let files = vec![file1, file2];
 
         let file_infos = collect_file_info(&files)?;
         let df = create_dataframe(file_infos)?;
@@ -1375,7 +2827,7 @@ mod tests {
         fs::write(&file1, "test content")?;
         fs::write(&file2, "test content")?; // Duplicate
 
-        let df = run_with_dataframe(temp_dir.path().to_str().unwrap(), "test", None)?;
+        let df = run_with_dataframe(temp_dir.path().to_str().unwrap(), "test", None)?.df;
 
         assert!(df.height() >= 2);
 
@@ -1416,12 +2868,13 @@ mod tests {
     fn test_find_similar_files() -> Result<(), Box<dyn Error>> {
         let file_infos = vec![
             FileInfo {
-                path: "file1.txt".to_string(),
+                path: PathBuf::from("file1.txt"),
                 name: "file1.txt".to_string(),
                 extension: "txt".to_string(),
                 size_bytes: 1000,
                 size_mb: 0.001,
-                md5_hash: "hash1".to_string(),
+                content_hash: Some("hash1".to_string()),
+                hash_algorithm: HashAlgorithm::Md5,
                 fuzzy_hash: Some("3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C".to_string()),
                 is_duplicate: false,
                 duplicate_group: None,
@@ -1430,14 +2883,27 @@ mod tests {
                 similarity_score: None,
                 created: None,
                 modified: None,
+                device: None,
+                inode: None,
+                nlink: 1,
+                is_hardlink: false,
+                physical_size_bytes: 1000,
+                reclaimable_bytes: 0,
+                verification_failed: false,
+                is_reference: false,
+                in_duplicate_dir: false,
+                archive: None,
+                normalized_hash: None,
+                normalized_group: None,
             },
             FileInfo {
-                path: "file2.txt".to_string(),
+                path: PathBuf::from("file2.txt"),
                 name: "file2.txt".to_string(),
                 extension: "txt".to_string(),
                 size_bytes: 1000,
                 size_mb: 0.001,
-                md5_hash: "hash2".to_string(),
+                content_hash: Some("hash2".to_string()),
+                hash_algorithm: HashAlgorithm::Md5,
                 fuzzy_hash: Some("3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C".to_string()),
                 is_duplicate: false,
                 duplicate_group: None,
@@ -1446,6 +2912,18 @@ mod tests {
                 similarity_score: None,
                 created: None,
                 modified: None,
+                device: None,
+                inode: None,
+                nlink: 1,
+                is_hardlink: false,
+                physical_size_bytes: 1000,
+                reclaimable_bytes: 0,
+                verification_failed: false,
+                is_reference: false,
+                in_duplicate_dir: false,
+                archive: None,
+                normalized_hash: None,
+                normalized_group: None,
             },
         ];
 
@@ -11,25 +11,36 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilte
 
 /// Common search options shared by commands
 struct SearchOptions {
-    path: String,
+    path: std::path::PathBuf,
     pattern: String,
-    pattern_type: PatternTypeArg,
+    pattern_type: rclean::patterns::PatternKind,
+    patterns: PatternArgs,
     hidden: bool,
     no_ignore: bool,
     max_depth: Option<usize>,
+    follow_links: bool,
+    same_file_system: bool,
+    filter: rclean::filters::FileFilter,
 }
 
 /// Outlier detection parameters
 struct OutlierParams {
-    path: String,
-    min_size: Option<String>,
+    path: std::path::PathBuf,
+    filter: rclean::filters::FileFilter,
     top: usize,
     std_dev: f64,
     check_hidden: bool,
     check_patterns: bool,
+    size_metric: rclean::outliers::SizeMetric,
+    check_sparse: bool,
+    snapshot_path: Option<std::path::PathBuf>,
+    growth_since: Option<u64>,
     cluster: bool,
     cluster_similarity: u8,
     min_cluster_size: usize,
+    empty_dirs: bool,
+    prune_empty: bool,
+    execute: bool,
     format: OutputFormat,
     csv: Option<String>,
 }
@@ -41,12 +52,12 @@ struct OutlierParams {
     version = "0.1.1",
     author = "Noah Gift",
     about = "A disk cleanup tool that finds duplicates and storage outliers",
-    after_help = "Examples:\n  rclean /path/to/directory                       # Find duplicate files\n  rclean ~/Documents --pattern '*.pdf' --pattern-type glob\n  rclean . --csv report.csv\n  rclean ~/Documents --similarity 70              # Find similar files\n  rclean search /path --pattern '*.txt'\n  rclean count ~/Documents\n  rclean outliers /path --min-size 100MB         # Find large file outliers\n  rclean outliers ~ --check-hidden --format json # Find hidden space consumers"
+    after_help = "Examples:\n  rclean /path/to/directory                       # Find duplicate files\n  rclean ~/Documents --pattern '*.pdf' --pattern-type glob\n  rclean . --csv report.csv\n  rclean ~/Documents --similarity 70              # Find similar files\n  rclean ~/Documents --hash blake3                # Use BLAKE3 instead of MD5\n  rclean /data --cache                            # Reuse hashes from the last run\n  rclean /data --paranoid                         # Byte-compare every duplicate group\n  rclean ~/Downloads --reference /archive         # Which downloads are already archived\n  rclean cache prune --older-than 30d\n  rclean resolve ~/Downloads --keep oldest       # Show which duplicates would be removed\n  rclean plan /shared -o plan.toml               # Write a reviewable plan\n  rclean apply plan.toml                         # Re-verify and apply it\n  rclean resolve /shared --action quarantine --execute\n  rclean purge --older-than 30d                  # Empty old quarantine sessions\n  rclean resolve /datasets --action hardlink --execute\n  rclean search /path --pattern '*.txt'\n  rclean count ~/Documents\n  rclean outliers /path --min-size 100MB         # Find large file outliers\n  rclean outliers ~ --check-hidden --format json # Find hidden space consumers\n  rclean outliers ~/Projects --prune-empty        # Show which empty directories would be removed"
 )]
 struct Cli {
     /// Path to scan for duplicates
     #[clap(default_value = ".")]
-    path: String,
+    path: std::path::PathBuf,
 
     /// Pattern to match files
     #[clap(long, default_value = "")]
@@ -54,7 +65,10 @@ struct Cli {
 
     /// Pattern type for matching
     #[clap(long, value_enum, default_value = "literal")]
-    pattern_type: PatternTypeArg,
+    pattern_type: rclean::patterns::PatternKind,
+
+    #[clap(flatten)]
+    patterns: PatternArgs,
 
     /// Include hidden files
     #[clap(long)]
@@ -68,6 +82,17 @@ struct Cli {
     #[clap(long)]
     max_depth: Option<usize>,
 
+    /// Follow symbolic links (symlink loops are detected and skipped)
+    #[clap(long)]
+    follow_links: bool,
+
+    /// Do not descend into directories on other filesystems
+    #[clap(long, visible_alias = "one-file-system")]
+    same_file_system: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
+
     /// Generate detailed CSV report
     #[clap(long)]
     csv: Option<String>,
@@ -76,51 +101,170 @@ struct Cli {
     #[clap(long)]
     similarity: Option<u32>,
 
+    /// Content hash algorithm used to identify duplicates
+    #[clap(long, value_enum, default_value = "md5")]
+    hash: rclean::HashAlgorithm,
+
+    /// Upper bound on memory used for hashing buffers (e.g., 256MB)
+    #[clap(long, value_parser = parse_size)]
+    memory_budget: Option<u64>,
+
+    /// Reuse and update the persistent hash cache
+    #[clap(long)]
+    cache: bool,
+
+    /// Hash cache location (implies --cache)
+    #[clap(long)]
+    cache_file: Option<String>,
+
+    /// Confirm duplicates with a byte-for-byte comparison
+    #[clap(long)]
+    paranoid: bool,
+
+    /// Also report identical directory trees, and directories contained in others, as single entries
+    #[clap(long)]
+    dirs: bool,
+
+    /// Also look for duplicates among the members of zip and tar archives
+    #[clap(long)]
+    archives: bool,
+
+    /// Also group text files that differ only in line endings, trailing whitespace, a BOM or encoding
+    #[clap(long)]
+    normalize_text: bool,
+
+    /// Also ignore all other whitespace differences between text files (implies --normalize-text)
+    #[clap(long)]
+    collapse_whitespace: bool,
+
+    /// Additional directory to scan (repeatable)
+    #[clap(long)]
+    root: Vec<std::path::PathBuf>,
+
+    /// Protected directory to compare against; its files are never modified (repeatable)
+    #[clap(long)]
+    reference: Vec<std::path::PathBuf>,
+
     #[clap(subcommand)]
     command: Option<Commands>,
 }
 
-/// Pattern matching type for CLI.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
-enum PatternTypeArg {
-    /// Simple string contains matching
-    Literal,
-    /// Glob pattern matching (e.g., *.txt, **/*.rs)
-    Glob,
-    /// Regular expression matching
-    Regex,
+/// Include/exclude pattern sets shared by the pattern-matching subcommands.
+#[derive(Clone, Debug, Default, clap::Args)]
+struct PatternArgs {
+    /// Only files matching a pattern; prefix with glob:, regex: or literal: to mix kinds (repeatable)
+    #[clap(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Skip files matching a pattern, with the same syntax as --include (repeatable)
+    #[clap(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Read exclude patterns from a file, one per line (repeatable)
+    #[clap(long, value_name = "FILE")]
+    exclude_from: Vec<String>,
+
+    /// Match patterns against the full path or only the file name
+    #[clap(long, value_enum, default_value = "path")]
+    match_on: rclean::patterns::MatchTarget,
+}
+
+/// Size, age, kind and type filters shared by every subcommand.
+#[derive(Clone, Debug, Default, clap::Args)]
+struct FilterArgs {
+    /// Only files at least this large (e.g., 10MB)
+    #[clap(long, value_parser = parse_size)]
+    min_size: Option<u64>,
+
+    /// Only files at most this large (e.g., 1GB)
+    #[clap(long, value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Only files modified after a date (YYYY-MM-DD) or age (e.g., 90d)
+    #[clap(long, value_parser = parse_time)]
+    modified_after: Option<std::time::SystemTime>,
+
+    /// Only files modified before a date (YYYY-MM-DD) or age (e.g., 90d)
+    #[clap(long, value_parser = parse_time)]
+    modified_before: Option<std::time::SystemTime>,
+
+    /// Only files accessed after a date (YYYY-MM-DD) or age (e.g., 90d)
+    #[clap(long, value_parser = parse_time)]
+    accessed_after: Option<std::time::SystemTime>,
+
+    /// Only files accessed before a date (YYYY-MM-DD) or age (e.g., 90d)
+    #[clap(long, value_parser = parse_time)]
+    accessed_before: Option<std::time::SystemTime>,
+
+    /// Only regular files or only symlinks (symlinks need --follow-links)
+    #[clap(long, value_enum)]
+    kind: Option<rclean::filters::FileKind>,
+
+    /// Only files of a type such as rust, py or images (repeatable)
+    #[clap(long = "type", value_name = "TYPE")]
+    types: Vec<String>,
 }
 
 #[derive(Parser)]
 enum Commands {
     Search {
         /// Path to search in
-        path: String,
+        path: std::path::PathBuf,
         #[clap(long, default_value = "")]
         pattern: String,
         #[clap(long, value_enum, default_value = "literal")]
-        pattern_type: PatternTypeArg,
+        pattern_type: rclean::patterns::PatternKind,
+        #[clap(flatten)]
+        patterns: PatternArgs,
         #[clap(long, help = "Include hidden files")]
         hidden: bool,
         #[clap(long, help = "Ignore .gitignore rules")]
         no_ignore: bool,
         #[clap(long, help = "Maximum depth to traverse")]
         max_depth: Option<usize>,
+        #[clap(
+            long,
+            help = "Follow symbolic links (symlink loops are detected and skipped)"
+        )]
+        follow_links: bool,
+        #[clap(
+            long,
+            visible_alias = "one-file-system",
+            help = "Do not descend into directories on other filesystems"
+        )]
+        same_file_system: bool,
+        #[clap(flatten)]
+        filter: FilterArgs,
     },
 
     Dedupe {
         /// Path to scan for duplicates
-        path: String,
+        path: std::path::PathBuf,
         #[clap(long, default_value = "")]
         pattern: String,
         #[clap(long, value_enum, default_value = "literal")]
-        pattern_type: PatternTypeArg,
+        pattern_type: rclean::patterns::PatternKind,
+        #[clap(flatten)]
+        patterns: PatternArgs,
         #[clap(long, help = "Include hidden files")]
         hidden: bool,
         #[clap(long, help = "Ignore .gitignore rules")]
         no_ignore: bool,
         #[clap(long, help = "Maximum depth to traverse")]
         max_depth: Option<usize>,
+        #[clap(
+            long,
+            help = "Follow symbolic links (symlink loops are detected and skipped)"
+        )]
+        follow_links: bool,
+        #[clap(
+            long,
+            visible_alias = "one-file-system",
+            help = "Do not descend into directories on other filesystems"
+        )]
+        same_file_system: bool,
+        #[clap(flatten)]
+        filter: FilterArgs,
         #[clap(long, help = "Generate detailed CSV report")]
         csv: Option<String>,
         #[clap(
@@ -128,29 +272,303 @@ enum Commands {
             help = "Find similar files (fuzzy matching), value is similarity threshold 0-100"
         )]
         similarity: Option<u32>,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "md5",
+            help = "Content hash algorithm used to identify duplicates"
+        )]
+        hash: rclean::HashAlgorithm,
+        #[clap(
+            long,
+            value_parser = parse_size,
+            help = "Upper bound on memory used for hashing buffers (e.g., 256MB)"
+        )]
+        memory_budget: Option<u64>,
+        #[clap(long, help = "Reuse and update the persistent hash cache")]
+        cache: bool,
+        #[clap(long, help = "Hash cache location (implies --cache)")]
+        cache_file: Option<String>,
+        #[clap(long, help = "Confirm duplicates with a byte-for-byte comparison")]
+        paranoid: bool,
+        #[clap(
+            long,
+            help = "Also report identical directory trees, and directories contained in others, as single entries"
+        )]
+        dirs: bool,
+        #[clap(
+            long,
+            help = "Also look for duplicates among the members of zip and tar archives"
+        )]
+        archives: bool,
+        #[clap(
+            long,
+            help = "Also group text files that differ only in line endings, trailing whitespace, a BOM or encoding"
+        )]
+        normalize_text: bool,
+        #[clap(
+            long,
+            help = "Also ignore all other whitespace differences between text files (implies --normalize-text)"
+        )]
+        collapse_whitespace: bool,
+        #[clap(long, help = "Additional directory to scan (repeatable)")]
+        root: Vec<std::path::PathBuf>,
+        #[clap(
+            long,
+            help = "Protected directory to compare against; its files are never modified (repeatable)"
+        )]
+        reference: Vec<std::path::PathBuf>,
+    },
+
+    /// Keep one file per duplicate group and remove or link the rest (dry run by default)
+    Resolve {
+        /// Path to scan for duplicates
+        path: std::path::PathBuf,
+        #[clap(long, default_value = "")]
+        pattern: String,
+        #[clap(long, value_enum, default_value = "literal")]
+        pattern_type: rclean::patterns::PatternKind,
+        #[clap(flatten)]
+        patterns: PatternArgs,
+        #[clap(long, help = "Include hidden files")]
+        hidden: bool,
+        #[clap(long, help = "Ignore .gitignore rules")]
+        no_ignore: bool,
+        #[clap(long, help = "Maximum depth to traverse")]
+        max_depth: Option<usize>,
+        #[clap(
+            long,
+            help = "Follow symbolic links (symlink loops are detected and skipped)"
+        )]
+        follow_links: bool,
+        #[clap(
+            long,
+            visible_alias = "one-file-system",
+            help = "Do not descend into directories on other filesystems"
+        )]
+        same_file_system: bool,
+        #[clap(flatten)]
+        filter: FilterArgs,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "md5",
+            help = "Content hash algorithm used to identify duplicates"
+        )]
+        hash: rclean::HashAlgorithm,
+        #[clap(long, help = "Reuse and update the persistent hash cache")]
+        cache: bool,
+        #[clap(long, help = "Hash cache location (implies --cache)")]
+        cache_file: Option<String>,
+        #[clap(long, help = "Confirm duplicates with a byte-for-byte comparison")]
+        paranoid: bool,
+        #[clap(long, help = "Additional directory to scan (repeatable)")]
+        root: Vec<std::path::PathBuf>,
+        #[clap(
+            long,
+            help = "Protected directory to compare against; its files are never modified (repeatable)"
+        )]
+        reference: Vec<std::path::PathBuf>,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "oldest",
+            help = "Which file of each group to keep"
+        )]
+        keep: KeepPolicyArg,
+        #[clap(
+            long,
+            help = "Directory whose copies are kept first (repeatable, in priority order)"
+        )]
+        prefer_dir: Vec<std::path::PathBuf>,
+        #[clap(
+            long,
+            help = "Regex whose matches are kept first (repeatable, in priority order)"
+        )]
+        priority_regex: Vec<String>,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "delete",
+            help = "What to do with the duplicates that are not kept"
+        )]
+        action: rclean::resolve::ResolveAction,
+        #[clap(
+            long,
+            help = "With --action hardlink, symlink duplicates on other filesystems instead of skipping them"
+        )]
+        symlink_across_devices: bool,
+        #[clap(
+            long,
+            help = "Quarantine root for --action quarantine (default: ~/.local/share/rclean/quarantine)"
+        )]
+        quarantine_dir: Option<std::path::PathBuf>,
+        #[clap(
+            long,
+            help = "Actually change files instead of printing what would be done"
+        )]
+        execute: bool,
+    },
+
+    /// Write a reviewable plan of resolve actions to a JSON or TOML file
+    Plan {
+        /// Path to scan for duplicates
+        path: std::path::PathBuf,
+        #[clap(
+            short,
+            long,
+            help = "Plan file to write; the format follows the extension (.json or .toml)"
+        )]
+        output: String,
+        #[clap(long, default_value = "")]
+        pattern: String,
+        #[clap(long, value_enum, default_value = "literal")]
+        pattern_type: rclean::patterns::PatternKind,
+        #[clap(flatten)]
+        patterns: PatternArgs,
+        #[clap(long, help = "Include hidden files")]
+        hidden: bool,
+        #[clap(long, help = "Ignore .gitignore rules")]
+        no_ignore: bool,
+        #[clap(long, help = "Maximum depth to traverse")]
+        max_depth: Option<usize>,
+        #[clap(
+            long,
+            help = "Follow symbolic links (symlink loops are detected and skipped)"
+        )]
+        follow_links: bool,
+        #[clap(
+            long,
+            visible_alias = "one-file-system",
+            help = "Do not descend into directories on other filesystems"
+        )]
+        same_file_system: bool,
+        #[clap(flatten)]
+        filter: FilterArgs,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "md5",
+            help = "Content hash algorithm used to identify duplicates"
+        )]
+        hash: rclean::HashAlgorithm,
+        #[clap(long, help = "Reuse and update the persistent hash cache")]
+        cache: bool,
+        #[clap(long, help = "Hash cache location (implies --cache)")]
+        cache_file: Option<String>,
+        #[clap(long, help = "Confirm duplicates with a byte-for-byte comparison")]
+        paranoid: bool,
+        #[clap(long, help = "Additional directory to scan (repeatable)")]
+        root: Vec<std::path::PathBuf>,
+        #[clap(
+            long,
+            help = "Protected directory to compare against; its files are never modified (repeatable)"
+        )]
+        reference: Vec<std::path::PathBuf>,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "oldest",
+            help = "Which file of each group to keep"
+        )]
+        keep: KeepPolicyArg,
+        #[clap(
+            long,
+            help = "Directory whose copies are kept first (repeatable, in priority order)"
+        )]
+        prefer_dir: Vec<std::path::PathBuf>,
+        #[clap(
+            long,
+            help = "Regex whose matches are kept first (repeatable, in priority order)"
+        )]
+        priority_regex: Vec<String>,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "delete",
+            help = "Action recorded for the duplicates that are not kept"
+        )]
+        action: rclean::resolve::ResolveAction,
+    },
+
+    /// Re-verify and carry out the actions of a plan file
+    Apply {
+        /// Plan file written by `rclean plan`
+        plan: String,
+        #[clap(long, help = "Only verify the plan and print what would be done")]
+        dry_run: bool,
+        #[clap(
+            long,
+            help = "Quarantine root for quarantine entries (default: ~/.local/share/rclean/quarantine)"
+        )]
+        quarantine_dir: Option<std::path::PathBuf>,
+    },
+
+    /// Move quarantined files back to their original locations
+    Restore {
+        /// Manifest of the quarantine session to restore
+        manifest: String,
+    },
+
+    /// Delete quarantine sessions
+    Purge {
+        #[clap(
+            long,
+            help = "Quarantine root (default: ~/.local/share/rclean/quarantine)"
+        )]
+        quarantine_dir: Option<std::path::PathBuf>,
+        #[clap(
+            long,
+            value_parser = parse_age,
+            help = "Only purge sessions older than this (e.g., 30d, 12h)"
+        )]
+        older_than: Option<u64>,
+        #[clap(long, help = "Purge every session", conflicts_with = "older_than")]
+        all: bool,
+    },
+
+    /// Inspect and maintain the persistent hash cache
+    Cache {
+        #[clap(subcommand)]
+        action: CacheAction,
     },
 
     //create count with path and pattern defaults for both
     Count {
         /// Path to count files in
-        path: String,
+        path: std::path::PathBuf,
         #[clap(long, default_value = "")]
         pattern: String,
         #[clap(long, value_enum, default_value = "literal")]
-        pattern_type: PatternTypeArg,
+        pattern_type: rclean::patterns::PatternKind,
+        #[clap(flatten)]
+        patterns: PatternArgs,
         #[clap(long, help = "Include hidden files")]
         hidden: bool,
         #[clap(long, help = "Ignore .gitignore rules")]
         no_ignore: bool,
         #[clap(long, help = "Maximum depth to traverse")]
         max_depth: Option<usize>,
+        #[clap(
+            long,
+            help = "Follow symbolic links (symlink loops are detected and skipped)"
+        )]
+        follow_links: bool,
+        #[clap(
+            long,
+            visible_alias = "one-file-system",
+            help = "Do not descend into directories on other filesystems"
+        )]
+        same_file_system: bool,
+        #[clap(flatten)]
+        filter: FilterArgs,
     },
 
     Outliers {
         /// Path to analyze for outliers
-        path: String,
-        #[clap(long, help = "Minimum file size to consider (e.g., 100MB, 1GB)")]
-        min_size: Option<String>,
+        path: std::path::PathBuf,
+        #[clap(flatten)]
+        filter: FilterArgs,
         #[clap(long, help = "Number of top outliers to show", default_value = "20")]
         top: usize,
         #[clap(
@@ -166,6 +584,31 @@ enum Commands {
         check_hidden: bool,
         #[clap(long, help = "Check for file patterns (backups, logs, etc.)")]
         check_patterns: bool,
+        #[clap(
+            long,
+            value_enum,
+            default_value = "apparent",
+            help = "Rank large files by apparent size or by allocated disk space"
+        )]
+        size_metric: rclean::outliers::SizeMetric,
+        #[clap(
+            long,
+            help = "Report sparse files whose holes span at least 1MB (VM images, databases)"
+        )]
+        check_sparse: bool,
+        #[clap(
+            long,
+            help = "Compare with size snapshots of earlier runs on this path, and record this one"
+        )]
+        track_growth: bool,
+        #[clap(long, help = "Size snapshot store location (implies --track-growth)")]
+        snapshot_file: Option<String>,
+        #[clap(
+            long,
+            value_parser = parse_age,
+            help = "Compare with the newest snapshot at least this old (e.g., 7d, 12h) (implies --track-growth)"
+        )]
+        growth_since: Option<u64>,
         #[clap(long, help = "Enable clustering of similar large files")]
         cluster: bool,
         #[clap(
@@ -176,6 +619,22 @@ enum Commands {
         cluster_similarity: u8,
         #[clap(long, default_value_t = 2, help = "Minimum files to form a cluster")]
         min_cluster_size: usize,
+        #[clap(
+            long,
+            help = "Report empty directories and ones holding only empty or junk files (.DS_Store, Thumbs.db)"
+        )]
+        empty_dirs: bool,
+        #[clap(
+            long,
+            help = "Remove the reported empty directories with their empty and junk files (implies --empty-dirs)"
+        )]
+        prune_empty: bool,
+        #[clap(
+            long,
+            requires = "prune_empty",
+            help = "Actually remove directories instead of printing what would be removed"
+        )]
+        execute: bool,
         #[clap(long, help = "Output format", value_enum, default_value = "table")]
         format: OutputFormat,
         #[clap(long, help = "Export results to CSV")]
@@ -183,6 +642,54 @@ enum Commands {
     },
 }
 
+#[derive(Parser)]
+enum CacheAction {
+    /// Show cache statistics, optionally listing entries
+    Inspect {
+        #[clap(long, help = "Hash cache location")]
+        cache_file: Option<String>,
+        #[clap(long, help = "List cached entries")]
+        list: bool,
+        #[clap(long, help = "Only list entries whose path contains this string")]
+        filter: Option<String>,
+    },
+    /// Drop entries for files that are gone or changed
+    Prune {
+        #[clap(long, help = "Hash cache location")]
+        cache_file: Option<String>,
+        #[clap(
+            long,
+            value_parser = parse_age,
+            help = "Also drop entries not seen for this long (e.g., 30d, 12h)"
+        )]
+        older_than: Option<u64>,
+    },
+    /// Remove entries under a path, or all entries
+    Invalidate {
+        /// Path prefix whose entries are removed
+        path: Option<std::path::PathBuf>,
+        #[clap(long, help = "Hash cache location")]
+        cache_file: Option<String>,
+        #[clap(long, help = "Remove every entry", conflicts_with = "path")]
+        all: bool,
+    },
+}
+
+/// Keep policy for `resolve`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum KeepPolicyArg {
+    /// Keep the file with the oldest modification time
+    Oldest,
+    /// Keep the file with the newest modification time
+    Newest,
+    /// Keep the file with the shortest path
+    ShortestPath,
+    /// Keep the copy under the first matching --prefer-dir
+    PreferredDir,
+    /// Keep the copy matching the first matching --priority-regex
+    Regex,
+}
+
 /// Output format for results
 #[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
 enum OutputFormat {
@@ -194,56 +701,74 @@ enum OutputFormat {
     Text,
 }
 
-/// Convert CLI pattern type to library pattern type.
+/// Build the library pattern from `--pattern` and the include/exclude sets.
 fn create_pattern(
-    pattern: &str,
-    pattern_type: PatternTypeArg,
-) -> Result<rclean::PatternType, Box<dyn std::error::Error>> {
-    match pattern_type {
-        PatternTypeArg::Literal => Ok(rclean::PatternType::Literal(pattern.to_string())),
-        PatternTypeArg::Glob => {
-            let glob = globset::Glob::new(pattern)?;
-            let mut builder = globset::GlobSetBuilder::new();
-            builder.add(glob);
-            let globset = builder.build()?;
-            Ok(rclean::PatternType::Glob(globset))
-        },
-        PatternTypeArg::Regex => {
-            let regex = regex::Regex::new(pattern)?;
-            Ok(rclean::PatternType::Regex(regex))
-        },
+    options: &SearchOptions,
+) -> Result<rclean::PatternType, rclean::patterns::PatternError> {
+    let kind = options.pattern_type;
+    let patterns = &options.patterns;
+    if patterns.include.is_empty()
+        && patterns.exclude.is_empty()
+        && patterns.exclude_from.is_empty()
+        && patterns.match_on == rclean::patterns::MatchTarget::Path
+    {
+        return kind.compile(&options.pattern);
+    }
+
+    let mut set = rclean::patterns::PatternSet::new(patterns.match_on);
+    if !options.pattern.is_empty() {
+        set.include.push(kind.compile(&options.pattern)?);
     }
+    for include in &patterns.include {
+        set.include(include, kind)?;
+    }
+    for exclude in &patterns.exclude {
+        set.exclude(exclude, kind)?;
+    }
+    for file in &patterns.exclude_from {
+        set.exclude_from(file, kind)?;
+    }
+    Ok(rclean::PatternType::Set(set))
 }
 
 /// Create walk options from CLI arguments.
-const fn create_walk_options(
-    hidden: bool,
-    no_ignore: bool,
-    max_depth: Option<usize>,
-) -> rclean::WalkOptions {
+fn create_walk_options(options: &SearchOptions) -> rclean::WalkOptions {
     rclean::WalkOptions {
-        include_hidden: hidden,
-        respect_gitignore: !no_ignore,
-        respect_ignore: !no_ignore,
-        max_depth,
+        include_hidden: options.hidden,
+        respect_gitignore: !options.no_ignore,
+        respect_ignore: !options.no_ignore,
+        max_depth: options.max_depth,
+        follow_links: options.follow_links,
+        same_file_system: options.same_file_system,
+        ..rclean::WalkOptions::default()
+    }
+}
+
+/// Tell the user on stderr which paths a scan had to leave out.
+fn report_skipped(diagnostics: &rclean::ScanDiagnostics) {
+    if !diagnostics.is_empty() || !diagnostics.is_complete() {
+        eprintln!("⚠️  {diagnostics}");
     }
 }
 
 fn handle_search(options: &SearchOptions) {
     println!(
         "Searching for files in {} matching {}",
-        options.path, options.pattern
+        rclean::paths::display(&options.path),
+        options.pattern
     );
 
-    let walk_options = create_walk_options(options.hidden, options.no_ignore, options.max_depth);
-    match rclean::walk_with_options(&options.path, &walk_options) {
-        Ok(files) => match create_pattern(&options.pattern, options.pattern_type) {
+    let walk_options = create_walk_options(options);
+    match rclean::walk_with_summary(&options.path, &walk_options) {
+        Ok(summary) => match create_pattern(options) {
             Ok(pattern_matcher) => {
-                let files = rclean::find_advanced(&files, &pattern_matcher);
+                let files =
+                    rclean::find_advanced(&summary.files, &pattern_matcher, &options.filter);
                 println!("Found {} files matching pattern", files.len());
                 for file in files {
-                    println!("{file}");
+                    println!("{}", rclean::paths::display(&file));
                 }
+                report_skipped(&summary.diagnostics);
             },
             Err(e) => eprintln!("Error creating pattern: {e}"),
         },
@@ -251,43 +776,61 @@ fn handle_search(options: &SearchOptions) {
     }
 }
 
-fn handle_dedupe(options: &SearchOptions, csv: Option<&str>, similarity: Option<u32>) {
+fn handle_dedupe(
+    options: &SearchOptions,
+    csv: Option<&str>,
+    dedupe_options: &rclean::DedupeOptions,
+) {
     println!("{}", rclean::display_thread_info());
     println!(
         "Analyzing files in {} matching '{}'",
-        options.path, options.pattern
+        rclean::paths::display(&options.path),
+        options.pattern
+    );
+    println!(
+        "Scanning directory: {}",
+        rclean::paths::display(&options.path)
     );
+    for root in &dedupe_options.roots {
+        if root.is_reference() {
+            println!("Reference directory: {}", root.path.display());
+        } else {
+            println!("Scanning directory: {}", root.path.display());
+        }
+    }
+    if let Some(threshold) = dedupe_options.similarity {
+        println!("Similarity threshold: {threshold}%");
+    }
 
-    let walk_options = create_walk_options(options.hidden, options.no_ignore, options.max_depth);
-    match create_pattern(&options.pattern, options.pattern_type) {
+    let walk_options = create_walk_options(options);
+    match create_pattern(options) {
         Ok(pattern_matcher) => {
-            let result = similarity.map_or_else(
-                || {
-                    rclean::run_with_advanced_options(
-                        &options.path,
-                        &pattern_matcher,
-                        &walk_options,
-                        csv,
-                    )
-                },
-                |threshold| {
-                    rclean::run_with_similarity(
-                        &options.path,
-                        &pattern_matcher,
-                        &walk_options,
-                        threshold,
-                        csv,
-                    )
-                },
+            let result = rclean::run_dedupe(
+                &options.path,
+                &pattern_matcher,
+                &walk_options,
+                dedupe_options,
+                csv,
             );
 
-            match result {
-                Ok(df) => {
+            match result.and_then(|report| {
+                print_dedupe_report(&options.path, &report, dedupe_options)?;
+                Ok(report)
+            }) {
+                Ok(report) => {
+                    if let (Some(csv_path), Some(rows)) = (csv, report.csv_rows) {
+                        if rows == 0 {
+                            println!("No duplicates found - CSV report not generated");
+                        } else {
+                            println!("CSV report generated: {csv_path} ({rows} files)");
+                        }
+                    }
                     println!("\n=== Analysis Complete ===");
-                    println!("Total files analyzed: {}", df.height());
-                    if let Some(csv_path) = csv {
+                    println!("Total files analyzed: {}", report.df.height());
+                    if let (Some(csv_path), Some(1..)) = (csv, report.csv_rows) {
                         println!("Detailed CSV report saved to: {csv_path}");
                     }
+                    report_skipped(&report.diagnostics);
                 },
                 Err(e) => println!("Error: {e}"),
             }
@@ -296,18 +839,228 @@ fn handle_dedupe(options: &SearchOptions, csv: Option<&str>, similarity: Option<
     }
 }
 
+/// Progress bar for the hashing stages, drawn on stderr.
+fn progress_bar() -> std::sync::Arc<dyn rclean::ProgressSink> {
+    std::sync::Arc::new(rclean::progress::IndicatifProgress::new())
+}
+
+/// Reference matches and duplicate groups listed before the rest are
+/// summarised.
+const LIST_LIMIT: usize = 50;
+
+/// Print what a dedupe run under `root` found.
+fn print_dedupe_report(
+    root: &std::path::Path,
+    report: &rclean::DedupeReport,
+    options: &rclean::DedupeOptions,
+) -> rclean::RcleanResult<()> {
+    println!("Found {} files matching pattern", report.matched_files);
+    if let Some(entries) = report.cache_entries {
+        println!("Using hash cache with {entries} entries");
+    }
+    let df = &report.df;
+    if df.height() == 0 {
+        println!("No files found to analyze.");
+        return Ok(());
+    }
+
+    if options.similarity.is_some() {
+        println!(
+            "\nFound {} exact duplicates in {} groups",
+            report.duplicate_files, report.duplicate_groups
+        );
+    } else {
+        println!(
+            "\nFound {} files in {} duplicate groups",
+            report.duplicate_files, report.duplicate_groups
+        );
+    }
+    if options.paranoid {
+        if report.unverified.is_empty() {
+            println!("✓ Byte-for-byte verification confirmed every duplicate group");
+        } else {
+            println!(
+                "⚠ Byte-for-byte verification split {} files from their hash groups:",
+                report.unverified.len()
+            );
+            for path in &report.unverified {
+                println!("  {}", rclean::paths::display(path));
+            }
+        }
+    }
+
+    if let Some(hashing) = &report.hashing {
+        println!("\n=== File Analysis Summary ===");
+        println!("{}", rclean::generate_statistics(df)?);
+        println!("\n=== Hashing Stages ({}) ===", options.hash_algorithm);
+        println!("{hashing}");
+    }
+    if let Some(threshold) = options.similarity {
+        println!(
+            "Found {} similar files in {} groups (≥{threshold}% similarity)",
+            report.similar_groups.iter().map(Vec::len).sum::<usize>(),
+            report.similar_groups.len()
+        );
+    }
+
+    if options.directories {
+        print_duplicate_dirs(&report.duplicate_dirs);
+    }
+
+    println!("\n=== Reclaimable Space ===");
+    print!(
+        "{}",
+        rclean::reclaim::reclaimable_space(df, &rclean::paths::display(root))?
+    );
+    let matches = rclean::roots::reference_matches(df)?;
+    if !matches.is_empty() {
+        println!("\n=== Already in Reference ===");
+        for reference_match in matches.iter().take(LIST_LIMIT) {
+            println!("{reference_match}");
+        }
+        if matches.len() > LIST_LIMIT {
+            println!("... and {} more", matches.len() - LIST_LIMIT);
+        }
+    }
+    let in_archives = rclean::archives::archive_duplicates(df)?;
+    if !in_archives.is_empty() {
+        println!("\n=== Duplicates Inside Archives ===");
+        for duplicate in in_archives.iter().take(LIST_LIMIT) {
+            println!("{duplicate}");
+        }
+        if in_archives.len() > LIST_LIMIT {
+            println!("... and {} more", in_archives.len() - LIST_LIMIT);
+        }
+    }
+    if options.normalize_text.is_some() {
+        print_normalized_groups(&report.normalized_groups);
+    }
+
+    match options.similarity {
+        Some(threshold) => print_similar_groups(&report.similar_groups, threshold),
+        None => print_duplicates(df)?,
+    }
+    Ok(())
+}
+
+/// Print the duplicate directories, largest first.
+fn print_duplicate_dirs(duplicate_dirs: &[rclean::duplicate_dirs::DuplicateDir]) {
+    println!("\n=== Duplicate Directories ===");
+    if duplicate_dirs.is_empty() {
+        println!("No duplicate directories found.");
+        return;
+    }
+    let mut sorted: Vec<_> = duplicate_dirs.iter().collect();
+    sorted.sort_by_key(|dir| std::cmp::Reverse(dir.reclaimable_bytes));
+    for dir in sorted.iter().take(LIST_LIMIT) {
+        let relation = match dir.kind {
+            rclean::duplicate_dirs::DirMatchKind::Identical => "identical to",
+            rclean::duplicate_dirs::DirMatchKind::Subset => "contained in",
+        };
+        for copy in &dir.copies {
+            println!(
+                "{} {relation} {} ({} files, {:.2} MB)",
+                rclean::paths::display(copy),
+                rclean::paths::display(&dir.keep),
+                dir.files,
+                dir.size_bytes as f64 / 1_048_576.0
+            );
+        }
+    }
+    if sorted.len() > LIST_LIMIT {
+        println!("... and {} more", sorted.len() - LIST_LIMIT);
+    }
+    let total: u64 = duplicate_dirs.iter().map(|dir| dir.reclaimable_bytes).sum();
+    println!(
+        "Reclaimable by removing duplicate directories: {:.2} MB (not counted below)",
+        total as f64 / 1_048_576.0
+    );
+}
+
+/// Print the groups of text files that only match once normalized.
+fn print_normalized_groups(groups: &[rclean::normalize::NormalizedGroup]) {
+    println!("\n=== Normalized Duplicates ===");
+    if groups.is_empty() {
+        println!("No normalized duplicates found.");
+        return;
+    }
+    for (idx, group) in groups.iter().enumerate().take(LIST_LIMIT) {
+        println!(
+            "\nGroup {} ({} files, {} variants):",
+            idx + 1,
+            group.paths.len(),
+            group.variants
+        );
+        for path in &group.paths {
+            println!("  {}", rclean::paths::display(path));
+        }
+    }
+    if groups.len() > LIST_LIMIT {
+        println!("... and {} more", groups.len() - LIST_LIMIT);
+    }
+    println!("Normalized duplicates differ byte for byte and are not counted as reclaimable");
+}
+
+/// Print each duplicate group's hash and size, then the duplicate rows.
+fn print_duplicates(df: &polars::prelude::DataFrame) -> rclean::RcleanResult<()> {
+    println!("\n=== Duplicate Detection Validation ===");
+    let groups = rclean::validate_duplicates(df)?;
+    if groups.is_empty() {
+        println!("✓ No duplicates found - validation passed");
+        println!("\nNo duplicate files found.");
+        return Ok(());
+    }
+    println!("Duplicate groups found:");
+    for (hash, count) in groups.iter().take(LIST_LIMIT) {
+        println!("  Hash: {hash} -> {count} files");
+    }
+    if groups.len() > LIST_LIMIT {
+        println!(
+            "  ... and {} more duplicate groups (showing first {LIST_LIMIT})",
+            groups.len() - LIST_LIMIT
+        );
+    }
+    println!("✓ Duplicate detection validation completed");
+
+    let is_duplicate = df.column("is_duplicate")?.bool()?;
+    println!("\n=== Duplicate Files Found ===");
+    println!("{}", df.filter(is_duplicate)?);
+    Ok(())
+}
+
+fn print_similar_groups(groups: &[rclean::SimilarFileGroup], threshold: u32) {
+    if groups.is_empty() {
+        return;
+    }
+    println!("\n=== Similar Files Found (≥{threshold}% similarity) ===");
+    for (idx, group) in groups.iter().enumerate() {
+        println!("\nGroup {} ({} files):", idx + 1, group.len());
+        for (file_info, score) in group {
+            println!(
+                "  {:.1}% - {} ({:.2} MB)",
+                score,
+                rclean::paths::display(&file_info.path),
+                file_info.size_mb
+            );
+        }
+    }
+}
+
 fn handle_count(options: &SearchOptions) {
     println!(
         "Counting files in {} matching {}",
-        options.path, options.pattern
+        rclean::paths::display(&options.path),
+        options.pattern
     );
 
-    let walk_options = create_walk_options(options.hidden, options.no_ignore, options.max_depth);
-    match rclean::walk_with_options(&options.path, &walk_options) {
-        Ok(files) => match create_pattern(&options.pattern, options.pattern_type) {
+    let walk_options = create_walk_options(options);
+    match rclean::walk_with_summary(&options.path, &walk_options) {
+        Ok(summary) => match create_pattern(options) {
             Ok(pattern_matcher) => {
-                let files = rclean::find_advanced(&files, &pattern_matcher);
+                let files =
+                    rclean::find_advanced(&summary.files, &pattern_matcher, &options.filter);
                 println!("Found {} files matching pattern", files.len());
+                report_skipped(&summary.diagnostics);
             },
             Err(e) => eprintln!("Error creating pattern: {e}"),
         },
@@ -316,21 +1069,32 @@ fn handle_count(options: &SearchOptions) {
 }
 
 fn handle_outliers(params: OutlierParams) {
-    println!("🔍 Analyzing outliers in {}", params.path);
+    println!(
+        "🔍 Analyzing outliers in {}",
+        rclean::paths::display(&params.path)
+    );
 
-    // Parse min_size if provided
-    let min_size_bytes = params.min_size.as_ref().and_then(|s| parse_size(s).ok());
+    // --min-size keeps its meaning of an outlier threshold: the size
+    // statistics are still computed over every file that passes the filter
+    let mut filter = params.filter;
+    let min_size = filter.min_size.take();
 
     let options = rclean::outliers::OutlierOptions {
-        min_size: min_size_bytes,
+        min_size,
         top_n: Some(params.top),
         std_dev_threshold: params.std_dev,
         check_hidden_consumers: params.check_hidden,
-        include_empty_dirs: false,
+        include_empty_dirs: params.empty_dirs || params.prune_empty,
         check_patterns: params.check_patterns,
+        size_metric: params.size_metric,
+        check_sparse: params.check_sparse,
+        snapshot_path: params.snapshot_path,
+        growth_since_secs: params.growth_since,
         enable_clustering: params.cluster,
         cluster_similarity_threshold: params.cluster_similarity,
         min_cluster_size: params.min_cluster_size,
+        filter,
+        ..rclean::outliers::OutlierOptions::default()
     };
 
     match rclean::outliers::detect_outliers(&params.path, &options) {
@@ -341,6 +1105,10 @@ fn handle_outliers(params: OutlierParams) {
                 "Total size analyzed: {:.2} GB",
                 report.total_size_analyzed as f64 / (1024.0 * 1024.0 * 1024.0)
             );
+            println!(
+                "Total allocated on disk: {:.2} GB",
+                report.total_allocated_analyzed as f64 / (1024.0 * 1024.0 * 1024.0)
+            );
 
             // Display results based on format
             match params.format {
@@ -348,12 +1116,25 @@ fn handle_outliers(params: OutlierParams) {
                 OutputFormat::Json => display_outliers_json(&report),
                 OutputFormat::Text => display_outliers_text(&report),
             }
+            report_skipped(&report.diagnostics);
+            if options.snapshot_path.is_some() && report.growth.is_none() {
+                println!("\nNo earlier size snapshot of this path to compare with yet");
+            }
+
+            if params.prune_empty {
+                let pruned =
+                    rclean::empty_dirs::prune_empty_dirs(&report.empty_dirs, !params.execute);
+                println!("\n{pruned}");
+                if !params.execute && !pruned.outcomes.is_empty() {
+                    println!("Run again with --execute to remove these directories");
+                }
+            }
 
             // Export to CSV if requested
             if let Some(csv_path) = params.csv {
                 if let Ok(mut df) = rclean::outliers::outliers_to_dataframe(&report) {
                     match rclean::generate_csv_report(&mut df, &csv_path) {
-                        Ok(()) => println!("\n💾 Results exported to: {}", csv_path),
+                        Ok(_) => println!("\n💾 Results exported to: {}", csv_path),
                         Err(e) => eprintln!("Error writing CSV: {}", e),
                     }
                 }
@@ -372,14 +1153,16 @@ fn display_outliers_table(report: &rclean::outliers::OutlierReport) {
         table.load_preset(UTF8_FULL).set_header(vec![
             "File Path",
             "Size (MB)",
+            "Allocated (MB)",
             "% of Total",
             "Std Devs",
         ]);
 
         for outlier in &report.large_files {
             table.add_row(vec![
-                outlier.path.to_string_lossy().to_string(),
-                format!("{:.2}", outlier.size_mb),
+                rclean::paths::display(&outlier.path).into_owned(),
+                format!("{:.2}", outlier.size_bytes as f64 / (1024.0 * 1024.0)),
+                format!("{:.2}", outlier.allocated_bytes as f64 / (1024.0 * 1024.0)),
                 format!("{:.1}%", outlier.percentage_of_total),
                 format!("{:.1}σ", outlier.std_devs_from_mean),
             ]);
@@ -401,7 +1184,7 @@ fn display_outliers_table(report: &rclean::outliers::OutlierReport) {
 
         for consumer in &report.hidden_consumers {
             table.add_row(vec![
-                consumer.path.to_string_lossy().to_string(),
+                rclean::paths::display(&consumer.path).into_owned(),
                 consumer.pattern_type.clone(),
                 format!(
                     "{:.2}",
@@ -454,7 +1237,7 @@ fn display_outliers_table(report: &rclean::outliers::OutlierReport) {
                     format!("{:.2} MB", cluster.total_size as f64 / (1024.0 * 1024.0)),
                     format!("{:.1}%", cluster.avg_similarity),
                     format!("{:.2}", cluster.density),
-                    cluster.files[0].path.to_string_lossy().to_string(),
+                    rclean::paths::display(&cluster.files[0].path).into_owned(),
                 ]);
 
                 // Additional rows for remaining files in cluster
@@ -465,7 +1248,7 @@ fn display_outliers_table(report: &rclean::outliers::OutlierReport) {
                         String::new(),
                         String::new(),
                         String::new(),
-                        file.path.to_string_lossy().to_string(),
+                        rclean::paths::display(&file.path).into_owned(),
                     ]);
                 }
             }
@@ -473,6 +1256,103 @@ fn display_outliers_table(report: &rclean::outliers::OutlierReport) {
 
         println!("{table}");
     }
+
+    if !report.sparse_files.is_empty() {
+        println!("\n🕳️  Sparse Files:");
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL).set_header(vec![
+            "File Path",
+            "Apparent (MB)",
+            "Allocated (MB)",
+            "Holes (MB)",
+            "Allocated %",
+        ]);
+
+        for file in &report.sparse_files {
+            table.add_row(vec![
+                rclean::paths::display(&file.path).into_owned(),
+                format!("{:.2}", file.apparent_bytes as f64 / (1024.0 * 1024.0)),
+                format!("{:.2}", file.allocated_bytes as f64 / (1024.0 * 1024.0)),
+                format!("{:.2}", file.hole_bytes as f64 / (1024.0 * 1024.0)),
+                format!("{:.1}%", file.allocated_percent),
+            ]);
+        }
+
+        println!("{table}");
+    }
+
+    if let Some(growth) = &report.growth {
+        println!(
+            "\n📈 Growth over {:.1} days: {:+.2} MB in total",
+            growth.elapsed_days,
+            growth.total_growth_bytes as f64 / (1024.0 * 1024.0)
+        );
+        if !growth.growing.is_empty() {
+            let mut table = Table::new();
+            table.load_preset(UTF8_FULL).set_header(vec![
+                "Path",
+                "Type",
+                "Before (MB)",
+                "Now (MB)",
+                "Growth (MB)",
+                "Growth %",
+                "MB/day",
+            ]);
+
+            for entry in &growth.growing {
+                table.add_row(vec![
+                    rclean::paths::display(&entry.path).into_owned(),
+                    entry.kind.to_string(),
+                    format!("{:.2}", entry.previous_bytes as f64 / (1024.0 * 1024.0)),
+                    format!("{:.2}", entry.current_bytes as f64 / (1024.0 * 1024.0)),
+                    format!("{:.2}", entry.growth_bytes as f64 / (1024.0 * 1024.0)),
+                    entry
+                        .growth_percent
+                        .map_or_else(|| "-".to_string(), |p| format!("{p:.1}%")),
+                    format!("{:.2}", entry.bytes_per_day / (1024.0 * 1024.0)),
+                ]);
+            }
+
+            println!("{table}");
+        }
+
+        if !growth.new_paths.is_empty() {
+            println!("\n🆕 New Large Paths:");
+            let mut table = Table::new();
+            table
+                .load_preset(UTF8_FULL)
+                .set_header(vec!["Path", "Type", "Size (MB)"]);
+
+            for entry in &growth.new_paths {
+                table.add_row(vec![
+                    rclean::paths::display(&entry.path).into_owned(),
+                    entry.kind.to_string(),
+                    format!("{:.2}", entry.bytes as f64 / (1024.0 * 1024.0)),
+                ]);
+            }
+
+            println!("{table}");
+        }
+    }
+
+    if !report.empty_dirs.is_empty() {
+        println!("\n📭 Empty Directories:");
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_header(vec!["Path", "Contents", "Files", "Size (bytes)"]);
+
+        for dir in &report.empty_dirs {
+            table.add_row(vec![
+                rclean::paths::display(&dir.path).into_owned(),
+                dir.kind.to_string(),
+                dir.files.to_string(),
+                dir.size_bytes.to_string(),
+            ]);
+        }
+
+        println!("{table}");
+    }
 }
 
 fn display_outliers_json(report: &rclean::outliers::OutlierReport) {
@@ -487,9 +1367,10 @@ fn display_outliers_text(report: &rclean::outliers::OutlierReport) {
         println!("\nLarge File Outliers:");
         for outlier in &report.large_files {
             println!(
-                "  {} - {:.2} MB ({:.1}% of total, {:.1}σ from mean)",
-                outlier.path.display(),
-                outlier.size_mb,
+                "  {} - {:.2} MB, {:.2} MB allocated ({:.1}% of total, {:.1}σ from mean)",
+                rclean::paths::display(&outlier.path),
+                outlier.size_bytes as f64 / (1024.0 * 1024.0),
+                outlier.allocated_bytes as f64 / (1024.0 * 1024.0),
                 outlier.percentage_of_total,
                 outlier.std_devs_from_mean
             );
@@ -501,7 +1382,7 @@ fn display_outliers_text(report: &rclean::outliers::OutlierReport) {
         for consumer in &report.hidden_consumers {
             println!(
                 "  {} ({}) - {:.2} MB in {} files - {}",
-                consumer.path.display(),
+                rclean::paths::display(&consumer.path),
                 consumer.pattern_type,
                 consumer.total_size_bytes as f64 / (1024.0 * 1024.0),
                 consumer.file_count,
@@ -536,12 +1417,347 @@ fn display_outliers_text(report: &rclean::outliers::OutlierReport) {
             for file in &cluster.files {
                 println!(
                     "    {} - {:.2} MB",
-                    file.path.display(),
+                    rclean::paths::display(&file.path),
                     file.size_bytes as f64 / (1024.0 * 1024.0)
                 );
             }
         }
     }
+
+    if !report.sparse_files.is_empty() {
+        println!("\nSparse Files:");
+        for file in &report.sparse_files {
+            println!(
+                "  {} - {:.2} MB apparent, {:.2} MB allocated ({:.2} MB of holes, {:.1}% allocated)",
+                rclean::paths::display(&file.path),
+                file.apparent_bytes as f64 / (1024.0 * 1024.0),
+                file.allocated_bytes as f64 / (1024.0 * 1024.0),
+                file.hole_bytes as f64 / (1024.0 * 1024.0),
+                file.allocated_percent
+            );
+        }
+    }
+
+    if let Some(growth) = &report.growth {
+        println!(
+            "\nGrowth over {:.1} days: {:+.2} MB in total",
+            growth.elapsed_days,
+            growth.total_growth_bytes as f64 / (1024.0 * 1024.0)
+        );
+        for entry in &growth.growing {
+            println!(
+                "  {} ({}) - {:.2} MB -> {:.2} MB (+{:.2} MB, {:.2} MB/day)",
+                rclean::paths::display(&entry.path),
+                entry.kind,
+                entry.previous_bytes as f64 / (1024.0 * 1024.0),
+                entry.current_bytes as f64 / (1024.0 * 1024.0),
+                entry.growth_bytes as f64 / (1024.0 * 1024.0),
+                entry.bytes_per_day / (1024.0 * 1024.0)
+            );
+        }
+        for entry in &growth.new_paths {
+            println!(
+                "  {} ({}) - new, {:.2} MB",
+                rclean::paths::display(&entry.path),
+                entry.kind,
+                entry.bytes as f64 / (1024.0 * 1024.0)
+            );
+        }
+    }
+
+    if !report.empty_dirs.is_empty() {
+        println!("\nEmpty Directories:");
+        for dir in &report.empty_dirs {
+            println!(
+                "  {} ({}, {} files, {} bytes)",
+                rclean::paths::display(&dir.path),
+                dir.kind,
+                dir.files,
+                dir.size_bytes
+            );
+        }
+    }
+}
+
+/// Convert CLI keep policy arguments to a library keep policy.
+fn create_keep_policy(
+    keep: KeepPolicyArg,
+    prefer_dir: Vec<std::path::PathBuf>,
+    priority_regex: &[String],
+) -> Result<rclean::resolve::KeepPolicy, Box<dyn std::error::Error>> {
+    use rclean::resolve::KeepPolicy;
+
+    Ok(match keep {
+        KeepPolicyArg::Oldest => KeepPolicy::Oldest,
+        KeepPolicyArg::Newest => KeepPolicy::Newest,
+        KeepPolicyArg::ShortestPath => KeepPolicy::ShortestPath,
+        KeepPolicyArg::PreferredDir => {
+            if prefer_dir.is_empty() {
+                return Err("--keep preferred-dir needs at least one --prefer-dir".into());
+            }
+            KeepPolicy::PreferredDirs(prefer_dir)
+        },
+        KeepPolicyArg::Regex => {
+            if priority_regex.is_empty() {
+                return Err("--keep regex needs at least one --priority-regex".into());
+            }
+            KeepPolicy::RegexPriority(
+                priority_regex
+                    .iter()
+                    .map(|r| regex::Regex::new(r))
+                    .collect::<Result<_, _>>()?,
+            )
+        },
+    })
+}
+
+/// Returns whether every duplicate was (or would be) resolved.
+fn handle_resolve(
+    options: &SearchOptions,
+    dedupe_options: &rclean::DedupeOptions,
+    resolve_options: &rclean::resolve::ResolveOptions,
+) -> bool {
+    println!(
+        "Resolving duplicates in {} (keep {}){}",
+        rclean::paths::display(&options.path),
+        resolve_options.policy,
+        if resolve_options.dry_run {
+            " - dry run, nothing will be changed"
+        } else {
+            ""
+        }
+    );
+
+    let walk_options = create_walk_options(options);
+    match create_pattern(options) {
+        Ok(pattern_matcher) => {
+            match rclean::run_resolve(
+                &options.path,
+                &pattern_matcher,
+                &walk_options,
+                dedupe_options,
+                resolve_options,
+            ) {
+                Ok((report, diagnostics)) => {
+                    println!("{report}");
+                    report_skipped(&diagnostics);
+                    if resolve_options.dry_run && !report.removals.is_empty() {
+                        println!("Run again with --execute to apply these changes");
+                    }
+                    if let Some(manifest) = &report.quarantine_manifest {
+                        println!(
+                            "Undo with: rclean restore {}",
+                            rclean::paths::display(manifest)
+                        );
+                    }
+                    report.failures().next().is_none()
+                },
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    false
+                },
+            }
+        },
+        Err(e) => {
+            eprintln!("Error creating pattern: {e}");
+            false
+        },
+    }
+}
+
+fn handle_plan(
+    options: &SearchOptions,
+    dedupe_options: &rclean::DedupeOptions,
+    policy: &rclean::resolve::KeepPolicy,
+    action: rclean::resolve::ResolveAction,
+    output: &str,
+) {
+    let output = std::path::Path::new(output);
+    if let Err(e) = rclean::plan::PlanFormat::from_path(output) {
+        eprintln!("Error: {e}");
+        return;
+    }
+    println!(
+        "Planning duplicate resolution in {} (keep {policy})",
+        rclean::paths::display(&options.path)
+    );
+
+    let walk_options = create_walk_options(options);
+    match create_pattern(options) {
+        Ok(pattern_matcher) => {
+            let plan = rclean::run_plan(
+                &options.path,
+                &pattern_matcher,
+                &walk_options,
+                dedupe_options,
+                policy,
+                action,
+            );
+            match plan.map(|(plan, diagnostics)| plan.save(output).map(|()| (plan, diagnostics))) {
+                Ok(Ok((plan, diagnostics))) => {
+                    report_skipped(&diagnostics);
+                    println!(
+                        "Wrote {} actions in {} groups to {}",
+                        plan.action_count(),
+                        plan.groups.len(),
+                        output.display()
+                    );
+                    println!(
+                        "Review the plan, then run: rclean apply {}",
+                        output.display()
+                    );
+                },
+                Ok(Err(e)) => eprintln!("Error: {e}"),
+                Err(e) => eprintln!("Error: {e}"),
+            }
+        },
+        Err(e) => eprintln!("Error creating pattern: {e}"),
+    }
+}
+
+/// Returns whether no entry of the plan was refused.
+fn handle_apply(plan: &str, apply_options: &rclean::plan::ApplyOptions) -> bool {
+    match rclean::plan::Plan::load(std::path::Path::new(plan)) {
+        Ok(plan) => {
+            let report = rclean::plan::apply_plan(&plan, apply_options);
+            println!("{report}");
+            if let Some(manifest) = &report.quarantine_manifest {
+                println!(
+                    "Undo with: rclean restore {}",
+                    rclean::paths::display(manifest)
+                );
+            }
+            let all_applied = report.refused().next().is_none();
+            all_applied
+        },
+        Err(e) => {
+            eprintln!("Error: {e}");
+            false
+        },
+    }
+}
+
+/// Resolve the hash cache location from `--cache` / `--cache-file`.
+fn cache_path(cache: bool, cache_file: Option<String>) -> Option<std::path::PathBuf> {
+    match cache_file {
+        Some(file) => Some(file.into()),
+        None if cache => Some(rclean::HashCache::default_path()),
+        None => None,
+    }
+}
+
+/// Extra scan roots given with `--root` and `--reference`.
+fn scan_roots(
+    roots: Vec<std::path::PathBuf>,
+    references: Vec<std::path::PathBuf>,
+) -> Vec<rclean::roots::ScanRoot> {
+    roots
+        .into_iter()
+        .map(rclean::roots::ScanRoot::candidate)
+        .chain(
+            references
+                .into_iter()
+                .map(rclean::roots::ScanRoot::reference),
+        )
+        .collect()
+}
+
+fn open_cache(cache_file: Option<String>) -> Option<rclean::HashCache> {
+    let path = cache_file.map_or_else(rclean::HashCache::default_path, Into::into);
+    match rclean::HashCache::open(&path) {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            None
+        },
+    }
+}
+
+fn handle_cache(action: CacheAction) {
+    match action {
+        CacheAction::Inspect {
+            cache_file,
+            list,
+            filter,
+        } => {
+            let Some(cache) = open_cache(cache_file) else {
+                return;
+            };
+            if let Some(path) = cache.path() {
+                println!("Hash cache: {}", path.display());
+            }
+            println!("{}", cache.stats());
+            if list || filter.is_some() {
+                for (key, entry) in cache.entries(filter.as_deref()) {
+                    let hashes: Vec<String> = entry
+                        .full
+                        .iter()
+                        .map(|(algorithm, digest)| format!("{algorithm}={digest}"))
+                        .collect();
+                    println!(
+                        "{}\t{} bytes\t{}",
+                        rclean::paths::display(&entry.path),
+                        key.size,
+                        hashes.join(" ")
+                    );
+                }
+            }
+        },
+        CacheAction::Prune {
+            cache_file,
+            older_than,
+        } => {
+            let Some(cache) = open_cache(cache_file) else {
+                return;
+            };
+            let removed = cache.prune(older_than);
+            match cache.save() {
+                Ok(()) => println!("Pruned {removed} entries, {} remaining", cache.len()),
+                Err(e) => eprintln!("Error: {e}"),
+            }
+        },
+        CacheAction::Invalidate {
+            path,
+            cache_file,
+            all,
+        } => {
+            if path.is_none() && !all {
+                eprintln!("Error: give a path to invalidate, or --all");
+                return;
+            }
+            let Some(cache) = open_cache(cache_file) else {
+                return;
+            };
+            let prefix = path.map(|p| std::path::absolute(&p).unwrap_or(p));
+            let removed = cache.invalidate(prefix.as_deref());
+            match cache.save() {
+                Ok(()) => println!("Invalidated {removed} entries, {} remaining", cache.len()),
+                Err(e) => eprintln!("Error: {e}"),
+            }
+        },
+    }
+}
+
+/// Build the shared file filter from its CLI arguments.
+fn create_filter(args: &FilterArgs) -> Result<rclean::filters::FileFilter> {
+    let mut filter = rclean::filters::FileFilter::default().with_types(&args.types)?;
+    filter.min_size = args.min_size;
+    filter.max_size = args.max_size;
+    filter.modified_after = args.modified_after;
+    filter.modified_before = args.modified_before;
+    filter.accessed_after = args.accessed_after;
+    filter.accessed_before = args.accessed_before;
+    filter.kind = args.kind;
+    Ok(filter)
+}
+
+fn parse_time(time_str: &str) -> Result<std::time::SystemTime, String> {
+    rclean::filters::parse_time(time_str).map_err(|e| e.to_string())
+}
+
+/// Parse a relative age such as `30d`, `12h`, `45m`, `2w` or `90s` into seconds.
+fn parse_age(age_str: &str) -> Result<u64, String> {
+    rclean::filters::parse_age(age_str).map_err(|e| e.to_string())
 }
 
 fn parse_size(size_str: &str) -> Result<u64, String> {
@@ -578,7 +1794,7 @@ fn parse_size(size_str: &str) -> Result<u64, String> {
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<std::process::ExitCode> {
     // Initialize tracing
     init_tracing()?;
 
@@ -588,7 +1804,8 @@ async fn main() -> Result<()> {
         ExecutionMode::Mcp => {
             info!("Running in MCP server mode");
             let server = rclean::mcp_server::server::McpServer::new();
-            server.run().await
+            server.run().await?;
+            Ok(std::process::ExitCode::SUCCESS)
         },
         ExecutionMode::Cli => {
             info!("Running in CLI mode");
@@ -626,19 +1843,38 @@ fn init_tracing() -> Result<()> {
     Ok(())
 }
 
-async fn run_cli() -> Result<()> {
+/// Run the command line, failing the exit code when `apply`, `resolve`,
+/// `restore` or `purge` could not act on every entry, so scripted runs notice
+/// refused or changed files.
+async fn run_cli() -> Result<std::process::ExitCode> {
     let cli = Cli::parse();
+    let mut exit_code = std::process::ExitCode::SUCCESS;
 
     // If no subcommand is provided, default to dedupe
     let command = cli.command.unwrap_or(Commands::Dedupe {
         path: cli.path,
         pattern: cli.pattern,
         pattern_type: cli.pattern_type,
+        patterns: cli.patterns,
         hidden: cli.hidden,
         no_ignore: cli.no_ignore,
         max_depth: cli.max_depth,
+        follow_links: cli.follow_links,
+        same_file_system: cli.same_file_system,
+        filter: cli.filter,
         csv: cli.csv,
         similarity: cli.similarity,
+        hash: cli.hash,
+        memory_budget: cli.memory_budget,
+        cache: cli.cache,
+        cache_file: cli.cache_file,
+        paranoid: cli.paranoid,
+        dirs: cli.dirs,
+        archives: cli.archives,
+        normalize_text: cli.normalize_text,
+        collapse_whitespace: cli.collapse_whitespace,
+        root: cli.root,
+        reference: cli.reference,
     });
 
     match command {
@@ -646,17 +1882,25 @@ async fn run_cli() -> Result<()> {
             path,
             pattern,
             pattern_type,
+            patterns,
             hidden,
             no_ignore,
             max_depth,
+            follow_links,
+            same_file_system,
+            filter,
         } => {
             let options = SearchOptions {
                 path,
                 pattern,
                 pattern_type,
+                patterns,
                 hidden,
                 no_ignore,
                 max_depth,
+                follow_links,
+                same_file_system,
+                filter: create_filter(&filter)?,
             };
             handle_search(&options);
         },
@@ -664,67 +1908,296 @@ async fn run_cli() -> Result<()> {
             path,
             pattern,
             pattern_type,
+            patterns,
             hidden,
             no_ignore,
             max_depth,
+            follow_links,
+            same_file_system,
+            filter,
             csv,
             similarity,
+            hash,
+            memory_budget,
+            cache,
+            cache_file,
+            paranoid,
+            dirs,
+            archives,
+            normalize_text,
+            collapse_whitespace,
+            root,
+            reference,
         } => {
             let options = SearchOptions {
                 path,
                 pattern,
                 pattern_type,
+                patterns,
                 hidden,
                 no_ignore,
                 max_depth,
+                follow_links,
+                same_file_system,
+                filter: create_filter(&filter)?,
+            };
+            let dedupe_options = rclean::DedupeOptions {
+                hash_algorithm: hash,
+                similarity,
+                memory_budget: memory_budget.map(|bytes| {
+                    std::sync::Arc::new(rclean::hashing::MemoryBudget::new(bytes as usize))
+                }),
+                cache_path: cache_path(cache, cache_file),
+                paranoid,
+                roots: scan_roots(root, reference),
+                filter: options.filter.clone(),
+                directories: dirs,
+                archives,
+                normalize_text: (normalize_text || collapse_whitespace).then_some(
+                    rclean::normalize::TextNormalization {
+                        collapse_whitespace,
+                    },
+                ),
+                progress: Some(progress_bar()),
             };
-            handle_dedupe(&options, csv.as_deref(), similarity);
+            handle_dedupe(&options, csv.as_deref(), &dedupe_options);
         },
         Commands::Count {
             path,
             pattern,
             pattern_type,
+            patterns,
             hidden,
             no_ignore,
             max_depth,
+            follow_links,
+            same_file_system,
+            filter,
         } => {
             let options = SearchOptions {
                 path,
                 pattern,
                 pattern_type,
+                patterns,
                 hidden,
                 no_ignore,
                 max_depth,
+                follow_links,
+                same_file_system,
+                filter: create_filter(&filter)?,
             };
             handle_count(&options);
         },
+        Commands::Resolve {
+            path,
+            pattern,
+            pattern_type,
+            patterns,
+            hidden,
+            no_ignore,
+            max_depth,
+            follow_links,
+            same_file_system,
+            filter,
+            hash,
+            cache,
+            cache_file,
+            paranoid,
+            root,
+            reference,
+            keep,
+            prefer_dir,
+            priority_regex,
+            action,
+            symlink_across_devices,
+            quarantine_dir,
+            execute,
+        } => {
+            let options = SearchOptions {
+                path,
+                pattern,
+                pattern_type,
+                patterns,
+                hidden,
+                no_ignore,
+                max_depth,
+                follow_links,
+                same_file_system,
+                filter: create_filter(&filter)?,
+            };
+            let dedupe_options = rclean::DedupeOptions {
+                hash_algorithm: hash,
+                cache_path: cache_path(cache, cache_file),
+                paranoid,
+                roots: scan_roots(root, reference),
+                filter: options.filter.clone(),
+                progress: Some(progress_bar()),
+                ..rclean::DedupeOptions::default()
+            };
+            match create_keep_policy(keep, prefer_dir, &priority_regex) {
+                Ok(policy) => {
+                    let resolve_options = rclean::resolve::ResolveOptions {
+                        policy,
+                        action,
+                        symlink_across_devices,
+                        quarantine_dir,
+                        references: Vec::new(),
+                        dry_run: !execute,
+                    };
+                    if !handle_resolve(&options, &dedupe_options, &resolve_options) {
+                        exit_code = std::process::ExitCode::FAILURE;
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    exit_code = std::process::ExitCode::FAILURE;
+                },
+            }
+        },
+        Commands::Plan {
+            path,
+            output,
+            pattern,
+            pattern_type,
+            patterns,
+            hidden,
+            no_ignore,
+            max_depth,
+            follow_links,
+            same_file_system,
+            filter,
+            hash,
+            cache,
+            cache_file,
+            paranoid,
+            root,
+            reference,
+            keep,
+            prefer_dir,
+            priority_regex,
+            action,
+        } => {
+            let options = SearchOptions {
+                path,
+                pattern,
+                pattern_type,
+                patterns,
+                hidden,
+                no_ignore,
+                max_depth,
+                follow_links,
+                same_file_system,
+                filter: create_filter(&filter)?,
+            };
+            let dedupe_options = rclean::DedupeOptions {
+                hash_algorithm: hash,
+                cache_path: cache_path(cache, cache_file),
+                paranoid,
+                roots: scan_roots(root, reference),
+                filter: options.filter.clone(),
+                progress: Some(progress_bar()),
+                ..rclean::DedupeOptions::default()
+            };
+            match create_keep_policy(keep, prefer_dir, &priority_regex) {
+                Ok(policy) => handle_plan(&options, &dedupe_options, &policy, action, &output),
+                Err(e) => eprintln!("Error: {e}"),
+            }
+        },
+        Commands::Apply {
+            plan,
+            dry_run,
+            quarantine_dir,
+        } => {
+            let apply_options = rclean::plan::ApplyOptions {
+                dry_run,
+                quarantine_dir,
+            };
+            if !handle_apply(&plan, &apply_options) {
+                exit_code = std::process::ExitCode::FAILURE;
+            }
+        },
+        Commands::Restore { manifest } => {
+            match rclean::quarantine::restore(std::path::Path::new(&manifest)) {
+                Ok(report) => {
+                    println!("{report}");
+                    if report.failures().next().is_some() {
+                        exit_code = std::process::ExitCode::FAILURE;
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    exit_code = std::process::ExitCode::FAILURE;
+                },
+            }
+        },
+        Commands::Purge {
+            quarantine_dir,
+            older_than,
+            all,
+        } => {
+            if older_than.is_none() && !all {
+                eprintln!("Error: give --older-than to purge old sessions, or --all");
+                exit_code = std::process::ExitCode::FAILURE;
+            } else {
+                let root = quarantine_dir.unwrap_or_else(rclean::quarantine::default_root);
+                match rclean::quarantine::purge(&root, older_than) {
+                    Ok(report) => println!("{report}"),
+                    Err(e) => {
+                        eprintln!("Error: {e}");
+                        exit_code = std::process::ExitCode::FAILURE;
+                    },
+                }
+            }
+        },
+        Commands::Cache { action } => handle_cache(action),
         Commands::Outliers {
             path,
-            min_size,
+            filter,
             top,
             std_dev,
             check_hidden,
             check_patterns,
+            size_metric,
+            check_sparse,
+            track_growth,
+            snapshot_file,
+            growth_since,
             cluster,
             cluster_similarity,
             min_cluster_size,
+            empty_dirs,
+            prune_empty,
+            execute,
             format,
             csv,
         } => {
             handle_outliers(OutlierParams {
                 path,
-                min_size,
+                filter: create_filter(&filter)?,
                 top,
                 std_dev,
                 check_hidden,
                 check_patterns,
+                size_metric,
+                check_sparse,
+                snapshot_path: match snapshot_file {
+                    Some(file) => Some(file.into()),
+                    None if track_growth || growth_since.is_some() => {
+                        Some(rclean::growth::SnapshotStore::default_path())
+                    },
+                    None => None,
+                },
+                growth_since,
                 cluster,
                 cluster_similarity,
                 min_cluster_size,
+                empty_dirs,
+                prune_empty,
+                execute,
                 format,
                 csv,
             });
         },
     }
-    Ok(())
+    Ok(exit_code)
 }
@@ -1,11 +1,28 @@
+use crate::hashing::MemoryBudget;
 use crate::models::mcp::{
-    CountArgs, DedupeArgs, McpRequest, McpResponse, OutliersArgs, SearchArgs, ToolCallParams,
+    CountArgs, DedupeArgs, FilterArgs, McpRequest, McpResponse, OutliersArgs, PatternArgs,
+    SearchArgs, ToolCallParams,
 };
-use crate::{PatternType, WalkOptions};
-use polars::prelude::IntoLazy;
+use crate::paths;
+use crate::patterns::{MatchTarget, PatternKind, PatternSet};
+use crate::{CancelToken, DedupeOptions, PatternType, WalkOptions};
 use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info};
 
+/// Largest groups, directories and extensions listed in the dedupe response.
+const RECLAIMABLE_ENTRIES: usize = 20;
+
+/// Candidate/reference pairs listed in the dedupe response.
+const REFERENCE_MATCH_ENTRIES: usize = 100;
+
+/// Duplicate archive members listed in the dedupe response.
+const ARCHIVE_DUPLICATE_ENTRIES: usize = 100;
+
+/// Normalized duplicate groups listed in the dedupe response.
+const NORMALIZED_GROUP_ENTRIES: usize = 100;
+
 pub fn handle_initialize(request: McpRequest) -> McpResponse {
     info!("Handling initialize request");
 
@@ -26,6 +43,97 @@ pub fn handle_initialize(request: McpRequest) -> McpResponse {
     )
 }
 
+/// Schema properties of the include/exclude pattern set arguments.
+fn pattern_set_properties() -> Value {
+    json!({
+        "include": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Only files matching one of these patterns; prefix with glob:, regex: or literal: to mix kinds"
+        },
+        "exclude": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Skip files matching any of these patterns (same syntax as include)"
+        },
+        "exclude_from": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Files listing exclude patterns, one per line"
+        },
+        "match_on": {
+            "type": "string",
+            "enum": ["path", "basename"],
+            "description": "Match patterns against the full path or only the file name",
+            "default": "path"
+        }
+    })
+}
+
+/// Schema properties of the file filter arguments.
+fn filter_properties() -> Value {
+    json!({
+        "min_size": {
+            "type": "string",
+            "description": "Only consider files of at least this size (e.g., 10MB)"
+        },
+        "max_size": {
+            "type": "string",
+            "description": "Only consider files of at most this size (e.g., 1GB)"
+        },
+        "modified_after": {
+            "type": "string",
+            "description": "Only consider files modified after a date (YYYY-MM-DD) or age (e.g., 30d)"
+        },
+        "modified_before": {
+            "type": "string",
+            "description": "Only consider files modified before a date (YYYY-MM-DD) or age (e.g., 90d)"
+        },
+        "accessed_after": {
+            "type": "string",
+            "description": "Only consider files accessed after a date (YYYY-MM-DD) or age (e.g., 30d)"
+        },
+        "accessed_before": {
+            "type": "string",
+            "description": "Only consider files accessed before a date (YYYY-MM-DD) or age (e.g., 180d)"
+        },
+        "kind": {
+            "type": "string",
+            "enum": ["file", "symlink"],
+            "description": "Only consider regular files or symlinks"
+        },
+        "types": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Only consider these file types (ripgrep names such as rust or py, plus images, video, audio, archives, documents)"
+        }
+    })
+}
+
+/// Schema property of the scan timeout argument.
+fn timeout_properties() -> Value {
+    json!({
+        "timeout_secs": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Stop scanning after this many seconds and return the partial results, marked as interrupted in diagnostics"
+        }
+    })
+}
+
+/// Add `shared` argument properties to a tool's input schema, keeping any
+/// property the tool defines itself.
+fn with_shared_properties(mut schema: Value, shared: &[Value]) -> Value {
+    if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+        for (name, property) in shared.iter().filter_map(Value::as_object).flatten() {
+            properties
+                .entry(name.clone())
+                .or_insert_with(|| property.clone());
+        }
+    }
+    schema
+}
+
 pub fn handle_tools_list(request: McpRequest) -> McpResponse {
     info!("Handling tools/list request");
 
@@ -36,160 +144,285 @@ pub fn handle_tools_list(request: McpRequest) -> McpResponse {
                 {
                     "name": "dedupe",
                     "description": "Find duplicate files in a directory",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "path": {
-                                "type": "string",
-                                "description": "Path to scan for duplicates"
-                            },
-                            "pattern": {
-                                "type": "string",
-                                "description": "Pattern to match files",
-                                "default": ""
-                            },
-                            "pattern_type": {
-                                "type": "string",
-                                "enum": ["literal", "glob", "regex"],
-                                "default": "literal"
-                            },
-                            "hidden": {
-                                "type": "boolean",
-                                "description": "Include hidden files",
-                                "default": false
+                    "inputSchema": with_shared_properties(
+                        json!({
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "Path to scan for duplicates"
+                                },
+                                "pattern": {
+                                    "type": "string",
+                                    "description": "Pattern to match files",
+                                    "default": ""
+                                },
+                                "pattern_type": {
+                                    "type": "string",
+                                    "enum": ["literal", "glob", "regex"],
+                                    "default": "literal"
+                                },
+                                "hidden": {
+                                    "type": "boolean",
+                                    "description": "Include hidden files",
+                                    "default": false
+                                },
+                                "no_ignore": {
+                                    "type": "boolean",
+                                    "description": "Ignore .gitignore rules",
+                                    "default": false
+                                },
+                                "max_depth": {
+                                    "type": "integer",
+                                    "description": "Maximum depth to traverse"
+                                },
+                                "follow_links": {
+                                    "type": "boolean",
+                                    "description": "Follow symbolic links (symlink loops are detected and skipped)",
+                                    "default": false
+                                },
+                                "same_file_system": {
+                                    "type": "boolean",
+                                    "description": "Do not descend into directories on other filesystems",
+                                    "default": false
+                                },
+                                "similarity": {
+                                    "type": "integer",
+                                    "description": "Similarity threshold (0-100) for fuzzy matching",
+                                    "minimum": 0,
+                                    "maximum": 100
+                                },
+                                "hash": {
+                                    "type": "string",
+                                    "enum": ["md5", "blake3", "xxh3", "sha256"],
+                                    "description": "Content hash algorithm used to identify duplicates",
+                                    "default": "md5"
+                                },
+                                "memory_budget": {
+                                    "type": "string",
+                                    "description": "Upper bound on memory used for hashing buffers (e.g., '256MB')"
+                                },
+                                "cache": {
+                                    "type": "boolean",
+                                    "description": "Reuse and update the persistent hash cache",
+                                    "default": false
+                                },
+                                "cache_file": {
+                                    "type": "string",
+                                    "description": "Hash cache location (implies cache)"
+                                },
+                                "paranoid": {
+                                    "type": "boolean",
+                                    "description": "Confirm duplicates with a byte-for-byte comparison",
+                                    "default": false
+                                },
+                                "directories": {
+                                    "type": "boolean",
+                                    "description": "Also report identical directory trees, and directories contained in others, as single entries; their files are left out of the file-level groups",
+                                    "default": false
+                                },
+                                "archives": {
+                                    "type": "boolean",
+                                    "description": "Also look for duplicates among the members of zip and tar archives; members are reported with a virtual path such as backup.zip!/src/main.rs and are never reclaimable",
+                                    "default": false
+                                },
+                                "normalize_text": {
+                                    "type": "boolean",
+                                    "description": "Also group text files that differ only in line endings, trailing whitespace, a BOM or encoding, as normalized duplicates kept apart from exact ones",
+                                    "default": false
+                                },
+                                "collapse_whitespace": {
+                                    "type": "boolean",
+                                    "description": "Also ignore all other whitespace differences between text files (implies normalize_text)",
+                                    "default": false
+                                },
+                                "roots": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "Additional directories to scan"
+                                },
+                                "references": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "Protected directories to compare against; their files are never suggested for deletion"
+                                }
                             },
-                            "no_ignore": {
-                                "type": "boolean",
-                                "description": "Ignore .gitignore rules",
-                                "default": false
-                            },
-                            "max_depth": {
-                                "type": "integer",
-                                "description": "Maximum depth to traverse"
-                            },
-                            "similarity": {
-                                "type": "integer",
-                                "description": "Similarity threshold (0-100) for fuzzy matching",
-                                "minimum": 0,
-                                "maximum": 100
-                            }
-                        },
-                        "required": ["path"]
-                    }
+                            "required": ["path"]
+                        }),
+                        &[pattern_set_properties(), filter_properties(), timeout_properties()],
+                    )
                 },
                 {
                     "name": "search",
                     "description": "Search for files matching a pattern",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "path": {
-                                "type": "string",
-                                "description": "Path to search in"
-                            },
-                            "pattern": {
-                                "type": "string",
-                                "description": "Pattern to match files",
-                                "default": ""
-                            },
-                            "pattern_type": {
-                                "type": "string",
-                                "enum": ["literal", "glob", "regex"],
-                                "default": "literal"
-                            },
-                            "hidden": {
-                                "type": "boolean",
-                                "description": "Include hidden files",
-                                "default": false
-                            },
-                            "no_ignore": {
-                                "type": "boolean",
-                                "description": "Ignore .gitignore rules",
-                                "default": false
+                    "inputSchema": with_shared_properties(
+                        json!({
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "Path to search in"
+                                },
+                                "pattern": {
+                                    "type": "string",
+                                    "description": "Pattern to match files",
+                                    "default": ""
+                                },
+                                "pattern_type": {
+                                    "type": "string",
+                                    "enum": ["literal", "glob", "regex"],
+                                    "default": "literal"
+                                },
+                                "hidden": {
+                                    "type": "boolean",
+                                    "description": "Include hidden files",
+                                    "default": false
+                                },
+                                "no_ignore": {
+                                    "type": "boolean",
+                                    "description": "Ignore .gitignore rules",
+                                    "default": false
+                                },
+                                "max_depth": {
+                                    "type": "integer",
+                                    "description": "Maximum depth to traverse"
+                                },
+                                "follow_links": {
+                                    "type": "boolean",
+                                    "description": "Follow symbolic links (symlink loops are detected and skipped)",
+                                    "default": false
+                                },
+                                "same_file_system": {
+                                    "type": "boolean",
+                                    "description": "Do not descend into directories on other filesystems",
+                                    "default": false
+                                }
                             },
-                            "max_depth": {
-                                "type": "integer",
-                                "description": "Maximum depth to traverse"
-                            }
-                        },
-                        "required": ["path"]
-                    }
+                            "required": ["path"]
+                        }),
+                        &[pattern_set_properties(), filter_properties(), timeout_properties()],
+                    )
                 },
                 {
                     "name": "count",
                     "description": "Count files matching a pattern",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "path": {
-                                "type": "string",
-                                "description": "Path to count files in"
-                            },
-                            "pattern": {
-                                "type": "string",
-                                "description": "Pattern to match files",
-                                "default": ""
-                            },
-                            "pattern_type": {
-                                "type": "string",
-                                "enum": ["literal", "glob", "regex"],
-                                "default": "literal"
+                    "inputSchema": with_shared_properties(
+                        json!({
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "Path to count files in"
+                                },
+                                "pattern": {
+                                    "type": "string",
+                                    "description": "Pattern to match files",
+                                    "default": ""
+                                },
+                                "pattern_type": {
+                                    "type": "string",
+                                    "enum": ["literal", "glob", "regex"],
+                                    "default": "literal"
+                                },
+                                "hidden": {
+                                    "type": "boolean",
+                                    "description": "Include hidden files",
+                                    "default": false
+                                },
+                                "no_ignore": {
+                                    "type": "boolean",
+                                    "description": "Ignore .gitignore rules",
+                                    "default": false
+                                },
+                                "max_depth": {
+                                    "type": "integer",
+                                    "description": "Maximum depth to traverse"
+                                },
+                                "follow_links": {
+                                    "type": "boolean",
+                                    "description": "Follow symbolic links (symlink loops are detected and skipped)",
+                                    "default": false
+                                },
+                                "same_file_system": {
+                                    "type": "boolean",
+                                    "description": "Do not descend into directories on other filesystems",
+                                    "default": false
+                                }
                             },
-                            "hidden": {
-                                "type": "boolean",
-                                "description": "Include hidden files",
-                                "default": false
-                            },
-                            "no_ignore": {
-                                "type": "boolean",
-                                "description": "Ignore .gitignore rules",
-                                "default": false
-                            },
-                            "max_depth": {
-                                "type": "integer",
-                                "description": "Maximum depth to traverse"
-                            }
-                        },
-                        "required": ["path"]
-                    }
+                            "required": ["path"]
+                        }),
+                        &[pattern_set_properties(), filter_properties(), timeout_properties()],
+                    )
                 },
                 {
                     "name": "outliers",
                     "description": "Detect storage outliers (large files, hidden consumers, patterns)",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "path": {
-                                "type": "string",
-                                "description": "Path to analyze for outliers"
-                            },
-                            "min_size": {
-                                "type": "string",
-                                "description": "Minimum file size to consider (e.g., 100MB, 1GB)"
+                    "inputSchema": with_shared_properties(
+                        json!({
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "Path to analyze for outliers"
+                                },
+                                "min_size": {
+                                    "type": "string",
+                                    "description": "Minimum file size to consider (e.g., 100MB, 1GB)"
+                                },
+                                "top_n": {
+                                    "type": "integer",
+                                    "description": "Number of top outliers to return",
+                                    "default": 20
+                                },
+                                "std_dev_threshold": {
+                                    "type": "number",
+                                    "description": "Standard deviations from mean to consider as outlier",
+                                    "default": 2.0
+                                },
+                                "check_hidden_consumers": {
+                                    "type": "boolean",
+                                    "description": "Check for hidden space consumers (node_modules, .git, etc.)",
+                                    "default": true
+                                },
+                                "check_patterns": {
+                                    "type": "boolean",
+                                    "description": "Check for file patterns (backups, logs, etc.)",
+                                    "default": true
+                                },
+                                "include_empty_dirs": {
+                                    "type": "boolean",
+                                    "description": "Report empty directories and ones holding only empty or junk files (.DS_Store, Thumbs.db)",
+                                    "default": false
+                                },
+                                "size_metric": {
+                                    "type": "string",
+                                    "enum": ["apparent", "allocated"],
+                                    "description": "Rank large files by apparent size (file length) or allocated size (disk space used)",
+                                    "default": "apparent"
+                                },
+                                "check_sparse": {
+                                    "type": "boolean",
+                                    "description": "Report sparse files whose holes span at least 1MB",
+                                    "default": true
+                                },
+                                "track_growth": {
+                                    "type": "boolean",
+                                    "description": "Compare with size snapshots of earlier scans of this path, and record this one",
+                                    "default": false
+                                },
+                                "snapshot_file": {
+                                    "type": "string",
+                                    "description": "Size snapshot store to use (implies track_growth)"
+                                },
+                                "growth_since": {
+                                    "type": "string",
+                                    "description": "Compare with the newest snapshot at least this old (e.g., 7d, 12h) instead of the newest one"
+                                }
                             },
-                            "top_n": {
-                                "type": "integer",
-                                "description": "Number of top outliers to return",
-                                "default": 20
-                            },
-                            "std_dev_threshold": {
-                                "type": "number",
-                                "description": "Standard deviations from mean to consider as outlier",
-                                "default": 2.0
-                            },
-                            "check_hidden_consumers": {
-                                "type": "boolean",
-                                "description": "Check for hidden space consumers (node_modules, .git, etc.)",
-                                "default": true
-                            },
-                            "check_patterns": {
-                                "type": "boolean",
-                                "description": "Check for file patterns (backups, logs, etc.)",
-                                "default": true
-                            }
-                        },
-                        "required": ["path"]
-                    }
+                            "required": ["path"]
+                        }),
+                        &[filter_properties(), timeout_properties()],
+                    )
                 },
                 {
                     "name": "analyze_file_clusters",
@@ -234,18 +467,24 @@ pub fn handle_tools_list(request: McpRequest) -> McpResponse {
 }
 
 pub async fn handle_tool_call(request: McpRequest) -> McpResponse {
+    handle_tool_call_with_cancel(request, CancelToken::new()).await
+}
+
+/// [`handle_tool_call`] whose scan stops once `cancel` fires, for the server
+/// to abort on `notifications/cancelled`.
+pub async fn handle_tool_call_with_cancel(request: McpRequest, cancel: CancelToken) -> McpResponse {
     let tool_params = match parse_tool_call_params(request.params, &request.id) {
         Ok(params) => params,
         Err(response) => return *response,
     };
 
     match tool_params.name.as_str() {
-        "dedupe" => handle_dedupe_tool(request.id, tool_params.arguments).await,
-        "search" => handle_search_tool(request.id, tool_params.arguments).await,
-        "count" => handle_count_tool(request.id, tool_params.arguments).await,
-        "outliers" => handle_outliers_tool(request.id, tool_params.arguments).await,
+        "dedupe" => handle_dedupe_tool(request.id, tool_params.arguments, &cancel).await,
+        "search" => handle_search_tool(request.id, tool_params.arguments, &cancel).await,
+        "count" => handle_count_tool(request.id, tool_params.arguments, &cancel).await,
+        "outliers" => handle_outliers_tool(request.id, tool_params.arguments, &cancel).await,
         "analyze_file_clusters" => {
-            handle_analyze_clusters_tool(request.id, tool_params.arguments).await
+            handle_analyze_clusters_tool(request.id, tool_params.arguments, &cancel).await
         },
         _ => McpResponse::error(
             request.id,
@@ -280,7 +519,16 @@ fn parse_tool_call_params(
     }
 }
 
-async fn handle_dedupe_tool(id: Value, arguments: Value) -> McpResponse {
+/// `cancel`, also stopping a tool's scan once `timeout_secs` have passed, if
+/// given.
+fn scan_deadline(cancel: &CancelToken, timeout_secs: Option<u64>) -> CancelToken {
+    match timeout_secs {
+        Some(secs) => cancel.clone().with_timeout(Duration::from_secs(secs)),
+        None => cancel.clone(),
+    }
+}
+
+async fn handle_dedupe_tool(id: Value, arguments: Value, cancel: &CancelToken) -> McpResponse {
     let args: DedupeArgs = match serde_json::from_value(arguments) {
         Ok(args) => args,
         Err(e) => {
@@ -293,45 +541,117 @@ async fn handle_dedupe_tool(id: Value, arguments: Value) -> McpResponse {
         respect_gitignore: !args.no_ignore,
         respect_ignore: !args.no_ignore,
         max_depth: args.max_depth,
+        follow_links: args.follow_links,
+        same_file_system: args.same_file_system,
+        cancel: scan_deadline(cancel, args.timeout_secs),
     };
 
-    let pattern = match create_pattern(&args.pattern, &args.pattern_type) {
+    let pattern = match create_pattern(&args.pattern, &args.pattern_type, &args.patterns) {
         Ok(p) => p,
         Err(e) => {
             return McpResponse::error(id, -32602, format!("Invalid pattern: {}", e));
         },
     };
 
-    // Run deduplication
-    let result = if let Some(threshold) = args.similarity {
-        crate::run_with_similarity(&args.path, &pattern, &walk_options, threshold, None)
-    } else {
-        crate::run_with_advanced_options(&args.path, &pattern, &walk_options, None)
+    let filter = match create_filter(&args.filter) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return McpResponse::error(id, -32602, format!("Invalid filter: {}", e));
+        },
+    };
+
+    let memory_budget = match args.memory_budget.as_deref().map(parse_size).transpose() {
+        Ok(bytes) => bytes.map(|bytes| Arc::new(MemoryBudget::new(bytes as usize))),
+        Err(e) => {
+            return McpResponse::error(id, -32602, format!("Invalid memory_budget: {}", e));
+        },
+    };
+
+    let dedupe_options = DedupeOptions {
+        hash_algorithm: args.hash,
+        similarity: args.similarity,
+        memory_budget,
+        cache_path: match args.cache_file {
+            Some(file) => Some(file.into()),
+            None if args.cache => Some(crate::HashCache::default_path()),
+            None => None,
+        },
+        paranoid: args.paranoid,
+        roots: args
+            .roots
+            .iter()
+            .map(crate::roots::ScanRoot::candidate)
+            .chain(
+                args.references
+                    .iter()
+                    .map(crate::roots::ScanRoot::reference),
+            )
+            .collect(),
+        filter,
+        directories: args.directories,
+        archives: args.archives,
+        normalize_text: (args.normalize_text || args.collapse_whitespace).then_some(
+            crate::normalize::TextNormalization {
+                collapse_whitespace: args.collapse_whitespace,
+            },
+        ),
+        // stdout is the MCP transport, so no progress is drawn
+        progress: None,
     };
 
+    // Run deduplication
+    let result = crate::run_dedupe(&args.path, &pattern, &walk_options, &dedupe_options, None);
+
     match result {
-        Ok(df) => {
+        Ok(report) => {
             // Convert DataFrame results to JSON
+            let df = &report.df;
             let height = df.height();
-            let duplicates = df
-                .clone()
-                .lazy()
-                .filter(polars::prelude::col("is_duplicate").eq(polars::prelude::lit(true)))
-                .collect()
-                .map(|df| df.height())
-                .unwrap_or(0);
+            let duplicates = report.duplicate_files;
+            let reclaimable = crate::reclaim::reclaimable_space(df, &args.path)
+                .map(|space| space.top(RECLAIMABLE_ENTRIES))
+                .unwrap_or_default();
+            let reference_matches = crate::roots::reference_matches(df).unwrap_or_default();
+            let archive_duplicates = crate::archives::archive_duplicates(df).unwrap_or_default();
 
             McpResponse::success(
                 id,
                 json!({
                     "total_files": height,
                     "duplicate_files": duplicates,
-                    "duplicate_groups": df
-                        .column("duplicate_group")
-                        .ok()
-                        .and_then(|col| col.unique().ok())
-                        .map(|unique| unique.len().saturating_sub(1)) // Subtract 1 for null values, prevent underflow
-                        .unwrap_or(0),
+                    "hash_algorithm": args.hash.name(),
+                    "duplicate_groups": report.duplicate_groups,
+                    "reclaimable_bytes": reclaimable.total_bytes,
+                    "reclaimable_by_group": reclaimable.by_group,
+                    "reclaimable_by_directory": reclaimable.by_directory,
+                    "reclaimable_by_extension": reclaimable.by_extension,
+                    "already_in_reference": reference_matches.len(),
+                    "reference_matches": reference_matches
+                        .iter()
+                        .take(REFERENCE_MATCH_ENTRIES)
+                        .collect::<Vec<_>>(),
+                    "duplicate_dirs": report.duplicate_dirs,
+                    "duplicate_dirs_reclaimable_bytes": report
+                        .duplicate_dirs
+                        .iter()
+                        .map(|dir| dir.reclaimable_bytes)
+                        .sum::<u64>(),
+                    "archive_duplicates": archive_duplicates.len(),
+                    "archive_members": archive_duplicates
+                        .iter()
+                        .take(ARCHIVE_DUPLICATE_ENTRIES)
+                        .collect::<Vec<_>>(),
+                    "normalized_duplicate_files": report
+                        .normalized_groups
+                        .iter()
+                        .map(|group| group.paths.len())
+                        .sum::<usize>(),
+                    "normalized_groups": report
+                        .normalized_groups
+                        .iter()
+                        .take(NORMALIZED_GROUP_ENTRIES)
+                        .collect::<Vec<_>>(),
+                    "diagnostics": report.diagnostics,
                     "message": format!("Found {} duplicate files in {} total files", duplicates, height)
                 }),
             )
@@ -343,7 +663,7 @@ async fn handle_dedupe_tool(id: Value, arguments: Value) -> McpResponse {
     }
 }
 
-async fn handle_search_tool(id: Value, arguments: Value) -> McpResponse {
+async fn handle_search_tool(id: Value, arguments: Value, cancel: &CancelToken) -> McpResponse {
     let args: SearchArgs = match serde_json::from_value(arguments) {
         Ok(args) => args,
         Err(e) => {
@@ -356,23 +676,34 @@ async fn handle_search_tool(id: Value, arguments: Value) -> McpResponse {
         respect_gitignore: !args.no_ignore,
         respect_ignore: !args.no_ignore,
         max_depth: args.max_depth,
+        follow_links: args.follow_links,
+        same_file_system: args.same_file_system,
+        cancel: scan_deadline(cancel, args.timeout_secs),
     };
 
-    let pattern = match create_pattern(&args.pattern, &args.pattern_type) {
+    let pattern = match create_pattern(&args.pattern, &args.pattern_type, &args.patterns) {
         Ok(p) => p,
         Err(e) => {
             return McpResponse::error(id, -32602, format!("Invalid pattern: {}", e));
         },
     };
 
-    match crate::walk_with_options(&args.path, &walk_options) {
-        Ok(files) => {
-            let matched_files = crate::find_advanced(&files, &pattern);
+    let filter = match create_filter(&args.filter) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return McpResponse::error(id, -32602, format!("Invalid filter: {}", e));
+        },
+    };
+
+    match crate::walk_with_summary(&args.path, &walk_options) {
+        Ok(summary) => {
+            let matched_files = crate::find_advanced(&summary.files, &pattern, &filter);
             McpResponse::success(
                 id,
                 json!({
-                    "files": matched_files,
+                    "files": matched_files.iter().map(|f| paths::to_json(f)).collect::<Vec<_>>(),
                     "count": matched_files.len(),
+                    "diagnostics": summary.diagnostics,
                     "message": format!("Found {} files matching pattern", matched_files.len())
                 }),
             )
@@ -384,7 +715,7 @@ async fn handle_search_tool(id: Value, arguments: Value) -> McpResponse {
     }
 }
 
-async fn handle_count_tool(id: Value, arguments: Value) -> McpResponse {
+async fn handle_count_tool(id: Value, arguments: Value, cancel: &CancelToken) -> McpResponse {
     let args: CountArgs = match serde_json::from_value(arguments) {
         Ok(args) => args,
         Err(e) => {
@@ -397,22 +728,33 @@ async fn handle_count_tool(id: Value, arguments: Value) -> McpResponse {
         respect_gitignore: !args.no_ignore,
         respect_ignore: !args.no_ignore,
         max_depth: args.max_depth,
+        follow_links: args.follow_links,
+        same_file_system: args.same_file_system,
+        cancel: scan_deadline(cancel, args.timeout_secs),
     };
 
-    let pattern = match create_pattern(&args.pattern, &args.pattern_type) {
+    let pattern = match create_pattern(&args.pattern, &args.pattern_type, &args.patterns) {
         Ok(p) => p,
         Err(e) => {
             return McpResponse::error(id, -32602, format!("Invalid pattern: {}", e));
         },
     };
 
-    match crate::walk_with_options(&args.path, &walk_options) {
-        Ok(files) => {
-            let matched_files = crate::find_advanced(&files, &pattern);
+    let filter = match create_filter(&args.filter) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return McpResponse::error(id, -32602, format!("Invalid filter: {}", e));
+        },
+    };
+
+    match crate::walk_with_summary(&args.path, &walk_options) {
+        Ok(summary) => {
+            let matched_files = crate::find_advanced(&summary.files, &pattern, &filter);
             McpResponse::success(
                 id,
                 json!({
                     "count": matched_files.len(),
+                    "diagnostics": summary.diagnostics,
                     "message": format!("Found {} files matching pattern", matched_files.len())
                 }),
             )
@@ -424,29 +766,68 @@ async fn handle_count_tool(id: Value, arguments: Value) -> McpResponse {
     }
 }
 
-fn create_pattern(pattern: &str, pattern_type: &str) -> Result<PatternType, String> {
-    match pattern_type {
-        "literal" | "" => Ok(PatternType::Literal(pattern.to_string())),
-        "glob" => {
-            let glob =
-                globset::Glob::new(pattern).map_err(|e| format!("Invalid glob pattern: {}", e))?;
-            let mut builder = globset::GlobSetBuilder::new();
-            builder.add(glob);
-            let globset = builder
-                .build()
-                .map_err(|e| format!("Failed to build globset: {}", e))?;
-            Ok(PatternType::Glob(globset))
-        },
-        "regex" => {
-            let regex =
-                regex::Regex::new(pattern).map_err(|e| format!("Invalid regex pattern: {}", e))?;
-            Ok(PatternType::Regex(regex))
-        },
-        _ => Err(format!("Unknown pattern type: {}", pattern_type)),
+fn create_filter(args: &FilterArgs) -> Result<crate::filters::FileFilter, String> {
+    let size = |value: &Option<String>| value.as_deref().map(parse_size).transpose();
+    let time = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(crate::filters::parse_time)
+            .transpose()
+            .map_err(|e| e.to_string())
+    };
+
+    let mut filter = crate::filters::FileFilter::default()
+        .with_types(&args.types)
+        .map_err(|e| e.to_string())?;
+    filter.min_size = size(&args.min_size)?;
+    filter.max_size = size(&args.max_size)?;
+    filter.modified_after = time(&args.modified_after)?;
+    filter.modified_before = time(&args.modified_before)?;
+    filter.accessed_after = time(&args.accessed_after)?;
+    filter.accessed_before = time(&args.accessed_before)?;
+    filter.kind = args.kind;
+    Ok(filter)
+}
+
+fn create_pattern(
+    pattern: &str,
+    pattern_type: &str,
+    patterns: &PatternArgs,
+) -> Result<PatternType, String> {
+    let kind = match pattern_type {
+        "literal" | "" => PatternKind::Literal,
+        "glob" => PatternKind::Glob,
+        "regex" => PatternKind::Regex,
+        _ => return Err(format!("Unknown pattern type: {}", pattern_type)),
+    };
+    if patterns.include.is_empty()
+        && patterns.exclude.is_empty()
+        && patterns.exclude_from.is_empty()
+        && patterns.match_on == MatchTarget::Path
+    {
+        return kind.compile(pattern).map_err(|e| e.to_string());
     }
+
+    let build = || -> Result<PatternSet, crate::patterns::PatternError> {
+        let mut set = PatternSet::new(patterns.match_on);
+        if !pattern.is_empty() {
+            set.include.push(kind.compile(pattern)?);
+        }
+        for include in &patterns.include {
+            set.include(include, kind)?;
+        }
+        for exclude in &patterns.exclude {
+            set.exclude(exclude, kind)?;
+        }
+        for file in &patterns.exclude_from {
+            set.exclude_from(file, kind)?;
+        }
+        Ok(set)
+    };
+    build().map(PatternType::Set).map_err(|e| e.to_string())
 }
 
-async fn handle_outliers_tool(id: Value, arguments: Value) -> McpResponse {
+async fn handle_outliers_tool(id: Value, arguments: Value, cancel: &CancelToken) -> McpResponse {
     let args: OutliersArgs = match serde_json::from_value(arguments) {
         Ok(args) => args,
         Err(e) => {
@@ -458,19 +839,45 @@ async fn handle_outliers_tool(id: Value, arguments: Value) -> McpResponse {
         },
     };
 
-    // Parse min_size if provided
-    let min_size_bytes = args.min_size.as_ref().and_then(|s| parse_size(s).ok());
+    let mut filter = match create_filter(&args.filter) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return McpResponse::error(id, -32602, format!("Invalid filter: {}", e));
+        },
+    };
+
+    let growth_since_secs = match args.growth_since.as_deref().map(crate::filters::parse_age) {
+        Some(Ok(secs)) => Some(secs),
+        Some(Err(e)) => {
+            return McpResponse::error(id, -32602, format!("Invalid growth_since: {}", e));
+        },
+        None => None,
+    };
+    let snapshot_path = match args.snapshot_file {
+        Some(file) => Some(file.into()),
+        None if args.track_growth || growth_since_secs.is_some() => {
+            Some(crate::growth::SnapshotStore::default_path())
+        },
+        None => None,
+    };
 
+    // min_size stays an outlier threshold rather than narrowing the statistics
     let options = crate::outliers::OutlierOptions {
-        min_size: min_size_bytes,
+        min_size: filter.min_size.take(),
         top_n: Some(args.top_n),
         std_dev_threshold: args.std_dev_threshold,
         check_hidden_consumers: args.check_hidden_consumers,
-        include_empty_dirs: false,
+        include_empty_dirs: args.include_empty_dirs,
         check_patterns: args.check_patterns,
+        size_metric: args.size_metric,
+        check_sparse: args.check_sparse,
+        snapshot_path,
+        growth_since_secs,
         enable_clustering: false, // Not enabled by default in outliers tool
         cluster_similarity_threshold: 70,
         min_cluster_size: 2,
+        filter,
+        cancel: scan_deadline(cancel, args.timeout_secs),
     };
 
     match crate::outliers::detect_outliers(&args.path, &options) {
@@ -480,15 +887,18 @@ async fn handle_outliers_tool(id: Value, arguments: Value) -> McpResponse {
                 "total_files_analyzed": report.total_files_analyzed,
                 "total_size_analyzed": report.total_size_analyzed,
                 "total_size_gb": report.total_size_analyzed as f64 / (1024.0 * 1024.0 * 1024.0),
+                "total_allocated_analyzed": report.total_allocated_analyzed,
+                "size_metric": args.size_metric,
                 "large_files": report.large_files.iter().map(|o| json!({
-                    "path": o.path.to_string_lossy(),
+                    "path": paths::to_json(&o.path),
                     "size_bytes": o.size_bytes,
+                    "allocated_bytes": o.allocated_bytes,
                     "size_mb": o.size_mb,
                     "percentage_of_total": o.percentage_of_total,
                     "std_devs_from_mean": o.std_devs_from_mean,
                 })).collect::<Vec<_>>(),
                 "hidden_consumers": report.hidden_consumers.iter().map(|c| json!({
-                    "path": c.path.to_string_lossy(),
+                    "path": paths::to_json(&c.path),
                     "pattern_type": c.pattern_type,
                     "total_size_bytes": c.total_size_bytes,
                     "file_count": c.file_count,
@@ -496,10 +906,14 @@ async fn handle_outliers_tool(id: Value, arguments: Value) -> McpResponse {
                 })).collect::<Vec<_>>(),
                 "pattern_groups": report.pattern_groups.iter().map(|g| json!({
                     "pattern": g.pattern,
-                    "sample_files": g.sample_files.iter().map(|f| f.to_string_lossy()).collect::<Vec<_>>(),
+                    "sample_files": g.sample_files.iter().map(|f| paths::to_json(f)).collect::<Vec<_>>(),
                     "total_size_bytes": g.total_size_bytes,
                     "count": g.count,
                 })).collect::<Vec<_>>(),
+                "sparse_files": report.sparse_files,
+                "growth": report.growth,
+                "empty_dirs": report.empty_dirs,
+                "diagnostics": report.diagnostics,
                 "message": format!("Found {} outliers across {} files",
                     report.large_files.len() + report.hidden_consumers.len() + report.pattern_groups.len(),
                     report.total_files_analyzed
@@ -515,7 +929,11 @@ async fn handle_outliers_tool(id: Value, arguments: Value) -> McpResponse {
     }
 }
 
-async fn handle_analyze_clusters_tool(id: Value, arguments: Value) -> McpResponse {
+async fn handle_analyze_clusters_tool(
+    id: Value,
+    arguments: Value,
+    cancel: &CancelToken,
+) -> McpResponse {
     // Parse arguments
     let path = arguments["path"].as_str().unwrap_or(".");
     let min_similarity = arguments["min_similarity"].as_u64().unwrap_or(70) as u8;
@@ -526,35 +944,46 @@ async fn handle_analyze_clusters_tool(id: Value, arguments: Value) -> McpRespons
     let min_size_bytes = parse_size(min_file_size).unwrap_or(10 * 1024 * 1024);
 
     // Support tool composition via files parameter
+    let mut diagnostics = crate::ScanDiagnostics::default();
     let files = if let Some(file_list) = arguments["files"].as_array() {
         // Analyze specific files from previous tool output
         let mut file_infos = Vec::new();
         for file_path in file_list {
-            if let Some(path_str) = file_path.as_str() {
-                if let Ok(metadata) = std::fs::metadata(path_str) {
-                    if metadata.is_file() && metadata.len() >= min_size_bytes {
+            if cancel.is_cancelled() {
+                diagnostics.interrupt(cancel);
+                break;
+            }
+            // Plain strings, or the `{ display, bytes_hex }` form of non-UTF-8 paths
+            if let Some(path) = paths::from_json(file_path) {
+                match std::fs::metadata(&path) {
+                    Ok(metadata) if metadata.is_file() && metadata.len() >= min_size_bytes => {
                         // Compute SSDEEP hash for large files
-                        let ssdeep_hash = if let Ok(content) = std::fs::read(path_str) {
-                            ssdeep::hash(&content).ok()
-                        } else {
-                            None
-                        };
+                        let ssdeep_hash = crate::hashing::fuzzy_hash_file(&path);
 
                         file_infos.push(crate::outliers::SimpleFileInfo {
-                            path: std::path::PathBuf::from(path_str),
+                            path,
                             size_bytes: metadata.len(),
+                            allocated_bytes: crate::outliers::allocated_size(&metadata),
                             ssdeep_hash,
                         });
-                    }
+                    },
+                    Ok(_) => {},
+                    Err(e) => diagnostics.push_io(path, &e),
                 }
             }
         }
         file_infos
     } else {
         // Full directory scan for large files
-        let walk_options = WalkOptions::default();
-        let all_files = match crate::walk_with_options(path, &walk_options) {
-            Ok(files) => files,
+        let walk_options = WalkOptions {
+            cancel: cancel.clone(),
+            ..WalkOptions::default()
+        };
+        let all_files = match crate::walk_with_summary(path, &walk_options) {
+            Ok(summary) => {
+                diagnostics.extend(summary.diagnostics);
+                summary.files
+            },
             Err(e) => {
                 return McpResponse::error(id, -32603, format!("Error scanning directory: {}", e));
             },
@@ -563,21 +992,24 @@ async fn handle_analyze_clusters_tool(id: Value, arguments: Value) -> McpRespons
         // Collect large files with SSDEEP hashes
         let mut file_infos = Vec::new();
         for file_path in all_files {
-            if let Ok(metadata) = std::fs::metadata(&file_path) {
-                if metadata.is_file() && metadata.len() >= min_size_bytes {
+            if cancel.is_cancelled() {
+                diagnostics.interrupt(cancel);
+                break;
+            }
+            match std::fs::metadata(&file_path) {
+                Ok(metadata) if metadata.is_file() && metadata.len() >= min_size_bytes => {
                     // Compute SSDEEP hash
-                    let ssdeep_hash = if let Ok(content) = std::fs::read(&file_path) {
-                        ssdeep::hash(&content).ok()
-                    } else {
-                        None
-                    };
+                    let ssdeep_hash = crate::hashing::fuzzy_hash_file(&file_path);
 
                     file_infos.push(crate::outliers::SimpleFileInfo {
-                        path: std::path::PathBuf::from(file_path),
+                        path: file_path,
                         size_bytes: metadata.len(),
+                        allocated_bytes: crate::outliers::allocated_size(&metadata),
                         ssdeep_hash,
                     });
-                }
+                },
+                Ok(_) => {},
+                Err(e) => diagnostics.push_io(file_path, &e),
             }
         }
         file_infos
@@ -590,7 +1022,7 @@ async fn handle_analyze_clusters_tool(id: Value, arguments: Value) -> McpRespons
                 "clusters": clusters.iter().map(|c| json!({
                     "cluster_id": c.cluster_id,
                     "files": c.files.iter().map(|f| json!({
-                        "path": f.path.to_string_lossy(),
+                        "path": paths::to_json(&f.path),
                         "size_bytes": f.size_bytes,
                         "size_mb": f.size_bytes as f64 / (1024.0 * 1024.0),
                     })).collect::<Vec<_>>(),
@@ -607,6 +1039,7 @@ async fn handle_analyze_clusters_tool(id: Value, arguments: Value) -> McpRespons
                         .flat_map(|c| c.files.iter().map(|f| f.path.to_string_lossy().to_string()))
                         .collect::<Vec<_>>()
                 },
+                "diagnostics": diagnostics,
                 "message": format!("Found {} clusters containing {} similar files",
                     clusters.len(),
                     clusters.iter().map(|c| c.files.len()).sum::<usize>()
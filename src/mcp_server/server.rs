@@ -1,23 +1,51 @@
+use crate::cancel::CancelToken;
 use crate::models::mcp::{McpRequest, McpResponse};
 use anyhow::Result;
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
+use tokio::sync::mpsc;
 use tracing::{debug, error, info};
 
-pub struct McpServer;
+/// Tokens of the tool calls still running, by request id (as JSON text).
+type InFlight = Arc<Mutex<HashMap<String, CancelToken>>>;
+
+pub struct McpServer {
+    in_flight: InFlight,
+}
 
 impl McpServer {
     pub fn new() -> Self {
-        Self
+        Self {
+            in_flight: InFlight::default(),
+        }
     }
 
+    /// Serve requests from stdin until it closes.
+    ///
+    /// Tool calls run on the blocking pool, so the read loop keeps handling
+    /// messages while a scan is running, and a `notifications/cancelled`
+    /// for a call stops its scan. A cancelled call still answers with what
+    /// it gathered, marked as interrupted.
     pub async fn run(&self) -> Result<()> {
         info!("Starting RDedupe MCP server");
 
         let stdin = tokio::io::stdin();
-        let stdout = tokio::io::stdout();
         let mut reader = AsyncBufReader::new(stdin);
-        let mut writer = stdout;
+        let (responses, mut outgoing) = mpsc::unbounded_channel::<McpResponse>();
+        let writer = tokio::spawn(async move {
+            let mut writer = tokio::io::stdout();
+            while let Some(response) = outgoing.recv().await {
+                let response_str = serde_json::to_string(&response)?;
+
+                debug!("Sending response: {}", response_str);
+                writer.write_all(response_str.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+            anyhow::Ok(())
+        });
 
         let mut line = String::new();
 
@@ -38,28 +66,30 @@ impl McpServer {
             debug!("Received request: {}", trimmed);
 
             match serde_json::from_str::<McpRequest>(trimmed) {
+                Ok(request) if request.method == "tools/call" => {
+                    self.spawn_tool_call(request, responses.clone());
+                },
+                Ok(request) if request.method.starts_with("notifications/") => {
+                    self.handle_notification(&request);
+                },
                 Ok(request) => {
-                    let response = self.handle_request(request).await;
-                    let response_str = serde_json::to_string(&response)?;
-
-                    debug!("Sending response: {}", response_str);
-                    writer.write_all(response_str.as_bytes()).await?;
-                    writer.write_all(b"\n").await?;
-                    writer.flush().await?;
+                    let _ = responses.send(self.handle_request(request).await);
                 },
                 Err(e) => {
                     error!("Failed to parse request: {}", e);
                     let error_response =
                         McpResponse::error(Value::Null, -32700, "Parse error".to_string());
-                    let response_str = serde_json::to_string(&error_response)?;
-                    writer.write_all(response_str.as_bytes()).await?;
-                    writer.write_all(b"\n").await?;
-                    writer.flush().await?;
+                    let _ = responses.send(error_response);
                 },
             }
         }
 
-        Ok(())
+        // Nobody is left to read the results of running calls
+        for cancel in self.in_flight().values() {
+            cancel.cancel();
+        }
+        drop(responses);
+        writer.await?
     }
 
     async fn handle_request(&self, request: McpRequest) -> McpResponse {
@@ -74,6 +104,59 @@ impl McpServer {
             ),
         }
     }
+
+    /// Run a tool call on the blocking pool with a token registered under its
+    /// id, and send its response once it is done.
+    fn spawn_tool_call(&self, request: McpRequest, responses: mpsc::UnboundedSender<McpResponse>) {
+        let key = request.id.to_string();
+        let cancel = CancelToken::new();
+        self.in_flight().insert(key.clone(), cancel.clone());
+
+        let in_flight = Arc::clone(&self.in_flight);
+        let runtime = tokio::runtime::Handle::current();
+        tokio::spawn(async move {
+            let id = request.id.clone();
+            let call = tokio::task::spawn_blocking(move || {
+                runtime.block_on(super::handlers::handle_tool_call_with_cancel(
+                    request, cancel,
+                ))
+            });
+            let response = call.await.unwrap_or_else(|e| {
+                McpResponse::error(id, -32603, format!("Tool call failed: {e}"))
+            });
+            lock(&in_flight).remove(&key);
+            let _ = responses.send(response);
+        });
+    }
+
+    /// Handle a notification, which gets no response: `notifications/cancelled`
+    /// stops the scan of the tool call named by its `requestId`.
+    fn handle_notification(&self, request: &McpRequest) {
+        if request.method != "notifications/cancelled" {
+            return;
+        }
+        let Some(id) = request
+            .params
+            .as_ref()
+            .and_then(|params| params.get("requestId"))
+        else {
+            return;
+        };
+        if let Some(cancel) = self.in_flight().get(&id.to_string()) {
+            debug!("Cancelling request {}", id);
+            cancel.cancel();
+        }
+    }
+
+    fn in_flight(&self) -> MutexGuard<'_, HashMap<String, CancelToken>> {
+        lock(&self.in_flight)
+    }
+}
+
+fn lock(in_flight: &InFlight) -> MutexGuard<'_, HashMap<String, CancelToken>> {
+    in_flight
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
 }
 
 impl Default for McpServer {
@@ -92,7 +175,7 @@ mod tests {
     fn test_mcp_server_new() {
         let _server = McpServer::new();
         // Just ensure it can be created
-        let _default_server = McpServer;
+        let _default_server = McpServer::default();
     }
 
     #[tokio::test]
@@ -125,6 +208,53 @@ mod tests {
         assert!(response.error.is_none());
     }
 
+    #[test]
+    fn test_cancelled_notification_cancels_the_call() {
+        let server = McpServer::new();
+        let cancel = CancelToken::new();
+        server
+            .in_flight()
+            .insert(json!(7).to_string(), cancel.clone());
+
+        let notification: McpRequest = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": 8 }
+        }))
+        .unwrap();
+        server.handle_notification(&notification);
+        assert!(!cancel.is_cancelled());
+
+        let notification = McpRequest {
+            params: Some(json!({ "requestId": 7, "reason": "user abort" })),
+            ..notification
+        };
+        server.handle_notification(&notification);
+        assert!(cancel.is_cancelled());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tool_calls_answer_from_the_blocking_pool() {
+        let server = McpServer::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let request = McpRequest {
+            jsonrpc: "2.0".to_string(),
+            id: json!("scan"),
+            method: "tools/call".to_string(),
+            params: Some(json!({
+                "name": "count",
+                "arguments": { "path": dir.path() }
+            })),
+        };
+
+        let (responses, mut outgoing) = mpsc::unbounded_channel();
+        server.spawn_tool_call(request, responses);
+        let response = outgoing.recv().await.unwrap();
+        assert_eq!(response.id, json!("scan"));
+        assert!(response.result.is_some());
+        assert!(server.in_flight().is_empty());
+    }
+
     #[tokio::test]
     async fn test_handle_request_unknown_method() {
         let server = McpServer::new();
@@ -4,6 +4,8 @@ use serde_json::Value;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpRequest {
     pub jsonrpc: String,
+    /// Request id; `null` for notifications, which carry none
+    #[serde(default)]
     pub id: Value,
     pub method: String,
     pub params: Option<Value>,
@@ -67,6 +69,8 @@ pub struct DedupeArgs {
     pub pattern: String,
     #[serde(default)]
     pub pattern_type: String,
+    #[serde(flatten)]
+    pub patterns: PatternArgs,
     #[serde(default)]
     pub hidden: bool,
     #[serde(default)]
@@ -74,7 +78,37 @@ pub struct DedupeArgs {
     #[serde(default)]
     pub max_depth: Option<usize>,
     #[serde(default)]
+    pub follow_links: bool,
+    #[serde(default)]
+    pub same_file_system: bool,
+    #[serde(flatten)]
+    pub filter: FilterArgs,
+    #[serde(default)]
     pub similarity: Option<u32>,
+    #[serde(default)]
+    pub hash: crate::HashAlgorithm,
+    #[serde(default)]
+    pub memory_budget: Option<String>,
+    #[serde(default)]
+    pub cache: bool,
+    #[serde(default)]
+    pub cache_file: Option<String>,
+    #[serde(default)]
+    pub paranoid: bool,
+    #[serde(default)]
+    pub directories: bool,
+    #[serde(default)]
+    pub archives: bool,
+    #[serde(default)]
+    pub normalize_text: bool,
+    #[serde(default)]
+    pub collapse_whitespace: bool,
+    #[serde(default)]
+    pub roots: Vec<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -84,12 +118,22 @@ pub struct SearchArgs {
     pub pattern: String,
     #[serde(default)]
     pub pattern_type: String,
+    #[serde(flatten)]
+    pub patterns: PatternArgs,
     #[serde(default)]
     pub hidden: bool,
     #[serde(default)]
     pub no_ignore: bool,
     #[serde(default)]
     pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub follow_links: bool,
+    #[serde(default)]
+    pub same_file_system: bool,
+    #[serde(flatten)]
+    pub filter: FilterArgs,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -99,18 +143,63 @@ pub struct CountArgs {
     pub pattern: String,
     #[serde(default)]
     pub pattern_type: String,
+    #[serde(flatten)]
+    pub patterns: PatternArgs,
     #[serde(default)]
     pub hidden: bool,
     #[serde(default)]
     pub no_ignore: bool,
     #[serde(default)]
     pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub follow_links: bool,
+    #[serde(default)]
+    pub same_file_system: bool,
+    #[serde(flatten)]
+    pub filter: FilterArgs,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Include/exclude pattern sets shared by the dedupe, search and count tools
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PatternArgs {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub exclude_from: Vec<String>,
+    #[serde(default)]
+    pub match_on: crate::patterns::MatchTarget,
+}
+
+/// File filter criteria shared by the dedupe, search, count and outliers tools
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FilterArgs {
+    #[serde(default)]
+    pub min_size: Option<String>,
+    #[serde(default)]
+    pub max_size: Option<String>,
+    #[serde(default)]
+    pub modified_after: Option<String>,
+    #[serde(default)]
+    pub modified_before: Option<String>,
+    #[serde(default)]
+    pub accessed_after: Option<String>,
+    #[serde(default)]
+    pub accessed_before: Option<String>,
+    #[serde(default)]
+    pub kind: Option<crate::filters::FileKind>,
+    #[serde(default)]
+    pub types: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OutliersArgs {
     pub path: String,
-    pub min_size: Option<String>,
+    #[serde(flatten)]
+    pub filter: FilterArgs,
     #[serde(default = "default_top_n")]
     pub top_n: usize,
     #[serde(default = "default_std_dev_threshold")]
@@ -119,6 +208,20 @@ pub struct OutliersArgs {
     pub check_hidden_consumers: bool,
     #[serde(default = "default_true")]
     pub check_patterns: bool,
+    #[serde(default)]
+    pub include_empty_dirs: bool,
+    #[serde(default)]
+    pub size_metric: crate::outliers::SizeMetric,
+    #[serde(default = "default_true")]
+    pub check_sparse: bool,
+    #[serde(default)]
+    pub track_growth: bool,
+    #[serde(default)]
+    pub snapshot_file: Option<String>,
+    #[serde(default)]
+    pub growth_since: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 fn default_top_n() -> usize {
@@ -0,0 +1,292 @@
+//! Text-normalized duplicates.
+//!
+//! Two copies of a source file or config that differ only in CRLF and LF
+//! line endings, trailing whitespace, a byte order mark or their encoding
+//! never share a content hash. With [`crate::DedupeOptions::normalize_text`],
+//! every text file is decoded, canonicalized and hashed again, and files
+//! whose normalized text matches while their bytes differ form a
+//! [`NormalizedGroup`].
+//!
+//! Normalized groups are kept apart from exact duplicates: they have their
+//! own [`NORMALIZED_GROUP_COLUMN`] in the report, and as their files are not
+//! byte-identical, none of them counts as reclaimable.
+//!
+//! Each candidate is read into memory whole, so the read is reserved from
+//! the scan's [`MemoryBudget`] first; the normalized text is fed to the
+//! hasher as it is produced rather than built as another copy.
+
+use crate::hashing::{HashAlgorithm, MemoryBudget};
+use crate::paths::display;
+use crate::FileInfo;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the column holding the normalized group of a file, null unless
+/// it has a normalized duplicate.
+pub const NORMALIZED_GROUP_COLUMN: &str = "normalized_group";
+
+/// Name of the column flagging files in a normalized group.
+pub const NORMALIZED_DUPLICATE_COLUMN: &str = "is_normalized_duplicate";
+
+/// Larger files are not read as text.
+pub const MAX_TEXT_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Leading bytes searched for a NUL byte to tell binary files apart.
+const SNIFF_BYTES: usize = 8192;
+
+/// How text is canonicalized before hashing. Line endings, a byte order mark,
+/// the encoding, trailing whitespace and trailing blank lines are always
+/// ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextNormalization {
+    /// Also treat every run of whitespace, blank lines included, as a single
+    /// space
+    pub collapse_whitespace: bool,
+}
+
+/// Decode `bytes` as text: UTF-8 or UTF-16 with a byte order mark, else
+/// UTF-8, else Latin-1. Returns `None` for binary content, recognized by a
+/// NUL byte near the start. UTF-8 text is borrowed, not copied.
+pub fn decode_text(bytes: &[u8]) -> Option<Cow<'_, str>> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return std::str::from_utf8(rest).ok().map(Cow::Borrowed);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        return decode_utf16(rest, u16::from_le_bytes).map(Cow::Owned);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        return decode_utf16(rest, u16::from_be_bytes).map(Cow::Owned);
+    }
+    if bytes[..bytes.len().min(SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(Cow::Borrowed(text)),
+        Err(_) => Some(Cow::Owned(bytes.iter().copied().map(char::from).collect())),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+/// Canonical form of `text`: LF line endings, no trailing whitespace on any
+/// line and no trailing blank lines, or with `collapse_whitespace`, its
+/// words separated by single spaces.
+pub fn normalize_text(text: &str, normalization: TextNormalization) -> String {
+    let mut normalized = String::new();
+    write_normalized(text, normalization, |part| normalized.push_str(part));
+    normalized
+}
+
+/// Pass the canonical form of `text` (see [`normalize_text`]) to `out` piece
+/// by piece, without building it.
+fn write_normalized(text: &str, normalization: TextNormalization, mut out: impl FnMut(&str)) {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    if normalization.collapse_whitespace {
+        for (index, word) in text.split_whitespace().enumerate() {
+            if index > 0 {
+                out(" ");
+            }
+            out(word);
+        }
+        return;
+    }
+    // CRLF, CR and LF all end a line; blank lines are only written once a
+    // later line has content, which drops the trailing ones
+    let lines = text
+        .split('\n')
+        .flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'));
+    let mut owed_newlines = 0;
+    for (index, line) in lines.enumerate() {
+        if index > 0 {
+            owed_newlines += 1;
+        }
+        let line = line.trim_end();
+        if !line.is_empty() {
+            for _ in 0..owed_newlines {
+                out("\n");
+            }
+            owed_newlines = 0;
+            out(line);
+        }
+    }
+}
+
+/// Hash of the normalized text of the file at `path`, or `None` if it is
+/// binary or larger than [`MAX_TEXT_BYTES`].
+///
+/// The file and, for UTF-16 or Latin-1 text, its decoded copy of up to twice
+/// its size are reserved from `budget` before the file is read.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+pub fn normalized_hash(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    normalization: TextNormalization,
+    budget: &MemoryBudget,
+) -> io::Result<Option<String>> {
+    let size = fs::metadata(path)?.len();
+    if size > MAX_TEXT_BYTES {
+        return Ok(None);
+    }
+    let _reservation =
+        budget.reserve(usize::try_from(size.saturating_mul(3)).unwrap_or(usize::MAX));
+    let bytes = fs::read(path)?;
+    let Some(text) = decode_text(&bytes) else {
+        return Ok(None);
+    };
+    let mut hasher = algorithm.hasher();
+    write_normalized(&text, normalization, |part| hasher.update(part.as_bytes()));
+    Ok(Some(hasher.finish_hex()))
+}
+
+/// Text files with the same normalized content but different bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalizedGroup {
+    /// Hash of the normalized text, also the group id
+    pub normalized_hash: String,
+    /// Every file in the group, by path
+    #[serde(with = "crate::paths::serde_paths")]
+    pub paths: Vec<PathBuf>,
+    /// Distinct byte contents among the files; exact duplicates of each
+    /// other count once
+    pub variants: usize,
+}
+
+impl fmt::Display for NormalizedGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paths: Vec<_> = self.paths.iter().map(|path| display(path)).collect();
+        write!(
+            f,
+            "{} files in {} variants: {}",
+            self.paths.len(),
+            self.variants,
+            paths.join(", ")
+        )
+    }
+}
+
+/// Group the files of `file_infos` by [`FileInfo::normalized_hash`], keeping
+/// groups whose files differ byte for byte, and set their
+/// [`FileInfo::normalized_group`]. Groups are returned by path.
+pub fn mark_normalized_groups(file_infos: &mut [FileInfo]) -> Vec<NormalizedGroup> {
+    let mut by_hash: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, file_info) in file_infos.iter().enumerate() {
+        if let Some(hash) = &file_info.normalized_hash {
+            by_hash.entry(hash.clone()).or_default().push(index);
+        }
+    }
+
+    let mut groups = Vec::new();
+    for (normalized_hash, indices) in by_hash {
+        // A file left without a content hash has no exact duplicate at all
+        let variants = indices
+            .iter()
+            .map(|&index| {
+                let file_info = &file_infos[index];
+                file_info
+                    .content_hash
+                    .as_deref()
+                    .map_or_else(|| display(&file_info.path), Into::into)
+            })
+            .collect::<HashSet<_>>()
+            .len();
+        if variants < 2 {
+            continue;
+        }
+        let mut paths = Vec::with_capacity(indices.len());
+        for &index in &indices {
+            file_infos[index].normalized_group = Some(normalized_hash.clone());
+            paths.push(file_infos[index].path.clone());
+        }
+        paths.sort();
+        groups.push(NormalizedGroup {
+            normalized_hash,
+            paths,
+            variants,
+        });
+    }
+    groups.sort_by(|a, b| a.paths.cmp(&b.paths));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(
+            decode_text(b"\xEF\xBB\xBFkey = 1").as_deref(),
+            Some("key = 1")
+        );
+        assert_eq!(decode_text(b"\xFF\xFEk\0e\0y\0").as_deref(), Some("key"));
+        assert_eq!(decode_text(b"\xFE\xFF\0k\0e\0y").as_deref(), Some("key"));
+        assert_eq!(decode_text(b"caf\xE9").as_deref(), Some("café"));
+        assert_eq!(decode_text(b"\x7FELF\0\x01"), None);
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        let strict = TextNormalization::default();
+        assert_eq!(normalize_text("a  \r\nb\t\r\n\r\n", strict), "a\nb");
+        assert_eq!(normalize_text("\u{feff}a\rb", strict), "a\nb");
+        assert_eq!(normalize_text("a  b\n\n c", strict), "a  b\n\n c");
+        assert_eq!(normalize_text("\n a\r\r\nb \n \n", strict), "\n a\n\nb");
+        assert_eq!(normalize_text(" \r\n\n", strict), "");
+
+        let collapse = TextNormalization {
+            collapse_whitespace: true,
+        };
+        assert_eq!(normalize_text("a  b\n\n c\n", collapse), "a b c");
+    }
+
+    #[test]
+    fn test_line_endings_bom_and_encoding_hash_alike() -> io::Result<()> {
+        let dir = TempDir::new()?;
+        let unix = dir.path().join("unix.toml");
+        let windows = dir.path().join("windows.toml");
+        let utf16 = dir.path().join("utf16.toml");
+        let binary = dir.path().join("binary.bin");
+        fs::write(&unix, "name = \"café\"\nport = 80\n")?;
+        fs::write(&windows, "\u{feff}name = \"café\"  \r\nport = 80\r\n")?;
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "name = \"café\"\nport = 80".encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        fs::write(&utf16, bytes)?;
+        fs::write(&binary, b"\0\x01\x02")?;
+
+        let budget = MemoryBudget::new(1024);
+        let hash = |path: &Path| {
+            normalized_hash(
+                path,
+                HashAlgorithm::Blake3,
+                TextNormalization::default(),
+                &budget,
+            )
+        };
+        let expected = hash(&unix)?;
+        assert!(expected.is_some());
+        assert_eq!(hash(&windows)?, expected);
+        assert_eq!(hash(&utf16)?, expected);
+        assert_eq!(hash(&binary)?, None);
+        assert_eq!(budget.in_use(), 0);
+        Ok(())
+    }
+}
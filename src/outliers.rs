@@ -2,18 +2,56 @@
 //!
 //! This module provides functionality to detect various types of storage outliers:
 //! - Large files that are statistical outliers
-//! - Rapidly growing files and directories
+//! - Rapidly growing files and directories, by comparing with size
+//!   snapshots of earlier scans (see [`crate::growth`])
 //! - Common space-wasting patterns
 //! - Hidden space consumers
 //! - Sparse files and empty directories
-
-use crate::{walk_with_options, WalkOptions};
+//!
+//! Every file is measured twice: its apparent size (`metadata.len()`) and
+//! the disk space allocated to it (its block count, see [`allocated_size`]).
+//! Sparse VM images and database files have a large apparent size but few
+//! allocated blocks, so large files can be ranked by either
+//! ([`SizeMetric`]), and files whose holes are large are reported on their
+//! own ([`SparseFile`]).
+
+use crate::{walk_with_summary, RcleanResult, ScanDiagnostics, WalkOptions};
 use polars::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Smallest unallocated part (apparent minus allocated size) for a file to
+/// be reported as sparse.
+pub const SPARSE_MIN_HOLE_BYTES: u64 = 1024 * 1024;
+
+/// Which size of a file large-file outliers are ranked by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeMetric {
+    /// The length of the file, holes included
+    #[default]
+    Apparent,
+    /// The disk space actually allocated to the file
+    Allocated,
+}
+
+/// Disk space allocated to a file: its 512-byte block count on Unix, its
+/// length elsewhere.
+#[cfg(unix)]
+pub fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt as _;
+    metadata.blocks() * 512
+}
+
+/// Disk space allocated to a file: its 512-byte block count on Unix, its
+/// length elsewhere.
+#[cfg(not(unix))]
+pub fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
 /// Options for outlier detection
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutlierOptions {
@@ -25,16 +63,38 @@ pub struct OutlierOptions {
     pub std_dev_threshold: f64,
     /// Include hidden space consumers (node_modules, .git, etc.)
     pub check_hidden_consumers: bool,
-    /// Include empty directories in results
+    /// Report empty directories and ones holding only empty or junk files
+    /// (see [`crate::empty_dirs`]); `filter` does not apply to them
     pub include_empty_dirs: bool,
     /// Check for common patterns (logs, backups, etc.)
     pub check_patterns: bool,
+    /// Size that large files are ranked by; `min_size` applies to it too
+    #[serde(default)]
+    pub size_metric: SizeMetric,
+    /// Report sparse files (see [`SPARSE_MIN_HOLE_BYTES`])
+    #[serde(default)]
+    pub check_sparse: bool,
+    /// Compare with the size snapshots in this store, and record this scan
+    /// in it (see [`crate::growth`])
+    #[serde(default)]
+    pub snapshot_path: Option<PathBuf>,
+    /// Compare with the newest snapshot at least this many seconds old
+    /// rather than the newest one
+    #[serde(default)]
+    pub growth_since_secs: Option<u64>,
     /// Enable clustering of similar large files
     pub enable_clustering: bool,
     /// Minimum similarity percentage for clustering (50-100)
     pub cluster_similarity_threshold: u8,
     /// Minimum files to form a cluster
     pub min_cluster_size: usize,
+    /// Size, age, kind and type criteria selecting the files to analyze
+    #[serde(skip)]
+    pub filter: crate::filters::FileFilter,
+    /// Stops the walk and the metadata pass early; the partial report is
+    /// marked as interrupted in its diagnostics
+    #[serde(skip)]
+    pub cancel: crate::CancelToken,
 }
 
 impl Default for OutlierOptions {
@@ -46,26 +106,52 @@ impl Default for OutlierOptions {
             check_hidden_consumers: true,
             include_empty_dirs: false,
             check_patterns: true,
+            size_metric: SizeMetric::Apparent,
+            check_sparse: true,
+            snapshot_path: None,
+            growth_since_secs: None,
             enable_clustering: false,
             cluster_similarity_threshold: 70,
             min_cluster_size: 2,
+            filter: crate::filters::FileFilter::default(),
+            cancel: crate::CancelToken::default(),
         }
     }
 }
 
-/// Represents a file that is a statistical outlier by size
+/// Represents a file that is a statistical outlier by size.
+///
+/// `size_mb`, `percentage_of_total` and `std_devs_from_mean` are measured
+/// with the [`SizeMetric`] the outliers were ranked by.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LargeFileOutlier {
+    #[serde(with = "crate::paths::serde_path")]
     pub path: PathBuf,
     pub size_bytes: u64,
+    #[serde(default)]
+    pub allocated_bytes: u64,
     pub size_mb: f64,
     pub percentage_of_total: f64,
     pub std_devs_from_mean: f64,
 }
 
+/// A file occupying much less disk space than its length.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SparseFile {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    pub apparent_bytes: u64,
+    pub allocated_bytes: u64,
+    /// Apparent size not backed by allocated blocks
+    pub hole_bytes: u64,
+    /// Allocated size as a percentage of the apparent size
+    pub allocated_percent: f64,
+}
+
 /// Represents a known space consumer pattern
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HiddenConsumer {
+    #[serde(with = "crate::paths::serde_path")]
     pub path: PathBuf,
     pub pattern_type: String,
     pub total_size_bytes: u64,
@@ -79,6 +165,7 @@ pub struct PatternGroup {
     pub pattern: String,
     pub count: usize,
     pub total_size_bytes: u64,
+    #[serde(with = "crate::paths::serde_paths")]
     pub sample_files: Vec<PathBuf>,
 }
 
@@ -90,17 +177,47 @@ pub struct OutlierReport {
     pub pattern_groups: Vec<PatternGroup>,
     pub large_file_clusters: Vec<crate::clustering::LargeFileCluster>,
     pub total_size_analyzed: u64,
+    /// Disk space allocated to the analyzed files
+    #[serde(default)]
+    pub total_allocated_analyzed: u64,
     pub total_files_analyzed: usize,
+    /// Files with large holes, with [`OutlierOptions::check_sparse`]
+    #[serde(default)]
+    pub sparse_files: Vec<SparseFile>,
+    /// Growth since an earlier snapshot, with [`OutlierOptions::snapshot_path`]
+    /// once the store holds a snapshot of this path
+    #[serde(default)]
+    pub growth: Option<crate::growth::GrowthReport>,
+    /// Prunable directories, with [`OutlierOptions::include_empty_dirs`]
+    #[serde(default)]
+    pub empty_dirs: Vec<crate::empty_dirs::EmptyDir>,
+    /// Paths left out of the analysis
+    #[serde(default)]
+    pub diagnostics: ScanDiagnostics,
 }
 
 /// Simple file info structure that doesn't compute hashes
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SimpleFileInfo {
+    #[serde(with = "crate::paths::serde_path")]
     pub path: PathBuf,
     pub size_bytes: u64,
+    /// Disk space allocated to the file (see [`allocated_size`])
+    #[serde(default)]
+    pub allocated_bytes: u64,
     pub ssdeep_hash: Option<String>,
 }
 
+impl SimpleFileInfo {
+    /// Size of the file as measured by `metric`.
+    pub const fn size(&self, metric: SizeMetric) -> u64 {
+        match metric {
+            SizeMetric::Apparent => self.size_bytes,
+            SizeMetric::Allocated => self.allocated_bytes,
+        }
+    }
+}
+
 /// Known patterns that commonly consume space
 const HIDDEN_CONSUMER_PATTERNS: &[(&str, &str, &str)] = &[
     (
@@ -167,11 +284,34 @@ const HIDDEN_CONSUMER_PATTERNS: &[(&str, &str, &str)] = &[
 /// }
 /// ```
 pub fn detect_outliers(
-    path: &str,
+    path: impl AsRef<Path>,
     options: &OutlierOptions,
-) -> Result<OutlierReport, Box<dyn std::error::Error>> {
-    let walk_options = WalkOptions::default();
-    let files = walk_with_options(path, &walk_options)?;
+) -> RcleanResult<OutlierReport> {
+    let walk_options = WalkOptions {
+        cancel: options.cancel.clone(),
+        ..WalkOptions::default()
+    };
+    let summary = walk_with_summary(&path, &walk_options)?;
+    let mut files = summary.files;
+    let mut diagnostics = summary.diagnostics;
+
+    let empty_dirs = if options.include_empty_dirs {
+        let (empty_dirs, unread) =
+            crate::empty_dirs::find_empty_dirs(path.as_ref(), &options.cancel);
+        // The walk has already reported most unreadable directories
+        for skipped in unread.skipped {
+            if !diagnostics.skipped.iter().any(|s| s.path == skipped.path) {
+                diagnostics.skipped.push(skipped);
+            }
+        }
+        diagnostics.interrupted = diagnostics.interrupted.or(unread.interrupted);
+        empty_dirs
+    } else {
+        vec![]
+    };
+    if !options.filter.is_empty() {
+        files.retain(|file| options.filter.matches(file));
+    }
 
     if files.is_empty() {
         return Ok(OutlierReport {
@@ -180,39 +320,75 @@ pub fn detect_outliers(
             pattern_groups: vec![],
             large_file_clusters: vec![],
             total_size_analyzed: 0,
+            total_allocated_analyzed: 0,
             total_files_analyzed: 0,
+            sparse_files: vec![],
+            growth: None,
+            empty_dirs,
+            diagnostics,
         });
     }
 
     // Collect file information without hashing
-    let file_infos: Vec<SimpleFileInfo> = files
-        .iter()
-        .filter_map(|path_str| {
-            let path = Path::new(path_str);
-            fs::metadata(path).ok().map(|metadata| {
-                // Only compute SSDEEP hash for large files if clustering is enabled
-                let ssdeep_hash = if options.enable_clustering && metadata.len() >= 1024 * 1024 {
-                    fs::read(path)
-                        .ok()
-                        .and_then(|content| ssdeep::hash(&content).ok())
-                } else {
-                    None
-                };
-
-                SimpleFileInfo {
-                    path: path.to_path_buf(),
-                    size_bytes: metadata.len(),
-                    ssdeep_hash,
-                }
-            })
-        })
-        .collect();
+    let mut file_infos: Vec<SimpleFileInfo> = Vec::with_capacity(files.len());
+    for path in &files {
+        if options.cancel.is_cancelled() {
+            diagnostics.interrupt(&options.cancel);
+            break;
+        }
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                diagnostics.push_io(path, &e);
+                continue;
+            },
+        };
+        // Only compute SSDEEP hash for large files if clustering is enabled
+        let ssdeep_hash = if options.enable_clustering && metadata.len() >= 1024 * 1024 {
+            crate::hashing::fuzzy_hash_file(path)
+        } else {
+            None
+        };
+
+        file_infos.push(SimpleFileInfo {
+            path: path.to_path_buf(),
+            size_bytes: metadata.len(),
+            allocated_bytes: allocated_size(&metadata),
+            ssdeep_hash,
+        });
+    }
 
     let total_size: u64 = file_infos.iter().map(|f| f.size_bytes).sum();
+    let total_allocated: u64 = file_infos.iter().map(|f| f.allocated_bytes).sum();
     let total_files = file_infos.len();
 
     // Detect large file outliers
-    let large_files = detect_large_file_outliers(&file_infos, total_size, options);
+    let large_files = detect_large_file_outliers(&file_infos, options);
+
+    let sparse_files = if options.check_sparse {
+        detect_sparse_files(&file_infos, options.top_n)
+    } else {
+        vec![]
+    };
+
+    let growth = match &options.snapshot_path {
+        Some(store) => {
+            let snapshot = crate::growth::SizeSnapshot::from_files(
+                path.as_ref(),
+                file_infos.iter().map(|f| (f.path.as_path(), f.size_bytes)),
+                crate::cache::unix_now(),
+            );
+            crate::growth::track_growth(
+                store,
+                path.as_ref(),
+                snapshot,
+                options.growth_since_secs.unwrap_or(0),
+                options.top_n,
+                diagnostics.is_complete(),
+            )?
+        },
+        None => None,
+    };
 
     // Detect hidden consumers
     let hidden_consumers = if options.check_hidden_consumers {
@@ -255,13 +431,17 @@ pub fn detect_outliers(
         pattern_groups,
         large_file_clusters,
         total_size_analyzed: total_size,
+        total_allocated_analyzed: total_allocated,
         total_files_analyzed: total_files,
+        sparse_files,
+        growth,
+        empty_dirs,
+        diagnostics,
     })
 }
 
 fn detect_large_file_outliers(
     files: &[SimpleFileInfo],
-    total_size: u64,
     options: &OutlierOptions,
 ) -> Vec<LargeFileOutlier> {
     if files.is_empty() {
@@ -269,7 +449,9 @@ fn detect_large_file_outliers(
     }
 
     // Calculate statistics
-    let sizes: Vec<f64> = files.iter().map(|f| f.size_bytes as f64).collect();
+    let metric = options.size_metric;
+    let total_size: u64 = files.iter().map(|f| f.size(metric)).sum();
+    let sizes: Vec<f64> = files.iter().map(|f| f.size(metric) as f64).collect();
     let mean = sizes.iter().sum::<f64>() / sizes.len() as f64;
 
     // Calculate standard deviation
@@ -289,14 +471,15 @@ fn detect_large_file_outliers(
         .iter()
         .filter_map(|f| {
             // Apply min size filter if specified
+            let size = f.size(metric);
             if let Some(min_size) = options.min_size {
-                if f.size_bytes < min_size {
+                if size < min_size {
                     return None;
                 }
             }
 
             let z_score = if std_dev > 0.0 {
-                (f.size_bytes as f64 - mean) / std_dev
+                (size as f64 - mean) / std_dev
             } else {
                 0.0
             };
@@ -305,8 +488,9 @@ fn detect_large_file_outliers(
                 let outlier = LargeFileOutlier {
                     path: f.path.clone(),
                     size_bytes: f.size_bytes,
-                    size_mb: f.size_bytes as f64 / (1024.0 * 1024.0),
-                    percentage_of_total: (f.size_bytes as f64 / total_size as f64) * 100.0,
+                    allocated_bytes: f.allocated_bytes,
+                    size_mb: size as f64 / (1024.0 * 1024.0),
+                    percentage_of_total: (size as f64 / total_size as f64) * 100.0,
                     std_devs_from_mean: z_score,
                 };
                 Some(outlier)
@@ -317,7 +501,7 @@ fn detect_large_file_outliers(
         .collect();
 
     // Sort by size descending
-    outliers.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes));
+    outliers.sort_by(|a, b| b.size_mb.total_cmp(&a.size_mb));
 
     // Apply top_n limit if specified
     if let Some(top_n) = options.top_n {
@@ -327,7 +511,31 @@ fn detect_large_file_outliers(
     outliers
 }
 
-fn detect_hidden_consumers(paths: &[String], file_infos: &[SimpleFileInfo]) -> Vec<HiddenConsumer> {
+/// Files with at least [`SPARSE_MIN_HOLE_BYTES`] of holes, largest holes
+/// first. Compressed filesystems make files look sparse in the same way.
+fn detect_sparse_files(files: &[SimpleFileInfo], top_n: Option<usize>) -> Vec<SparseFile> {
+    let mut sparse: Vec<SparseFile> = files
+        .iter()
+        .filter(|f| f.size_bytes.saturating_sub(f.allocated_bytes) >= SPARSE_MIN_HOLE_BYTES)
+        .map(|f| SparseFile {
+            path: f.path.clone(),
+            apparent_bytes: f.size_bytes,
+            allocated_bytes: f.allocated_bytes,
+            hole_bytes: f.size_bytes - f.allocated_bytes,
+            allocated_percent: f.allocated_bytes as f64 / f.size_bytes as f64 * 100.0,
+        })
+        .collect();
+    sparse.sort_by_key(|f| std::cmp::Reverse(f.hole_bytes));
+    if let Some(top_n) = top_n {
+        sparse.truncate(top_n);
+    }
+    sparse
+}
+
+fn detect_hidden_consumers(
+    paths: &[PathBuf],
+    file_infos: &[SimpleFileInfo],
+) -> Vec<HiddenConsumer> {
     let mut consumers = Vec::new();
     let mut path_to_info: HashMap<&Path, &SimpleFileInfo> = HashMap::new();
 
@@ -336,9 +544,9 @@ fn detect_hidden_consumers(paths: &[String], file_infos: &[SimpleFileInfo]) -> V
     }
 
     // Group paths by directory
-    let mut dir_contents: HashMap<PathBuf, Vec<&str>> = HashMap::new();
+    let mut dir_contents: HashMap<PathBuf, Vec<&Path>> = HashMap::new();
     for path in paths {
-        if let Some(parent) = Path::new(path).parent() {
+        if let Some(parent) = path.parent() {
             dir_contents
                 .entry(parent.to_path_buf())
                 .or_default()
@@ -356,8 +564,7 @@ fn detect_hidden_consumers(paths: &[String], file_infos: &[SimpleFileInfo]) -> V
                 let mut total_size = 0u64;
                 let mut file_count = 0;
 
-                for path_str in &contents {
-                    let path = Path::new(path_str);
+                for &path in &contents {
                     if path.starts_with(&dir) {
                         if let Some(info) = path_to_info.get(path) {
                             total_size += info.size_bytes;
@@ -381,7 +588,7 @@ fn detect_hidden_consumers(paths: &[String], file_infos: &[SimpleFileInfo]) -> V
     }
 
     // Sort by size descending
-    consumers.sort_by(|a, b| b.total_size_bytes.cmp(&a.total_size_bytes));
+    consumers.sort_by_key(|c| std::cmp::Reverse(c.total_size_bytes));
     consumers
 }
 
@@ -425,7 +632,7 @@ fn detect_pattern_groups(files: &[SimpleFileInfo]) -> Vec<PatternGroup> {
         .collect();
 
     // Sort by total size descending
-    groups.sort_by(|a, b| b.total_size_bytes.cmp(&a.total_size_bytes));
+    groups.sort_by_key(|g| std::cmp::Reverse(g.total_size_bytes));
     groups
 }
 
@@ -473,7 +680,12 @@ pub fn outliers_to_dataframe(report: &OutlierReport) -> Result<DataFrame, Polars
     let file_paths: Vec<String> = report
         .large_files
         .iter()
-        .map(|f| f.path.to_string_lossy().to_string())
+        .map(|f| crate::paths::display(&f.path).into_owned())
+        .collect();
+    let path_hex: Vec<Option<String>> = report
+        .large_files
+        .iter()
+        .map(|f| crate::paths::to_hex(&f.path))
         .collect();
 
     let size_mb: Vec<f64> = report.large_files.iter().map(|f| f.size_mb).collect();
@@ -492,6 +704,7 @@ pub fn outliers_to_dataframe(report: &OutlierReport) -> Result<DataFrame, Polars
 
     let df = DataFrame::new(vec![
         Series::new("file_path", file_paths),
+        Series::new(crate::paths::HEX_COLUMN, path_hex),
         Series::new("size_mb", size_mb),
         Series::new("percentage_of_total", percentage),
         Series::new("std_devs_from_mean", std_devs),
@@ -504,6 +717,38 @@ pub fn outliers_to_dataframe(report: &OutlierReport) -> Result<DataFrame, Polars
 mod tests {
     use super::*;
 
+    fn info(path: &str, size_bytes: u64, allocated_bytes: u64) -> SimpleFileInfo {
+        SimpleFileInfo {
+            path: PathBuf::from(path),
+            size_bytes,
+            allocated_bytes,
+            ssdeep_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_detect_sparse_files() {
+        const MB: u64 = 1024 * 1024;
+        let files = [
+            info("dense.bin", 8 * MB, 8 * MB),
+            info("vm.img", 100 * MB, 25 * MB),
+            info("db.sqlite", 10 * MB, 0),
+            info("almost.bin", 4 * MB, 4 * MB - 4096),
+            info("compressed.bin", MB, 2 * MB),
+        ];
+
+        let sparse = detect_sparse_files(&files, None);
+        let paths: Vec<_> = sparse.iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(paths, ["vm.img", "db.sqlite"]);
+        assert_eq!(sparse[0].hole_bytes, 75 * MB);
+        assert_eq!(sparse[0].allocated_percent, 25.0);
+        assert_eq!(sparse[1].allocated_percent, 0.0);
+        assert_eq!(detect_sparse_files(&files, Some(1)).len(), 1);
+
+        assert_eq!(files[1].size(SizeMetric::Allocated), 25 * MB);
+        assert_eq!(files[1].size(SizeMetric::Apparent), 100 * MB);
+    }
+
     #[test]
     fn test_detect_numbered_pattern() {
         assert_eq!(
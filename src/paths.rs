@@ -0,0 +1,240 @@
+//! Lossless handling of paths that are not valid UTF-8.
+//!
+//! Paths travel through the library as [`PathBuf`]s, so files with Latin-1
+//! or other non-UTF-8 names are walked, hashed and resolved like any other.
+//! Where a path has to become text, two encodings are used:
+//!
+//! - [`display`] is the escape convention for human-readable output: valid
+//!   UTF-8 is kept as is and every other byte is written as `\xNN`.
+//! - [`to_hex`] encodes the raw bytes losslessly. Dedupe and outlier
+//!   `DataFrame`s (and so their CSV reports) carry it in a [`HEX_COLUMN`]
+//!   that is only set for non-UTF-8 paths, and JSON and TOML documents write
+//!   such paths as `{ display, bytes_hex }` instead of a plain string (see
+//!   [`serde_path`] and [`to_json`]).
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Name of the column holding the hex-encoded bytes of non-UTF-8 paths.
+pub const HEX_COLUMN: &str = "file_path_hex";
+
+/// Raw bytes of `path` (its platform encoding; plain bytes on Unix).
+pub fn bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_encoded_bytes()
+}
+
+/// `path` as text, with every byte that is not part of valid UTF-8 escaped
+/// as `\xNN`.
+pub fn display(path: &Path) -> Cow<'_, str> {
+    if let Some(text) = path.to_str() {
+        return Cow::Borrowed(text);
+    }
+    let mut text = String::new();
+    for chunk in bytes(path).utf8_chunks() {
+        text.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            let _ = write!(text, "\\x{byte:02X}");
+        }
+    }
+    Cow::Owned(text)
+}
+
+/// Hex encoding of the bytes of `path`, or `None` if it is valid UTF-8 and
+/// [`display`] already represents it exactly.
+pub fn to_hex(path: &Path) -> Option<String> {
+    if path.to_str().is_some() {
+        return None;
+    }
+    let mut hex = String::with_capacity(bytes(path).len() * 2);
+    for byte in bytes(path) {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    Some(hex)
+}
+
+/// Decode a path written by [`to_hex`].
+pub fn from_hex(hex: &str) -> Option<PathBuf> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(from_bytes(bytes))
+}
+
+#[cfg(unix)]
+fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt as _;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Serialized form of a path: a plain string, or both encodings when the
+/// path is not valid UTF-8.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Encoded<'a> {
+    Text(Cow<'a, str>),
+    Bytes {
+        display: Cow<'a, str>,
+        bytes_hex: String,
+    },
+}
+
+impl<'a> Encoded<'a> {
+    fn new(path: &'a Path) -> Self {
+        match to_hex(path) {
+            None => Self::Text(display(path)),
+            Some(bytes_hex) => Self::Bytes {
+                display: display(path),
+                bytes_hex,
+            },
+        }
+    }
+
+    fn into_path(self) -> Option<PathBuf> {
+        match self {
+            Self::Text(text) => Some(PathBuf::from(text.into_owned())),
+            Self::Bytes { bytes_hex, .. } => from_hex(&bytes_hex),
+        }
+    }
+}
+
+/// A path read back from a `DataFrame` row: its [`HEX_COLUMN`] value when
+/// set, else its [`display`] text.
+pub fn from_column(display: &str, hex: Option<&str>) -> PathBuf {
+    hex.and_then(from_hex)
+        .unwrap_or_else(|| PathBuf::from(display))
+}
+
+/// `path` as a JSON value: a string, or `{ "display", "bytes_hex" }` if it
+/// is not valid UTF-8.
+pub fn to_json(path: &Path) -> serde_json::Value {
+    serde_json::to_value(Encoded::new(path)).unwrap_or_default()
+}
+
+/// Read a path written by [`to_json`].
+pub fn from_json(value: &serde_json::Value) -> Option<PathBuf> {
+    Encoded::deserialize(value).ok()?.into_path()
+}
+
+/// `#[serde(with = "crate::paths::serde_path")]` for `PathBuf` fields, using
+/// the encoding of [`to_json`].
+pub mod serde_path {
+    use super::Encoded;
+    use serde::{de, Deserialize as _, Deserializer, Serialize as _, Serializer};
+    use std::path::{Path, PathBuf};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        Encoded::new(path).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Encoded::deserialize(deserializer)?
+            .into_path()
+            .ok_or_else(|| de::Error::custom("invalid bytes_hex in path"))
+    }
+}
+
+/// `#[serde(with = "crate::paths::serde_paths")]` for `Vec<PathBuf>` fields.
+pub mod serde_paths {
+    use super::Encoded;
+    use serde::{de, Deserialize as _, Deserializer, Serializer};
+    use std::path::PathBuf;
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| Encoded::new(path)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PathBuf>, D::Error> {
+        Vec::<Encoded>::deserialize(deserializer)?
+            .into_iter()
+            .map(|encoded| {
+                encoded
+                    .into_path()
+                    .ok_or_else(|| de::Error::custom("invalid bytes_hex in path"))
+            })
+            .collect()
+    }
+}
+
+/// `#[serde(default, with = "crate::paths::serde_opt_path")]` for
+/// `Option<PathBuf>` fields.
+pub mod serde_opt_path {
+    use super::Encoded;
+    use serde::{de, Deserialize as _, Deserializer, Serializer};
+    use std::path::PathBuf;
+
+    pub fn serialize<S: Serializer>(
+        path: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match path {
+            Some(path) => serializer.serialize_some(&Encoded::new(path)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        Option::<Encoded>::deserialize(deserializer)?
+            .map(|encoded| {
+                encoded
+                    .into_path()
+                    .ok_or_else(|| de::Error::custom("invalid bytes_hex in path"))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn latin1() -> PathBuf {
+        from_bytes(b"share/caf\xe9.txt".to_vec())
+    }
+
+    #[test]
+    fn test_utf8_paths_are_unchanged() {
+        let path = Path::new("photos/été.jpg");
+        assert_eq!(display(path), "photos/été.jpg");
+        assert_eq!(to_hex(path), None);
+        assert_eq!(to_json(path), serde_json::json!("photos/été.jpg"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths_round_trip() {
+        let path = latin1();
+        assert_eq!(display(&path), "share/caf\\xE9.txt");
+        let hex = to_hex(&path).unwrap();
+        assert_eq!(hex, "73686172652f636166e92e747874");
+        assert_eq!(from_hex(&hex), Some(path.clone()));
+        assert_eq!(from_json(&to_json(&path)), Some(path.clone()));
+
+        #[derive(Serialize, Deserialize)]
+        struct Entry {
+            #[serde(with = "serde_path")]
+            path: PathBuf,
+        }
+        let json = serde_json::to_string(&Entry { path: path.clone() }).unwrap();
+        assert!(json.contains("\"bytes_hex\""));
+        let entry: Entry = serde_json::from_str(&json).unwrap();
+        assert_eq!(entry.path, path);
+        let entry: Entry = toml::from_str(&toml::to_string(&entry).unwrap()).unwrap();
+        assert_eq!(entry.path, path);
+    }
+}
@@ -0,0 +1,220 @@
+//! Composite include/exclude pattern sets.
+//!
+//! A [`PatternSet`] combines any number of include and exclude patterns of
+//! mixed kinds. A file is selected when it matches at least one include
+//! pattern (or there are none) and no exclude pattern. Patterns are written
+//! as `kind:pattern`, e.g. `glob:*.rs` or `regex:^target/`; without a prefix
+//! the set's default kind applies. Matching is against the full path or only
+//! the file name, see [`MatchTarget`].
+
+use crate::PatternType;
+use globset::{Glob, GlobSetBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Error types for pattern construction
+#[derive(Debug, Error)]
+pub enum PatternError {
+    #[error("Invalid glob pattern: {0}")]
+    Glob(#[from] globset::Error),
+
+    #[error("Invalid regex pattern: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("Failed to read pattern file {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Result type for pattern construction
+pub type PatternResult<T> = Result<T, PatternError>;
+
+/// How a pattern without a `kind:` prefix is interpreted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternKind {
+    /// Substring match
+    #[default]
+    Literal,
+    /// Glob pattern (e.g., *.txt, **/*.rs)
+    Glob,
+    /// Regular expression
+    Regex,
+}
+
+impl PatternKind {
+    /// Compile `pattern` as this kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pattern` is not a valid glob or regex.
+    pub fn compile(self, pattern: &str) -> PatternResult<PatternType> {
+        Ok(match self {
+            Self::Literal => PatternType::Literal(pattern.to_string()),
+            Self::Glob => {
+                let mut builder = GlobSetBuilder::new();
+                builder.add(Glob::new(pattern)?);
+                PatternType::Glob(builder.build()?)
+            },
+            Self::Regex => PatternType::Regex(Regex::new(pattern)?),
+        })
+    }
+}
+
+/// Which part of a path the patterns of a set are matched against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchTarget {
+    /// The full path as walked, e.g. `src/lib.rs`
+    #[default]
+    Path,
+    /// Only the file name, e.g. `lib.rs`
+    Basename,
+}
+
+impl MatchTarget {
+    fn select(self, path: &str) -> &str {
+        match self {
+            Self::Path => path,
+            Self::Basename => Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(path),
+        }
+    }
+}
+
+/// Include and exclude patterns evaluated together.
+#[derive(Debug, Clone, Default)]
+pub struct PatternSet {
+    pub include: Vec<PatternType>,
+    pub exclude: Vec<PatternType>,
+    pub target: MatchTarget,
+}
+
+impl PatternSet {
+    /// An empty set matching every path by `target`.
+    pub fn new(target: MatchTarget) -> Self {
+        Self {
+            target,
+            ..Self::default()
+        }
+    }
+
+    /// Add an include pattern written as `[kind:]pattern`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern does not compile.
+    pub fn include(&mut self, spec: &str, default: PatternKind) -> PatternResult<&mut Self> {
+        self.include.push(parse_pattern(spec, default)?);
+        Ok(self)
+    }
+
+    /// Add an exclude pattern written as `[kind:]pattern`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern does not compile.
+    pub fn exclude(&mut self, spec: &str, default: PatternKind) -> PatternResult<&mut Self> {
+        self.exclude.push(parse_pattern(spec, default)?);
+        Ok(self)
+    }
+
+    /// Add every exclude pattern listed in `path`, one per line. Blank lines
+    /// and lines starting with `#` are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or a pattern does not
+    /// compile.
+    pub fn exclude_from(
+        &mut self,
+        path: impl AsRef<Path>,
+        default: PatternKind,
+    ) -> PatternResult<&mut Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| PatternError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        for line in contents.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') {
+                self.exclude(line, default)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Whether `path` matches an include pattern (or there are none) and no
+    /// exclude pattern.
+    pub fn is_match(&self, path: &str) -> bool {
+        let text = self.target.select(path);
+        (self.include.is_empty() || self.include.iter().any(|p| p.is_match(text)))
+            && !self.exclude.iter().any(|p| p.is_match(text))
+    }
+}
+
+/// Compile a pattern written as `literal:`, `glob:` or `regex:` followed by
+/// the pattern, or a bare pattern of kind `default`.
+///
+/// # Errors
+///
+/// Returns an error if the pattern does not compile.
+pub fn parse_pattern(spec: &str, default: PatternKind) -> PatternResult<PatternType> {
+    let (kind, pattern) = match spec.split_once(':') {
+        Some(("literal", pattern)) => (PatternKind::Literal, pattern),
+        Some(("glob", pattern)) => (PatternKind::Glob, pattern),
+        Some(("regex", pattern)) => (PatternKind::Regex, pattern),
+        _ => (default, spec),
+    };
+    kind.compile(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_mixed_include_and_exclude() -> PatternResult<()> {
+        let mut set = PatternSet::new(MatchTarget::Path);
+        set.include("glob:**/*.rs", PatternKind::Literal)?
+            .include("README", PatternKind::Literal)?
+            .exclude("regex:^target/", PatternKind::Literal)?;
+
+        assert!(set.is_match("src/lib.rs"));
+        assert!(set.is_match("README.md"));
+        assert!(!set.is_match("target/debug/build.rs"));
+        assert!(!set.is_match("Cargo.toml"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_basename_target_and_exclude_from() -> PatternResult<()> {
+        let dir = TempDir::new().unwrap();
+        let excludes = dir.path().join("excludes");
+        fs::write(&excludes, "# build output\n\n*.o\nregex:^tmp\n").unwrap();
+
+        let mut set = PatternSet::new(MatchTarget::Basename);
+        set.exclude_from(&excludes, PatternKind::Glob)?;
+        assert!(set.is_match("tmpdir/main.c"));
+        assert!(!set.is_match("src/main.o"));
+        assert!(!set.is_match("src/tmp.c"));
+
+        let mut full_path = PatternSet::new(MatchTarget::Path);
+        full_path.exclude("regex:^tmp", PatternKind::Literal)?;
+        assert!(!full_path.is_match("tmpdir/main.c"));
+        assert!(full_path.is_match("src/tmp.c"));
+
+        assert!(PatternSet::default()
+            .exclude_from(dir.path().join("missing"), PatternKind::Glob)
+            .is_err());
+        Ok(())
+    }
+}
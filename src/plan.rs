@@ -0,0 +1,550 @@
+//! Reviewable two-phase duplicate resolution.
+//!
+//! [`build_plan`] records every duplicate group with the file to keep and the
+//! action for each other member, together with the size, modification time
+//! and content hash seen at planning time. The plan is written as JSON or TOML
+//! (chosen by file extension) so it can be reviewed and edited by hand.
+//! [`apply_plan`] re-verifies every file against those fingerprints before
+//! acting and refuses entries that changed since the plan was written.
+
+use crate::cache::{CacheKey, HashCache};
+use crate::hashing::{self, HashAlgorithm};
+use crate::paths::display;
+use crate::resolve::{self, KeepPolicy, RemovalOutcome, ResolveAction};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Current plan file format version.
+pub const PLAN_VERSION: u32 = 1;
+
+/// Error types for plan files
+#[derive(Debug, Error)]
+pub enum PlanError {
+    #[error("I/O error on {0}: {1}")]
+    Io(PathBuf, io::Error),
+
+    #[error("Invalid JSON plan: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid TOML plan: {0}")]
+    TomlRead(#[from] toml::de::Error),
+
+    #[error("Cannot write TOML plan: {0}")]
+    TomlWrite(#[from] toml::ser::Error),
+
+    #[error("Unsupported plan format for {0}; use a .json or .toml extension")]
+    UnknownFormat(PathBuf),
+
+    #[error("Unsupported plan version {0} (expected {PLAN_VERSION})")]
+    Version(u32),
+}
+
+/// Result type for plan operations
+pub type PlanResult<T> = Result<T, PlanError>;
+
+/// On-disk format of a plan file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanFormat {
+    Json,
+    Toml,
+}
+
+impl PlanFormat {
+    /// Format implied by the extension of `path`.
+    pub fn from_path(path: &Path) -> PlanResult<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(Self::Json),
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Ok(Self::Toml),
+            _ => Err(PlanError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+}
+
+/// What to do with one non-kept member of a group.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    /// Leave the file alone
+    Keep,
+    #[default]
+    Delete,
+    Hardlink,
+    Symlink,
+    Quarantine,
+}
+
+impl PlanAction {
+    /// The resolve action to perform, or `None` for [`PlanAction::Keep`].
+    pub const fn resolve_action(self) -> Option<ResolveAction> {
+        match self {
+            Self::Keep => None,
+            Self::Delete => Some(ResolveAction::Delete),
+            Self::Hardlink => Some(ResolveAction::Hardlink),
+            Self::Symlink => Some(ResolveAction::Symlink),
+            Self::Quarantine => Some(ResolveAction::Quarantine),
+        }
+    }
+}
+
+impl From<ResolveAction> for PlanAction {
+    fn from(action: ResolveAction) -> Self {
+        match action {
+            ResolveAction::Delete => Self::Delete,
+            ResolveAction::Hardlink => Self::Hardlink,
+            ResolveAction::Symlink => Self::Symlink,
+            ResolveAction::Quarantine => Self::Quarantine,
+        }
+    }
+}
+
+/// The kept file of a group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanKeep {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime_ns: i64,
+}
+
+/// A non-kept member of a group and what to do with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime_ns: i64,
+    pub action: PlanAction,
+}
+
+/// One duplicate group; all members share `size_bytes` and `hash`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanGroup {
+    pub hash: String,
+    pub size_bytes: u64,
+    pub keep: PlanKeep,
+    pub files: Vec<PlanEntry>,
+}
+
+/// A reviewable list of actions for a set of duplicate groups.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub version: u32,
+    /// Creation time in seconds since the Unix epoch
+    pub created: u64,
+    pub algorithm: HashAlgorithm,
+    /// Keep policy the plan was built with (informational)
+    pub policy: String,
+    #[serde(default)]
+    pub groups: Vec<PlanGroup>,
+}
+
+impl Plan {
+    /// Serialize the plan in `format`.
+    pub fn to_string(&self, format: PlanFormat) -> PlanResult<String> {
+        Ok(match format {
+            PlanFormat::Json => serde_json::to_string_pretty(self)?,
+            PlanFormat::Toml => toml::to_string_pretty(self)?,
+        })
+    }
+
+    /// Parse a plan in `format`, rejecting unknown versions.
+    pub fn parse(text: &str, format: PlanFormat) -> PlanResult<Self> {
+        let plan: Self = match format {
+            PlanFormat::Json => serde_json::from_str(text)?,
+            PlanFormat::Toml => toml::from_str(text)?,
+        };
+        if plan.version != PLAN_VERSION {
+            return Err(PlanError::Version(plan.version));
+        }
+        Ok(plan)
+    }
+
+    /// Write the plan to `path`, in the format implied by its extension.
+    pub fn save(&self, path: &Path) -> PlanResult<()> {
+        let text = self.to_string(PlanFormat::from_path(path)?)?;
+        fs::write(path, text).map_err(|e| PlanError::Io(path.to_path_buf(), e))
+    }
+
+    /// Read a plan from `path`, in the format implied by its extension.
+    pub fn load(path: &Path) -> PlanResult<Self> {
+        let format = PlanFormat::from_path(path)?;
+        let text = fs::read_to_string(path).map_err(|e| PlanError::Io(path.to_path_buf(), e))?;
+        Self::parse(&text, format)
+    }
+
+    /// Number of entries that will be acted on.
+    pub fn action_count(&self) -> usize {
+        self.groups
+            .iter()
+            .flat_map(|g| &g.files)
+            .filter(|f| f.action != PlanAction::Keep)
+            .count()
+    }
+}
+
+/// Size, modification time and content hash of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub size_bytes: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime_ns: i64,
+    pub hash: String,
+}
+
+fn mtime_ns(metadata: &fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| i64::try_from(d.as_nanos()).unwrap_or(i64::MAX))
+}
+
+/// Fingerprint `path`, taking the hash from `cache` when the file is unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be stat'ed or hashed.
+pub fn fingerprint(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    cache: Option<&HashCache>,
+) -> io::Result<Fingerprint> {
+    let metadata = fs::metadata(path)?;
+    let cached = cache.zip(CacheKey::from_metadata(&metadata));
+    let hash = match cached.and_then(|(cache, key)| cache.full_hash(&key, algorithm)) {
+        Some(hash) => hash,
+        None => hashing::hash_file(path, algorithm)?.0,
+    };
+    Ok(Fingerprint {
+        size_bytes: metadata.len(),
+        mtime_ns: mtime_ns(&metadata),
+        hash,
+    })
+}
+
+/// Why `path` no longer matches the `expected` fingerprint, if it does not.
+///
+/// # Errors
+///
+/// Returns a description of the first difference: not a regular file any
+/// more, another size or mtime, or other content.
+pub fn verify(path: &Path, expected: &Fingerprint, algorithm: HashAlgorithm) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| format!("cannot read: {e}"))?;
+    if !metadata.is_file() {
+        return Err("no longer a regular file".to_string());
+    }
+    if metadata.len() != expected.size_bytes {
+        return Err(format!(
+            "size changed from {} to {} bytes",
+            expected.size_bytes,
+            metadata.len()
+        ));
+    }
+    if mtime_ns(&metadata) != expected.mtime_ns {
+        return Err("modified in the meantime".to_string());
+    }
+    let (hash, _) = hashing::hash_file(path, algorithm).map_err(|e| format!("cannot hash: {e}"))?;
+    if hash != expected.hash {
+        return Err("content changed in the meantime".to_string());
+    }
+    Ok(())
+}
+
+/// Build a plan for duplicate groups (as returned by [`crate::find_duplicates`]).
+///
+/// Hashes come from `cache` where possible. Members whose content no longer
+/// matches the keeper are left out of the plan, and files under the
+/// `references` directories are only ever kept.
+pub fn build_plan(
+    groups: &[Vec<PathBuf>],
+    policy: &KeepPolicy,
+    references: &[PathBuf],
+    action: ResolveAction,
+    algorithm: HashAlgorithm,
+    cache: Option<&HashCache>,
+) -> Plan {
+    let groups = resolve::plan_resolution_with_references(groups, policy, references)
+        .into_iter()
+        .filter_map(|resolution| {
+            let keep = fingerprint(&resolution.keep, algorithm, cache).ok()?;
+            let files: Vec<PlanEntry> = resolution
+                .remove
+                .iter()
+                .filter_map(|path| {
+                    let print = fingerprint(path, algorithm, cache).ok()?;
+                    (print.hash == keep.hash && print.size_bytes == keep.size_bytes).then(|| {
+                        PlanEntry {
+                            path: path.clone(),
+                            mtime_ns: print.mtime_ns,
+                            action: action.into(),
+                        }
+                    })
+                })
+                .collect();
+            (!files.is_empty()).then_some(PlanGroup {
+                hash: keep.hash,
+                size_bytes: keep.size_bytes,
+                keep: PlanKeep {
+                    path: resolution.keep,
+                    mtime_ns: keep.mtime_ns,
+                },
+                files,
+            })
+        })
+        .collect();
+
+    Plan {
+        version: PLAN_VERSION,
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        algorithm,
+        policy: policy.to_string(),
+        groups,
+    }
+}
+
+/// Options controlling how a plan is applied.
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    /// Only verify the plan and report what would be done
+    pub dry_run: bool,
+    /// Quarantine root for [`PlanAction::Quarantine`] entries; defaults to
+    /// [`crate::quarantine::default_root`].
+    pub quarantine_dir: Option<PathBuf>,
+}
+
+/// Result of applying (or verifying) a plan.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyReport {
+    pub dry_run: bool,
+    pub groups: usize,
+    pub outcomes: Vec<RemovalOutcome>,
+    /// Manifest of the quarantine session, if files were quarantined
+    #[serde(default, with = "crate::paths::serde_opt_path")]
+    pub quarantine_manifest: Option<PathBuf>,
+}
+
+impl ApplyReport {
+    /// Bytes freed (or that would be freed) by successful actions.
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.outcomes
+            .iter()
+            .filter(|o| o.error.is_none())
+            .map(|o| o.size_bytes)
+            .sum()
+    }
+
+    /// Entries that were refused or failed.
+    pub fn refused(&self) -> impl Iterator<Item = &RemovalOutcome> {
+        self.outcomes.iter().filter(|o| o.error.is_some())
+    }
+}
+
+impl fmt::Display for ApplyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for outcome in &self.outcomes {
+            match &outcome.error {
+                None => writeln!(
+                    f,
+                    "{}: {} (keeping {})",
+                    outcome.action.verb(self.dry_run),
+                    display(&outcome.path),
+                    display(&outcome.kept)
+                )?,
+                Some(error) => writeln!(f, "Refused: {} ({error})", display(&outcome.path))?,
+            }
+        }
+        let refused = self.refused().count();
+        write!(
+            f,
+            "{} {} of {} actions in {} groups, {} bytes; {refused} refused",
+            if self.dry_run { "Verified" } else { "Applied" },
+            self.outcomes.len() - refused,
+            self.outcomes.len(),
+            self.groups,
+            self.bytes_reclaimed()
+        )?;
+        if let Some(manifest) = &self.quarantine_manifest {
+            write!(f, "\nQuarantine manifest: {}", display(manifest))?;
+        }
+        Ok(())
+    }
+}
+
+/// Re-verify every entry of `plan` and perform its action, or only verify in
+/// a dry run.
+///
+/// An entry is refused if it or its group's kept file changed size, mtime or
+/// content since the plan was written, or if it resolves to the kept file.
+/// Entries marked [`PlanAction::Keep`] are skipped.
+pub fn apply_plan(plan: &Plan, options: &ApplyOptions) -> ApplyReport {
+    let mut report = ApplyReport {
+        dry_run: options.dry_run,
+        groups: plan.groups.len(),
+        outcomes: Vec::new(),
+        quarantine_manifest: None,
+    };
+    let mut executor = resolve::Executor::new(options.quarantine_dir.as_deref());
+
+    for group in &plan.groups {
+        let expected = |mtime_ns| Fingerprint {
+            size_bytes: group.size_bytes,
+            mtime_ns,
+            hash: group.hash.clone(),
+        };
+        let keeper = verify(
+            &group.keep.path,
+            &expected(group.keep.mtime_ns),
+            plan.algorithm,
+        )
+        .map_err(|e| format!("kept copy {} {e}", display(&group.keep.path)));
+        let keep = group.keep.path.as_path();
+
+        for entry in &group.files {
+            let Some(action) = entry.action.resolve_action() else {
+                continue;
+            };
+            let error = if let Err(e) = &keeper {
+                Some(e.clone())
+            } else if let Err(e) = verify(&entry.path, &expected(entry.mtime_ns), plan.algorithm) {
+                Some(e)
+            } else if options.dry_run {
+                resolve::check_not_keeper(keep, &entry.path).err()
+            } else {
+                let hash = Some((plan.algorithm, group.hash.as_str()));
+                executor.perform(action, keep, &entry.path, hash).err()
+            };
+            report.outcomes.push(RemovalOutcome {
+                path: entry.path.clone(),
+                kept: group.keep.path.clone(),
+                size_bytes: group.size_bytes,
+                action,
+                error,
+            });
+        }
+    }
+
+    report.quarantine_manifest = executor.quarantine_manifest();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn group(dir: &TempDir) -> Vec<PathBuf> {
+        ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                fs::write(&path, "duplicate").unwrap();
+                path
+            })
+            .collect()
+    }
+
+    fn plan(dir: &TempDir) -> Plan {
+        build_plan(
+            &[group(dir)],
+            &KeepPolicy::ShortestPath,
+            &[],
+            ResolveAction::Delete,
+            HashAlgorithm::Md5,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_plan_round_trips_through_json_and_toml() {
+        let dir = TempDir::new().unwrap();
+        let plan = plan(&dir);
+        assert_eq!(plan.groups.len(), 1);
+        assert_eq!(plan.action_count(), 2);
+
+        for format in [PlanFormat::Json, PlanFormat::Toml] {
+            let text = plan.to_string(format).unwrap();
+            assert_eq!(Plan::parse(&text, format).unwrap(), plan);
+        }
+        assert!(matches!(
+            PlanFormat::from_path(Path::new("plan.yaml")),
+            Err(PlanError::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_refuses_changed_entries() {
+        let dir = TempDir::new().unwrap();
+        let mut plan = plan(&dir);
+        let changed = plan.groups[0].files[0].path.clone();
+        let untouched = plan.groups[0].files[1].path.clone();
+        fs::write(&changed, "different").unwrap();
+
+        let verify_only = ApplyOptions {
+            dry_run: true,
+            ..ApplyOptions::default()
+        };
+        let report = apply_plan(&plan, &verify_only);
+        assert_eq!(report.refused().count(), 1);
+        assert!(untouched.exists());
+
+        plan.groups[0].files[0].action = PlanAction::Keep;
+        let report = apply_plan(&plan, &ApplyOptions::default());
+        assert_eq!(report.refused().count(), 0);
+        assert_eq!(report.outcomes.len(), 1);
+        assert!(changed.exists());
+        assert!(!untouched.exists());
+        assert!(plan.groups[0].keep.path.exists());
+    }
+
+    #[test]
+    fn test_changed_keeper_refuses_whole_group() {
+        let dir = TempDir::new().unwrap();
+        let plan = plan(&dir);
+        fs::remove_file(&plan.groups[0].keep.path).unwrap();
+
+        let report = apply_plan(&plan, &ApplyOptions::default());
+        assert_eq!(report.refused().count(), 2);
+        assert!(plan.groups[0].files.iter().all(|f| f.path.exists()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_refuses_aliases_of_keeper() {
+        let dir = TempDir::new().unwrap();
+        let mut plan = plan(&dir);
+        let keep = plan.groups[0].keep.path.clone();
+        let name = keep.file_name().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("parent")).unwrap();
+        let hardlink = dir.path().join("hardlink.txt");
+        fs::hard_link(&keep, &hardlink).unwrap();
+
+        let entry = plan.groups[0].files[0].clone();
+        plan.groups[0].files = [
+            dir.path().join(".").join(name),
+            dir.path().join("sub").join("..").join(name),
+            dir.path().join("parent").join(name),
+            hardlink,
+        ]
+        .into_iter()
+        .map(|path| PlanEntry {
+            path,
+            ..entry.clone()
+        })
+        .collect();
+
+        let report = apply_plan(&plan, &ApplyOptions::default());
+        assert_eq!(report.refused().count(), 4);
+        assert!(report
+            .refused()
+            .all(|o| o.error.as_deref() == Some("entry is the kept file")));
+        assert_eq!(fs::read_to_string(&keep).unwrap(), "duplicate");
+    }
+}
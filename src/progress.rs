@@ -0,0 +1,190 @@
+//! Progress reporting for long-running scans.
+//!
+//! The library never writes to stdout or draws progress bars itself: hashing
+//! reports its progress to a [`ProgressSink`]. [`NoProgress`] is used unless
+//! the caller passes another sink, and the CLI passes an [`IndicatifProgress`].
+//! Keeping stdout free matters for the MCP server, which uses it as its
+//! transport.
+
+use crate::hashing::HashAlgorithm;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A stage of work reported to a [`ProgressSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Hashing the first and last blocks of same-size files
+    PartialHash,
+    /// Hashing the full content of the remaining candidates
+    FullHash(HashAlgorithm),
+    /// Hashing every file in full, plus a fuzzy hash for similarity detection
+    FuzzyHash(HashAlgorithm),
+    /// Reading and hashing the members of archives
+    ArchiveMembers,
+    /// Hashing the normalized text of text files
+    NormalizedHash,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PartialHash => write!(f, "Hashing file edges..."),
+            Self::FullHash(algorithm) => write!(f, "Computing {algorithm} hashes..."),
+            Self::FuzzyHash(algorithm) => write!(f, "Computing {algorithm} and fuzzy hashes..."),
+            Self::ArchiveMembers => write!(f, "Reading archive members..."),
+            Self::NormalizedHash => write!(f, "Hashing normalized text..."),
+        }
+    }
+}
+
+/// Receives progress events from hashing. Events for one phase may arrive
+/// from several threads at once.
+pub trait ProgressSink: Send + Sync + fmt::Debug {
+    /// `phase` started with `total` items to process.
+    fn phase_started(&self, _phase: Phase, _total: u64) {}
+
+    /// `count` more items of the current phase are done.
+    fn items_done(&self, _count: u64) {}
+
+    /// `bytes` more bytes were read from disk for hashing.
+    fn bytes_hashed(&self, _bytes: u64) {}
+
+    /// All phases of the operation are done.
+    fn finished(&self) {}
+}
+
+/// Sink that ignores every event; the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
+
+/// Sink drawing an indicatif progress bar on stderr, as the CLI does.
+#[derive(Debug)]
+pub struct IndicatifProgress {
+    bar: ProgressBar,
+    bytes: AtomicU64,
+}
+
+impl IndicatifProgress {
+    pub fn new() -> Self {
+        let bar = ProgressBar::new(0);
+        #[allow(clippy::expect_used)]
+        let style = ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) {msg}")
+            .expect("Failed to create progress bar template")
+            .progress_chars("##-");
+        bar.set_style(style);
+        Self {
+            bar,
+            bytes: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for IndicatifProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressSink for IndicatifProgress {
+    fn phase_started(&self, phase: Phase, total: u64) {
+        // Enable steady tick to ensure spinner is visible
+        self.bar
+            .enable_steady_tick(std::time::Duration::from_millis(100));
+        self.bar.set_length(total);
+        self.bar.set_position(0);
+        self.bar.set_message(phase.to_string());
+    }
+
+    fn items_done(&self, count: u64) {
+        self.bar.inc(count);
+    }
+
+    fn bytes_hashed(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn finished(&self) {
+        let bytes = self.bytes.swap(0, Ordering::Relaxed);
+        #[allow(clippy::cast_precision_loss)]
+        let mb = bytes as f64 / 1_048_576.0;
+        self.bar
+            .finish_with_message(format!("✓ File analysis complete! ({mb:.2} MB read)"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records every event, to check what the library reports.
+    #[derive(Debug, Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+        items: AtomicU64,
+        bytes: AtomicU64,
+    }
+
+    impl ProgressSink for Recorder {
+        fn phase_started(&self, phase: Phase, total: u64) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{phase} ({total})"));
+        }
+
+        fn items_done(&self, count: u64) {
+            self.items.fetch_add(count, Ordering::Relaxed);
+        }
+
+        fn bytes_hashed(&self, bytes: u64) {
+            self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+
+        fn finished(&self) {
+            self.events.lock().unwrap().push("finished".to_string());
+        }
+    }
+
+    #[test]
+    fn test_hashing_reports_phases_items_and_bytes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let files: Vec<std::path::PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, if *name == "c" { "other" } else { "same" }).unwrap();
+                path
+            })
+            .collect();
+
+        let recorder = Recorder::default();
+        let (checksums, report, _) = crate::checksum_with_report(
+            &files,
+            HashAlgorithm::Md5,
+            None,
+            crate::hashing::MemoryBudget::global(),
+            &recorder,
+            &crate::CancelToken::default(),
+        )
+        .unwrap();
+        assert_eq!(checksums.len(), 1);
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec![
+                "Hashing file edges... (2)",
+                "Computing md5 hashes... (0)",
+                "finished"
+            ]
+        );
+        assert_eq!(recorder.items.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            recorder.bytes.load(Ordering::Relaxed),
+            report.total_bytes_read()
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! Quarantine: move files aside instead of deleting them.
+//!
+//! Every run that quarantines files opens a session directory under the
+//! quarantine root. Files are moved into `<session>/files/` under their full
+//! original path, so the directory layout is preserved, and each move is
+//! appended to `<session>/manifest.jsonl` with the original path, content
+//! hash, size and time. [`restore`] moves the files of a manifest back and
+//! [`purge`] deletes sessions once they are old enough.
+
+use crate::hashing::{self, HashAlgorithm};
+use crate::paths::display;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Name of the manifest file inside a session directory.
+pub const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// Hash recorded for quarantined files when the caller does not supply one.
+const MANIFEST_HASH: HashAlgorithm = HashAlgorithm::Blake3;
+
+/// Error types for quarantine operations
+#[derive(Debug, Error)]
+pub enum QuarantineError {
+    #[error("I/O error on {0}: {1}")]
+    Io(PathBuf, io::Error),
+
+    #[error("Invalid manifest entry in {0}: {1}")]
+    Manifest(PathBuf, serde_json::Error),
+}
+
+/// Result type for quarantine operations
+pub type QuarantineResult<T> = Result<T, QuarantineError>;
+
+/// One quarantined file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Where the file lived before it was quarantined
+    #[serde(with = "crate::paths::serde_path")]
+    pub original: PathBuf,
+    /// Where the file lives now
+    #[serde(with = "crate::paths::serde_path")]
+    pub quarantined: PathBuf,
+    /// Copy that was kept in its place, if any
+    #[serde(default, with = "crate::paths::serde_opt_path")]
+    pub kept: Option<PathBuf>,
+    pub algorithm: HashAlgorithm,
+    pub hash: String,
+    pub size_bytes: u64,
+    /// Time of the move in seconds since the Unix epoch
+    pub quarantined_at: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Default quarantine root: `$XDG_DATA_HOME/rclean/quarantine`, falling back
+/// to `~/.local/share/rclean/quarantine`.
+pub fn default_root() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rclean")
+        .join("quarantine")
+}
+
+/// `path` made absolute and stripped of its root, for use below `files/`.
+fn layout_path(path: &Path) -> PathBuf {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Move `from` to `to`, copying across filesystems when a rename is impossible.
+///
+/// The copy keeps the original's permissions and modification time, and the
+/// source is only removed once the copy is complete.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    let modified = fs::metadata(from)?.modified()?;
+    fs::copy(from, to)?;
+    let finish = fs::File::options()
+        .write(true)
+        .open(to)
+        .and_then(|file| file.set_modified(modified))
+        .and_then(|()| fs::remove_file(from));
+    if finish.is_err() {
+        let _ = fs::remove_file(to);
+    }
+    finish
+}
+
+/// A quarantine directory being filled by one run.
+#[derive(Debug)]
+pub struct QuarantineSession {
+    dir: PathBuf,
+    manifest: fs::File,
+}
+
+impl QuarantineSession {
+    /// Open a new session directory under `root`.
+    ///
+    /// Sessions are named `{secs}-{pid}`, with a `-{n}` suffix when another
+    /// session of the same process already took that name, as the MCP server
+    /// may open several within a second.
+    pub fn create(root: &Path) -> QuarantineResult<Self> {
+        fs::create_dir_all(root).map_err(|e| QuarantineError::Io(root.to_path_buf(), e))?;
+        let base = format!("{}-{}", now_secs(), std::process::id());
+        let mut attempt = 0u32;
+        let dir = loop {
+            let dir = match attempt {
+                0 => root.join(&base),
+                n => root.join(format!("{base}-{n}")),
+            };
+            match fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => return Err(QuarantineError::Io(dir, e)),
+            }
+        };
+        fs::create_dir(dir.join("files")).map_err(|e| QuarantineError::Io(dir.clone(), e))?;
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = fs::File::options()
+            .create(true)
+            .append(true)
+            .open(&manifest_path)
+            .map_err(|e| QuarantineError::Io(manifest_path, e))?;
+        Ok(Self { dir, manifest })
+    }
+
+    /// Session directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Manifest of this session.
+    pub fn manifest_path(&self) -> PathBuf {
+        self.dir.join(MANIFEST_FILE)
+    }
+
+    /// Move `path` into the session and record it in the manifest.
+    ///
+    /// `hash` is the file's known content hash; without it the file is hashed
+    /// with BLAKE3 before it is moved.
+    pub fn quarantine(
+        &mut self,
+        path: &Path,
+        kept: Option<&Path>,
+        hash: Option<(HashAlgorithm, &str)>,
+    ) -> QuarantineResult<ManifestEntry> {
+        let io_err = |e| QuarantineError::Io(path.to_path_buf(), e);
+        let size_bytes = fs::metadata(path).map_err(io_err)?.len();
+        let (algorithm, hash) = match hash {
+            Some((algorithm, hash)) => (algorithm, hash.to_string()),
+            None => (
+                MANIFEST_HASH,
+                hashing::hash_file(path, MANIFEST_HASH).map_err(io_err)?.0,
+            ),
+        };
+
+        let destination = self.dir.join("files").join(layout_path(path));
+        move_file(path, &destination).map_err(io_err)?;
+
+        let entry = ManifestEntry {
+            original: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+            quarantined: destination,
+            kept: kept.map(Path::to_path_buf),
+            algorithm,
+            hash,
+            size_bytes,
+            quarantined_at: now_secs(),
+        };
+        // One line per move, so an interrupted run still leaves a usable manifest
+        let manifest_path = self.manifest_path();
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| QuarantineError::Manifest(manifest_path.clone(), e))?;
+        writeln!(self.manifest, "{line}").map_err(|e| QuarantineError::Io(manifest_path, e))?;
+        Ok(entry)
+    }
+}
+
+/// Read all entries of a manifest.
+pub fn read_manifest(path: &Path) -> QuarantineResult<Vec<ManifestEntry>> {
+    let file = fs::File::open(path).map_err(|e| QuarantineError::Io(path.to_path_buf(), e))?;
+    io::BufReader::new(file)
+        .lines()
+        .map(|line| line.map_err(|e| QuarantineError::Io(path.to_path_buf(), e)))
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            serde_json::from_str(&line?)
+                .map_err(|e| QuarantineError::Manifest(path.to_path_buf(), e))
+        })
+        .collect()
+}
+
+/// Replace the manifest at `path` with `entries` (atomically).
+fn write_manifest(path: &Path, entries: &[ManifestEntry]) -> QuarantineResult<()> {
+    let io_err = |e| QuarantineError::Io(path.to_path_buf(), e);
+    let mut text = String::new();
+    for entry in entries {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| QuarantineError::Manifest(path.to_path_buf(), e))?;
+        text.push_str(&line);
+        text.push('\n');
+    }
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, text).map_err(io_err)?;
+    fs::rename(&tmp, path).map_err(io_err)
+}
+
+/// Outcome of restoring one manifest entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreOutcome {
+    #[serde(with = "crate::paths::serde_path")]
+    pub original: PathBuf,
+    pub size_bytes: u64,
+    /// `None` on success, otherwise why the file stayed in quarantine.
+    pub error: Option<String>,
+}
+
+/// Result of restoring a manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub outcomes: Vec<RestoreOutcome>,
+}
+
+impl RestoreReport {
+    /// Entries that could not be restored.
+    pub fn failures(&self) -> impl Iterator<Item = &RestoreOutcome> {
+        self.outcomes.iter().filter(|o| o.error.is_some())
+    }
+}
+
+impl fmt::Display for RestoreReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for outcome in &self.outcomes {
+            match &outcome.error {
+                None => writeln!(f, "Restored: {}", display(&outcome.original))?,
+                Some(error) => writeln!(f, "Skipped: {} ({error})", display(&outcome.original))?,
+            }
+        }
+        let failed = self.failures().count();
+        write!(
+            f,
+            "Restored {} files, {failed} left in quarantine",
+            self.outcomes.len() - failed
+        )
+    }
+}
+
+/// Check that the quarantined copy of `entry` still has the recorded size and
+/// content hash.
+fn verify_quarantined(entry: &ManifestEntry) -> Result<(), String> {
+    let metadata = fs::metadata(&entry.quarantined)
+        .map_err(|e| format!("quarantined copy unavailable: {e}"))?;
+    if metadata.len() != entry.size_bytes {
+        return Err("quarantined copy changed size".to_string());
+    }
+    let (hash, _) = hashing::hash_file(&entry.quarantined, entry.algorithm)
+        .map_err(|e| format!("quarantined copy unreadable: {e}"))?;
+    if hash != entry.hash {
+        return Err("quarantined copy changed content".to_string());
+    }
+    Ok(())
+}
+
+/// Move every file of the manifest at `manifest` back to its original path.
+///
+/// Files whose original path is occupied again, or whose quarantined copy is
+/// missing or no longer matches the recorded size and hash, stay where they
+/// are. The manifest is rewritten to
+/// list only the entries left in quarantine; once it is empty, the manifest
+/// and the emptied session directory are removed.
+pub fn restore(manifest: &Path) -> QuarantineResult<RestoreReport> {
+    let entries = read_manifest(manifest)?;
+    let mut remaining = Vec::new();
+    let mut report = RestoreReport::default();
+
+    for entry in entries {
+        let original = entry.original.as_path();
+        let quarantined = entry.quarantined.as_path();
+        let error = match verify_quarantined(&entry) {
+            Err(error) => Some(error),
+            Ok(()) if fs::symlink_metadata(original).is_ok() => {
+                Some("original path exists".to_string())
+            },
+            Ok(()) => move_file(quarantined, original)
+                .err()
+                .map(|e| e.to_string()),
+        };
+        report.outcomes.push(RestoreOutcome {
+            original: entry.original.clone(),
+            size_bytes: entry.size_bytes,
+            error: error.clone(),
+        });
+        if error.is_some() {
+            remaining.push(entry);
+        }
+    }
+
+    if remaining.is_empty() {
+        fs::remove_file(manifest).map_err(|e| QuarantineError::Io(manifest.to_path_buf(), e))?;
+        let session = manifest.parent().unwrap_or_else(|| Path::new("."));
+        remove_empty_dirs(&session.join("files"));
+        let _ = fs::remove_dir(session);
+    } else {
+        write_manifest(manifest, &remaining)?;
+    }
+    Ok(report)
+}
+
+/// Remove `dir` and its subdirectories as long as they contain no files.
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    let _ = fs::remove_dir(dir);
+}
+
+/// Result of purging a quarantine root.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgeReport {
+    pub sessions: usize,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl fmt::Display for PurgeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Purged {} sessions, {} files, {} bytes",
+            self.sessions, self.files, self.bytes
+        )
+    }
+}
+
+/// Delete quarantine sessions under `root` whose newest file was quarantined
+/// more than `older_than_secs` ago (all sessions when `None`).
+pub fn purge(root: &Path, older_than_secs: Option<u64>) -> QuarantineResult<PurgeReport> {
+    let mut report = PurgeReport::default();
+    let sessions = match fs::read_dir(root) {
+        Ok(sessions) => sessions,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(report),
+        Err(e) => return Err(QuarantineError::Io(root.to_path_buf(), e)),
+    };
+    let cutoff = older_than_secs.map(|age| now_secs().saturating_sub(age));
+
+    for session in sessions.flatten() {
+        let manifest = session.path().join(MANIFEST_FILE);
+        // Only touch directories that look like sessions
+        let Ok(entries) = read_manifest(&manifest) else {
+            continue;
+        };
+        let newest = entries.iter().map(|e| e.quarantined_at).max().unwrap_or(0);
+        if cutoff.is_some_and(|cutoff| newest > cutoff) {
+            continue;
+        }
+        fs::remove_dir_all(session.path()).map_err(|e| QuarantineError::Io(session.path(), e))?;
+        report.sessions += 1;
+        report.files += entries.len();
+        report.bytes += entries.iter().map(|e| e.size_bytes).sum::<u64>();
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, name: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "duplicate").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_quarantine_preserves_layout_and_restores() {
+        let data = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        let path = write(&data, "nested/dir/copy.txt");
+
+        let mut session = QuarantineSession::create(root.path()).unwrap();
+        let entry = session
+            .quarantine(&path, Some(Path::new("keep.txt")), None)
+            .unwrap();
+        assert!(!path.exists());
+        assert!(entry.quarantined.ends_with("nested/dir/copy.txt"));
+        assert_eq!(entry.hash, HashAlgorithm::Blake3.digest(b"duplicate"));
+
+        let manifest = session.manifest_path();
+        let session_dir = session.dir().to_path_buf();
+        assert_eq!(read_manifest(&manifest).unwrap(), vec![entry]);
+
+        let report = restore(&manifest).unwrap();
+        assert_eq!(report.failures().count(), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "duplicate");
+        assert!(!session_dir.exists());
+    }
+
+    #[test]
+    fn test_restore_does_not_overwrite() {
+        let data = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        let path = write(&data, "copy.txt");
+
+        let mut session = QuarantineSession::create(root.path()).unwrap();
+        session.quarantine(&path, None, None).unwrap();
+        fs::write(&path, "new file").unwrap();
+
+        let report = restore(&session.manifest_path()).unwrap();
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new file");
+        assert_eq!(read_manifest(&session.manifest_path()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_restore_refuses_changed_content() {
+        let data = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        let path = write(&data, "copy.txt");
+
+        let mut session = QuarantineSession::create(root.path()).unwrap();
+        let entry = session.quarantine(&path, None, None).unwrap();
+        fs::write(&entry.quarantined, "tampered!").unwrap();
+
+        let report = restore(&session.manifest_path()).unwrap();
+        assert_eq!(report.failures().count(), 1);
+        assert!(!path.exists());
+        assert_eq!(read_manifest(&session.manifest_path()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_sessions_in_the_same_second_get_their_own_dirs() {
+        let root = TempDir::new().unwrap();
+        let first = QuarantineSession::create(root.path()).unwrap();
+        let second = QuarantineSession::create(root.path()).unwrap();
+        assert_ne!(first.dir(), second.dir());
+        assert!(second.dir().join("files").is_dir());
+    }
+
+    #[test]
+    fn test_purge_respects_age() {
+        let data = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        let mut session = QuarantineSession::create(root.path()).unwrap();
+        session
+            .quarantine(&write(&data, "a.txt"), None, None)
+            .unwrap();
+        fs::create_dir(root.path().join("not-a-session")).unwrap();
+
+        assert_eq!(purge(root.path(), Some(3600)).unwrap().sessions, 0);
+        let report = purge(root.path(), None).unwrap();
+        assert_eq!((report.sessions, report.files, report.bytes), (1, 1, 9));
+        assert!(!session.dir().exists());
+        assert!(root.path().join("not-a-session").exists());
+    }
+}
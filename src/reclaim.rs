@@ -0,0 +1,261 @@
+//! Reclaimable-space accounting for dedupe results.
+//!
+//! Keeping one copy of every duplicate group frees the size of each other
+//! physical copy. [`crate::create_dataframe`] records that amount per file in
+//! the `reclaimable_bytes` column (0 for the copy that would be kept and for
+//! hardlinks, which occupy no extra space). This module totals the column per
+//! duplicate group, per top-level directory below the scan root and per
+//! extension.
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path};
+
+/// Name of the per-file reclaimable bytes column.
+pub const RECLAIMABLE_COLUMN: &str = "reclaimable_bytes";
+
+/// Name of the per-group reclaimable bytes column added to CSV reports.
+pub const GROUP_RECLAIMABLE_COLUMN: &str = "group_reclaimable_bytes";
+
+/// Reclaimable bytes for one group, directory or extension.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReclaimableEntry {
+    pub name: String,
+    pub duplicate_files: usize,
+    pub reclaimable_bytes: u64,
+}
+
+/// Bytes freed by keeping one copy per duplicate group, broken down by group,
+/// top-level directory and extension. Each list is sorted by reclaimable
+/// bytes, largest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReclaimableSpace {
+    pub total_bytes: u64,
+    pub by_group: Vec<ReclaimableEntry>,
+    pub by_directory: Vec<ReclaimableEntry>,
+    pub by_extension: Vec<ReclaimableEntry>,
+}
+
+impl ReclaimableSpace {
+    /// Keep only the `limit` largest entries of each breakdown.
+    #[must_use]
+    pub fn top(mut self, limit: usize) -> Self {
+        self.by_group.truncate(limit);
+        self.by_directory.truncate(limit);
+        self.by_extension.truncate(limit);
+        self
+    }
+}
+
+/// Rows shown per breakdown when printing.
+const DISPLAY_LIMIT: usize = 10;
+
+fn write_breakdown(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    entries: &[ReclaimableEntry],
+) -> fmt::Result {
+    writeln!(f, "{title}:")?;
+    for entry in entries.iter().take(DISPLAY_LIMIT) {
+        writeln!(
+            f,
+            "  {:>14} bytes  {:>5} files  {}",
+            entry.reclaimable_bytes, entry.duplicate_files, entry.name
+        )?;
+    }
+    if entries.len() > DISPLAY_LIMIT {
+        writeln!(f, "  ... and {} more", entries.len() - DISPLAY_LIMIT)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for ReclaimableSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Reclaimable by keeping one copy per group: {} bytes ({:.2} MB)",
+            self.total_bytes,
+            self.total_bytes as f64 / 1_048_576.0
+        )?;
+        write_breakdown(f, "By duplicate group", &self.by_group)?;
+        write_breakdown(f, "By top-level directory", &self.by_directory)?;
+        write_breakdown(f, "By extension", &self.by_extension)
+    }
+}
+
+/// First path component of `path` below `root`, or `.` for files directly in
+/// `root`. Paths outside `root` are attributed to their parent directory.
+pub fn top_level_dir(path: &str, root: &str) -> String {
+    let path = Path::new(path);
+    let Ok(relative) = path.strip_prefix(root) else {
+        return path
+            .parent()
+            .map_or_else(|| ".".to_string(), |p| p.to_string_lossy().into_owned());
+    };
+    let mut components = relative
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)));
+    match (components.next(), components.next()) {
+        (Some(first), Some(_)) => first.as_os_str().to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    }
+}
+
+fn sorted(totals: HashMap<String, (usize, u64)>) -> Vec<ReclaimableEntry> {
+    let mut entries: Vec<ReclaimableEntry> = totals
+        .into_iter()
+        .map(
+            |(name, (duplicate_files, reclaimable_bytes))| ReclaimableEntry {
+                name,
+                duplicate_files,
+                reclaimable_bytes,
+            },
+        )
+        .collect();
+    entries.sort_by(|a, b| {
+        b.reclaimable_bytes
+            .cmp(&a.reclaimable_bytes)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    entries
+}
+
+/// Total the `reclaimable_bytes` column of a dedupe `DataFrame`.
+///
+/// `root` is the scanned directory, used to find each file's top-level
+/// directory. Frames without the column report nothing reclaimable.
+///
+/// # Errors
+///
+/// Returns an error if the path, extension or duplicate columns are missing.
+pub fn reclaimable_space(df: &DataFrame, root: &str) -> PolarsResult<ReclaimableSpace> {
+    let mut space = ReclaimableSpace::default();
+    let Ok(reclaimable) = df.column(RECLAIMABLE_COLUMN) else {
+        return Ok(space);
+    };
+    let reclaimable = reclaimable.u64()?;
+    let paths = df.column("file_path")?.utf8()?;
+    let extensions = df.column("extension")?.utf8()?;
+    let groups = df.column("duplicate_group")?.utf8()?;
+
+    let mut by_group: HashMap<String, (usize, u64)> = HashMap::new();
+    let mut by_directory: HashMap<String, (usize, u64)> = HashMap::new();
+    let mut by_extension: HashMap<String, (usize, u64)> = HashMap::new();
+
+    for row in 0..df.height() {
+        let Some(group) = groups.get(row) else {
+            continue;
+        };
+        let bytes = reclaimable.get(row).unwrap_or(0);
+        let path = paths.get(row).unwrap_or_default();
+        let extension = match extensions.get(row).unwrap_or_default() {
+            "" => "(none)".to_string(),
+            extension => extension.to_lowercase(),
+        };
+        space.total_bytes += bytes;
+        for (totals, key) in [
+            (&mut by_group, group.to_string()),
+            (&mut by_directory, top_level_dir(path, root)),
+            (&mut by_extension, extension),
+        ] {
+            let total = totals.entry(key).or_default();
+            total.0 += 1;
+            total.1 += bytes;
+        }
+    }
+
+    space.by_group = sorted(by_group);
+    space.by_directory = sorted(by_directory);
+    space.by_extension = sorted(by_extension);
+    Ok(space)
+}
+
+/// Add a `group_reclaimable_bytes` column holding each row's group total.
+///
+/// # Errors
+///
+/// Returns an error if the duplicate group column is missing.
+pub fn with_group_reclaimable(df: &DataFrame) -> PolarsResult<DataFrame> {
+    let Ok(reclaimable) = df.column(RECLAIMABLE_COLUMN) else {
+        return Ok(df.clone());
+    };
+    let reclaimable = reclaimable.u64()?;
+    let groups = df.column("duplicate_group")?.utf8()?;
+
+    let mut totals: HashMap<&str, u64> = HashMap::new();
+    for (group, bytes) in groups.into_iter().zip(reclaimable) {
+        if let Some(group) = group {
+            *totals.entry(group).or_default() += bytes.unwrap_or(0);
+        }
+    }
+    let per_row: Vec<Option<u64>> = groups
+        .into_iter()
+        .map(|group| group.map(|group| totals[group]))
+        .collect();
+
+    let mut df = df.clone();
+    df.with_column(Series::new(GROUP_RECLAIMABLE_COLUMN, per_row))?;
+    Ok(df)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_top_level_dir() {
+        assert_eq!(top_level_dir("/data/photos/2020/a.jpg", "/data"), "photos");
+        assert_eq!(top_level_dir("/data/a.jpg", "/data"), ".");
+        assert_eq!(top_level_dir("/data/a.jpg", "/data/"), ".");
+        assert_eq!(top_level_dir("/elsewhere/x/a.jpg", "/data"), "/elsewhere/x");
+    }
+
+    #[test]
+    fn test_reclaimable_breakdowns() -> Result<(), Box<dyn Error>> {
+        let df = df! [
+            "file_path" => ["/r/a/x.JPG", "/r/b/x.jpg", "/r/b/y.txt", "/r/y.txt", "/r/z.bin"],
+            "extension" => ["JPG", "jpg", "txt", "txt", "bin"],
+            "duplicate_group" => [Some("g1"), Some("g1"), Some("g2"), Some("g2"), None],
+            RECLAIMABLE_COLUMN => [0u64, 100, 0, 10, 0],
+        ]?;
+
+        let space = reclaimable_space(&df, "/r")?;
+        assert_eq!(space.total_bytes, 110);
+        assert_eq!(space.by_group[0].name, "g1");
+        assert_eq!(space.by_group[0].reclaimable_bytes, 100);
+        assert_eq!(
+            space.by_directory,
+            vec![
+                ReclaimableEntry {
+                    name: "b".into(),
+                    duplicate_files: 2,
+                    reclaimable_bytes: 100
+                },
+                ReclaimableEntry {
+                    name: ".".into(),
+                    duplicate_files: 1,
+                    reclaimable_bytes: 10
+                },
+                ReclaimableEntry {
+                    name: "a".into(),
+                    duplicate_files: 1,
+                    reclaimable_bytes: 0
+                },
+            ]
+        );
+        assert_eq!(space.by_extension[0].name, "jpg");
+        assert_eq!(space.by_extension[0].duplicate_files, 2);
+
+        let with_groups = with_group_reclaimable(&df)?;
+        let totals: Vec<Option<u64>> = with_groups
+            .column(GROUP_RECLAIMABLE_COLUMN)?
+            .u64()?
+            .into_iter()
+            .collect();
+        assert_eq!(totals, vec![Some(100), Some(100), Some(10), Some(10), None]);
+        Ok(())
+    }
+}
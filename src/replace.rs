@@ -0,0 +1,302 @@
+//! Atomic replacement of duplicate files with links to a kept copy.
+//!
+//! A replacement never leaves the duplicate's path missing: the link is first
+//! created under a temporary name in the same directory and then renamed over
+//! the original, which is atomic on POSIX filesystems. The kept file's
+//! permissions and modification time are restored afterwards in case the
+//! filesystem touched them.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+/// Error types for link replacement
+#[derive(Debug, Error)]
+pub enum ReplaceError {
+    #[error("{0} is on a different device than {1}; hardlinks cannot cross filesystems")]
+    CrossDevice(PathBuf, PathBuf),
+
+    #[error("{0} already is a hardlink to {1}")]
+    AlreadyLinked(PathBuf, PathBuf),
+
+    #[error("Symlinks are not supported on this platform")]
+    SymlinkUnsupported,
+
+    #[error("I/O error on {0}: {1}")]
+    Io(PathBuf, io::Error),
+}
+
+/// Result type for link replacement
+pub type ReplaceResult<T> = Result<T, ReplaceError>;
+
+/// Snapshot of the attributes a replacement must not change on the kept file.
+struct KeptAttributes {
+    permissions: fs::Permissions,
+    modified: Option<SystemTime>,
+}
+
+impl KeptAttributes {
+    fn capture(path: &Path) -> ReplaceResult<Self> {
+        let metadata = fs::metadata(path).map_err(|e| ReplaceError::Io(path.to_path_buf(), e))?;
+        Ok(Self {
+            permissions: metadata.permissions(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn restore(&self, path: &Path) -> ReplaceResult<()> {
+        let io_err = |e| ReplaceError::Io(path.to_path_buf(), e);
+        let metadata = fs::metadata(path).map_err(io_err)?;
+        if metadata.permissions() != self.permissions {
+            fs::set_permissions(path, self.permissions.clone()).map_err(io_err)?;
+        }
+        if let Some(modified) = self.modified {
+            if metadata.modified().ok() != Some(modified) {
+                fs::File::options()
+                    .write(true)
+                    .open(path)
+                    .and_then(|file| file.set_modified(modified))
+                    .map_err(io_err)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether two paths live on the same device (always `true` off Unix, where
+/// the hardlink call itself reports the problem).
+pub fn same_device(a: &Path, b: &Path) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+        Ok(fs::metadata(a)?.dev() == fs::metadata(b)?.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        Ok(true)
+    }
+}
+
+/// Whether two paths resolve to the same file, whatever their spelling:
+/// the same device and inode on Unix (so hardlinks match too), the same
+/// canonical path elsewhere.
+#[cfg(unix)]
+pub fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt as _;
+    let (a, b) = (fs::metadata(a)?, fs::metadata(b)?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+/// Whether two paths resolve to the same file, whatever their spelling:
+/// the same device and inode on Unix (so hardlinks match too), the same
+/// canonical path elsewhere.
+#[cfg(not(unix))]
+pub fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    Ok(fs::canonicalize(a)? == fs::canonicalize(b)?)
+}
+
+/// Temporary sibling of `path` used while the link is being put in place.
+fn temp_sibling(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.rclean-{}.tmp", std::process::id()))
+}
+
+/// Create a link with `create` at a temporary name and rename it over `target`.
+fn swap_in(target: &Path, create: impl FnOnce(&Path) -> io::Result<()>) -> ReplaceResult<()> {
+    let tmp = temp_sibling(target);
+    // A leftover from an interrupted run would make the link call fail
+    let _ = fs::remove_file(&tmp);
+    create(&tmp).map_err(|e| ReplaceError::Io(tmp.clone(), e))?;
+    fs::rename(&tmp, target).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        ReplaceError::Io(target.to_path_buf(), e)
+    })
+}
+
+/// Replace `target` with a hardlink to `keep`.
+///
+/// # Errors
+///
+/// Returns [`ReplaceError::CrossDevice`] if the two paths are on different
+/// filesystems, or an I/O error; `target` is left untouched in both cases.
+pub fn replace_with_hardlink(keep: &Path, target: &Path) -> ReplaceResult<()> {
+    let io_err = |e| ReplaceError::Io(target.to_path_buf(), e);
+    if !same_device(keep, target).map_err(io_err)? {
+        return Err(ReplaceError::CrossDevice(
+            target.to_path_buf(),
+            keep.to_path_buf(),
+        ));
+    }
+    if same_file(keep, target).map_err(io_err)? {
+        return Err(ReplaceError::AlreadyLinked(
+            target.to_path_buf(),
+            keep.to_path_buf(),
+        ));
+    }
+
+    let attributes = KeptAttributes::capture(keep)?;
+    swap_in(target, |tmp| fs::hard_link(keep, tmp))?;
+    attributes.restore(keep)
+}
+
+/// Replace `target` with a relative symlink to `keep`.
+///
+/// # Errors
+///
+/// Returns [`ReplaceError::AlreadyLinked`] if `target` is `keep` under
+/// another spelling or a hardlink to it, or an error if either path cannot be
+/// resolved or the link cannot be created; `target` is left untouched in all
+/// cases.
+pub fn replace_with_symlink(keep: &Path, target: &Path) -> ReplaceResult<()> {
+    if same_file(keep, target).map_err(|e| ReplaceError::Io(target.to_path_buf(), e))? {
+        return Err(ReplaceError::AlreadyLinked(
+            target.to_path_buf(),
+            keep.to_path_buf(),
+        ));
+    }
+    let link = symlink_target(keep, target)?;
+    let attributes = KeptAttributes::capture(keep)?;
+    swap_in(target, |tmp| symlink(&link, tmp))?;
+    attributes.restore(keep)
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_original: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        ReplaceError::SymlinkUnsupported.to_string(),
+    ))
+}
+
+/// Path to `keep` relative to the directory containing `target`, resolving
+/// symlinked ancestors so the link works from where it is placed.
+pub fn symlink_target(keep: &Path, target: &Path) -> ReplaceResult<PathBuf> {
+    let keep = fs::canonicalize(keep).map_err(|e| ReplaceError::Io(keep.to_path_buf(), e))?;
+    let parent = target
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let from = fs::canonicalize(parent).map_err(|e| ReplaceError::Io(parent.to_path_buf(), e))?;
+    Ok(relative_path(&from, &keep))
+}
+
+/// Lexical path from directory `from` to `to`; both must be absolute.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component<'_>> = from.components().collect();
+    let to: Vec<Component<'_>> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("/a/b/c"), Path::new("/a/x/y.txt")),
+            PathBuf::from("../../x/y.txt")
+        );
+        assert_eq!(
+            relative_path(Path::new("/a"), Path::new("/a/y.txt")),
+            PathBuf::from("y.txt")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_replacement_shares_inode_and_keeps_mtime() {
+        use std::os::unix::fs::MetadataExt as _;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dir = TempDir::new().unwrap();
+        let keep = dir.path().join("keep.bin");
+        let target = dir.path().join("dup.bin");
+        fs::write(&keep, "same").unwrap();
+        fs::write(&target, "same").unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&keep)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        replace_with_hardlink(&keep, &target).unwrap();
+
+        let (k, t) = (fs::metadata(&keep).unwrap(), fs::metadata(&target).unwrap());
+        assert_eq!(k.ino(), t.ino());
+        assert_eq!(k.modified().unwrap(), mtime);
+        assert!(!temp_sibling(&target).exists());
+        assert!(matches!(
+            replace_with_hardlink(&keep, &target),
+            Err(ReplaceError::AlreadyLinked(..))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_replacement_is_relative() {
+        let dir = TempDir::new().unwrap();
+        let keep = dir.path().join("data").join("keep.txt");
+        let target = dir.path().join("other").join("nested").join("dup.txt");
+        fs::create_dir_all(keep.parent().unwrap()).unwrap();
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::write(&keep, "same").unwrap();
+        fs::write(&target, "same").unwrap();
+
+        replace_with_symlink(&keep, &target).unwrap();
+
+        assert_eq!(
+            fs::read_link(&target).unwrap(),
+            PathBuf::from("../../data/keep.txt")
+        );
+        assert_eq!(fs::read_to_string(&target).unwrap(), "same");
+    }
+
+    #[test]
+    fn test_symlink_refuses_aliased_keeper() {
+        let dir = TempDir::new().unwrap();
+        let keep = dir.path().join("keep.txt");
+        fs::write(&keep, "same").unwrap();
+
+        let alias = dir.path().join(".").join("keep.txt");
+        assert!(matches!(
+            replace_with_symlink(&keep, &alias),
+            Err(ReplaceError::AlreadyLinked(..))
+        ));
+        assert_eq!(fs::read_to_string(&keep).unwrap(), "same");
+        assert!(!fs::symlink_metadata(&keep).unwrap().is_symlink());
+    }
+
+    #[test]
+    fn test_failed_replacement_leaves_target() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("dup.txt");
+        fs::write(&target, "same").unwrap();
+
+        assert!(replace_with_hardlink(&dir.path().join("missing"), &target).is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "same");
+    }
+}
@@ -0,0 +1,791 @@
+//! Duplicate resolution: keep one file per duplicate group, remove the rest.
+//!
+//! A [`KeepPolicy`] ranks the members of each group and the best-ranked file
+//! is kept. The other members are deleted, moved into quarantine (see
+//! [`crate::quarantine`]), or replaced with hardlinks or relative symlinks to
+//! the keeper (see [`ResolveAction`] and [`crate::replace`]). Resolution is a dry run unless
+//! [`ResolveOptions::dry_run`] is turned off, and the returned
+//! [`ResolveReport`] lists every file that was (or would be) changed together
+//! with the copy that is kept.
+
+use crate::cache::HashCache;
+use crate::hashing::HashAlgorithm;
+use crate::paths::display;
+use crate::plan::{self, Fingerprint};
+use crate::quarantine::QuarantineSession;
+use crate::replace;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+/// Error types for duplicate resolution
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("Duplicate group is empty")]
+    EmptyGroup,
+
+    #[error("Cannot read metadata of {}: {1}", crate::paths::display(.0))]
+    Metadata(PathBuf, std::io::Error),
+}
+
+/// Result type for resolution operations
+pub type ResolveResult<T> = Result<T, ResolveError>;
+
+/// Which file of a duplicate group to keep.
+#[derive(Debug, Clone, Default)]
+pub enum KeepPolicy {
+    /// Keep the file with the oldest modification time
+    #[default]
+    Oldest,
+    /// Keep the file with the newest modification time
+    Newest,
+    /// Keep the file with the shortest path
+    ShortestPath,
+    /// Keep a file under the earliest listed directory; ties go to the shortest path
+    PreferredDirs(Vec<PathBuf>),
+    /// Keep the file matching the earliest listed regex; ties go to the shortest path
+    RegexPriority(Vec<Regex>),
+}
+
+impl fmt::Display for KeepPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Oldest => f.write_str("oldest"),
+            Self::Newest => f.write_str("newest"),
+            Self::ShortestPath => f.write_str("shortest-path"),
+            Self::PreferredDirs(dirs) => {
+                let dirs: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+                write!(f, "preferred directories [{}]", dirs.join(", "))
+            },
+            Self::RegexPriority(regexes) => {
+                let regexes: Vec<&str> = regexes.iter().map(Regex::as_str).collect();
+                write!(f, "regex priority [{}]", regexes.join(", "))
+            },
+        }
+    }
+}
+
+/// What happens to the duplicates that are not kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolveAction {
+    /// Delete the duplicate
+    #[default]
+    Delete,
+    /// Replace the duplicate with a hardlink to the kept file
+    Hardlink,
+    /// Replace the duplicate with a relative symlink to the kept file
+    Symlink,
+    /// Move the duplicate into a quarantine directory, recorded in a manifest
+    Quarantine,
+}
+
+impl ResolveAction {
+    /// Verb for report lines, in dry-run or executed form.
+    pub const fn verb(self, dry_run: bool) -> &'static str {
+        match (self, dry_run) {
+            (Self::Delete, true) => "Would remove",
+            (Self::Delete, false) => "Removed",
+            (Self::Hardlink, true) => "Would hardlink",
+            (Self::Hardlink, false) => "Hardlinked",
+            (Self::Symlink, true) => "Would symlink",
+            (Self::Symlink, false) => "Symlinked",
+            (Self::Quarantine, true) => "Would quarantine",
+            (Self::Quarantine, false) => "Quarantined",
+        }
+    }
+}
+
+/// Options controlling duplicate resolution.
+#[derive(Debug, Clone)]
+pub struct ResolveOptions {
+    pub policy: KeepPolicy,
+    pub action: ResolveAction,
+    /// With [`ResolveAction::Hardlink`], fall back to a symlink for duplicates
+    /// on another filesystem than the keeper instead of skipping them.
+    pub symlink_across_devices: bool,
+    /// Quarantine root for [`ResolveAction::Quarantine`]; defaults to
+    /// [`crate::quarantine::default_root`].
+    pub quarantine_dir: Option<PathBuf>,
+    /// Reference directories: their files are never changed and are kept in
+    /// preference to any other copy (see [`crate::roots`]).
+    pub references: Vec<PathBuf>,
+    /// Only report what would be done (default)
+    pub dry_run: bool,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            policy: KeepPolicy::default(),
+            action: ResolveAction::default(),
+            symlink_across_devices: false,
+            quarantine_dir: None,
+            references: Vec::new(),
+            dry_run: true,
+        }
+    }
+}
+
+/// The keeper and the files to remove for one duplicate group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupResolution {
+    #[serde(with = "crate::paths::serde_path")]
+    pub keep: PathBuf,
+    #[serde(with = "crate::paths::serde_paths")]
+    pub remove: Vec<PathBuf>,
+    /// Size of one copy; `remove.len()` copies are reclaimed.
+    pub size_bytes: u64,
+    /// Fingerprints taken right after the scan (see
+    /// [`fingerprint_resolutions`]); when set, files are verified against
+    /// them again before an executed action.
+    #[serde(skip)]
+    pub scanned: Option<ScanFingerprints>,
+}
+
+/// Size, mtime and content hash of the members of a group as scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanFingerprints {
+    pub algorithm: HashAlgorithm,
+    pub files: HashMap<PathBuf, Fingerprint>,
+}
+
+/// Outcome for a single file that is not kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemovalOutcome {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    #[serde(with = "crate::paths::serde_path")]
+    pub kept: PathBuf,
+    pub size_bytes: u64,
+    /// Action taken (or planned); differs from the requested one when a
+    /// cross-device hardlink fell back to a symlink.
+    pub action: ResolveAction,
+    /// `None` on success (or in a dry run), otherwise why the file was left alone.
+    pub error: Option<String>,
+}
+
+/// Result of resolving a set of duplicate groups.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolveReport {
+    pub dry_run: bool,
+    pub action: ResolveAction,
+    pub groups: usize,
+    pub removals: Vec<RemovalOutcome>,
+    /// Manifest of the quarantine session, if files were quarantined
+    #[serde(default, with = "crate::paths::serde_opt_path")]
+    pub quarantine_manifest: Option<PathBuf>,
+}
+
+impl ResolveReport {
+    /// Bytes freed (or that would be freed) by successful removals.
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.removals
+            .iter()
+            .filter(|r| r.error.is_none())
+            .map(|r| r.size_bytes)
+            .sum()
+    }
+
+    /// Removals that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &RemovalOutcome> {
+        self.removals.iter().filter(|r| r.error.is_some())
+    }
+}
+
+impl fmt::Display for ResolveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for removal in &self.removals {
+            match &removal.error {
+                None => writeln!(
+                    f,
+                    "{}: {} (keeping {})",
+                    removal.action.verb(self.dry_run),
+                    display(&removal.path),
+                    display(&removal.kept)
+                )?,
+                Some(error) => writeln!(f, "Skipped: {} ({error})", display(&removal.path))?,
+            }
+        }
+        let done = self.removals.len() - self.failures().count();
+        write!(
+            f,
+            "{} {done} files in {} groups, {} bytes",
+            self.action.verb(self.dry_run),
+            self.groups,
+            self.bytes_reclaimed()
+        )?;
+        if let Some(manifest) = &self.quarantine_manifest {
+            write!(f, "\nQuarantine manifest: {}", display(manifest))?;
+        }
+        Ok(())
+    }
+}
+
+/// Metadata used to rank group members.
+struct Candidate<'a> {
+    path: &'a Path,
+    modified: Option<SystemTime>,
+    size_bytes: u64,
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn shortest_path(a: &Candidate<'_>, b: &Candidate<'_>) -> Ordering {
+    a.path.as_os_str().len().cmp(&b.path.as_os_str().len())
+}
+
+impl KeepPolicy {
+    /// Ordering where the file to keep sorts first; ties fall back to the path
+    /// so the choice is deterministic.
+    fn compare(&self, a: &Candidate<'_>, b: &Candidate<'_>) -> Ordering {
+        let primary = match self {
+            // `None` (unknown mtime) sorts last for both time-based policies
+            Self::Oldest => match (a.modified, b.modified) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (x, y) => x.is_none().cmp(&y.is_none()),
+            },
+            Self::Newest => match (a.modified, b.modified) {
+                (Some(x), Some(y)) => y.cmp(&x),
+                (x, y) => x.is_none().cmp(&y.is_none()),
+            },
+            Self::ShortestPath => shortest_path(a, b),
+            Self::PreferredDirs(dirs) => {
+                let rank = |c: &Candidate<'_>| {
+                    let path = absolute(c.path);
+                    dirs.iter()
+                        .position(|dir| path.starts_with(absolute(dir)))
+                        .unwrap_or(dirs.len())
+                };
+                rank(a).cmp(&rank(b)).then_with(|| shortest_path(a, b))
+            },
+            Self::RegexPriority(regexes) => {
+                let rank = |c: &Candidate<'_>| {
+                    regexes
+                        .iter()
+                        .position(|r| r.is_match(&display(c.path)))
+                        .unwrap_or(regexes.len())
+                };
+                rank(a).cmp(&rank(b)).then_with(|| shortest_path(a, b))
+            },
+        };
+        primary.then_with(|| a.path.cmp(b.path))
+    }
+}
+
+/// Pick the keeper of one duplicate group according to `policy`.
+///
+/// # Errors
+///
+/// Returns an error if the group is empty or a member cannot be stat'ed.
+pub fn resolve_group(group: &[PathBuf], policy: &KeepPolicy) -> ResolveResult<GroupResolution> {
+    let mut candidates = group
+        .iter()
+        .map(|path| {
+            let metadata =
+                fs::metadata(path).map_err(|e| ResolveError::Metadata(path.clone(), e))?;
+            Ok(Candidate {
+                path: path.as_path(),
+                modified: metadata.modified().ok(),
+                size_bytes: metadata.len(),
+            })
+        })
+        .collect::<ResolveResult<Vec<_>>>()?;
+
+    candidates.sort_by(|a, b| policy.compare(a, b));
+    let mut candidates = candidates.into_iter();
+    let keep = candidates.next().ok_or(ResolveError::EmptyGroup)?;
+
+    Ok(GroupResolution {
+        keep: keep.path.to_path_buf(),
+        remove: candidates.map(|c| c.path.to_path_buf()).collect(),
+        size_bytes: keep.size_bytes,
+        scanned: None,
+    })
+}
+
+/// Pick a keeper for every group with at least two members.
+///
+/// Groups whose members cannot be stat'ed (e.g. a file vanished since the
+/// scan) are skipped rather than failing the whole run.
+pub fn plan_resolution(groups: &[Vec<PathBuf>], policy: &KeepPolicy) -> Vec<GroupResolution> {
+    plan_resolution_with_references(groups, policy, &[])
+}
+
+/// Like [`plan_resolution`], but files under the `references` directories are
+/// never removed. A group with reference members keeps the one `policy` ranks
+/// best and removes every other member; groups made up only of reference
+/// files are skipped.
+pub fn plan_resolution_with_references(
+    groups: &[Vec<PathBuf>],
+    policy: &KeepPolicy,
+    references: &[PathBuf],
+) -> Vec<GroupResolution> {
+    let references = crate::roots::canonical_references(references.iter().map(PathBuf::as_path));
+    groups
+        .iter()
+        .filter(|group| group.len() > 1)
+        .filter_map(|group| {
+            let (protected, candidates): (Vec<PathBuf>, Vec<PathBuf>) = group
+                .iter()
+                .cloned()
+                .partition(|path| crate::roots::is_under_reference(path, &references));
+            if protected.is_empty() {
+                return resolve_group(group, policy).ok();
+            }
+            if candidates.is_empty() {
+                return None;
+            }
+            let keep = resolve_group(&protected, policy).ok()?;
+            Some(GroupResolution {
+                remove: candidates,
+                ..keep
+            })
+        })
+        .collect()
+}
+
+/// Action actually used for `path`: a hardlink across devices either falls
+/// back to a symlink or is refused, depending on the options.
+fn effective_action(
+    keep: &Path,
+    path: &Path,
+    options: &ResolveOptions,
+) -> Result<ResolveAction, String> {
+    if options.action != ResolveAction::Hardlink {
+        return Ok(options.action);
+    }
+    match replace::same_device(keep, path) {
+        Ok(true) => Ok(ResolveAction::Hardlink),
+        Ok(false) if options.symlink_across_devices => Ok(ResolveAction::Symlink),
+        Ok(false) => Err(replace::ReplaceError::CrossDevice(
+            path.to_path_buf(),
+            keep.to_path_buf(),
+        )
+        .to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Fingerprint the keeper and the other members of every resolution, taking
+/// hashes from the scan's `cache`, so that [`apply_resolution`] only changes
+/// files that still look as they did when scanned. Members whose content no
+/// longer matches the keeper get no fingerprint and are left alone.
+pub fn fingerprint_resolutions(
+    resolutions: &mut [GroupResolution],
+    algorithm: HashAlgorithm,
+    cache: &HashCache,
+) {
+    for resolution in resolutions {
+        let mut files = HashMap::new();
+        if let Ok(keep) = plan::fingerprint(&resolution.keep, algorithm, Some(cache)) {
+            for path in &resolution.remove {
+                let Ok(print) = plan::fingerprint(path, algorithm, Some(cache)) else {
+                    continue;
+                };
+                if print.hash == keep.hash && print.size_bytes == keep.size_bytes {
+                    files.insert(path.clone(), print);
+                }
+            }
+            files.insert(resolution.keep.clone(), keep);
+        }
+        resolution.scanned = Some(ScanFingerprints { algorithm, files });
+    }
+}
+
+/// Check that the keeper of `resolution` is still there and, before an
+/// executed action, still matches its scanned fingerprint. Returns the
+/// content hash to record in a quarantine manifest.
+fn check_keeper(
+    resolution: &GroupResolution,
+    dry_run: bool,
+) -> Result<Option<(HashAlgorithm, &str)>, String> {
+    let keep = resolution.keep.as_path();
+    if !keep.is_file() {
+        return Err(format!("kept copy {} is missing", display(keep)));
+    }
+    let Some(scanned) = resolution.scanned.as_ref().filter(|_| !dry_run) else {
+        return Ok(None);
+    };
+    let kept_copy = |e: &str| format!("kept copy {} {e}", display(keep));
+    let expected = scanned
+        .files
+        .get(keep)
+        .ok_or_else(|| kept_copy("could not be fingerprinted"))?;
+    plan::verify(keep, expected, scanned.algorithm).map_err(|e| kept_copy(&e))?;
+    Ok(Some((scanned.algorithm, expected.hash.as_str())))
+}
+
+/// Check that `path` still matches its scanned fingerprint, if `resolution`
+/// has any.
+fn check_scanned(resolution: &GroupResolution, path: &Path) -> Result<(), String> {
+    let Some(scanned) = &resolution.scanned else {
+        return Ok(());
+    };
+    let expected = scanned
+        .files
+        .get(path)
+        .ok_or("content no longer matches the kept copy")?;
+    plan::verify(path, expected, scanned.algorithm)
+}
+
+/// Delete or link every non-kept file, or only report them in a dry run.
+///
+/// A file is never touched if its keeper has disappeared in the meantime.
+/// For resolutions with scanned fingerprints, an executed action also
+/// re-checks the size, mtime and content hash of the file and its keeper,
+/// and skips the file if either changed since the scan.
+pub fn apply_resolution(
+    resolutions: &[GroupResolution],
+    options: &ResolveOptions,
+) -> ResolveReport {
+    let mut report = ResolveReport {
+        dry_run: options.dry_run,
+        action: options.action,
+        groups: resolutions.len(),
+        removals: Vec::new(),
+        quarantine_manifest: None,
+    };
+    let mut executor = Executor::new(options.quarantine_dir.as_deref());
+
+    for resolution in resolutions {
+        let keep = resolution.keep.as_path();
+        let keeper = check_keeper(resolution, options.dry_run);
+        for path in &resolution.remove {
+            let (action, error) = match &keeper {
+                Err(error) => (options.action, Some(error.clone())),
+                Ok(hash) => match effective_action(keep, path, options) {
+                    Ok(action) if options.dry_run => (action, check_not_keeper(keep, path).err()),
+                    Ok(action) => match check_scanned(resolution, path) {
+                        Ok(()) => (action, executor.perform(action, keep, path, *hash).err()),
+                        Err(error) => (action, Some(error)),
+                    },
+                    Err(error) => (options.action, Some(error)),
+                },
+            };
+            report.removals.push(RemovalOutcome {
+                path: path.clone(),
+                kept: resolution.keep.clone(),
+                size_bytes: resolution.size_bytes,
+                action,
+                error,
+            });
+        }
+    }
+
+    report.quarantine_manifest = executor.quarantine_manifest();
+    report
+}
+
+/// Refuse `path` if it is the kept file itself, under another spelling,
+/// through a symlinked directory or as a hardlink.
+pub(crate) fn check_not_keeper(keep: &Path, path: &Path) -> Result<(), String> {
+    if path == keep || replace::same_file(path, keep).unwrap_or(false) {
+        return Err("entry is the kept file".to_string());
+    }
+    Ok(())
+}
+
+/// Carries out resolve actions, opening a quarantine session on first use so
+/// that runs without quarantined files leave nothing behind.
+pub(crate) struct Executor<'a> {
+    quarantine_dir: Option<&'a Path>,
+    session: Option<QuarantineSession>,
+}
+
+impl<'a> Executor<'a> {
+    pub(crate) const fn new(quarantine_dir: Option<&'a Path>) -> Self {
+        Self {
+            quarantine_dir,
+            session: None,
+        }
+    }
+
+    /// Apply `action` to `path`, keeping `keep`. `hash` is recorded in the
+    /// quarantine manifest when known. A `path` that resolves to `keep` is
+    /// refused whatever the action.
+    pub(crate) fn perform(
+        &mut self,
+        action: ResolveAction,
+        keep: &Path,
+        path: &Path,
+        hash: Option<(HashAlgorithm, &str)>,
+    ) -> Result<(), String> {
+        check_not_keeper(keep, path)?;
+        match action {
+            ResolveAction::Delete => fs::remove_file(path).map_err(|e| e.to_string()),
+            ResolveAction::Hardlink => {
+                replace::replace_with_hardlink(keep, path).map_err(|e| e.to_string())
+            },
+            ResolveAction::Symlink => {
+                replace::replace_with_symlink(keep, path).map_err(|e| e.to_string())
+            },
+            ResolveAction::Quarantine => {
+                let session = match &mut self.session {
+                    Some(session) => session,
+                    None => {
+                        let root = self
+                            .quarantine_dir
+                            .map_or_else(crate::quarantine::default_root, Path::to_path_buf);
+                        let session =
+                            QuarantineSession::create(&root).map_err(|e| e.to_string())?;
+                        self.session.insert(session)
+                    },
+                };
+                session
+                    .quarantine(path, Some(keep), hash)
+                    .map(drop)
+                    .map_err(|e| e.to_string())
+            },
+        }
+    }
+
+    /// Manifest of the quarantine session, if one was opened.
+    pub(crate) fn quarantine_manifest(&self) -> Option<PathBuf> {
+        self.session.as_ref().map(QuarantineSession::manifest_path)
+    }
+}
+
+/// Resolve duplicate groups (as returned by [`crate::find_duplicates`]).
+pub fn resolve_duplicates(groups: &[Vec<PathBuf>], options: &ResolveOptions) -> ResolveReport {
+    let resolutions = plan_resolution_with_references(groups, &options.policy, &options.references);
+    apply_resolution(&resolutions, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    fn write_with_mtime(dir: &TempDir, name: &str, secs: u64) -> PathBuf {
+        let path = dir.path().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, "duplicate").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+        path
+    }
+
+    fn group(dir: &TempDir) -> Vec<PathBuf> {
+        vec![
+            write_with_mtime(dir, "archive/deep/nested/b.txt", 2_000),
+            write_with_mtime(dir, "a.txt", 3_000),
+            write_with_mtime(dir, "downloads/c.txt", 1_000),
+        ]
+    }
+
+    #[test]
+    fn test_time_and_path_policies() {
+        let dir = TempDir::new().unwrap();
+        let group = group(&dir);
+
+        let keep = |policy| resolve_group(&group, &policy).unwrap().keep;
+        assert_eq!(keep(KeepPolicy::Oldest), group[2]);
+        assert_eq!(keep(KeepPolicy::Newest), group[1]);
+        assert_eq!(keep(KeepPolicy::ShortestPath), group[1]);
+    }
+
+    #[test]
+    fn test_preferred_dirs_and_regex_priority() {
+        let dir = TempDir::new().unwrap();
+        let group = group(&dir);
+
+        let preferred =
+            KeepPolicy::PreferredDirs(vec![dir.path().join("missing"), dir.path().join("archive")]);
+        let resolution = resolve_group(&group, &preferred).unwrap();
+        assert_eq!(resolution.keep, group[0]);
+        assert_eq!(resolution.remove.len(), 2);
+
+        let regex = KeepPolicy::RegexPriority(vec![Regex::new("downloads/").unwrap()]);
+        assert_eq!(resolve_group(&group, &regex).unwrap().keep, group[2]);
+
+        // Nothing matches: falls back to the shortest path
+        let regex = KeepPolicy::RegexPriority(vec![Regex::new("nope").unwrap()]);
+        assert_eq!(resolve_group(&group, &regex).unwrap().keep, group[1]);
+    }
+
+    #[test]
+    fn test_dry_run_removes_nothing() {
+        let dir = TempDir::new().unwrap();
+        let group = group(&dir);
+
+        let report = resolve_duplicates(std::slice::from_ref(&group), &ResolveOptions::default());
+        assert!(report.dry_run);
+        assert_eq!(report.removals.len(), 2);
+        assert_eq!(report.bytes_reclaimed(), 18);
+        assert!(group.iter().all(|p| p.exists()));
+        assert!(report.to_string().contains("Would remove"));
+    }
+
+    #[test]
+    fn test_execute_removes_all_but_keeper() {
+        let dir = TempDir::new().unwrap();
+        let group = group(&dir);
+
+        let options = ResolveOptions {
+            policy: KeepPolicy::Newest,
+            dry_run: false,
+            ..ResolveOptions::default()
+        };
+        let report = resolve_duplicates(std::slice::from_ref(&group), &options);
+        assert_eq!(report.failures().count(), 0);
+        assert!(group[1].exists());
+        assert!(!group[0].exists());
+        assert!(!group[2].exists());
+    }
+
+    #[test]
+    fn test_quarantine_action_records_manifest() {
+        let dir = TempDir::new().unwrap();
+        let quarantine = TempDir::new().unwrap();
+        let group = group(&dir);
+
+        let options = ResolveOptions {
+            action: ResolveAction::Quarantine,
+            quarantine_dir: Some(quarantine.path().to_path_buf()),
+            dry_run: false,
+            ..ResolveOptions::default()
+        };
+        let report = resolve_duplicates(std::slice::from_ref(&group), &options);
+        assert_eq!(report.failures().count(), 0);
+        assert!(group[2].exists());
+        assert!(!group[0].exists());
+
+        let manifest = report.quarantine_manifest.unwrap();
+        let entries = crate::quarantine::read_manifest(&manifest).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries
+            .iter()
+            .all(|e| e.kept.as_deref() == Some(group[2].as_path())));
+    }
+
+    #[test]
+    fn test_reference_files_are_kept_and_never_removed() {
+        let dir = TempDir::new().unwrap();
+        let group = group(&dir);
+        let references = vec![dir.path().join("archive")];
+
+        // Without the reference the newest file would be kept
+        let resolutions = plan_resolution_with_references(
+            std::slice::from_ref(&group),
+            &KeepPolicy::Newest,
+            &references,
+        );
+        assert_eq!(resolutions[0].keep, group[0]);
+        assert_eq!(resolutions[0].remove, group[1..].to_vec());
+
+        let archived = vec![
+            group[0].clone(),
+            write_with_mtime(&dir, "archive/other.txt", 4_000),
+        ];
+        assert!(
+            plan_resolution_with_references(&[archived], &KeepPolicy::Newest, &references)
+                .is_empty()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_action_links_duplicates() {
+        use std::os::unix::fs::MetadataExt as _;
+
+        let dir = TempDir::new().unwrap();
+        let group = group(&dir);
+
+        let options = ResolveOptions {
+            action: ResolveAction::Hardlink,
+            dry_run: false,
+            ..ResolveOptions::default()
+        };
+        let report = resolve_duplicates(std::slice::from_ref(&group), &options);
+        assert_eq!(report.failures().count(), 0);
+        assert!(report.to_string().contains("Hardlinked 2 files"));
+
+        let inode = |p: &PathBuf| fs::metadata(p).unwrap().ino();
+        assert!(group.iter().all(|p| inode(p) == inode(&group[2])));
+    }
+
+    #[test]
+    fn test_missing_keeper_blocks_removal() {
+        let resolution = GroupResolution {
+            keep: PathBuf::from("/nonexistent/keeper"),
+            remove: vec![PathBuf::from("/nonexistent/other")],
+            size_bytes: 1,
+            scanned: None,
+        };
+        let options = ResolveOptions {
+            dry_run: false,
+            ..ResolveOptions::default()
+        };
+        let report = apply_resolution(&[resolution], &options);
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(report.bytes_reclaimed(), 0);
+    }
+
+    #[test]
+    fn test_aliased_keeper_is_never_removed() {
+        let dir = TempDir::new().unwrap();
+        let keep = write_with_mtime(&dir, "a.txt", 3_000);
+        let alias = dir.path().join(".").join("a.txt");
+
+        for action in [ResolveAction::Delete, ResolveAction::Quarantine] {
+            let options = ResolveOptions {
+                action,
+                quarantine_dir: Some(dir.path().join("quarantine")),
+                dry_run: false,
+                ..ResolveOptions::default()
+            };
+            let report = resolve_duplicates(&[vec![keep.clone(), alias.clone()]], &options);
+            assert_eq!(report.failures().count(), 1);
+            assert_eq!(fs::read_to_string(&keep).unwrap(), "duplicate");
+        }
+    }
+
+    #[test]
+    fn test_files_changed_since_scan_are_skipped() {
+        let dir = TempDir::new().unwrap();
+        let group = group(&dir);
+        let mut resolutions = plan_resolution(std::slice::from_ref(&group), &KeepPolicy::Newest);
+        fingerprint_resolutions(
+            &mut resolutions,
+            HashAlgorithm::Blake3,
+            &HashCache::in_memory(),
+        );
+
+        // Same size, new content
+        fs::write(&group[0], "DUPLICATE").unwrap();
+        let options = ResolveOptions {
+            dry_run: false,
+            ..ResolveOptions::default()
+        };
+        let report = apply_resolution(&resolutions, &options);
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(report.failures().next().unwrap().path, group[0]);
+        assert!(group[0].exists());
+        assert!(!group[2].exists());
+
+        // A changed keeper blocks the whole group
+        let mut resolutions = plan_resolution(&[group[..2].to_vec()], &KeepPolicy::Newest);
+        fingerprint_resolutions(
+            &mut resolutions,
+            HashAlgorithm::Blake3,
+            &HashCache::in_memory(),
+        );
+        fs::write(&group[1], "changed!!").unwrap();
+        let report = apply_resolution(&resolutions, &options);
+        assert_eq!(report.failures().count(), 1);
+        assert!(group[0].exists());
+    }
+}
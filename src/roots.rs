@@ -0,0 +1,271 @@
+//! Multi-root scans with protected reference directories.
+//!
+//! A scan can cover several roots at once. Each [`ScanRoot`] is either a
+//! candidate, whose duplicates may be reported and resolved, or a reference,
+//! whose files are never modified and never suggested for deletion. A
+//! duplicate group that contains a reference file always keeps one, and
+//! groups made up only of reference files are not reported, so scanning
+//! `~/Downloads` with `/archive` as a reference answers "which downloads
+//! already exist in the archive?".
+
+use crate::{RcleanResult, WalkOptions, WalkSummary};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Name of the column flagging files under a reference root.
+pub const REFERENCE_COLUMN: &str = "is_reference";
+
+/// How the files of a scan root may be treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RootRole {
+    /// Duplicates under this root may be removed or replaced
+    #[default]
+    Candidate,
+    /// Files under this root are never modified and always preferred as keepers
+    Reference,
+}
+
+/// A directory included in a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanRoot {
+    pub path: PathBuf,
+    pub role: RootRole,
+}
+
+impl ScanRoot {
+    /// A root whose duplicates may be resolved.
+    pub fn candidate(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            role: RootRole::Candidate,
+        }
+    }
+
+    /// A protected root whose files are only ever kept.
+    pub fn reference(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            role: RootRole::Reference,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        self.role == RootRole::Reference
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `path` with symlinks and `..` resolved, or only made absolute if it does
+/// not exist.
+pub fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| absolute(path))
+}
+
+/// `path` with its parent directory made canonical, so every spelling of a
+/// directory entry maps to the same path while a symlink stays itself.
+pub fn canonical_entry(path: &Path) -> PathBuf {
+    let path = absolute(path);
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => canonical(parent).join(name),
+        _ => canonical(&path),
+    }
+}
+
+/// Canonical paths of the reference roots among `roots`, ready for
+/// [`is_under_reference`].
+pub fn reference_paths(roots: &[ScanRoot]) -> Vec<PathBuf> {
+    canonical_references(
+        roots
+            .iter()
+            .filter(|root| root.is_reference())
+            .map(|root| root.path.as_path()),
+    )
+}
+
+/// Canonicalize `references` once, before matching many paths against them.
+pub fn canonical_references<'a>(references: impl IntoIterator<Item = &'a Path>) -> Vec<PathBuf> {
+    references.into_iter().map(canonical).collect()
+}
+
+/// Whether `path` lies under one of the `references` directories.
+///
+/// The directory of `path` is made canonical first, so `./archive/a.txt`,
+/// `dir/../archive/a.txt` and a path through a symlink to `archive` are all
+/// under `archive`; a path that is itself a symlink into a reference counts
+/// too. `references` should be canonical already (see
+/// [`canonical_references`]); otherwise they are only made absolute. A
+/// reference nested inside a candidate root still protects its files.
+pub fn is_under_reference(path: impl AsRef<Path>, references: &[PathBuf]) -> bool {
+    if references.is_empty() {
+        return false;
+    }
+    let entry = canonical_entry(path.as_ref());
+    let target = canonical(&entry);
+    references.iter().any(|reference| {
+        let reference = absolute(reference);
+        entry.starts_with(&reference) || target.starts_with(&reference)
+    })
+}
+
+/// Walk every root and return the files found, each once, in root order,
+/// together with everything skipped under any of the roots. A file reached
+/// from two roots, even through a symlinked root, is listed under the first.
+///
+/// # Errors
+///
+/// Returns an error if walking any of the roots fails.
+pub fn walk_roots(roots: &[ScanRoot], options: &WalkOptions) -> RcleanResult<WalkSummary> {
+    let mut seen = HashSet::new();
+    let mut summary = WalkSummary::default();
+    for root in roots {
+        let walked = crate::walk_with_summary(&root.path, options)?;
+        for file in walked.files {
+            if seen.insert(canonical_entry(&file)) {
+                summary.files.push(file);
+            }
+        }
+        summary.diagnostics.extend(walked.diagnostics);
+    }
+    Ok(summary)
+}
+
+/// A candidate file whose content already exists under a reference root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReferenceMatch {
+    pub candidate: String,
+    pub reference: String,
+}
+
+impl fmt::Display for ReferenceMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} already exists in reference {}",
+            self.candidate, self.reference
+        )
+    }
+}
+
+/// Pair every candidate in a duplicate group with the group's first
+/// reference file. Frames without an `is_reference` column have no matches.
+///
+/// # Errors
+///
+/// Returns an error if the path or duplicate group columns are missing.
+pub fn reference_matches(df: &DataFrame) -> PolarsResult<Vec<ReferenceMatch>> {
+    let Ok(is_reference) = df.column(REFERENCE_COLUMN) else {
+        return Ok(Vec::new());
+    };
+    let is_reference = is_reference.bool()?;
+    let paths = df.column("file_path")?.utf8()?;
+    let groups = df.column("duplicate_group")?.utf8()?;
+
+    let mut references: HashMap<&str, &str> = HashMap::new();
+    for row in 0..df.height() {
+        if let (Some(group), Some(true), Some(path)) =
+            (groups.get(row), is_reference.get(row), paths.get(row))
+        {
+            references.entry(group).or_insert(path);
+        }
+    }
+
+    let mut matches = Vec::new();
+    for row in 0..df.height() {
+        if is_reference.get(row) == Some(true) {
+            continue;
+        }
+        let Some(reference) = groups.get(row).and_then(|group| references.get(group)) else {
+            continue;
+        };
+        matches.push(ReferenceMatch {
+            candidate: paths.get(row).unwrap_or_default().to_string(),
+            reference: (*reference).to_string(),
+        });
+    }
+    matches.sort_by(|a, b| a.candidate.cmp(&b.candidate));
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_under_reference() {
+        let references = vec![PathBuf::from("/archive"), PathBuf::from("data/ref")];
+        assert!(is_under_reference("/archive/2020/a.jpg", &references));
+        assert!(is_under_reference("./data/ref/a.txt", &references));
+        assert!(!is_under_reference("/archive2/a.jpg", &references));
+        assert!(!is_under_reference("/downloads/a.jpg", &[]));
+    }
+
+    #[test]
+    fn test_walk_roots_lists_overlapping_files_once() {
+        let dir = TempDir::new().unwrap();
+        let nested = dir.path().join("ref");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(nested.join("b.txt"), "b").unwrap();
+
+        let roots = [
+            ScanRoot::candidate(dir.path()),
+            ScanRoot::reference(&nested),
+        ];
+        let summary = walk_roots(&roots, &WalkOptions::default()).unwrap();
+        assert_eq!(summary.files.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_reference_root() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("archive");
+        let link = dir.path().join("archive-link");
+        fs::create_dir_all(&archive).unwrap();
+        fs::write(archive.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(&archive, &link).unwrap();
+
+        let references = reference_paths(&[ScanRoot::reference(&link)]);
+        assert!(is_under_reference(archive.join("a.txt"), &references));
+        assert!(is_under_reference(
+            archive.join("../archive/a.txt"),
+            &references
+        ));
+        assert!(!is_under_reference(dir.path().join("b.txt"), &references));
+
+        let references = reference_paths(&[ScanRoot::reference(&archive)]);
+        assert!(is_under_reference(link.join("a.txt"), &references));
+
+        let roots = [ScanRoot::candidate(dir.path()), ScanRoot::reference(&link)];
+        let summary = walk_roots(&roots, &WalkOptions::default()).unwrap();
+        assert_eq!(summary.files.len(), 1);
+    }
+
+    #[test]
+    fn test_reference_matches() -> Result<(), Box<dyn Error>> {
+        let df = df! [
+            "file_path" => ["/dl/a.jpg", "/archive/a.jpg", "/dl/b.txt", "/dl/c.txt", "/dl/z"],
+            "duplicate_group" => [Some("g1"), Some("g1"), Some("g2"), Some("g2"), None],
+            REFERENCE_COLUMN => [false, true, false, false, false],
+        ]?;
+
+        assert_eq!(
+            reference_matches(&df)?,
+            vec![ReferenceMatch {
+                candidate: "/dl/a.jpg".into(),
+                reference: "/archive/a.jpg".into(),
+            }]
+        );
+        Ok(())
+    }
+}
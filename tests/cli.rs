@@ -33,3 +33,453 @@ fn search() {
         .stdout(predicate::str::contains(DUPE2))
         .stdout(predicate::str::contains(NOTDUPE));
 }
+
+#[test]
+fn dedupe_csv_names_hash_algorithm() {
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let csv_path = temp_dir.path().join("report.csv");
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("dedupe")
+        .arg("tests/inputs")
+        .arg("--hash")
+        .arg("sha256")
+        .arg("--csv")
+        .arg(&csv_path)
+        .assert()
+        .success();
+
+    #[allow(clippy::unwrap_used)]
+    let report = std::fs::read_to_string(&csv_path).unwrap();
+    assert!(report
+        .lines()
+        .next()
+        .is_some_and(|header| header.contains("sha256_hash")));
+    assert!(!report.contains("md5_hash"));
+}
+
+#[test]
+fn dedupe_cache_is_reused_and_inspectable() {
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let cache_path = temp_dir.path().join("hashes.json");
+
+    for expected in [
+        "Using hash cache with 0 entries",
+        "Using hash cache with 3 entries",
+    ] {
+        #[allow(clippy::unwrap_used)]
+        let mut cmd = Command::cargo_bin(PRG).unwrap();
+        cmd.arg("dedupe")
+            .arg("tests/inputs")
+            .arg("--cache-file")
+            .arg(&cache_path)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    // Every hash of the second run came from the cache
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("dedupe")
+        .arg("tests/inputs")
+        .arg("--cache-file")
+        .arg(&cache_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Total bytes read: 0"));
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("cache")
+        .arg("inspect")
+        .arg("--cache-file")
+        .arg(&cache_path)
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Entries:        3"))
+        .stdout(predicate::str::contains(DUPE1));
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("cache")
+        .arg("invalidate")
+        .arg("--all")
+        .arg("--cache-file")
+        .arg(&cache_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Invalidated 3 entries, 0 remaining",
+        ));
+}
+
+#[test]
+fn resolve_is_dry_run_unless_executed() {
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let keep = temp_dir.path().join("a.txt");
+    let extra = temp_dir.path().join("nested").join("copy-of-a.txt");
+    #[allow(clippy::unwrap_used)]
+    {
+        std::fs::create_dir_all(extra.parent().unwrap()).unwrap();
+        std::fs::write(&keep, "duplicate").unwrap();
+        std::fs::write(&extra, "duplicate").unwrap();
+    }
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("resolve")
+        .arg(temp_dir.path())
+        .arg("--keep")
+        .arg("shortest-path")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Would remove: {}",
+            extra.display()
+        )))
+        .stdout(predicate::str::contains("--execute"));
+    assert!(extra.exists());
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("resolve")
+        .arg(temp_dir.path())
+        .arg("--keep")
+        .arg("shortest-path")
+        .arg("--execute")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Removed 1 files in 1 groups, 9 bytes",
+        ));
+    assert!(keep.exists());
+    assert!(!extra.exists());
+}
+
+#[test]
+fn reference_copies_are_reported_and_protected() {
+    #[allow(clippy::unwrap_used)]
+    let downloads = tempfile::TempDir::new().unwrap();
+    #[allow(clippy::unwrap_used)]
+    let archive = tempfile::TempDir::new().unwrap();
+    let download = downloads.path().join("photo.jpg");
+    let archived = archive.path().join("2020").join("photo.jpg");
+    let archived_copy = archive.path().join("backup.jpg");
+    #[allow(clippy::unwrap_used)]
+    {
+        std::fs::create_dir_all(archived.parent().unwrap()).unwrap();
+        std::fs::write(&download, "duplicate").unwrap();
+        std::fs::write(&archived, "duplicate").unwrap();
+        std::fs::write(&archived_copy, "duplicate").unwrap();
+    }
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("dedupe")
+        .arg(downloads.path())
+        .arg("--reference")
+        .arg(archive.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{} already exists in reference",
+            download.display()
+        )));
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("resolve")
+        .arg(downloads.path())
+        .arg("--reference")
+        .arg(archive.path())
+        .arg("--keep")
+        .arg("newest")
+        .arg("--execute")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Removed 1 files in 1 groups, 9 bytes",
+        ));
+    assert!(!download.exists());
+    assert!(archived.exists());
+    assert!(archived_copy.exists());
+}
+
+#[test]
+fn plan_is_reverified_before_apply() {
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    #[allow(clippy::unwrap_used)]
+    let plan_dir = tempfile::TempDir::new().unwrap();
+    let plan = plan_dir.path().join("plan.toml");
+    let keep = temp_dir.path().join("a.txt");
+    let changed = temp_dir.path().join("b-copy.txt");
+    let extra = temp_dir.path().join("c-copy.txt");
+    #[allow(clippy::unwrap_used)]
+    for path in [&keep, &changed, &extra] {
+        std::fs::write(path, "duplicate").unwrap();
+    }
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("plan")
+        .arg(temp_dir.path())
+        .arg("--keep")
+        .arg("shortest-path")
+        .arg("--output")
+        .arg(&plan)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 2 actions in 1 groups"));
+    #[allow(clippy::unwrap_used)]
+    let text = std::fs::read_to_string(&plan).unwrap();
+    assert!(text.contains("action = \"delete\""));
+
+    #[allow(clippy::unwrap_used)]
+    std::fs::write(&changed, "different").unwrap();
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("apply")
+        .arg(&plan)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(format!(
+            "Refused: {}",
+            changed.display()
+        )))
+        .stdout(predicate::str::contains(
+            "Applied 1 of 2 actions in 1 groups, 9 bytes; 1 refused",
+        ));
+    assert!(keep.exists());
+    assert!(changed.exists());
+    assert!(!extra.exists());
+}
+
+#[test]
+fn quarantine_can_be_restored_and_purged() {
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    #[allow(clippy::unwrap_used)]
+    let quarantine = tempfile::TempDir::new().unwrap();
+    let keep = temp_dir.path().join("a.txt");
+    let extra = temp_dir.path().join("nested").join("copy-of-a.txt");
+    #[allow(clippy::unwrap_used)]
+    {
+        std::fs::create_dir_all(extra.parent().unwrap()).unwrap();
+        std::fs::write(&keep, "duplicate").unwrap();
+        std::fs::write(&extra, "duplicate").unwrap();
+    }
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    let output = cmd
+        .arg("resolve")
+        .arg(temp_dir.path())
+        .arg("--keep")
+        .arg("shortest-path")
+        .arg("--action")
+        .arg("quarantine")
+        .arg("--quarantine-dir")
+        .arg(quarantine.path())
+        .arg("--execute")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Quarantined 1 files in 1 groups, 9 bytes",
+        ));
+    assert!(!extra.exists());
+
+    let stdout = String::from_utf8_lossy(&output.get_output().stdout).into_owned();
+    #[allow(clippy::unwrap_used)]
+    let manifest = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Quarantine manifest: "))
+        .unwrap();
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("restore")
+        .arg(manifest)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Restored 1 files, 0 left in quarantine",
+        ));
+    #[allow(clippy::unwrap_used)]
+    let restored = std::fs::read_to_string(&extra).unwrap();
+    assert_eq!(restored, "duplicate");
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("purge")
+        .arg("--quarantine-dir")
+        .arg(quarantine.path())
+        .arg("--all")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Purged 0 sessions"));
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("purge")
+        .arg("--quarantine-dir")
+        .arg(quarantine.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn search_applies_type_and_size_filters() {
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    #[allow(clippy::unwrap_used)]
+    std::fs::write(temp_dir.path().join("main.rs"), "fn main() {}").unwrap();
+    #[allow(clippy::unwrap_used)]
+    std::fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+    #[allow(clippy::unwrap_used)]
+    std::fs::write(temp_dir.path().join("big.rs"), vec![b'x'; 4096]).unwrap();
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("search")
+        .arg(temp_dir.path())
+        .arg("--type")
+        .arg("rust")
+        .arg("--max-size")
+        .arg("1KB")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("main.rs"))
+        .stdout(predicate::str::contains("notes.txt").not())
+        .stdout(predicate::str::contains("big.rs").not());
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("search")
+        .arg(temp_dir.path())
+        .arg("--type")
+        .arg("nonsense")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn search_combines_include_and_exclude_sets() {
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    for name in ["main.rs", "skip_me.rs", "notes.md", "data.csv"] {
+        #[allow(clippy::unwrap_used)]
+        std::fs::write(temp_dir.path().join(name), name).unwrap();
+    }
+    let excludes = temp_dir.path().join("excludes.txt");
+    #[allow(clippy::unwrap_used)]
+    std::fs::write(&excludes, "# generated data\nglob:*.csv\n").unwrap();
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("search")
+        .arg(temp_dir.path())
+        .arg("--include")
+        .arg("glob:*.rs")
+        .arg("--include")
+        .arg("notes")
+        .arg("--include")
+        .arg("data")
+        .arg("--exclude")
+        .arg("regex:^skip")
+        .arg("--exclude-from")
+        .arg(&excludes)
+        .arg("--match-on")
+        .arg("basename")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Found 2 files matching pattern"))
+        .stdout(predicate::str::contains("main.rs"))
+        .stdout(predicate::str::contains("notes.md"))
+        .stdout(predicate::str::contains("skip_me.rs").not())
+        .stdout(predicate::str::contains("data.csv").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths_are_escaped_and_kept_in_reports() {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let latin1 = temp_dir
+        .path()
+        .join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+    for path in [latin1, temp_dir.path().join("copy.txt")] {
+        #[allow(clippy::unwrap_used)]
+        std::fs::write(path, "same content").unwrap();
+    }
+    let csv_path = temp_dir.path().join("report.csv");
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("search")
+        .arg(temp_dir.path())
+        .arg("--pattern")
+        .arg("caf")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Found 1 files matching pattern"))
+        .stdout(predicate::str::contains("caf\\xE9.txt"));
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("dedupe")
+        .arg(temp_dir.path())
+        .arg("--pattern")
+        .arg(".txt")
+        .arg("--csv")
+        .arg(&csv_path)
+        .assert()
+        .success();
+
+    #[allow(clippy::unwrap_used)]
+    let report = std::fs::read_to_string(&csv_path).unwrap();
+    assert!(report
+        .lines()
+        .next()
+        .is_some_and(|header| header.contains("file_path_hex")));
+    let row = report.lines().find(|line| line.contains("caf\\xE9.txt"));
+    // Hex of the file name's raw bytes ends the hex path
+    assert!(row.is_some_and(|row| row.contains("636166e92e747874") && row.contains("true")));
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_scan_root_is_accepted() {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    #[allow(clippy::unwrap_used)]
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let share = temp_dir
+        .path()
+        .join(std::ffi::OsStr::from_bytes(b"caf\xe9"));
+    #[allow(clippy::unwrap_used)]
+    std::fs::create_dir(&share).unwrap();
+    for name in ["a.txt", "b.txt"] {
+        #[allow(clippy::unwrap_used)]
+        std::fs::write(share.join(name), "same content").unwrap();
+    }
+
+    #[allow(clippy::unwrap_used)]
+    let mut cmd = Command::cargo_bin(PRG).unwrap();
+    cmd.arg("dedupe")
+        .arg(&share)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Found 2 files in 1 duplicate groups",
+        ));
+}
@@ -31,7 +31,7 @@ fn test_full_deduplication_workflow() {
     );
 
     assert!(result.is_ok());
-    let df = result.unwrap();
+    let df = result.unwrap().df;
     assert!(df.height() >= 4);
 }
 
@@ -61,7 +61,7 @@ fn test_similarity_detection_workflow() {
     );
 
     assert!(result.is_ok());
-    let df = result.unwrap();
+    let df = result.unwrap().df;
     assert!(df.height() >= 3);
 }
 
@@ -77,7 +77,7 @@ fn test_pattern_types() {
 
     // Test literal pattern
     let pattern = PatternType::Literal("test".to_string());
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(matches.len() >= 2);
 
     // Test glob pattern
@@ -85,13 +85,13 @@ fn test_pattern_types() {
     builder.add(rclean::Glob::new("*.txt").unwrap());
     let globset = builder.build().unwrap();
     let pattern = PatternType::Glob(globset);
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(!matches.is_empty());
 
     // Test regex pattern
     let regex = rclean::Regex::new(r".*\.rs$").unwrap();
     let pattern = PatternType::Regex(regex);
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(!matches.is_empty());
 }
 
@@ -112,7 +112,9 @@ fn test_walk_options_variations() {
     let files =
         rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &WalkOptions::default())
             .unwrap();
-    assert!(!files.iter().any(|f| f.contains(".hidden")));
+    assert!(!files
+        .iter()
+        .any(|f| f.to_string_lossy().contains(".hidden")));
 
     // Test with hidden files
     let options = WalkOptions {
@@ -120,9 +122,12 @@ fn test_walk_options_variations() {
         respect_gitignore: false,
         respect_ignore: false,
         max_depth: None,
+        ..WalkOptions::default()
     };
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(files.iter().any(|f| f.contains(".hidden")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains(".hidden")));
 
     // Test with max depth
     let options = WalkOptions {
@@ -130,9 +135,12 @@ fn test_walk_options_variations() {
         respect_gitignore: true,
         respect_ignore: true,
         max_depth: Some(1),
+        ..WalkOptions::default()
     };
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(!files.iter().any(|f| f.contains("deep.txt")));
+    assert!(!files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("deep.txt")));
 }
 
 #[test]
@@ -168,7 +176,7 @@ fn test_error_handling() {
     assert!(files.is_empty());
 
     // Test with empty pattern
-    let files = vec!["test.txt".to_string()];
+    let files = vec![std::path::PathBuf::from("test.txt")];
     let matches = rclean::find(&files, "");
     assert_eq!(matches.len(), 1);
 
@@ -187,3 +195,108 @@ fn test_run_simple_api() {
     let result = rclean::run(temp_dir.path().to_str().unwrap(), "test");
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_archive_members_join_duplicate_groups() {
+    use std::io::Write as _;
+
+    let temp_dir = TempDir::new().unwrap();
+    let notes = "meeting notes, 2024";
+    fs::write(temp_dir.path().join("notes.txt"), notes).unwrap();
+    fs::write(temp_dir.path().join("other.txt"), "unrelated").unwrap();
+
+    let archive = temp_dir.path().join("backup.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    zip.start_file("docs/notes.txt", zip::write::SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(notes.as_bytes()).unwrap();
+    zip.finish().unwrap();
+
+    let path = temp_dir.path().to_str().unwrap();
+    let pattern = PatternType::Literal(String::new());
+    let report = rclean::run_dedupe(
+        path,
+        &pattern,
+        &WalkOptions::default(),
+        &rclean::DedupeOptions::default(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(report.duplicate_groups, 0);
+
+    let options = rclean::DedupeOptions {
+        archives: true,
+        ..rclean::DedupeOptions::default()
+    };
+    let report =
+        rclean::run_dedupe(path, &pattern, &WalkOptions::default(), &options, None).unwrap();
+    assert!(report.diagnostics.is_empty());
+    assert_eq!(report.df.height(), 4);
+    assert_eq!(report.duplicate_groups, 1);
+    assert_eq!(report.duplicate_files, 2);
+
+    // The loose copy is the one to remove; the member cannot be
+    let space = rclean::reclaim::reclaimable_space(&report.df, path).unwrap();
+    assert_eq!(space.total_bytes, notes.len() as u64);
+    let members = rclean::archives::archive_duplicates(&report.df).unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(
+        members[0].member,
+        format!("{}!/docs/notes.txt", archive.display())
+    );
+    assert_eq!(members[0].archive, archive.display().to_string());
+}
+
+#[test]
+fn test_normalized_duplicates_are_kept_apart_from_exact_ones() {
+    let temp_dir = TempDir::new().unwrap();
+    let unix = "[server]\nport = 8080\n";
+    fs::write(temp_dir.path().join("unix.toml"), unix).unwrap();
+    fs::write(temp_dir.path().join("copy.toml"), unix).unwrap();
+    fs::write(
+        temp_dir.path().join("windows.toml"),
+        "\u{feff}[server]  \r\nport = 8080\r\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("spaced.toml"),
+        "[server]\nport  =  8080\n",
+    )
+    .unwrap();
+
+    let path = temp_dir.path().to_str().unwrap();
+    let pattern = PatternType::Literal(String::new());
+    let mut options = rclean::DedupeOptions {
+        normalize_text: Some(rclean::normalize::TextNormalization::default()),
+        ..rclean::DedupeOptions::default()
+    };
+    let report =
+        rclean::run_dedupe(path, &pattern, &WalkOptions::default(), &options, None).unwrap();
+    assert_eq!(report.duplicate_groups, 1);
+    assert_eq!(report.duplicate_files, 2);
+    assert_eq!(report.normalized_groups.len(), 1);
+    let group = &report.normalized_groups[0];
+    assert_eq!(group.paths.len(), 3);
+    assert_eq!(group.variants, 2);
+    assert!(group.paths.contains(&temp_dir.path().join("windows.toml")));
+
+    let normalized = report
+        .df
+        .column(rclean::normalize::NORMALIZED_DUPLICATE_COLUMN)
+        .unwrap()
+        .bool()
+        .unwrap()
+        .sum();
+    assert_eq!(normalized, Some(3));
+    // Only the exact copy is reclaimable
+    let space = rclean::reclaim::reclaimable_space(&report.df, path).unwrap();
+    assert_eq!(space.total_bytes, unix.len() as u64);
+
+    options.normalize_text = Some(rclean::normalize::TextNormalization {
+        collapse_whitespace: true,
+    });
+    let report =
+        rclean::run_dedupe(path, &pattern, &WalkOptions::default(), &options, None).unwrap();
+    assert_eq!(report.normalized_groups[0].paths.len(), 4);
+    assert_eq!(report.normalized_groups[0].variants, 3);
+}
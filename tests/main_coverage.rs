@@ -13,7 +13,7 @@ fn test_create_pattern_literal() {
     // Test that we can create a literal pattern
     let pattern = rclean::PatternType::Literal("test".to_string());
     let files = rclean::walk(temp_dir.path().to_str().unwrap()).unwrap();
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(!matches.is_empty());
 }
 
@@ -30,7 +30,7 @@ fn test_create_pattern_glob() {
     let pattern = rclean::PatternType::Glob(globset);
 
     let files = rclean::walk(temp_dir.path().to_str().unwrap()).unwrap();
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(!matches.is_empty());
 }
 
@@ -45,7 +45,7 @@ fn test_create_pattern_regex() {
     let pattern = rclean::PatternType::Regex(regex);
 
     let files = rclean::walk(temp_dir.path().to_str().unwrap()).unwrap();
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(!matches.is_empty());
 }
 
@@ -63,11 +63,16 @@ fn test_walk_options_creation() {
         respect_gitignore: true,
         respect_ignore: true,
         max_depth: None,
+        ..rclean::WalkOptions::default()
     };
 
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(!files.iter().any(|f| f.contains(".hidden")));
-    assert!(files.iter().any(|f| f.contains("normal.txt")));
+    assert!(!files
+        .iter()
+        .any(|f| f.to_string_lossy().contains(".hidden")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("normal.txt")));
 
     // Test with hidden files enabled
     let options = rclean::WalkOptions {
@@ -75,11 +80,16 @@ fn test_walk_options_creation() {
         respect_gitignore: false,
         respect_ignore: false,
         max_depth: None,
+        ..rclean::WalkOptions::default()
     };
 
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(files.iter().any(|f| f.contains(".hidden")));
-    assert!(files.iter().any(|f| f.contains("normal.txt")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains(".hidden")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("normal.txt")));
 }
 
 #[test]
@@ -98,11 +108,16 @@ fn test_walk_options_max_depth() {
         respect_gitignore: true,
         respect_ignore: true,
         max_depth: Some(1),
+        ..rclean::WalkOptions::default()
     };
 
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(!files.iter().any(|f| f.contains("deep.txt")));
-    assert!(files.iter().any(|f| f.contains("shallow.txt")));
+    assert!(!files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("deep.txt")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("shallow.txt")));
 
     // Test with no max_depth (should find both)
     let options = rclean::WalkOptions {
@@ -110,11 +125,16 @@ fn test_walk_options_max_depth() {
         respect_gitignore: true,
         respect_ignore: true,
         max_depth: None,
+        ..rclean::WalkOptions::default()
     };
 
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(files.iter().any(|f| f.contains("deep.txt")));
-    assert!(files.iter().any(|f| f.contains("shallow.txt")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("deep.txt")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("shallow.txt")));
 }
 
 #[test]
@@ -167,7 +187,7 @@ fn test_similarity_detection() {
     );
 
     assert!(result.is_ok());
-    let df = result.unwrap();
+    let df = result.unwrap().df;
     assert!(df.height() >= 3);
 }
 
@@ -188,10 +208,13 @@ fn test_advanced_options_with_ignore() {
         respect_gitignore: true,
         respect_ignore: true,
         max_depth: None,
+        ..rclean::WalkOptions::default()
     };
 
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(files.iter().any(|f| f.contains("important.txt")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("important.txt")));
     // Note: .gitignore might not always be respected in tests
 
     // Test with gitignore ignored
@@ -200,11 +223,16 @@ fn test_advanced_options_with_ignore() {
         respect_gitignore: false,
         respect_ignore: false,
         max_depth: None,
+        ..rclean::WalkOptions::default()
     };
 
     let files = rclean::walk_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
-    assert!(files.iter().any(|f| f.contains("important.txt")));
-    assert!(files.iter().any(|f| f.contains("debug.log")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("important.txt")));
+    assert!(files
+        .iter()
+        .any(|f| f.to_string_lossy().contains("debug.log")));
 }
 
 #[test]
@@ -256,7 +284,7 @@ fn test_dataframe_functionality() {
     let result = rclean::run_with_dataframe(temp_dir.path().to_str().unwrap(), "", None);
 
     assert!(result.is_ok());
-    let df = result.unwrap();
+    let df = result.unwrap().df;
     assert!(df.height() >= 3);
 
     // Test statistics generation
@@ -316,11 +344,11 @@ fn test_pattern_edge_cases() {
 
     // Test literal matching with special characters
     let pattern = rclean::PatternType::Literal("with".to_string());
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(matches.len() >= 2);
 
     // Test empty pattern (should match all)
     let _pattern = rclean::PatternType::Literal("".to_string());
-    let matches = rclean::find_advanced(&files, &pattern);
+    let matches = rclean::find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
     assert!(matches.len() >= 3);
 }
@@ -127,6 +127,50 @@ async fn test_dedupe_tool_with_similarity() {
     assert!(response.error.is_none());
 }
 
+#[tokio::test]
+async fn test_dedupe_tool_with_hash_algorithm() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("test1.txt"), "same content").unwrap();
+    std::fs::write(temp_dir.path().join("test2.txt"), "same content").unwrap();
+
+    let request = McpRequest {
+        jsonrpc: "2.0".to_string(),
+        id: json!(1),
+        method: "tools/call".to_string(),
+        params: Some(json!({
+            "name": "dedupe",
+            "arguments": {
+                "path": temp_dir.path().to_str().unwrap(),
+                "hash": "blake3"
+            }
+        })),
+    };
+
+    let response = handle_tool_call(request).await;
+    assert!(response.error.is_none());
+
+    let result = response.result.unwrap();
+    assert_eq!(result["hash_algorithm"], "blake3");
+    assert_eq!(result["duplicate_files"], 2);
+
+    // Unknown algorithms are rejected as invalid arguments
+    let request = McpRequest {
+        jsonrpc: "2.0".to_string(),
+        id: json!(2),
+        method: "tools/call".to_string(),
+        params: Some(json!({
+            "name": "dedupe",
+            "arguments": {
+                "path": temp_dir.path().to_str().unwrap(),
+                "hash": "crc32"
+            }
+        })),
+    };
+
+    let response = handle_tool_call(request).await;
+    assert_eq!(response.error.unwrap().code, -32602);
+}
+
 #[tokio::test]
 async fn test_dedupe_tool_with_csv() {
     let temp_dir = tempfile::TempDir::new().unwrap();
@@ -389,6 +433,32 @@ async fn test_count_tool_handler() {
     assert!(count >= 2);
 }
 
+#[tokio::test]
+async fn test_count_tool_with_elapsed_timeout() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("test1.txt"), "content").unwrap();
+
+    let request = McpRequest {
+        jsonrpc: "2.0".to_string(),
+        id: json!(1),
+        method: "tools/call".to_string(),
+        params: Some(json!({
+            "name": "count",
+            "arguments": {
+                "path": temp_dir.path().to_str().unwrap(),
+                "timeout_secs": 0
+            }
+        })),
+    };
+
+    let response = handle_tool_call(request).await;
+    assert!(response.error.is_none());
+
+    let result = response.result.unwrap();
+    assert_eq!(result["count"], 0);
+    assert_eq!(result["diagnostics"]["interrupted"], "deadline_exceeded");
+}
+
 #[tokio::test]
 async fn test_tool_call_unknown_tool() {
     let request = McpRequest {
@@ -12,6 +12,8 @@ fn test_outlier_options_default() {
     assert!(options.check_hidden_consumers);
     assert!(options.check_patterns);
     assert!(!options.include_empty_dirs);
+    assert_eq!(options.size_metric, SizeMetric::Apparent);
+    assert!(options.check_sparse);
     assert!(!options.enable_clustering);
     assert_eq!(options.cluster_similarity_threshold, 70);
     assert_eq!(options.min_cluster_size, 2);
@@ -29,6 +31,7 @@ fn test_outlier_options_custom() {
         enable_clustering: false,
         cluster_similarity_threshold: 70,
         min_cluster_size: 2,
+        ..OutlierOptions::default()
     };
 
     assert_eq!(options.min_size, Some(1024 * 1024));
@@ -78,6 +81,7 @@ fn test_detect_large_file_outliers() {
         enable_clustering: false,
         cluster_similarity_threshold: 70,
         min_cluster_size: 2,
+        ..OutlierOptions::default()
     };
 
     let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
@@ -116,6 +120,7 @@ fn test_detect_hidden_consumers() {
         enable_clustering: false,
         cluster_similarity_threshold: 70,
         min_cluster_size: 2,
+        ..OutlierOptions::default()
     };
 
     let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
@@ -128,6 +133,34 @@ fn test_detect_hidden_consumers() {
     assert!(node_modules_found);
 }
 
+#[test]
+fn test_detect_empty_dirs() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("build/out")).unwrap();
+    fs::create_dir(temp_dir.path().join("photos")).unwrap();
+    fs::write(temp_dir.path().join("photos/.DS_Store"), "junk").unwrap();
+
+    let options = OutlierOptions {
+        include_empty_dirs: true,
+        ..OutlierOptions::default()
+    };
+    let report = detect_outliers(temp_dir.path(), &options).unwrap();
+
+    // No regular file was analyzed, but the directories are still reported
+    assert_eq!(report.total_files_analyzed, 0);
+    let paths: Vec<_> = report.empty_dirs.iter().map(|d| d.path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            temp_dir.path().join("build"),
+            temp_dir.path().join("photos")
+        ]
+    );
+
+    let report = detect_outliers(temp_dir.path(), &OutlierOptions::default()).unwrap();
+    assert!(report.empty_dirs.is_empty());
+}
+
 #[test]
 fn test_detect_pattern_groups() {
     let temp_dir = TempDir::new().unwrap();
@@ -164,6 +197,7 @@ fn test_detect_pattern_groups() {
         enable_clustering: false,
         cluster_similarity_threshold: 70,
         min_cluster_size: 2,
+        ..OutlierOptions::default()
     };
 
     let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
@@ -187,6 +221,7 @@ fn test_outliers_to_dataframe() {
             LargeFileOutlier {
                 path: std::path::PathBuf::from("/tmp/test1.txt"),
                 size_bytes: 1024 * 1024 * 10, // 10MB
+                allocated_bytes: 1024 * 1024 * 10,
                 size_mb: 10.0,
                 percentage_of_total: 50.0,
                 std_devs_from_mean: 3.2,
@@ -194,6 +229,7 @@ fn test_outliers_to_dataframe() {
             LargeFileOutlier {
                 path: std::path::PathBuf::from("/tmp/test2.txt"),
                 size_bytes: 1024 * 1024 * 5, // 5MB
+                allocated_bytes: 1024 * 1024 * 5,
                 size_mb: 5.0,
                 percentage_of_total: 25.0,
                 std_devs_from_mean: 2.1,
@@ -203,17 +239,23 @@ fn test_outliers_to_dataframe() {
         pattern_groups: vec![],
         large_file_clusters: vec![],
         total_size_analyzed: 1024 * 1024 * 20, // 20MB
+        total_allocated_analyzed: 1024 * 1024 * 20,
         total_files_analyzed: 10,
+        sparse_files: vec![],
+        growth: None,
+        empty_dirs: vec![],
+        diagnostics: Default::default(),
     };
 
     let df = outliers_to_dataframe(&report).unwrap();
 
     assert_eq!(df.height(), 2);
-    assert_eq!(df.width(), 4);
+    assert_eq!(df.width(), 5);
     assert!(df.column("file_path").is_ok());
     assert!(df.column("size_mb").is_ok());
     assert!(df.column("percentage_of_total").is_ok());
     assert!(df.column("std_devs_from_mean").is_ok());
+    assert!(df.column(rclean::paths::HEX_COLUMN).is_ok());
 }
 
 #[test]
@@ -224,13 +266,18 @@ fn test_outliers_to_dataframe_empty() {
         pattern_groups: vec![],
         large_file_clusters: vec![],
         total_size_analyzed: 0,
+        total_allocated_analyzed: 0,
         total_files_analyzed: 0,
+        sparse_files: vec![],
+        growth: None,
+        empty_dirs: vec![],
+        diagnostics: Default::default(),
     };
 
     let df = outliers_to_dataframe(&report).unwrap();
 
     assert_eq!(df.height(), 0);
-    assert_eq!(df.width(), 4);
+    assert_eq!(df.width(), 5);
 }
 
 #[test]
@@ -252,6 +299,7 @@ fn test_min_size_filter() {
         enable_clustering: false,
         cluster_similarity_threshold: 70,
         min_cluster_size: 2,
+        ..OutlierOptions::default()
     };
 
     let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
@@ -286,6 +334,7 @@ fn test_top_n_limiting() {
         enable_clustering: false,
         cluster_similarity_threshold: 70,
         min_cluster_size: 2,
+        ..OutlierOptions::default()
     };
 
     let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
@@ -298,6 +347,106 @@ fn test_top_n_limiting() {
     }
 }
 
+#[test]
+fn test_sparse_files_and_allocated_ranking() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // A 64MB file holding a single written byte
+    let image = temp_dir.path().join("disk.img");
+    let file = fs::File::create(&image).unwrap();
+    file.set_len(64 * 1024 * 1024).unwrap();
+    drop(file);
+    fs::write(
+        temp_dir.path().join("dense.bin"),
+        vec![1u8; 2 * 1024 * 1024],
+    )
+    .unwrap();
+    for i in 0..8 {
+        fs::write(temp_dir.path().join(format!("small{i}.txt")), "x").unwrap();
+    }
+
+    let options = OutlierOptions {
+        std_dev_threshold: 0.5,
+        check_hidden_consumers: false,
+        check_patterns: false,
+        ..OutlierOptions::default()
+    };
+    let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
+    assert!(report.total_allocated_analyzed < report.total_size_analyzed);
+    assert_eq!(report.large_files[0].path, image);
+
+    if cfg!(unix) {
+        // Filesystems without sparse file support allocate the whole image
+        if let Some(sparse) = report.sparse_files.first() {
+            assert_eq!(sparse.path, image);
+            assert_eq!(sparse.apparent_bytes, 64 * 1024 * 1024);
+            assert_eq!(
+                sparse.hole_bytes,
+                sparse.apparent_bytes - sparse.allocated_bytes
+            );
+            assert!(sparse.allocated_percent < 50.0);
+
+            let options = OutlierOptions {
+                size_metric: SizeMetric::Allocated,
+                ..options
+            };
+            let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
+            assert_eq!(
+                report.large_files[0].path,
+                temp_dir.path().join("dense.bin")
+            );
+        }
+    }
+
+    let options = OutlierOptions {
+        check_sparse: false,
+        ..OutlierOptions::default()
+    };
+    let report = detect_outliers(temp_dir.path().to_str().unwrap(), &options).unwrap();
+    assert!(report.sparse_files.is_empty());
+}
+
+#[test]
+fn test_growth_between_scans() {
+    let temp_dir = TempDir::new().unwrap();
+    let store_dir = TempDir::new().unwrap();
+    let logs = temp_dir.path().join("logs");
+    fs::create_dir(&logs).unwrap();
+    fs::write(logs.join("app.log"), vec![b'a'; 1024]).unwrap();
+    fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+
+    let options = OutlierOptions {
+        snapshot_path: Some(store_dir.path().join("snapshots.json")),
+        ..OutlierOptions::default()
+    };
+    let report = detect_outliers(temp_dir.path(), &options).unwrap();
+    assert!(report.growth.is_none());
+
+    fs::write(logs.join("app.log"), vec![b'a'; 3 * 1024 * 1024]).unwrap();
+    fs::create_dir(temp_dir.path().join("cache")).unwrap();
+    fs::write(
+        temp_dir.path().join("cache").join("blob"),
+        vec![b'b'; 2 * 1024 * 1024],
+    )
+    .unwrap();
+
+    let report = detect_outliers(temp_dir.path(), &options).unwrap();
+    let growth = report.growth.expect("compared with the first scan");
+    assert_eq!(growth.total_growth_bytes, 5 * 1024 * 1024 - 1024);
+    let growing: Vec<_> = growth.growing.iter().map(|g| g.path.clone()).collect();
+    assert_eq!(growing, [logs.clone(), logs.join("app.log")]);
+    assert_eq!(growth.new_paths.len(), 1);
+    assert_eq!(growth.new_paths[0].path, temp_dir.path().join("cache"));
+
+    // Only snapshots at least a day old qualify as the baseline
+    let options = OutlierOptions {
+        growth_since_secs: Some(86_400),
+        ..options
+    };
+    let report = detect_outliers(temp_dir.path(), &options).unwrap();
+    assert!(report.growth.is_none());
+}
+
 // Pattern detection functions are tested implicitly through
 // test_detect_pattern_groups above
 
@@ -1,12 +1,13 @@
 use proptest::prelude::*;
 use rclean::outliers::{detect_outliers, OutlierOptions};
 use rclean::{find, find_advanced, PatternType};
+use std::path::PathBuf;
 
 // Property: find() should always return a subset of the input files
 proptest! {
     #[test]
     fn find_returns_subset(files in prop::collection::vec("[a-z0-9./]+", 0..100), pattern in "[a-z0-9]+") {
-        let files: Vec<String> = files.into_iter().collect();
+        let files: Vec<PathBuf> = files.into_iter().map(PathBuf::from).collect();
         let result = find(&files, &pattern);
 
         // Every result should be in the original files
@@ -23,7 +24,7 @@ proptest! {
 proptest! {
     #[test]
     fn find_empty_pattern_returns_all(files in prop::collection::vec("[a-z0-9./]+", 0..100)) {
-        let files: Vec<String> = files.into_iter().collect();
+        let files: Vec<PathBuf> = files.into_iter().map(PathBuf::from).collect();
         let result = find(&files, "");
 
         prop_assert_eq!(result.len(), files.len());
@@ -37,11 +38,11 @@ proptest! {
         files in prop::collection::vec("[a-z0-9./]+\\.txt", 1..50),
         pattern_str in "[a-z0-9]+"
     ) {
-        let files: Vec<String> = files.into_iter().collect();
+        let files: Vec<PathBuf> = files.into_iter().map(PathBuf::from).collect();
 
         // Literal pattern
         let literal_pattern = PatternType::Literal(pattern_str.clone());
-        let literal_results = find_advanced(&files, &literal_pattern);
+        let literal_results = find_advanced(&files, &literal_pattern, &rclean::filters::FileFilter::default());
 
         // Simple find should give same results for literal patterns
         let simple_results = find(&files, &pattern_str);
@@ -80,9 +81,9 @@ proptest! {
         base_names in prop::collection::vec("[a-z0-9]+", 1..20),
         extensions in prop::collection::vec("txt|csv|json|xml", 1..20)
     ) {
-        let files: Vec<String> = base_names.into_iter()
+        let files: Vec<PathBuf> = base_names.into_iter()
             .zip(extensions.into_iter())
-            .map(|(base, ext)| format!("{base}.{ext}"))
+            .map(|(base, ext)| PathBuf::from(format!("{base}.{ext}")))
             .collect();
 
         // Create glob pattern for .txt files
@@ -94,7 +95,7 @@ proptest! {
             .map_err(|_| TestCaseError::fail("Failed to build globset"))?;
         let pattern = PatternType::Glob(globset);
 
-        let results = find_advanced(&files, &pattern);
+        let results = find_advanced(&files, &pattern, &rclean::filters::FileFilter::default());
 
         // All results should end with .txt
         for file in &results {
@@ -120,6 +121,7 @@ proptest! {
             respect_gitignore: true,
             respect_ignore: true,
             max_depth,
+            ..rclean::WalkOptions::default()
         };
 
         // Property: max_depth should be what we set
@@ -148,7 +150,7 @@ proptest! {
 proptest! {
     #[test]
     fn empty_files_handling(pattern in "[a-z0-9]+") {
-        let files: Vec<String> = vec![];
+        let files: Vec<PathBuf> = vec![];
         let result = find(&files, &pattern);
 
         prop_assert!(result.is_empty());
@@ -163,23 +165,23 @@ proptest! {
         literal_pattern in "[a-z0-9]+",
         glob_pattern in "[a-z0-9*?]+",
     ) {
-        let files: Vec<String> = files.into_iter().collect();
+        let files: Vec<PathBuf> = files.into_iter().map(PathBuf::from).collect();
 
         // Test all pattern types don't panic
-        let _ = find_advanced(&files, &PatternType::Literal(literal_pattern.clone()));
+        let _ = find_advanced(&files, &PatternType::Literal(literal_pattern.clone()), &rclean::filters::FileFilter::default());
 
         // Only test valid glob patterns
         if let Ok(glob) = rclean::Glob::new(&glob_pattern) {
             let mut builder = rclean::GlobSetBuilder::new();
             builder.add(glob);
             if let Ok(globset) = builder.build() {
-                let _ = find_advanced(&files, &PatternType::Glob(globset));
+                let _ = find_advanced(&files, &PatternType::Glob(globset), &rclean::filters::FileFilter::default());
             }
         }
 
         // Test regex pattern
         if let Ok(regex) = rclean::Regex::new(&literal_pattern) {
-            let _ = find_advanced(&files, &PatternType::Regex(regex));
+            let _ = find_advanced(&files, &PatternType::Regex(regex), &rclean::filters::FileFilter::default());
         }
 
         // Property: function should not panic
@@ -221,6 +223,7 @@ proptest! {
             enable_clustering: false,
             cluster_similarity_threshold: 70,
             min_cluster_size: 2,
+            ..OutlierOptions::default()
         };
 
         if let Ok(report) = detect_outliers(temp_dir.path().to_str().unwrap(), &options) {
@@ -268,6 +271,7 @@ proptest! {
             enable_clustering: false,
             cluster_similarity_threshold: 70,
             min_cluster_size: 2,
+            ..OutlierOptions::default()
         };
 
         if let Ok(report) = detect_outliers(temp_dir.path().to_str().unwrap(), &options) {
@@ -307,6 +311,7 @@ proptest! {
             enable_clustering: false,
             cluster_similarity_threshold: 70,
             min_cluster_size: 2,
+            ..OutlierOptions::default()
         };
 
         if let Ok(report) = detect_outliers(temp_dir.path().to_str().unwrap(), &options) {
@@ -345,6 +350,7 @@ proptest! {
             enable_clustering: false,
             cluster_similarity_threshold: 70,
             min_cluster_size: 2,
+            ..OutlierOptions::default()
         };
 
         if let Ok(report) = detect_outliers(temp_dir.path().to_str().unwrap(), &options) {